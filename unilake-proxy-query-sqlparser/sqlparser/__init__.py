@@ -1,8 +1,14 @@
 import sqlglot
 
 from build.lib.sqlparser.data import TranspilerInput
-from sqlparser.data import ScanOutput, TranspilerOutput, ErrorMessage, ParserError
-from sqlparser.transpiler import inner_scan, inner_transpile
+from sqlparser.data import (
+    CatalogOutput,
+    ScanOutput,
+    TranspilerOutput,
+    ErrorMessage,
+    ParserError,
+)
+from sqlparser.transpiler import inner_execute_catalog, inner_scan, inner_split, inner_transpile
 
 
 def scan(sql: str, dialect: str, catalog: str, database: str) -> ScanOutput:
@@ -19,6 +25,24 @@ def scan(sql: str, dialect: str, catalog: str, database: str) -> ScanOutput:
         return ScanOutput.from_parser_error(parser_error)
 
 
+def split(sql: str, dialect: str) -> list[str]:
+    try:
+        return transpiler.inner_split(sql, dialect)
+    except Exception:
+        # let the scan of the complete batch report the error
+        return [sql]
+
+
+def execute_catalog(sql: str, dialect: str, tables: str) -> CatalogOutput:
+    try:
+        return transpiler.inner_execute_catalog(sql, dialect, tables)
+    except sqlglot.errors.ParseError:
+        # let the backend report the error
+        return CatalogOutput.not_catalog_query()
+    except Exception as e:
+        return CatalogOutput.from_error(str(e))
+
+
 def transpile(
     source: str | dict | TranspilerInput, secure_output: bool = False
 ) -> TranspilerOutput:
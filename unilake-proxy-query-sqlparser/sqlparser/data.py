@@ -225,3 +225,28 @@ class TranspilerOutput:
     @staticmethod
     def from_parser_error(parser_error: ParserError) -> "TranspilerOutput":
         return TranspilerOutput(sql_transformed="", error=parser_error)
+
+
+@dataclass
+class CatalogOutput:
+    columns: list[str]
+    rows: list[list]
+    # Whether the query only references the emulated system catalog
+    is_catalog_query: bool
+    error: str | None
+
+    def to_json(self) -> dict:
+        return {
+            "columns": self.columns,
+            "rows": self.rows,
+            "is_catalog_query": self.is_catalog_query,
+            "error": self.error,
+        }
+
+    @staticmethod
+    def not_catalog_query() -> "CatalogOutput":
+        return CatalogOutput(columns=[], rows=[], is_catalog_query=False, error=None)
+
+    @staticmethod
+    def from_error(error: str) -> "CatalogOutput":
+        return CatalogOutput(columns=[], rows=[], is_catalog_query=True, error=error)
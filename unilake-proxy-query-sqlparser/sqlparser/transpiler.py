@@ -1,13 +1,18 @@
 import json
+from decimal import Decimal
 from typing import Type
 
 from sqlglot import parse_one, exp, Expression, maybe_parse, MappingSchema
+from sqlglot.dialects.dialect import Dialect
+from sqlglot.executor import execute
+from sqlglot.tokens import TokenType
 from sqlglot.expressions import replace_placeholders
 from sqlglot.optimizer import traverse_scope
 from sqlglot.optimizer.qualify import qualify
 
 from sqlparser.dialects import Unilake
 from sqlparser.data import (
+    CatalogOutput,
     ScanOutput,
     ScanEntity,
     ScanAttribute,
@@ -21,6 +26,8 @@ from sqlparser.data import (
 )
 
 OUTPUT_DIALECT = "starrocks"
+# Schemas of the emulated system catalog
+CATALOG_SCHEMAS = {"sys", "information_schema"}
 
 
 def _get_dialect(dialect: str) -> str | Type[Unilake]:
@@ -142,6 +149,65 @@ def inner_scan(sql: str, dialect: str, catalog: str, database: str) -> ScanOutpu
         target_entity=target_entity,
     )
 
+
+def inner_split(sql: str, dialect: str) -> list[str]:
+    """Splits a batch into its statements, using the original text of each statement"""
+    tokens = Dialect.get_or_raise(_get_dialect(dialect)).tokenize(sql)
+
+    statements = []
+    start = None
+    end = None
+    for token in tokens:
+        if token.token_type == TokenType.SEMICOLON:
+            if start is not None:
+                statements.append(sql[start : end + 1])
+            start = None
+            continue
+        if start is None:
+            start = token.start
+        end = token.end
+
+    if start is not None:
+        statements.append(sql[start : end + 1])
+    return statements
+
+
+def _to_catalog_value(value):
+    if value is None or isinstance(value, (bool, int, float, str)):
+        return value
+    if isinstance(value, Decimal):
+        return float(value)
+    return str(value)
+
+
+def inner_execute_catalog(sql: str, dialect: str, tables: str) -> CatalogOutput:
+    """Executes a query on the emulated system catalog. The tables are given as json, in the form
+    {"<schema>": {"<table>": [{"<column>": <value>, ...}, ...]}} with lowercase names. Queries
+    referencing any other table are not catalog queries and are not executed."""
+    parsed = parse_one(sql, dialect=_get_dialect(dialect))
+    if not isinstance(parsed, exp.Query):
+        return CatalogOutput.not_catalog_query()
+
+    cte_names = {cte.alias_or_name.lower() for cte in parsed.find_all(exp.CTE)}
+    found = [t for t in parsed.find_all(exp.Table) if t.db or t.name.lower() not in cte_names]
+    if not found or any(t.db.lower() not in CATALOG_SCHEMAS for t in found):
+        return CatalogOutput.not_catalog_query()
+
+    # the catalog is that of the session, e.g. master.sys.databases is read as sys.databases
+    for table in found:
+        table.set("catalog", None)
+        table.set("db", exp.to_identifier(table.db.lower()))
+        table.set("this", exp.to_identifier(table.name.lower()))
+
+    result = execute(parsed, read=_get_dialect(dialect), tables=json.loads(tables))
+    return CatalogOutput(
+        columns=list(result.columns),
+        rows=[[_to_catalog_value(value) for value in row] for row in result.rows],
+        is_catalog_query=True,
+        error=None,
+    )
+
+
 def _transform_filters(node: exp.Select, scope_id: int, filter_lookup: list):
     filters = []
     for (scope, column, filter_definition) in filter_lookup:
@@ -0,0 +1,65 @@
+import json
+import unittest
+
+from sqlparser import execute_catalog
+
+TABLES = json.dumps(
+    {
+        "sys": {
+            "schemas": [
+                {"name": "sales", "schema_id": 1},
+                {"name": "finance", "schema_id": 2},
+            ],
+            "tables": [
+                {"name": "orders", "object_id": 1, "schema_id": 1},
+                {"name": "customers", "object_id": 2, "schema_id": 1},
+                {"name": "ledger", "object_id": 3, "schema_id": 2},
+            ],
+        },
+        "information_schema": {
+            "tables": [
+                {"table_catalog": "lake", "table_schema": "sales", "table_name": "orders"},
+                {"table_catalog": "lake", "table_schema": "sales", "table_name": "customers"},
+                {"table_catalog": "lake", "table_schema": "finance", "table_name": "ledger"},
+            ],
+        },
+    }
+)
+
+
+class TestCatalog(unittest.TestCase):
+    def test_execute_filtered_query(self):
+        sql = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = 'sales' ORDER BY TABLE_NAME"
+        output = execute_catalog(sql, "tsql", TABLES)
+        self.assertTrue(output.is_catalog_query)
+        self.assertIsNone(output.error)
+        self.assertEqual(output.columns, ["table_name"])
+        self.assertEqual(output.rows, [["customers"], ["orders"]])
+
+    def test_execute_join(self):
+        sql = """
+            SELECT s.name AS schema_name, COUNT(*) AS table_count
+            FROM [master].[sys].[tables] t
+            JOIN sys.schemas s ON s.schema_id = t.schema_id
+            GROUP BY s.name
+            ORDER BY s.name
+        """
+        output = execute_catalog(sql, "tsql", TABLES)
+        self.assertIsNone(output.error)
+        self.assertEqual(output.columns, ["schema_name", "table_count"])
+        self.assertEqual(output.rows, [["finance", 1], ["sales", 2]])
+
+    def test_other_queries_are_not_catalog_queries(self):
+        for sql in [
+            "SELECT * FROM sales.orders",
+            "SELECT t.name FROM sys.tables t JOIN sales.orders o ON o.id = t.object_id",
+            "SELECT 1",
+            "UPDATE sys.tables SET name = 'x'",
+            "SELECT * FROM",
+        ]:
+            self.assertFalse(execute_catalog(sql, "tsql", TABLES).is_catalog_query, sql)
+
+    def test_unknown_catalog_table(self):
+        output = execute_catalog("SELECT name FROM sys.procedures", "tsql", TABLES)
+        self.assertTrue(output.is_catalog_query)
+        self.assertIsNotNone(output.error)
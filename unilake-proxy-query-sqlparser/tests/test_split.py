@@ -0,0 +1,23 @@
+import unittest
+
+from sqlparser import split
+
+
+class TestSplit(unittest.TestCase):
+    def test_split_single_statement(self):
+        self.assertEqual(split("select 1", "tsql"), ["select 1"])
+
+    def test_split_empty_input(self):
+        self.assertEqual(split("", "tsql"), [])
+        self.assertEqual(split(" ; ;", "tsql"), [])
+
+    def test_split_multiple_statements(self):
+        sql = "USE db1;\nSELECT a FROM t1 ;SELECT 'x;y' AS b -- trailing; comment\n"
+        self.assertEqual(
+            split(sql, "tsql"),
+            ["USE db1", "SELECT a FROM t1", "SELECT 'x;y' AS b"],
+        )
+
+    def test_split_invalid_input(self):
+        sql = "select 'unterminated; select 2"
+        self.assertEqual(split(sql, "tsql"), [sql])
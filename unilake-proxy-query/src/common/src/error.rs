@@ -82,6 +82,12 @@ pub enum TdsWireError {
     #[error("Error forming TLS connection: {}", _0)]
     /// An error in the TLS handshake.
     Tls(String),
+    #[error("IO error: {}", _0)]
+    /// Reading from or writing to the connection failed.
+    Io(#[from] std::io::Error),
+    #[error("Query was cancelled")]
+    /// The running query was cancelled by the client.
+    Cancelled,
     #[error("Column input failure: {0}")]
     /// Invalid input
     Input(String),
@@ -92,12 +98,6 @@ impl From<TdsWireError> for std::io::Error {
         std::io::Error::new(std::io::ErrorKind::Other, e)
     }
 }
-impl From<std::io::Error> for TdsWireError {
-    fn from(value: std::io::Error) -> Self {
-        println!(" Error occurred: {}", value);
-        todo!()
-    }
-}
 
 pub type TdsWireResult<T> = Result<T, TdsWireError>;
 pub type Error = TdsWireError;
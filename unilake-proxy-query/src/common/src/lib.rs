@@ -1,4 +1,4 @@
 pub mod error;
-mod metrics;
+pub mod metrics;
 pub mod model;
 pub mod settings;
@@ -1 +1,284 @@
-// todo(mrhamburg): prom. metrics for monitoring
+//! Prometheus metrics of the query proxy. The metrics are registered on first use and exposed in
+//! the text format on `/metrics`, see [`serve_metrics`].
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const NAMESPACE: &str = "unilake";
+/// Time a client gets to send its request before the connection is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after a failed accept, so a persistent failure (e.g. out of file descriptors) does not
+/// spin the task
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+pub struct Metrics {
+    registry: Registry,
+    /// Sessions connected to this server instance
+    pub active_sessions: IntGauge,
+    /// Logins by outcome: success or failure
+    pub logins: IntCounterVec,
+    /// Queries by outcome: success, denied, error or cancelled
+    pub queries: IntCounterVec,
+    /// Errors reported to clients by error code
+    pub query_errors: IntCounterVec,
+    pub query_proxy_duration: Histogram,
+    pub query_backend_duration: Histogram,
+    pub rows_streamed: IntCounter,
+    pub bytes_streamed: IntCounter,
+    /// Cache lookups by cache, layer (local, distributed or repository) and result (hit or miss)
+    pub cache_requests: IntCounterVec,
+    /// Duration of the python sql operations by operation: scan, split, transpile, secure or
+    /// catalog
+    pub sql_operation_duration: HistogramVec,
+    /// Backend connections in use by cluster
+    pub backend_connections: IntGaugeVec,
+    /// Failures to get a backend connection by cluster
+    pub backend_connection_errors: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let duration_buckets = exponential_buckets(0.001, 2.0, 16)?;
+        let metrics = Metrics {
+            active_sessions: IntGauge::with_opts(opts(
+                "active_sessions",
+                "Sessions connected to this server instance",
+            ))?,
+            logins: IntCounterVec::new(opts("logins_total", "Logins by outcome"), &["outcome"])?,
+            queries: IntCounterVec::new(opts("queries_total", "Queries by outcome"), &["outcome"])?,
+            query_errors: IntCounterVec::new(
+                opts("query_errors_total", "Errors reported to clients by code"),
+                &["code"],
+            )?,
+            query_proxy_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "query_proxy_duration_seconds",
+                    "Time spent on a query by the proxy",
+                )
+                .namespace(NAMESPACE)
+                .buckets(duration_buckets.clone()),
+            )?,
+            query_backend_duration: Histogram::with_opts(
+                HistogramOpts::new(
+                    "query_backend_duration_seconds",
+                    "Time spent on a query by the backend",
+                )
+                .namespace(NAMESPACE)
+                .buckets(duration_buckets.clone()),
+            )?,
+            rows_streamed: IntCounter::with_opts(opts(
+                "rows_streamed_total",
+                "Rows streamed to clients",
+            ))?,
+            bytes_streamed: IntCounter::with_opts(opts(
+                "bytes_streamed_total",
+                "Bytes of row data streamed to clients",
+            ))?,
+            cache_requests: IntCounterVec::new(
+                opts(
+                    "cache_requests_total",
+                    "Cache lookups by cache, layer and result",
+                ),
+                &["cache", "layer", "result"],
+            )?,
+            sql_operation_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "sql_operation_duration_seconds",
+                    "Duration of the sql parser operations",
+                )
+                .namespace(NAMESPACE)
+                .buckets(duration_buckets),
+                &["operation"],
+            )?,
+            backend_connections: IntGaugeVec::new(
+                opts(
+                    "backend_connections",
+                    "Backend connections in use by cluster",
+                ),
+                &["cluster"],
+            )?,
+            backend_connection_errors: IntCounterVec::new(
+                opts(
+                    "backend_connection_errors_total",
+                    "Failures to get a backend connection by cluster",
+                ),
+                &["cluster"],
+            )?,
+            registry,
+        };
+
+        metrics
+            .registry
+            .register(Box::new(metrics.active_sessions.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.logins.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.queries.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.query_errors.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.query_proxy_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.query_backend_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.rows_streamed.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.bytes_streamed.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.cache_requests.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.sql_operation_duration.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.backend_connections.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.backend_connection_errors.clone()))?;
+        Ok(metrics)
+    }
+
+    pub fn observe_sql_operation(&self, operation: &str, duration: Duration) {
+        self.sql_operation_duration
+            .with_label_values(&[operation])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn inc_cache_request(&self, cache: &str, layer: &str, hit: bool) {
+        let result = match hit {
+            true => "hit",
+            false => "miss",
+        };
+        self.cache_requests
+            .with_label_values(&[cache, layer, result])
+            .inc();
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+
+/// The metrics of this process
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("Failed to register metrics"))
+}
+
+/// Serves the metrics on `GET /metrics`, any other request is answered with a 404. Failures to
+/// accept a connection are logged and do not end the task
+pub async fn serve_metrics(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((socket, _)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = handle_metrics_request(socket).await {
+                        tracing::debug!("Failed to serve metrics request: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::error!("Failed to accept metrics connection: {}", e);
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+            }
+        }
+    }
+}
+
+async fn handle_metrics_request(mut socket: TcpStream) -> std::io::Result<()> {
+    // only the request line is of interest, the request has no body
+    let mut buffer = vec![0u8; 4096];
+    let len = tokio::time::timeout(REQUEST_TIMEOUT, async {
+        let mut len = 0;
+        while !buffer[..len].windows(4).any(|w| w == b"\r\n\r\n") && len < buffer.len() {
+            match socket.read(&mut buffer[len..]).await? {
+                0 => break,
+                read => len += read,
+            }
+        }
+        Ok::<_, std::io::Error>(len)
+    })
+    .await
+    .map_err(|_| {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "Metrics request timed out")
+    })??;
+
+    let request = String::from_utf8_lossy(&buffer[..len]);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) if path.split('?').next() == Some("/metrics") => {
+            ("200 OK", metrics().encode())
+        }
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{metrics, serve_metrics};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn get(address: &str, path: &str) -> String {
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        socket
+            .write_all(format!("GET {} HTTP/1.1\r\nhost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serve_metrics_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve_metrics(listener));
+
+        metrics().logins.with_label_values(&["success"]).inc();
+        metrics().inc_cache_request("UserModel", "local", true);
+
+        let response = get(&address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("unilake_logins_total{outcome=\"success\"}"));
+        assert!(response.contains(
+            "unilake_cache_requests_total{cache=\"UserModel\",layer=\"local\",result=\"hit\"}"
+        ));
+        assert!(response.contains("# TYPE unilake_active_sessions gauge"));
+
+        let response = get(&address, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
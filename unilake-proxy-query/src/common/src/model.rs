@@ -253,3 +253,15 @@ impl PolicyRule {
         ]
     }
 }
+
+#[derive(Serialize)]
+pub struct AuthenticationRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuthenticationResponse {
+    #[serde(rename = "userId")]
+    pub user_id: String,
+}
@@ -35,6 +35,11 @@ pub fn settings_server_api_endpoint() -> String {
         .expect("Could not find 'api_endpoint' in config")
 }
 
+/// Time a request to the api endpoint may take before it fails
+pub fn settings_server_api_timeout_in_ms() -> u64 {
+    global_config().get::<u64>("api_timeout").unwrap_or(10_000)
+}
+
 pub fn settings_cache_invalidation_enabled() -> bool {
     global_config()
         .get::<bool>("cache_invalidation")
@@ -74,3 +79,251 @@ pub fn settings_backend_register_activity_timeout_in_seconds() -> i64 {
         .get::<i64>("backend_register_activity_timeout")
         .unwrap_or(15)
 }
+
+pub fn settings_server_encryption() -> Option<String> {
+    global_config().get_string("server_encryption").ok()
+}
+
+pub fn settings_server_tls_certificate_path() -> Option<String> {
+    global_config().get_string("server_tls_certificate").ok()
+}
+
+pub fn settings_server_tls_private_key_path() -> Option<String> {
+    global_config().get_string("server_tls_private_key").ok()
+}
+
+pub fn settings_server_authentication() -> String {
+    global_config()
+        .get_string("server_authentication")
+        .unwrap_or_else(|_| "api".to_string())
+}
+
+pub fn settings_server_authentication_users_file() -> Option<String> {
+    global_config()
+        .get_string("server_authentication_users_file")
+        .ok()
+}
+
+pub fn settings_server_fedauth_jwks_file() -> Option<String> {
+    global_config().get_string("server_fedauth_jwks_file").ok()
+}
+
+pub fn settings_server_fedauth_issuer() -> Option<String> {
+    global_config().get_string("server_fedauth_issuer").ok()
+}
+
+pub fn settings_server_fedauth_audience() -> Option<String> {
+    global_config().get_string("server_fedauth_audience").ok()
+}
+
+pub fn settings_server_fedauth_user_id_claim() -> String {
+    global_config()
+        .get_string("server_fedauth_user_id_claim")
+        .unwrap_or_else(|_| "sub".to_string())
+}
+
+pub fn settings_server_fedauth_sts_url() -> Option<String> {
+    global_config().get_string("server_fedauth_sts_url").ok()
+}
+
+pub fn settings_server_fedauth_spn() -> Option<String> {
+    global_config().get_string("server_fedauth_spn").ok()
+}
+
+pub fn settings_server_interceptors_file() -> Option<String> {
+    global_config().get_string("server_interceptors_file").ok()
+}
+
+pub fn settings_backend_starrocks_clusters() -> String {
+    global_config()
+        .get_string("backend_starrocks_clusters")
+        .unwrap_or_else(|_| "api".to_string())
+}
+
+pub fn settings_backend_starrocks_clusters_file() -> Option<String> {
+    global_config()
+        .get_string("backend_starrocks_clusters_file")
+        .ok()
+}
+
+pub fn settings_backend_health_check_interval_in_seconds() -> u64 {
+    global_config()
+        .get::<u64>("backend_health_check_interval")
+        .unwrap_or(10)
+}
+
+pub fn settings_server_audit_sinks() -> String {
+    global_config()
+        .get_string("server_audit_sinks")
+        .unwrap_or_default()
+}
+
+pub fn settings_server_audit_file_path() -> Option<String> {
+    global_config().get_string("server_audit_file_path").ok()
+}
+
+pub fn settings_server_audit_file_max_size_in_mb() -> u64 {
+    global_config()
+        .get::<u64>("server_audit_file_max_size")
+        .unwrap_or(100)
+}
+
+pub fn settings_server_audit_file_max_files() -> usize {
+    global_config()
+        .get::<usize>("server_audit_file_max_files")
+        .unwrap_or(10)
+}
+
+pub fn settings_server_audit_webhook_url() -> Option<String> {
+    global_config().get_string("server_audit_webhook_url").ok()
+}
+
+pub fn settings_server_audit_webhook_token() -> Option<String> {
+    global_config()
+        .get_string("server_audit_webhook_token")
+        .ok()
+}
+
+pub fn settings_server_audit_webhook_timeout_in_ms() -> u64 {
+    global_config()
+        .get::<u64>("server_audit_webhook_timeout")
+        .unwrap_or(10_000)
+}
+
+pub fn settings_server_audit_syslog_address() -> Option<String> {
+    global_config()
+        .get_string("server_audit_syslog_address")
+        .ok()
+}
+
+pub fn settings_server_audit_syslog_protocol() -> String {
+    global_config()
+        .get_string("server_audit_syslog_protocol")
+        .unwrap_or_else(|_| "udp".to_string())
+}
+
+pub fn settings_server_audit_batch_size() -> usize {
+    global_config()
+        .get::<usize>("server_audit_batch_size")
+        .unwrap_or(100)
+}
+
+pub fn settings_server_audit_flush_interval_in_ms() -> u64 {
+    global_config()
+        .get::<u64>("server_audit_flush_interval")
+        .unwrap_or(1000)
+}
+
+pub fn settings_server_audit_max_retries() -> u32 {
+    global_config()
+        .get::<u32>("server_audit_max_retries")
+        .unwrap_or(3)
+}
+
+pub fn settings_server_audit_queue_size() -> usize {
+    global_config()
+        .get::<usize>("server_audit_queue_size")
+        .unwrap_or(10_000)
+}
+
+pub fn settings_server_audit_spool_path() -> Option<String> {
+    global_config().get_string("server_audit_spool_path").ok()
+}
+
+pub fn settings_server_audit_spool_max_size_in_mb() -> u64 {
+    global_config()
+        .get::<u64>("server_audit_spool_max_size")
+        .unwrap_or(512)
+}
+
+/// Address of the Prometheus metrics endpoint, an empty value disables the endpoint
+pub fn settings_server_metrics_address() -> String {
+    global_config()
+        .get_string("server_metrics_address")
+        .unwrap_or("0.0.0.0:9090".to_string())
+}
+
+/// Time running requests get to complete on shutdown before they are cancelled
+pub fn settings_server_shutdown_timeout_in_seconds() -> u64 {
+    global_config()
+        .get::<u64>("server_shutdown_timeout")
+        .unwrap_or(30)
+}
+
+/// Maximum number of concurrent sessions of the server instance
+pub fn settings_server_session_limit() -> usize {
+    global_config()
+        .get::<usize>("server_session_limit")
+        .unwrap_or(1000)
+}
+
+/// Maximum number of concurrent sessions of a single user, 0 for no limit
+pub fn settings_server_session_limit_per_user() -> usize {
+    global_config()
+        .get::<usize>("server_session_limit_per_user")
+        .unwrap_or(0)
+}
+
+/// Maximum number of concurrent sessions from a single client address, 0 for no limit
+pub fn settings_server_session_limit_per_address() -> usize {
+    global_config()
+        .get::<usize>("server_session_limit_per_address")
+        .unwrap_or(0)
+}
+
+/// Maximum number of concurrent sessions which have not logged in yet, 0 for no limit
+pub fn settings_server_pending_session_limit() -> usize {
+    global_config()
+        .get::<usize>("server_pending_session_limit")
+        .unwrap_or(100)
+}
+
+/// Time a session gets to log in before its connection is closed, 0 for no timeout
+pub fn settings_server_login_timeout_in_seconds() -> u64 {
+    global_config()
+        .get::<u64>("server_login_timeout")
+        .unwrap_or(60)
+}
+
+/// Maximum number of failed logins from a single client address within the login failure
+/// window, 0 disables the login rate limiter
+pub fn settings_server_login_max_failures() -> usize {
+    global_config()
+        .get::<usize>("server_login_max_failures")
+        .unwrap_or(10)
+}
+
+pub fn settings_server_login_failure_window_in_seconds() -> u64 {
+    global_config()
+        .get::<u64>("server_login_failure_window")
+        .unwrap_or(60)
+}
+
+/// Acknowledge session recovery, so clients reconnect transparently after a connection drop
+pub fn settings_server_session_recovery() -> bool {
+    global_config()
+        .get::<bool>("server_session_recovery")
+        .unwrap_or(true)
+}
+
+/// Key signing the session state sent to clients. Must be shared by all server instances behind
+/// a load balancer, a random key is generated when not configured.
+pub fn settings_server_session_recovery_key() -> Option<String> {
+    global_config()
+        .get_string("server_session_recovery_key")
+        .ok()
+}
+
+/// Packet size of sessions of which the client leaves the packet size up to the server
+pub fn settings_server_packet_size() -> u16 {
+    global_config()
+        .get::<u16>("server_packet_size")
+        .unwrap_or(4096)
+}
+
+/// Maximum packet size a client can negotiate, at most 32767
+pub fn settings_server_max_packet_size() -> u16 {
+    global_config()
+        .get::<u16>("server_max_packet_size")
+        .unwrap_or(32767)
+}
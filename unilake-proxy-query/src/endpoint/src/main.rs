@@ -3,10 +3,22 @@
 use std::env;
 use std::error::Error;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
-use unilake_protocol::backend::starrocks::StarRocksTdsHandlerFactory;
+use unilake_common::metrics::serve_metrics;
+use unilake_common::settings::{
+    settings_server_metrics_address, settings_server_shutdown_timeout_in_seconds,
+};
+use unilake_protocol::audit::audit_pipeline_from_settings;
+use unilake_protocol::auth::{
+    authenticator_from_settings, jwt_validator_from_settings, login_rate_limiter_from_settings,
+};
+use unilake_protocol::backend::app::interceptor::interceptor_registry_from_settings;
+use unilake_protocol::backend::starrocks::{
+    cluster_resolver_from_settings, StarRocksTdsHandlerFactory,
+};
 use unilake_protocol::frontend::codec::process_socket;
 use unilake_protocol::frontend::prot::ServerInstance;
 use unilake_protocol::frontend::tds::server_context::ServerContext;
@@ -30,21 +42,96 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let listener = TcpListener::bind(&addr).await?;
     println!("Listening on: {}", addr);
 
-    // todo(mrhamburg): use bgworker for graceful shutdown
-    let (instance, _) = {
-        let mut instance = ServerInstance::new(ServerContext::default());
+    let metrics_addr = settings_server_metrics_address();
+    if !metrics_addr.is_empty() {
+        let metrics_listener = TcpListener::bind(&metrics_addr).await?;
+        println!("Serving metrics on: {}/metrics", metrics_addr);
+        tokio::spawn(serve_metrics(metrics_listener));
+    }
+
+    let (instance, bgworker) = {
+        let ctx = ServerContext::default()
+            .with_encryption_from_settings()?
+            .with_federated_authentication_from_settings()
+            .with_packet_size_from_settings()
+            .with_session_limits_from_settings()
+            .with_session_recovery_from_settings()?;
+        let mut instance = ServerInstance::new(ctx);
+        if let Some(audit_pipeline) = audit_pipeline_from_settings()? {
+            instance = instance.with_audit_pipeline(audit_pipeline);
+        }
         instance.load_abac_model().await;
         let (instance, bgworker) = instance.start_instance().await;
         (instance, bgworker)
     };
 
-    let factory = Arc::new(StarRocksTdsHandlerFactory::new(instance.clone()));
+    let mut factory = StarRocksTdsHandlerFactory::new(
+        instance.clone(),
+        authenticator_from_settings()?,
+        cluster_resolver_from_settings()?,
+    )
+    .with_interceptors(interceptor_registry_from_settings()?);
+    if let Some(jwt_validator) = jwt_validator_from_settings()? {
+        factory = factory.with_jwt_validator(jwt_validator);
+    }
+    if let Some(login_rate_limiter) = login_rate_limiter_from_settings() {
+        factory = factory.with_login_rate_limiter(login_rate_limiter);
+    }
+    let factory = Arc::new(factory);
+    let tls_acceptor = instance.ctx.tls_acceptor()?.map(Arc::new);
 
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
     loop {
-        let (socket, _) = listener.accept().await?;
+        let (socket, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut shutdown => break,
+        };
         let factory = factory.clone();
         let instance = instance.clone();
+        let tls_acceptor = tls_acceptor.clone();
+
+        tokio::spawn(async move { process_socket(socket, tls_acceptor, factory, instance).await });
+    }
+
+    // stop accepting connections, let running requests complete and flush the audit events
+    drop(listener);
+    instance
+        .shutdown(Duration::from_secs(
+            settings_server_shutdown_timeout_in_seconds(),
+        ))
+        .await;
+    bgworker.await?;
+    println!("Shutdown complete");
+    Ok(())
+}
+
+/// Completes on SIGINT or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-        tokio::spawn(async move { process_socket(socket, None, factory, instance).await });
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
     }
 }
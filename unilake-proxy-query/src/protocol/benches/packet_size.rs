@@ -0,0 +1,66 @@
+//! Throughput of sending a wide result set to a client, using the smallest commonly negotiated
+//! packet size (4K) and the largest packet size (32K).
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::atomic::AtomicU16;
+use std::sync::Arc;
+use tokio_util::bytes::BytesMut;
+use tokio_util::codec::Encoder;
+use unilake_protocol::frontend::codec::TdsWireMessageServerCodec;
+use unilake_protocol::frontend::tds::codec::{ColumnData, TdsBackendResponse, TdsToken, TokenRow};
+
+const ROWS: usize = 1_000;
+const COLUMNS: usize = 50;
+
+fn wide_row(row: usize) -> TokenRow {
+    let mut token = TokenRow::new(COLUMNS, false);
+    for column in 0..COLUMNS {
+        match column % 3 {
+            0 => token.push_row(ColumnData::I64N(Some(row as i64 * column as i64))),
+            1 => token.push_row(ColumnData::F64N(Some(row as f64 / 3.0))),
+            _ => token.push_row(ColumnData::new_varchar(
+                &format!("value {} of column {}", row, column),
+                100,
+            )),
+        }
+    }
+    token
+}
+
+fn send_result_set(codec: &mut TdsWireMessageServerCodec, dst: &mut BytesMut) {
+    for row in 0..ROWS {
+        codec
+            .encode(TdsBackendResponse::Token(TdsToken::Row(wide_row(row))), dst)
+            .unwrap();
+    }
+    codec.encode(TdsBackendResponse::Done, dst).unwrap();
+}
+
+fn packet_size(c: &mut Criterion) {
+    let mut size = BytesMut::new();
+    send_result_set(
+        &mut TdsWireMessageServerCodec::new(Arc::new(AtomicU16::new(4096))),
+        &mut size,
+    );
+
+    let mut group = c.benchmark_group("wide_result_set");
+    group.throughput(Throughput::Bytes(size.len() as u64));
+    for packet_size in [4096u16, 32767] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(packet_size),
+            &packet_size,
+            |b, packet_size| {
+                let mut codec =
+                    TdsWireMessageServerCodec::new(Arc::new(AtomicU16::new(*packet_size)));
+                let mut dst = BytesMut::with_capacity(size.len() * 2);
+                b.iter(|| {
+                    dst.clear();
+                    send_result_set(&mut codec, &mut dst);
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, packet_size);
+criterion_main!(benches);
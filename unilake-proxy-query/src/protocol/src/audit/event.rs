@@ -0,0 +1,223 @@
+use crate::backend::telemetry::QueryTelemetry;
+use crate::frontend::prot::{ServerInstanceMessage, SessionAuditMessage, SessionUserInfo};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+/// Version of the event schema, increased on breaking changes only. New optional fields can be
+/// added without changing the version.
+pub const AUDIT_SCHEMA_VERSION: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventType {
+    /// Query executed, or attempted to execute, by a user
+    SqlQuery,
+    LoginSucceeded,
+    LoginFailed,
+    /// Time spent and records processed by a query
+    QueryTelemetry,
+    /// A backend cluster is in use by a connection
+    ConnectionActivity,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::SqlQuery => "sql_query",
+            AuditEventType::LoginSucceeded => "login_succeeded",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::QueryTelemetry => "query_telemetry",
+            AuditEventType::ConnectionActivity => "connection_activity",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    /// The login failed or the query was rejected, either denied by the policies or invalid
+    Failure,
+}
+
+/// Audit event as shipped to the sinks. Queries are secured before they are added, so literals
+/// never end up in the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub schema_version: u16,
+    pub event_id: String,
+    pub event_type: AuditEventType,
+    /// Time the event was processed, in milliseconds since the unix epoch
+    pub timestamp_utc: i64,
+    pub server: String,
+    pub outcome: AuditOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// Address of the client as `<ip>:<port>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_id: Option<String>,
+    /// Query as received from the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Query as executed on the backend, after applying the policies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executed_query: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_time_in_ms: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_time_in_ms: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub records_processed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_processed: Option<u64>,
+}
+
+impl AuditEvent {
+    pub fn new(event_type: AuditEventType, server: &str, outcome: AuditOutcome) -> Self {
+        AuditEvent {
+            schema_version: AUDIT_SCHEMA_VERSION,
+            event_id: Ulid::new().to_string(),
+            event_type,
+            timestamp_utc: chrono::offset::Utc::now().timestamp_millis(),
+            server: server.to_string(),
+            outcome,
+            user_id: None,
+            client_address: None,
+            query_id: None,
+            query: None,
+            executed_query: None,
+            cluster_id: None,
+            proxy_time_in_ms: None,
+            backend_time_in_ms: None,
+            records_processed: None,
+            bytes_processed: None,
+        }
+    }
+
+    fn with_user(mut self, user_info: &SessionUserInfo) -> Self {
+        self.user_id = Some(user_info.user_id().to_string());
+        self.client_address = Some(user_info.socket_addr().to_string());
+        self
+    }
+
+    /// Converts the server instance message into an audit event, none if the message has
+    /// nothing to audit. The queries of the security handler are secured here, which is why
+    /// this is done by the background workers instead of the session.
+    pub fn from_message(msg: ServerInstanceMessage, server: &str) -> Option<AuditEvent> {
+        match msg {
+            ServerInstanceMessage::Audit(SessionAuditMessage::SqlQuery(user_info, mut handler)) => {
+                // the handler is closed with an empty output query when the query is rejected
+                let executed = handler
+                    .get_output_query()
+                    .is_some_and(|query| !query.is_empty());
+                let outcome = match executed {
+                    true => AuditOutcome::Success,
+                    false => AuditOutcome::Failure,
+                };
+                let mut event = AuditEvent::new(AuditEventType::SqlQuery, server, outcome)
+                    .with_user(&user_info);
+                event.query_id = Some(handler.get_query_id().to_string());
+                event.query = secured_query(handler.secure_input_query().map(str::to_string));
+                if executed {
+                    event.executed_query =
+                        secured_query(handler.secure_output_query().map(str::to_string));
+                }
+                Some(event)
+            }
+            ServerInstanceMessage::Audit(SessionAuditMessage::LoginSucceeded(user_info)) => Some(
+                AuditEvent::new(
+                    AuditEventType::LoginSucceeded,
+                    server,
+                    AuditOutcome::Success,
+                )
+                .with_user(&user_info),
+            ),
+            ServerInstanceMessage::Audit(SessionAuditMessage::LoginFailed(user_info)) => Some(
+                AuditEvent::new(AuditEventType::LoginFailed, server, AuditOutcome::Failure)
+                    .with_user(&user_info),
+            ),
+            ServerInstanceMessage::QueryTelemetry(telemetry) => {
+                Some(AuditEvent::from_telemetry(&telemetry, server))
+            }
+            ServerInstanceMessage::ActivityConnection(cluster_id) => {
+                let mut event = AuditEvent::new(
+                    AuditEventType::ConnectionActivity,
+                    server,
+                    AuditOutcome::Success,
+                );
+                event.cluster_id = Some(cluster_id);
+                Some(event)
+            }
+            ServerInstanceMessage::Telemetry => None,
+        }
+    }
+
+    fn from_telemetry(telemetry: &QueryTelemetry, server: &str) -> AuditEvent {
+        let mut event = AuditEvent::new(
+            AuditEventType::QueryTelemetry,
+            server,
+            AuditOutcome::Success,
+        );
+        event.query_id = telemetry.get_query_id().map(|s| s.to_string());
+        event.proxy_time_in_ms = Some(telemetry.get_proxy_time_in_ms());
+        event.backend_time_in_ms = Some(telemetry.get_backend_time_in_ms());
+        event.records_processed = Some(telemetry.get_records_processed());
+        event.bytes_processed = Some(telemetry.get_bytes_processed());
+        event
+    }
+}
+
+/// The secured query, or none if it could not be secured. The original query is never audited,
+/// since it might contain sensitive literals.
+fn secured_query<E>(query: Result<String, E>) -> Option<String> {
+    match query {
+        Ok(query) => Some(query),
+        Err(_) => {
+            tracing::warn!("Could not secure query for the audit log, leaving it out");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+    use crate::frontend::prot::{ServerInstanceMessage, SessionAuditMessage, SessionUserInfo};
+
+    #[test]
+    fn login_event_schema() {
+        let user_info = SessionUserInfo::new("10.0.0.1:50123".parse().unwrap(), "u-42".into());
+        let mut event = AuditEvent::from_message(
+            ServerInstanceMessage::Audit(SessionAuditMessage::LoginFailed(user_info)),
+            "proxy-1",
+        )
+        .unwrap();
+        assert_eq!(event.event_type, AuditEventType::LoginFailed);
+        assert_eq!(event.outcome, AuditOutcome::Failure);
+
+        event.event_id = "01JBZ6Q1W9K0000000000000000".to_string();
+        event.timestamp_utc = 1730299362000;
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"event_id":"01JBZ6Q1W9K0000000000000000","event_type":"login_failed","timestamp_utc":1730299362000,"server":"proxy-1","outcome":"failure","user_id":"u-42","client_address":"10.0.0.1:50123"}"#
+        );
+        assert_eq!(serde_json::from_str::<AuditEvent>(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn activity_event() {
+        let event = AuditEvent::from_message(
+            ServerInstanceMessage::ActivityConnection("cluster-1".to_string()),
+            "proxy-1",
+        )
+        .unwrap();
+        assert_eq!(event.event_type, AuditEventType::ConnectionActivity);
+        assert_eq!(event.cluster_id.as_deref(), Some("cluster-1"));
+        assert!(AuditEvent::from_message(ServerInstanceMessage::Telemetry, "proxy-1").is_none());
+    }
+}
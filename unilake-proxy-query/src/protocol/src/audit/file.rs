@@ -0,0 +1,172 @@
+use crate::audit::event::AuditEvent;
+use crate::audit::AuditSink;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Writes the events as JSON lines to a local file. Once the file exceeds the maximum size it is
+/// rotated to `<path>.1`, moving older files up to `<path>.<max_files>`, the oldest is removed.
+pub struct FileAuditSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    /// The open file and its current size
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl FileAuditSink {
+    pub fn new(path: PathBuf, max_bytes: u64, max_files: usize) -> Self {
+        FileAuditSink {
+            path,
+            max_bytes,
+            max_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    async fn open(&self) -> TdsWireResult<(File, u64)> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| file_error(&self.path, e))?;
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| file_error(&self.path, e))?;
+        let size = file
+            .metadata()
+            .await
+            .map_err(|e| file_error(&self.path, e))?
+            .len();
+        Ok((file, size))
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    async fn rotate(&self) -> TdsWireResult<()> {
+        if self.max_files == 0 {
+            return tokio::fs::remove_file(&self.path)
+                .await
+                .map_err(|e| file_error(&self.path, e));
+        }
+        let oldest = self.rotated_path(self.max_files);
+        if tokio::fs::try_exists(&oldest).await.unwrap_or(false) {
+            tokio::fs::remove_file(&oldest)
+                .await
+                .map_err(|e| file_error(&oldest, e))?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+                tokio::fs::rename(&from, self.rotated_path(index + 1))
+                    .await
+                    .map_err(|e| file_error(&from, e))?;
+            }
+        }
+        tokio::fs::rename(&self.path, self.rotated_path(1))
+            .await
+            .map_err(|e| file_error(&self.path, e))
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileAuditSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn write(&self, events: &[AuditEvent]) -> TdsWireResult<()> {
+        let mut buffer = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut buffer, event).map_err(|e| {
+                TdsWireError::Protocol(format!("Failed to serialize audit event: {}", e))
+            })?;
+            buffer.push(b'\n');
+        }
+
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            *file = Some(self.open().await?);
+        }
+        if let Some((_, size)) = file.as_ref() {
+            if *size > 0 && size + buffer.len() as u64 > self.max_bytes {
+                *file = None;
+                self.rotate().await?;
+                *file = Some(self.open().await?);
+            }
+        }
+
+        let (handle, size) = file.as_mut().unwrap();
+        let result = match handle.write_all(&buffer).await {
+            Ok(_) => handle.flush().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                *size += buffer.len() as u64;
+                Ok(())
+            }
+            Err(e) => {
+                // reopen the file on the next write
+                *file = None;
+                Err(file_error(&self.path, e))
+            }
+        }
+    }
+}
+
+fn file_error(path: &Path, e: std::io::Error) -> TdsWireError {
+    TdsWireError::Protocol(format!(
+        "Failed to write audit file '{}': {}",
+        path.display(),
+        e
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+    use crate::audit::file::FileAuditSink;
+    use crate::audit::AuditSink;
+
+    #[tokio::test]
+    async fn file_sink_rotates_files() {
+        let dir = std::env::temp_dir().join(format!("audit-{}", ulid::Ulid::new()));
+        let path = dir.join("audit.jsonl");
+        let event = AuditEvent::new(
+            AuditEventType::LoginSucceeded,
+            "proxy",
+            AuditOutcome::Success,
+        );
+        let event_size = serde_json::to_vec(&event).unwrap().len() as u64 + 1;
+
+        // two events per file, keeping a single rotated file
+        let sink = FileAuditSink::new(path.clone(), event_size * 2, 1);
+        for _ in 0..5 {
+            sink.write(std::slice::from_ref(&event)).await.unwrap();
+        }
+
+        let lines = |name: &str| {
+            std::fs::read_to_string(dir.join(name))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<AuditEvent>(line).unwrap())
+                .collect::<Vec<_>>()
+                .len()
+        };
+        assert_eq!(lines("audit.jsonl"), 1);
+        assert_eq!(lines("audit.jsonl.1"), 2);
+        assert!(!dir.join("audit.jsonl.2").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,535 @@
+mod event;
+mod file;
+mod spool;
+mod syslog;
+mod webhook;
+
+pub use event::{AuditEvent, AuditEventType, AuditOutcome, AUDIT_SCHEMA_VERSION};
+pub use file::FileAuditSink;
+pub use spool::AuditSpool;
+pub use syslog::{SyslogAuditSink, SyslogProtocol};
+pub use webhook::WebhookAuditSink;
+
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::settings::{
+    settings_server_audit_batch_size, settings_server_audit_file_max_files,
+    settings_server_audit_file_max_size_in_mb, settings_server_audit_file_path,
+    settings_server_audit_flush_interval_in_ms, settings_server_audit_max_retries,
+    settings_server_audit_queue_size, settings_server_audit_sinks,
+    settings_server_audit_spool_max_size_in_mb, settings_server_audit_spool_path,
+    settings_server_audit_syslog_address, settings_server_audit_syslog_protocol,
+    settings_server_audit_webhook_timeout_in_ms, settings_server_audit_webhook_token,
+    settings_server_audit_webhook_url, settings_server_name,
+};
+
+/// Destination of audit events, such as a file, a webhook or a SIEM
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Name of the sink, also used to name its spool file
+    fn name(&self) -> &str;
+
+    /// Writes the batch of events, on error the complete batch is considered failed
+    async fn write(&self, events: &[AuditEvent]) -> TdsWireResult<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditPipelineConfig {
+    /// Maximum number of events written to a sink at once
+    pub batch_size: usize,
+    /// Events are written at least this often, even if the batch is not full
+    pub flush_interval: Duration,
+    /// Number of retries for a failed batch, the interval doubles for each retry
+    pub max_retries: u32,
+    pub retry_interval: Duration,
+    /// Number of events queued per sink, new events are dropped when the queue is full
+    pub queue_size: usize,
+    /// Directory for the spool files of the sinks, failed batches are dropped if not set
+    pub spool_path: Option<PathBuf>,
+    pub spool_max_bytes: u64,
+}
+
+impl Default for AuditPipelineConfig {
+    fn default() -> Self {
+        AuditPipelineConfig {
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            max_retries: 3,
+            retry_interval: Duration::from_millis(500),
+            queue_size: 10_000,
+            spool_path: None,
+            spool_max_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// Ships audit events to the sinks. Each sink has its own queue and background worker, so a slow
+/// or unavailable sink does not hold up the others. Events are written in batches, failed batches
+/// are retried and then spooled to disk, to be replayed before the next batch.
+pub struct AuditPipeline {
+    senders: Vec<(String, mpsc::Sender<AuditEvent>)>,
+    workers: std::sync::Mutex<Vec<JoinHandle<()>>>,
+    shutdown: CancellationToken,
+    /// Cancelled when the shutdown deadline passes, unwritten events are spooled
+    deadline: CancellationToken,
+}
+
+impl AuditPipeline {
+    /// Starts the background workers for the sinks
+    pub fn start(sinks: Vec<Arc<dyn AuditSink>>, config: AuditPipelineConfig) -> Self {
+        let shutdown = CancellationToken::new();
+        let deadline = CancellationToken::new();
+        let mut senders = Vec::new();
+        let mut workers = Vec::new();
+        for sink in sinks {
+            let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+            senders.push((sink.name().to_string(), sender));
+            let worker = AuditWorker {
+                spool: config.spool_path.as_ref().map(|path| {
+                    AuditSpool::new(
+                        path.join(format!("{}.jsonl", sink.name())),
+                        config.spool_max_bytes,
+                    )
+                }),
+                sink,
+                config: config.clone(),
+                deadline: deadline.clone(),
+            };
+            workers.push(tokio::spawn(worker.run(receiver, shutdown.clone())));
+        }
+        AuditPipeline {
+            senders,
+            workers: std::sync::Mutex::new(workers),
+            shutdown,
+            deadline,
+        }
+    }
+
+    /// Queues the event for all sinks, without waiting for the event to be written
+    pub fn submit(&self, event: AuditEvent) {
+        for (name, sender) in &self.senders {
+            match sender.try_send(event.clone()) {
+                Ok(_) => {}
+                Err(TrySendError::Full(event)) => tracing::error!(
+                    "Audit queue of sink '{}' is full, dropping event {}",
+                    name,
+                    event.event_id
+                ),
+                Err(TrySendError::Closed(event)) => tracing::error!(
+                    "Audit sink '{}' has been stopped, dropping event {}",
+                    name,
+                    event.event_id
+                ),
+            }
+        }
+    }
+
+    /// Stops the workers after writing the queued events. Events which have not been written
+    /// by the deadline are spooled instead.
+    pub async fn shutdown(&self, deadline: Instant) {
+        self.shutdown.cancel();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        let workers = futures::future::join_all(workers);
+        tokio::pin!(workers);
+        let results = match tokio::time::timeout_at(deadline, &mut workers).await {
+            Ok(results) => results,
+            Err(_) => {
+                tracing::warn!("Audit shutdown deadline passed, spooling unwritten events");
+                self.deadline.cancel();
+                workers.await
+            }
+        };
+        for result in results {
+            if let Err(e) = result {
+                tracing::error!("Audit worker failed: {}", e);
+            }
+        }
+    }
+}
+
+struct AuditWorker {
+    sink: Arc<dyn AuditSink>,
+    spool: Option<AuditSpool>,
+    config: AuditPipelineConfig,
+    deadline: CancellationToken,
+}
+
+impl AuditWorker {
+    async fn run(self, mut receiver: mpsc::Receiver<AuditEvent>, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + self.config.flush_interval,
+            self.config.flush_interval,
+        );
+        let mut batch = Vec::with_capacity(self.config.batch_size);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                event = receiver.recv() => match event {
+                    Some(event) => batch.push(event),
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    self.flush(std::mem::take(&mut batch)).await;
+                    continue;
+                }
+            }
+            if batch.len() >= self.config.batch_size {
+                self.flush(std::mem::take(&mut batch)).await;
+            }
+        }
+
+        receiver.close();
+        while let Ok(event) = receiver.try_recv() {
+            batch.push(event);
+        }
+        self.flush(batch).await;
+        tracing::info!("Audit sink '{}' stopped", self.sink.name());
+    }
+
+    /// Writes the spooled events followed by the batch, anything that cannot be written is
+    /// spooled to keep the events in order
+    async fn flush(&self, batch: Vec<AuditEvent>) {
+        let mut events = match &self.spool {
+            Some(spool) if !spool.is_empty().await => match spool.take().await {
+                Ok(spooled) => spooled,
+                Err(e) => {
+                    tracing::error!("Failed to read audit spool: {}", e);
+                    Vec::new()
+                }
+            },
+            _ => Vec::new(),
+        };
+        events.extend(batch);
+
+        let batch_size = self.config.batch_size.max(1);
+        let mut start = 0;
+        while start < events.len() {
+            let end = (start + batch_size).min(events.len());
+            let result = tokio::select! {
+                result = self.write_with_retry(&events[start..end]) => result,
+                _ = self.deadline.cancelled() => Err(TdsWireError::Protocol(
+                    "Shutdown deadline passed".to_string(),
+                )),
+            };
+            if let Err(e) = result {
+                tracing::error!(
+                    "Failed to write audit events to sink '{}': {}",
+                    self.sink.name(),
+                    e
+                );
+                self.spool(&events[start..]).await;
+                return;
+            }
+            start = end;
+        }
+    }
+
+    async fn write_with_retry(&self, events: &[AuditEvent]) -> TdsWireResult<()> {
+        let mut retry_interval = self.config.retry_interval;
+        let mut attempt = 0;
+        loop {
+            match self.sink.write(events).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt >= self.config.max_retries => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        "Writing audit events to sink '{}' failed, retrying: {}",
+                        self.sink.name(),
+                        e
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(retry_interval).await;
+                    retry_interval *= 2;
+                }
+            }
+        }
+    }
+
+    async fn spool(&self, events: &[AuditEvent]) {
+        let Some(spool) = &self.spool else {
+            tracing::error!(
+                "No audit spool configured, dropping {} events for sink '{}'",
+                events.len(),
+                self.sink.name()
+            );
+            return;
+        };
+        match spool.append(events).await {
+            Ok(0) => {}
+            Ok(dropped) => tracing::error!(
+                "Audit spool of sink '{}' is full, dropped {} events",
+                self.sink.name(),
+                dropped
+            ),
+            Err(e) => tracing::error!(
+                "Failed to spool {} audit events for sink '{}': {}",
+                events.len(),
+                self.sink.name(),
+                e
+            ),
+        }
+    }
+}
+
+/// Creates the audit pipeline for the configured sinks, a comma-separated list of "file",
+/// "webhook" and "syslog". None if no sinks are configured.
+pub fn audit_pipeline_from_settings() -> TdsWireResult<Option<AuditPipeline>> {
+    let mut sinks: Vec<Arc<dyn AuditSink>> = Vec::new();
+    for sink in settings_server_audit_sinks()
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+    {
+        match sink.as_str() {
+            "file" => {
+                let path = settings_server_audit_file_path().ok_or_else(|| {
+                    TdsWireError::Input(
+                        "File audit sink requires 'server_audit_file_path'".to_string(),
+                    )
+                })?;
+                sinks.push(Arc::new(FileAuditSink::new(
+                    PathBuf::from(path),
+                    settings_server_audit_file_max_size_in_mb() * 1024 * 1024,
+                    settings_server_audit_file_max_files(),
+                )));
+            }
+            "webhook" => {
+                let url = settings_server_audit_webhook_url().ok_or_else(|| {
+                    TdsWireError::Input(
+                        "Webhook audit sink requires 'server_audit_webhook_url'".to_string(),
+                    )
+                })?;
+                // an unresponsive webhook must fail the batch, instead of holding up the worker
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_millis(
+                        settings_server_audit_webhook_timeout_in_ms(),
+                    ))
+                    .build()
+                    .map_err(|e| {
+                        TdsWireError::Input(format!("Failed to create webhook client: {}", e))
+                    })?;
+                sinks.push(Arc::new(WebhookAuditSink::new(
+                    url,
+                    settings_server_audit_webhook_token(),
+                    client,
+                )));
+            }
+            "syslog" => {
+                let address = settings_server_audit_syslog_address().ok_or_else(|| {
+                    TdsWireError::Input(
+                        "Syslog audit sink requires 'server_audit_syslog_address'".to_string(),
+                    )
+                })?;
+                let protocol = match settings_server_audit_syslog_protocol()
+                    .to_lowercase()
+                    .as_str()
+                {
+                    "udp" => SyslogProtocol::Udp,
+                    "tcp" => SyslogProtocol::Tcp,
+                    other => {
+                        return Err(TdsWireError::Input(format!(
+                            "Unknown syslog protocol '{}'",
+                            other
+                        )))
+                    }
+                };
+                sinks.push(Arc::new(SyslogAuditSink::new(
+                    address,
+                    protocol,
+                    settings_server_name(),
+                )));
+            }
+            other => {
+                return Err(TdsWireError::Input(format!(
+                    "Unknown audit sink '{}'",
+                    other
+                )))
+            }
+        }
+    }
+
+    if sinks.is_empty() {
+        return Ok(None);
+    }
+    let config = AuditPipelineConfig {
+        batch_size: settings_server_audit_batch_size(),
+        flush_interval: Duration::from_millis(settings_server_audit_flush_interval_in_ms()),
+        max_retries: settings_server_audit_max_retries(),
+        queue_size: settings_server_audit_queue_size(),
+        spool_path: settings_server_audit_spool_path().map(PathBuf::from),
+        spool_max_bytes: settings_server_audit_spool_max_size_in_mb() * 1024 * 1024,
+        ..AuditPipelineConfig::default()
+    };
+    Ok(Some(AuditPipeline::start(sinks, config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{
+        AuditEvent, AuditEventType, AuditOutcome, AuditPipeline, AuditPipelineConfig, AuditSink,
+        AuditSpool,
+    };
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use unilake_common::error::{TdsWireError, TdsWireResult};
+
+    /// Sink failing the first writes, recording the events written
+    struct FlakySink {
+        failures: AtomicUsize,
+        written: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AuditSink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn write(&self, events: &[AuditEvent]) -> TdsWireResult<()> {
+            if self.failures.load(Ordering::Relaxed) > 0 {
+                self.failures.fetch_sub(1, Ordering::Relaxed);
+                return Err(TdsWireError::Protocol("unavailable".to_string()));
+            }
+            let mut written = self.written.lock().unwrap();
+            written.extend(events.iter().map(|e| e.user_id.clone().unwrap()));
+            Ok(())
+        }
+    }
+
+    fn event(user_id: &str) -> AuditEvent {
+        let mut event = AuditEvent::new(
+            AuditEventType::LoginSucceeded,
+            "proxy",
+            AuditOutcome::Success,
+        );
+        event.user_id = Some(user_id.to_string());
+        event
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[tokio::test]
+    async fn failed_batches_are_spooled_and_replayed_in_order() {
+        let spool_path = std::env::temp_dir().join(format!("audit-{}", ulid::Ulid::new()));
+        let sink = Arc::new(FlakySink {
+            // fails the first batch including its retry
+            failures: AtomicUsize::new(2),
+            written: Mutex::new(Vec::new()),
+        });
+        let pipeline = AuditPipeline::start(
+            vec![sink.clone()],
+            AuditPipelineConfig {
+                batch_size: 2,
+                flush_interval: Duration::from_secs(60),
+                max_retries: 1,
+                retry_interval: Duration::from_millis(1),
+                spool_path: Some(spool_path.clone()),
+                ..AuditPipelineConfig::default()
+            },
+        );
+
+        pipeline.submit(event("u-1"));
+        pipeline.submit(event("u-2"));
+        wait_for(|| sink.failures.load(Ordering::Relaxed) == 0).await;
+        pipeline.submit(event("u-3"));
+        pipeline
+            .shutdown(Instant::now() + Duration::from_secs(5))
+            .await;
+
+        assert_eq!(*sink.written.lock().unwrap(), vec!["u-1", "u-2", "u-3"]);
+        assert!(!spool_path.join("flaky.jsonl").exists());
+        std::fs::remove_dir_all(spool_path).ok();
+    }
+
+    #[tokio::test]
+    async fn failed_batches_without_spool_are_dropped() {
+        let sink = Arc::new(FlakySink {
+            failures: AtomicUsize::new(1),
+            written: Mutex::new(Vec::new()),
+        });
+        let pipeline = AuditPipeline::start(
+            vec![sink.clone()],
+            AuditPipelineConfig {
+                batch_size: 1,
+                max_retries: 0,
+                ..AuditPipelineConfig::default()
+            },
+        );
+
+        pipeline.submit(event("u-1"));
+        wait_for(|| sink.failures.load(Ordering::Relaxed) == 0).await;
+        pipeline.submit(event("u-2"));
+        pipeline
+            .shutdown(Instant::now() + Duration::from_secs(5))
+            .await;
+        assert_eq!(*sink.written.lock().unwrap(), vec!["u-2"]);
+    }
+
+    /// Sink accepting the events, but never completing the write
+    struct HangingSink;
+
+    #[async_trait]
+    impl AuditSink for HangingSink {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        async fn write(&self, _events: &[AuditEvent]) -> TdsWireResult<()> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn unwritten_events_are_spooled_at_the_deadline() {
+        let spool_path = std::env::temp_dir().join(format!("audit-{}", ulid::Ulid::new()));
+        let pipeline = AuditPipeline::start(
+            vec![Arc::new(HangingSink)],
+            AuditPipelineConfig {
+                batch_size: 10,
+                flush_interval: Duration::from_secs(60),
+                spool_path: Some(spool_path.clone()),
+                ..AuditPipelineConfig::default()
+            },
+        );
+
+        pipeline.submit(event("u-1"));
+        pipeline.submit(event("u-2"));
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            pipeline.shutdown(Instant::now() + Duration::from_millis(100)),
+        )
+        .await
+        .expect("shutdown must complete after the deadline");
+
+        let spooled = AuditSpool::new(spool_path.join("hanging.jsonl"), u64::MAX)
+            .take()
+            .await
+            .unwrap();
+        assert_eq!(
+            spooled
+                .iter()
+                .map(|e| e.user_id.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["u-1", "u-2"]
+        );
+        std::fs::remove_dir_all(spool_path).ok();
+    }
+}
@@ -0,0 +1,130 @@
+use crate::audit::event::AuditEvent;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Bounded JSONL file with the events a sink could not accept, replayed once the sink is
+/// available again. When the spool is full, new events are dropped so the oldest events are
+/// kept in order.
+pub struct AuditSpool {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl AuditSpool {
+    pub fn new(path: PathBuf, max_bytes: u64) -> Self {
+        AuditSpool { path, max_bytes }
+    }
+
+    /// Appends the events to the spool, returns the number of events dropped since the spool is
+    /// full
+    pub async fn append(&self, events: &[AuditEvent]) -> TdsWireResult<usize> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| spool_error("create", e))?;
+        }
+        let mut size = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+
+        let mut buffer = Vec::new();
+        let mut dropped = 0;
+        for event in events {
+            let mut line = serde_json::to_vec(event).map_err(|e| {
+                TdsWireError::Protocol(format!("Failed to serialize audit event: {}", e))
+            })?;
+            line.push(b'\n');
+            if size + line.len() as u64 > self.max_bytes {
+                dropped += 1;
+                continue;
+            }
+            size += line.len() as u64;
+            buffer.extend(line);
+        }
+
+        if !buffer.is_empty() {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await
+                .map_err(|e| spool_error("open", e))?;
+            file.write_all(&buffer)
+                .await
+                .map_err(|e| spool_error("write", e))?;
+            file.flush().await.map_err(|e| spool_error("write", e))?;
+        }
+        Ok(dropped)
+    }
+
+    /// Takes all events from the spool, leaving it empty
+    pub async fn take(&self) -> TdsWireResult<Vec<AuditEvent>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(spool_error("read", e)),
+        };
+        tokio::fs::remove_file(&self.path)
+            .await
+            .map_err(|e| spool_error("remove", e))?;
+
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    tracing::error!("Skipping invalid audit event in spool: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len() == 0,
+            Err(_) => true,
+        }
+    }
+}
+
+fn spool_error(action: &str, e: std::io::Error) -> TdsWireError {
+    TdsWireError::Protocol(format!("Failed to {} audit spool: {}", action, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+    use crate::audit::spool::AuditSpool;
+
+    #[tokio::test]
+    async fn spool_is_bounded_and_keeps_oldest_events() {
+        let path = std::env::temp_dir().join(format!("audit-spool-{}.jsonl", ulid::Ulid::new()));
+        let events = (0..3)
+            .map(|i| {
+                let mut event = AuditEvent::new(
+                    AuditEventType::LoginSucceeded,
+                    "proxy",
+                    AuditOutcome::Success,
+                );
+                event.user_id = Some(format!("u-{}", i));
+                event
+            })
+            .collect::<Vec<_>>();
+        let event_size = serde_json::to_vec(&events[0]).unwrap().len() as u64 + 1;
+
+        let spool = AuditSpool::new(path.clone(), event_size * 2);
+        assert!(spool.is_empty().await);
+        assert_eq!(spool.append(&events[..1]).await.unwrap(), 0);
+        assert_eq!(spool.append(&events[1..]).await.unwrap(), 1);
+        assert!(!spool.is_empty().await);
+
+        assert_eq!(spool.take().await.unwrap(), events[..2].to_vec());
+        assert!(spool.is_empty().await);
+        assert!(spool.take().await.unwrap().is_empty());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+use crate::audit::AuditSink;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat};
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Facility used for all messages: log audit (13)
+const SYSLOG_FACILITY: u8 = 13;
+const SYSLOG_APP_NAME: &str = "unilake-proxy-query";
+const CEF_VENDOR: &str = "Unilake";
+const CEF_PRODUCT: &str = "Unilake Query Proxy";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyslogProtocol {
+    /// A datagram per event
+    Udp,
+    /// Octet-counted framing (RFC 6587)
+    Tcp,
+}
+
+enum SyslogConnection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// Sends the events as CEF messages in RFC 5424 syslog messages, for ingestion by a SIEM
+pub struct SyslogAuditSink {
+    address: String,
+    protocol: SyslogProtocol,
+    hostname: String,
+    connection: Mutex<Option<SyslogConnection>>,
+}
+
+impl SyslogAuditSink {
+    pub fn new(address: String, protocol: SyslogProtocol, hostname: String) -> Self {
+        SyslogAuditSink {
+            address,
+            protocol,
+            hostname,
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> std::io::Result<SyslogConnection> {
+        match self.protocol {
+            SyslogProtocol::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(&self.address).await?;
+                Ok(SyslogConnection::Udp(socket))
+            }
+            SyslogProtocol::Tcp => Ok(SyslogConnection::Tcp(
+                TcpStream::connect(&self.address).await?,
+            )),
+        }
+    }
+
+    async fn send(&self, messages: Vec<String>) -> std::io::Result<()> {
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            *connection = Some(self.connect().await?);
+        }
+        let result = match connection.as_mut().unwrap() {
+            SyslogConnection::Udp(socket) => {
+                let mut result = Ok(());
+                for message in messages {
+                    if let Err(e) = socket.send(message.as_bytes()).await {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                result
+            }
+            SyslogConnection::Tcp(stream) => {
+                let framed = messages
+                    .into_iter()
+                    .map(|message| format!("{} {}", message.len(), message))
+                    .collect::<String>();
+                match stream.write_all(framed.as_bytes()).await {
+                    Ok(_) => stream.flush().await,
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        if result.is_err() {
+            // reconnect on the next write
+            *connection = None;
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl AuditSink for SyslogAuditSink {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    async fn write(&self, events: &[AuditEvent]) -> TdsWireResult<()> {
+        let messages = events
+            .iter()
+            .map(|event| to_syslog_message(event, &self.hostname))
+            .collect();
+        self.send(messages).await.map_err(|e| {
+            TdsWireError::Protocol(format!(
+                "Failed to send audit events to syslog '{}': {}",
+                self.address, e
+            ))
+        })
+    }
+}
+
+/// Formats the event as an RFC 5424 syslog message with a CEF payload
+pub fn to_syslog_message(event: &AuditEvent, hostname: &str) -> String {
+    // warning for failures, informational otherwise
+    let severity = match event.outcome {
+        AuditOutcome::Success => 6,
+        AuditOutcome::Failure => 4,
+    };
+    let timestamp = DateTime::from_timestamp_millis(event.timestamp_utc)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_else(|| "-".to_string());
+    format!(
+        "<{}>1 {} {} {} - {} - {}",
+        SYSLOG_FACILITY * 8 + severity,
+        timestamp,
+        syslog_header_value(hostname),
+        SYSLOG_APP_NAME,
+        event.event_type.as_str(),
+        to_cef(event)
+    )
+}
+
+/// Formats the event as a CEF (Common Event Format) message
+pub fn to_cef(event: &AuditEvent) -> String {
+    let (name, severity) = match (event.event_type, event.outcome) {
+        (AuditEventType::SqlQuery, AuditOutcome::Success) => ("SQL query executed", 3),
+        (AuditEventType::SqlQuery, AuditOutcome::Failure) => ("SQL query rejected", 6),
+        (AuditEventType::LoginSucceeded, _) => ("Login succeeded", 3),
+        (AuditEventType::LoginFailed, _) => ("Login failed", 6),
+        (AuditEventType::QueryTelemetry, _) => ("Query telemetry", 1),
+        (AuditEventType::ConnectionActivity, _) => ("Connection activity", 1),
+    };
+
+    let client_address = event
+        .client_address
+        .as_ref()
+        .and_then(|a| a.parse::<SocketAddr>().ok());
+    let outcome = match event.outcome {
+        AuditOutcome::Success => "success",
+        AuditOutcome::Failure => "failure",
+    };
+    let extensions = [
+        ("rt", Some(event.timestamp_utc.to_string())),
+        ("externalId", Some(event.event_id.clone())),
+        ("dvchost", Some(event.server.clone())),
+        ("outcome", Some(outcome.to_string())),
+        ("suser", event.user_id.clone()),
+        ("src", client_address.map(|a| a.ip().to_string())),
+        ("spt", client_address.map(|a| a.port().to_string())),
+        (
+            "cs1Label",
+            event.query_id.as_ref().map(|_| "queryId".to_string()),
+        ),
+        ("cs1", event.query_id.clone()),
+        (
+            "cs2Label",
+            event.query.as_ref().map(|_| "query".to_string()),
+        ),
+        ("cs2", event.query.clone()),
+        (
+            "cs3Label",
+            event
+                .executed_query
+                .as_ref()
+                .map(|_| "executedQuery".to_string()),
+        ),
+        ("cs3", event.executed_query.clone()),
+        (
+            "cs4Label",
+            event.cluster_id.as_ref().map(|_| "clusterId".to_string()),
+        ),
+        ("cs4", event.cluster_id.clone()),
+        (
+            "cn1Label",
+            event.records_processed.map(|_| "records".to_string()),
+        ),
+        ("cn1", event.records_processed.map(|v| v.to_string())),
+        (
+            "cn2Label",
+            event.proxy_time_in_ms.map(|_| "proxyTimeMs".to_string()),
+        ),
+        ("cn2", event.proxy_time_in_ms.map(|v| v.to_string())),
+        (
+            "cn3Label",
+            event
+                .backend_time_in_ms
+                .map(|_| "backendTimeMs".to_string()),
+        ),
+        ("cn3", event.backend_time_in_ms.map(|v| v.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|v| format!("{}={}", key, cef_extension_value(&v))))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        cef_header_value(CEF_VENDOR),
+        cef_header_value(CEF_PRODUCT),
+        cef_header_value(env!("CARGO_PKG_VERSION")),
+        cef_header_value(event.event_type.as_str()),
+        cef_header_value(name),
+        severity,
+        extensions
+    )
+}
+
+fn cef_header_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn cef_extension_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Header fields are printable ASCII without spaces, `-` if empty
+fn syslog_header_value(value: &str) -> String {
+    let value = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(255)
+        .collect::<String>();
+    match value.is_empty() {
+        true => "-".to_string(),
+        false => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+    use crate::audit::syslog::{to_syslog_message, SyslogAuditSink, SyslogProtocol};
+    use crate::audit::AuditSink;
+    use tokio::net::UdpSocket;
+
+    fn query_event() -> AuditEvent {
+        let mut event = AuditEvent::new(AuditEventType::SqlQuery, "proxy|1", AuditOutcome::Failure);
+        event.event_id = "01JBZ6Q1W9K0000000000000000".to_string();
+        event.timestamp_utc = 1730299362000;
+        event.user_id = Some("u-42".to_string());
+        event.client_address = Some("10.0.0.1:50123".to_string());
+        event.query_id = Some("q-1".to_string());
+        event.query = Some("SELECT a\nFROM b WHERE c = ?".to_string());
+        event
+    }
+
+    #[test]
+    fn format_cef_syslog_message() {
+        assert_eq!(
+            to_syslog_message(&query_event(), "proxy host"),
+            "<108>1 2024-10-30T14:42:42.000Z proxyhost unilake-proxy-query - sql_query - \
+             CEF:0|Unilake|Unilake Query Proxy|0.1.0|sql_query|SQL query rejected|6|\
+             rt=1730299362000 externalId=01JBZ6Q1W9K0000000000000000 dvchost=proxy|1 \
+             outcome=failure suser=u-42 src=10.0.0.1 spt=50123 cs1Label=queryId cs1=q-1 \
+             cs2Label=query cs2=SELECT a\\nFROM b WHERE c \\= ?"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_udp_messages() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sink = SyslogAuditSink::new(
+            receiver.local_addr().unwrap().to_string(),
+            SyslogProtocol::Udp,
+            "proxy".to_string(),
+        );
+        sink.write(&[query_event()]).await.unwrap();
+
+        let mut buffer = [0u8; 2048];
+        let len = receiver.recv(&mut buffer).await.unwrap();
+        let message = String::from_utf8_lossy(&buffer[..len]);
+        assert!(message.starts_with("<108>1 2024-10-30T14:42:42.000Z proxy "));
+        assert!(message.contains("CEF:0|Unilake|"));
+    }
+}
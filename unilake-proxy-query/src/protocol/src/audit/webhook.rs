@@ -0,0 +1,85 @@
+use crate::audit::event::AuditEvent;
+use crate::audit::AuditSink;
+use async_trait::async_trait;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Posts each batch of events as a JSON array to an HTTP endpoint. Any response other than a
+/// success status fails the batch, so it is retried.
+pub struct WebhookAuditSink {
+    url: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookAuditSink {
+    pub fn new(url: String, token: Option<String>, client: reqwest::Client) -> Self {
+        WebhookAuditSink { url, token, client }
+    }
+}
+
+#[async_trait]
+impl AuditSink for WebhookAuditSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn write(&self, events: &[AuditEvent]) -> TdsWireResult<()> {
+        let mut request = self.client.post(&self.url).json(events);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| TdsWireError::Protocol(format!("Failed to send audit events: {}", e)))?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(TdsWireError::Protocol(format!(
+                "Unexpected audit webhook response status: {}",
+                status
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::event::{AuditEvent, AuditEventType, AuditOutcome};
+    use crate::audit::webhook::WebhookAuditSink;
+    use crate::audit::AuditSink;
+    use crate::test_util::serve_once;
+
+    #[tokio::test]
+    async fn webhook_posts_events() {
+        let (endpoint, handle) =
+            serve_once("HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n").await;
+        let sink = WebhookAuditSink::new(
+            format!("{}/audit", endpoint),
+            Some("secret".to_string()),
+            reqwest::Client::new(),
+        );
+        let event = AuditEvent::new(AuditEventType::LoginFailed, "proxy", AuditOutcome::Failure);
+
+        sink.write(std::slice::from_ref(&event)).await.unwrap();
+        let request = handle.await.unwrap();
+        assert!(request.starts_with("POST /audit"));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer secret"));
+        let body = request.split_once("\r\n\r\n").unwrap().1;
+        assert_eq!(
+            serde_json::from_str::<Vec<AuditEvent>>(body).unwrap(),
+            vec![event]
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_fails_on_error_status() {
+        let (endpoint, _) =
+            serve_once("HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n").await;
+        let sink = WebhookAuditSink::new(endpoint, None, reqwest::Client::new());
+        let event = AuditEvent::new(AuditEventType::LoginFailed, "proxy", AuditOutcome::Failure);
+        assert!(sink.write(&[event]).await.is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::auth::{AuthenticationResult, Authenticator};
+use async_trait::async_trait;
+use reqwest::{StatusCode, Url};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::model::{AuthenticationRequest, AuthenticationResponse};
+
+/// Authenticates users against the Unilake API
+pub struct ApiAuthenticator {
+    api_endpoint: String,
+    client: reqwest::Client,
+}
+
+impl ApiAuthenticator {
+    pub fn new(api_endpoint: String, client: reqwest::Client) -> Self {
+        ApiAuthenticator {
+            api_endpoint,
+            client,
+        }
+    }
+
+    /// The tenant id is sent by the client, so it is encoded as a single path segment
+    fn get_path(&self, tenant_id: &str) -> TdsWireResult<Url> {
+        let mut url = Url::parse(&self.api_endpoint)
+            .map_err(|e| TdsWireError::Input(format!("Invalid api endpoint: {}", e)))?;
+        url.path_segments_mut()
+            .map_err(|_| TdsWireError::Input("Invalid api endpoint".to_string()))?
+            .pop_if_empty()
+            .extend(["tenants", tenant_id, "security", "proxy", "authenticate"]);
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Authenticator for ApiAuthenticator {
+    async fn authenticate(
+        &self,
+        tenant_id: &str,
+        username: &str,
+        password: &str,
+    ) -> TdsWireResult<AuthenticationResult> {
+        let response = self
+            .client
+            .post(self.get_path(tenant_id)?)
+            .json(&AuthenticationRequest {
+                username: username.to_string(),
+                password: password.to_string(),
+            })
+            .send()
+            .await
+            .map_err(|e| {
+                TdsWireError::Protocol(format!("Failed to send authentication request: {}", e))
+            })?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let result = response
+                    .json::<AuthenticationResponse>()
+                    .await
+                    .map_err(|e| {
+                        TdsWireError::Protocol(format!(
+                            "Failed to parse authentication response: {}",
+                            e
+                        ))
+                    })?;
+                Ok(AuthenticationResult::Authenticated(result.user_id))
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+                Ok(AuthenticationResult::Denied)
+            }
+            status => Err(TdsWireError::Protocol(format!(
+                "Unexpected authentication response status: {}",
+                status
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{ApiAuthenticator, AuthenticationResult, Authenticator};
+    use crate::test_util::serve_once;
+
+    #[tokio::test]
+    async fn authenticate_succeeds() {
+        let (endpoint, handle) = serve_once(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 17\r\n\r\n{\"userId\":\"u-42\"}",
+        )
+        .await;
+
+        let auth = ApiAuthenticator::new(endpoint, reqwest::Client::new());
+        let result = auth.authenticate("tenant", "alice", "pw").await.unwrap();
+        let request = handle.await.unwrap();
+
+        assert_eq!(
+            result,
+            AuthenticationResult::Authenticated("u-42".to_string())
+        );
+        assert!(request.starts_with("POST /tenants/tenant/security/proxy/authenticate"));
+        assert!(request.contains("\"username\":\"alice\""));
+    }
+
+    #[tokio::test]
+    async fn authenticate_denied() {
+        let (endpoint, _) =
+            serve_once("HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n").await;
+
+        let auth = ApiAuthenticator::new(endpoint, reqwest::Client::new());
+        let result = auth.authenticate("tenant", "alice", "wrong").await.unwrap();
+
+        assert_eq!(result, AuthenticationResult::Denied);
+    }
+
+    #[tokio::test]
+    async fn authenticate_encodes_tenant_id() {
+        let (endpoint, handle) =
+            serve_once("HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n").await;
+
+        let auth = ApiAuthenticator::new(format!("{}/api/", endpoint), reqwest::Client::new());
+        auth.authenticate("../admin?x=1#", "alice", "pw")
+            .await
+            .unwrap();
+        let request = handle.await.unwrap();
+
+        assert!(request
+            .starts_with("POST /api/tenants/..%2Fadmin%3Fx=1%23/security/proxy/authenticate "));
+    }
+}
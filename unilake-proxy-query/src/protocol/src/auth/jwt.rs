@@ -0,0 +1,209 @@
+use crate::auth::AuthenticationResult;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Validates the access tokens received using federated authentication (FEDAUTH). Tokens are
+/// expected to be JWTs signed by one of the keys in the configured JWKS.
+pub struct JwtValidator {
+    keys: JwkSet,
+    issuer: Option<String>,
+    audience: Option<String>,
+    /// Claim containing the Unilake user id
+    user_id_claim: String,
+}
+
+impl JwtValidator {
+    pub fn new(
+        keys: JwkSet,
+        issuer: Option<String>,
+        audience: Option<String>,
+        user_id_claim: String,
+    ) -> TdsWireResult<Self> {
+        // symmetric keys would allow anyone with access to the key set to issue tokens
+        if keys
+            .keys
+            .iter()
+            .any(|k| matches!(k.algorithm, AlgorithmParameters::OctetKey(_)))
+        {
+            return Err(TdsWireError::Input(
+                "Symmetric keys are not supported for federated authentication".to_string(),
+            ));
+        }
+
+        Ok(JwtValidator {
+            keys,
+            issuer,
+            audience,
+            user_id_claim,
+        })
+    }
+
+    pub fn from_file(
+        path: &str,
+        issuer: Option<String>,
+        audience: Option<String>,
+        user_id_claim: String,
+    ) -> TdsWireResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            TdsWireError::Input(format!("Failed to read JWKS file '{}': {}", path, e))
+        })?;
+        let keys = serde_json::from_str::<JwkSet>(&content).map_err(|e| {
+            TdsWireError::Input(format!("Failed to parse JWKS file '{}': {}", path, e))
+        })?;
+        Self::new(keys, issuer, audience, user_id_claim)
+    }
+
+    /// Validates the token and maps the configured claim to the user id
+    pub fn validate(&self, token: &str) -> TdsWireResult<AuthenticationResult> {
+        match self.validate_token(token) {
+            Ok(user_id) => Ok(AuthenticationResult::Authenticated(user_id)),
+            Err(reason) => {
+                tracing::info!("Access token rejected: {}", reason);
+                Ok(AuthenticationResult::Denied)
+            }
+        }
+    }
+
+    fn validate_token(&self, token: &str) -> Result<String, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.to_string())?;
+        let jwk = match header.kid {
+            Some(ref kid) => self.keys.find(kid),
+            None if self.keys.keys.len() == 1 => self.keys.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| "no matching key found".to_string())?;
+
+        // the algorithm of the key takes precedence over the one in the token header
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            if Algorithm::from_str(&key_algorithm.to_string()).ok() != Some(header.alg) {
+                return Err(format!(
+                    "algorithm {:?} does not match the key algorithm",
+                    header.alg
+                ));
+            }
+        }
+
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?;
+        let mut validation = Validation::new(header.alg);
+        match self.issuer {
+            Some(ref issuer) => validation.set_issuer(&[issuer]),
+            None => validation.iss = None,
+        }
+        match self.audience {
+            Some(ref audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<HashMap<String, Value>>(token, &key, &validation)
+            .map_err(|e| e.to_string())?
+            .claims;
+        match claims.get(&self.user_id_claim) {
+            Some(Value::String(user_id)) if !user_id.is_empty() => Ok(user_id.clone()),
+            _ => Err(format!("claim '{}' is missing", self.user_id_claim)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{AuthenticationResult, JwtValidator};
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::jwk::JwkSet;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::json;
+
+    /// Generates a keypair, returns the signing key and the public key as JWKS
+    fn generate_key(kid: &str) -> (EncodingKey, JwkSet) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let jwks = json!({
+            "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": BASE64_URL_SAFE_NO_PAD.encode(pair.public_key().as_ref()),
+            }]
+        });
+        (
+            EncodingKey::from_ed_der(pkcs8.as_ref()),
+            serde_json::from_value(jwks).unwrap(),
+        )
+    }
+
+    fn sign(key: &EncodingKey, kid: &str, audience: &str, expires_in: i64) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_string());
+        let claims = json!({
+            "iss": "https://idp.unilake.local",
+            "aud": audience,
+            "exp": chrono::Utc::now().timestamp() + expires_in,
+            "oid": "user-1",
+        });
+        jsonwebtoken::encode(&header, &claims, key).unwrap()
+    }
+
+    fn get_validator(keys: JwkSet) -> JwtValidator {
+        JwtValidator::new(
+            keys,
+            Some("https://idp.unilake.local".to_string()),
+            Some("unilake-proxy".to_string()),
+            "oid".to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_token() {
+        let (key, keys) = generate_key("key-1");
+        let validator = get_validator(keys);
+
+        assert_eq!(
+            validator
+                .validate(&sign(&key, "key-1", "unilake-proxy", 3600))
+                .unwrap(),
+            AuthenticationResult::Authenticated("user-1".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_token_denied() {
+        let (key, keys) = generate_key("key-1");
+        let (other_key, _) = generate_key("key-1");
+        let validator = get_validator(keys);
+
+        let denied = [
+            // wrong audience
+            sign(&key, "key-1", "other", 3600),
+            // expired
+            sign(&key, "key-1", "unilake-proxy", -3600),
+            // unknown key id
+            sign(&key, "key-2", "unilake-proxy", 3600),
+            // signed by a different key
+            sign(&other_key, "key-1", "unilake-proxy", 3600),
+            "not-a-token".to_string(),
+        ];
+        for token in denied {
+            assert_eq!(
+                validator.validate(&token).unwrap(),
+                AuthenticationResult::Denied
+            );
+        }
+    }
+
+    #[test]
+    fn reject_symmetric_keys() {
+        let keys = serde_json::from_value::<JwkSet>(json!({
+            "keys": [{"kty": "oct", "k": "c2VjcmV0", "kid": "key-1"}]
+        }))
+        .unwrap();
+        assert!(JwtValidator::new(keys, None, None, "sub".to_string()).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::auth::{AuthenticationResult, Authenticator};
+use async_trait::async_trait;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use ring::{hmac, pbkdf2, rand};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+const PBKDF2_SHA256_PREFIX: &str = "pbkdf2_sha256";
+
+/// User entry of the local users file
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocalUser {
+    pub username: String,
+    pub user_id: String,
+    /// Static password, only intended for development purposes
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Hashed password in the format pbkdf2_sha256$<iterations>$<salt>$<hash> (base64 encoded)
+    #[serde(default)]
+    pub password_hash: Option<String>,
+}
+
+#[derive(Clone)]
+enum Credential {
+    /// Static password, stored as a tag so it can be verified in constant time
+    Static(hmac::Tag),
+    Pbkdf2Sha256 {
+        iterations: NonZeroU32,
+        salt: Vec<u8>,
+        hash: Vec<u8>,
+    },
+}
+
+/// Authenticates users against a local JSON file with a list of users
+pub struct LocalAuthenticator {
+    key: hmac::Key,
+    users: HashMap<String, (String, Credential)>,
+    /// Verified for unknown users, so their response time matches the one of existing users
+    dummy: Credential,
+}
+
+impl LocalAuthenticator {
+    pub fn new(users: Vec<LocalUser>) -> TdsWireResult<Self> {
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &rand::SystemRandom::new())
+            .map_err(|_| TdsWireError::Protocol("Failed to generate key".to_string()))?;
+
+        let mut entries = HashMap::new();
+        for user in users {
+            let credential = match (&user.password_hash, &user.password) {
+                (Some(hash), _) => Self::parse_hash(hash).ok_or_else(|| {
+                    TdsWireError::Input(format!(
+                        "Invalid password hash for user '{}'",
+                        user.username
+                    ))
+                })?,
+                (None, Some(password)) => Credential::Static(hmac::sign(&key, password.as_bytes())),
+                (None, None) => {
+                    return Err(TdsWireError::Input(format!(
+                        "No password configured for user '{}'",
+                        user.username
+                    )))
+                }
+            };
+            entries.insert(user.username.to_lowercase(), (user.user_id, credential));
+        }
+
+        // as costly as the most costly hash configured
+        let dummy = entries
+            .values()
+            .filter_map(|(_, credential)| match credential {
+                Credential::Pbkdf2Sha256 { iterations, .. } => Some(*iterations),
+                Credential::Static(_) => None,
+            })
+            .max()
+            .map(|iterations| Credential::Pbkdf2Sha256 {
+                iterations,
+                salt: vec![0; 16],
+                hash: vec![0; 32],
+            })
+            .unwrap_or_else(|| Credential::Static(hmac::sign(&key, b"")));
+
+        Ok(LocalAuthenticator {
+            key,
+            users: entries,
+            dummy,
+        })
+    }
+
+    pub fn from_file(path: &str) -> TdsWireResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            TdsWireError::Input(format!("Failed to read users file '{}': {}", path, e))
+        })?;
+        let users = serde_json::from_str::<Vec<LocalUser>>(&content).map_err(|e| {
+            TdsWireError::Input(format!("Failed to parse users file '{}': {}", path, e))
+        })?;
+        Self::new(users)
+    }
+
+    fn parse_hash(value: &str) -> Option<Credential> {
+        let mut parts = value.split('$');
+        if parts.next()? != PBKDF2_SHA256_PREFIX {
+            return None;
+        }
+        let iterations = NonZeroU32::new(parts.next()?.parse().ok()?)?;
+        let salt = BASE64_STANDARD.decode(parts.next()?).ok()?;
+        let hash = BASE64_STANDARD.decode(parts.next()?).ok()?;
+        if parts.next().is_some() || hash.is_empty() {
+            return None;
+        }
+        Some(Credential::Pbkdf2Sha256 {
+            iterations,
+            salt,
+            hash,
+        })
+    }
+
+    /// Verifies the password against the credential. Hashes are verified on the blocking thread
+    /// pool, as their iterations would otherwise stall the sessions sharing the worker.
+    async fn verify(&self, credential: &Credential, password: &str) -> TdsWireResult<bool> {
+        match credential.clone() {
+            Credential::Static(tag) => {
+                Ok(hmac::verify(&self.key, password.as_bytes(), tag.as_ref()).is_ok())
+            }
+            Credential::Pbkdf2Sha256 {
+                iterations,
+                salt,
+                hash,
+            } => {
+                let password = password.to_string();
+                tokio::task::spawn_blocking(move || {
+                    pbkdf2::verify(
+                        pbkdf2::PBKDF2_HMAC_SHA256,
+                        iterations,
+                        &salt,
+                        password.as_bytes(),
+                        &hash,
+                    )
+                    .is_ok()
+                })
+                .await
+                .map_err(|e| TdsWireError::Protocol(format!("Failed to verify password: {}", e)))
+            }
+        }
+    }
+
+    /// Creates a password hash in the format expected by the users file
+    pub fn hash_password(password: &str, salt: &[u8], iterations: NonZeroU32) -> String {
+        let mut hash = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            password.as_bytes(),
+            &mut hash,
+        );
+        format!(
+            "{}${}${}${}",
+            PBKDF2_SHA256_PREFIX,
+            iterations,
+            BASE64_STANDARD.encode(salt),
+            BASE64_STANDARD.encode(hash)
+        )
+    }
+}
+
+#[async_trait]
+impl Authenticator for LocalAuthenticator {
+    async fn authenticate(
+        &self,
+        _tenant_id: &str,
+        username: &str,
+        password: &str,
+    ) -> TdsWireResult<AuthenticationResult> {
+        let Some((user_id, credential)) = self.users.get(&username.to_lowercase()) else {
+            self.verify(&self.dummy, password).await?;
+            return Ok(AuthenticationResult::Denied);
+        };
+
+        if self.verify(credential, password).await? {
+            Ok(AuthenticationResult::Authenticated(user_id.clone()))
+        } else {
+            Ok(AuthenticationResult::Denied)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Credential;
+    use crate::auth::{AuthenticationResult, Authenticator, LocalAuthenticator, LocalUser};
+    use std::num::NonZeroU32;
+
+    fn get_authenticator() -> LocalAuthenticator {
+        let hash = LocalAuthenticator::hash_password(
+            "s3cret!",
+            b"some-salt",
+            NonZeroU32::new(1000).unwrap(),
+        );
+        LocalAuthenticator::new(vec![
+            LocalUser {
+                username: "static".to_string(),
+                user_id: "user-1".to_string(),
+                password: Some("password".to_string()),
+                password_hash: None,
+            },
+            LocalUser {
+                username: "Hashed".to_string(),
+                user_id: "user-2".to_string(),
+                password: None,
+                password_hash: Some(hash),
+            },
+        ])
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn authenticate_static_password() {
+        let auth = get_authenticator();
+        assert_eq!(
+            auth.authenticate("", "static", "password").await.unwrap(),
+            AuthenticationResult::Authenticated("user-1".to_string())
+        );
+        assert_eq!(
+            auth.authenticate("", "static", "Password").await.unwrap(),
+            AuthenticationResult::Denied
+        );
+    }
+
+    #[tokio::test]
+    async fn authenticate_hashed_password() {
+        let auth = get_authenticator();
+        assert_eq!(
+            auth.authenticate("", "hashed", "s3cret!").await.unwrap(),
+            AuthenticationResult::Authenticated("user-2".to_string())
+        );
+        assert_eq!(
+            auth.authenticate("", "hashed", "s3cret").await.unwrap(),
+            AuthenticationResult::Denied
+        );
+        assert_eq!(
+            auth.authenticate("", "unknown", "s3cret!").await.unwrap(),
+            AuthenticationResult::Denied
+        );
+    }
+
+    #[test]
+    fn unknown_users_verify_dummy_hash() {
+        // unknown users are as costly to verify as the most costly hash
+        match get_authenticator().dummy {
+            Credential::Pbkdf2Sha256 { iterations, .. } => assert_eq!(iterations.get(), 1000),
+            Credential::Static(_) => panic!("expected a dummy hash"),
+        }
+    }
+
+    #[test]
+    fn parse_users_file() {
+        let users = serde_json::from_str::<Vec<LocalUser>>(
+            r#"[{"username": "a", "user_id": "1", "password": "x"},
+                {"username": "b", "user_id": "2", "password_hash": "md5$abc"}]"#,
+        )
+        .unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(LocalAuthenticator::new(users).is_err());
+    }
+}
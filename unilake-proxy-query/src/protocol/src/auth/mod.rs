@@ -0,0 +1,104 @@
+mod api;
+mod jwt;
+mod local;
+mod rate_limit;
+
+pub use api::ApiAuthenticator;
+pub use jwt::JwtValidator;
+pub use local::{LocalAuthenticator, LocalUser};
+pub use rate_limit::LoginRateLimiter;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::settings::{
+    settings_server_api_endpoint, settings_server_api_timeout_in_ms,
+    settings_server_authentication, settings_server_authentication_users_file,
+    settings_server_fedauth_audience, settings_server_fedauth_issuer,
+    settings_server_fedauth_jwks_file, settings_server_fedauth_user_id_claim,
+    settings_server_login_failure_window_in_seconds, settings_server_login_max_failures,
+    settings_server_login_timeout_in_seconds,
+};
+
+/// Result of validating the credentials of a SQL authentication login
+#[derive(Debug, PartialEq)]
+pub enum AuthenticationResult {
+    /// Credentials are valid, contains the user id of the authenticated user
+    Authenticated(String),
+    /// Credentials are invalid or the user is unknown
+    Denied,
+}
+
+/// Validates the credentials received in the LOGIN7 message (SQL authentication). The password
+/// has already been de-obfuscated when decoding the LOGIN7 message.
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    async fn authenticate(
+        &self,
+        tenant_id: &str,
+        username: &str,
+        password: &str,
+    ) -> TdsWireResult<AuthenticationResult>;
+}
+
+/// Creates the configured authenticator, either "api" (default) or "file"
+pub fn authenticator_from_settings() -> TdsWireResult<Arc<dyn Authenticator>> {
+    match settings_server_authentication().to_lowercase().as_str() {
+        "api" => {
+            // the request must fail before the login times out, so the client gets a login error
+            let mut timeout = Duration::from_millis(settings_server_api_timeout_in_ms());
+            let login_timeout = settings_server_login_timeout_in_seconds();
+            if login_timeout > 0 {
+                timeout = timeout.min(Duration::from_secs(login_timeout) / 2);
+            }
+            let client = reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .map_err(|e| {
+                    TdsWireError::Input(format!("Failed to create authentication client: {}", e))
+                })?;
+            Ok(Arc::new(ApiAuthenticator::new(
+                settings_server_api_endpoint(),
+                client,
+            )))
+        }
+        "file" => {
+            let path = settings_server_authentication_users_file().ok_or_else(|| {
+                TdsWireError::Input(
+                    "File authentication requires 'server_authentication_users_file'".to_string(),
+                )
+            })?;
+            Ok(Arc::new(LocalAuthenticator::from_file(&path)?))
+        }
+        other => Err(TdsWireError::Input(format!(
+            "Unknown authentication method '{}'",
+            other
+        ))),
+    }
+}
+
+/// Creates the validator for federated authentication, none if federated authentication is not
+/// configured
+pub fn jwt_validator_from_settings() -> TdsWireResult<Option<Arc<JwtValidator>>> {
+    match settings_server_fedauth_jwks_file() {
+        Some(path) => Ok(Some(Arc::new(JwtValidator::from_file(
+            &path,
+            settings_server_fedauth_issuer(),
+            settings_server_fedauth_audience(),
+            settings_server_fedauth_user_id_claim(),
+        )?))),
+        None => Ok(None),
+    }
+}
+
+/// Creates the login rate limiter, none if it has been disabled
+pub fn login_rate_limiter_from_settings() -> Option<Arc<LoginRateLimiter>> {
+    match settings_server_login_max_failures() {
+        0 => None,
+        max_failures => Some(Arc::new(LoginRateLimiter::new(
+            max_failures,
+            Duration::from_secs(settings_server_login_failure_window_in_seconds()),
+        ))),
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of tracked addresses after which expired failures of all addresses are removed
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Limits the failed logins per client address within a sliding window. Once the limit has been
+/// reached, logins from the address are rejected without authenticating until failures expire
+/// from the window, so guessing passwords for many users (password spraying) is slowed down.
+pub struct LoginRateLimiter {
+    max_failures: usize,
+    window: Duration,
+    failures: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl LoginRateLimiter {
+    pub fn new(max_failures: usize, window: Duration) -> Self {
+        LoginRateLimiter {
+            max_failures,
+            window,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether logins from the address are rejected
+    pub fn is_limited(&self, address: IpAddr) -> bool {
+        self.is_limited_at(address, Instant::now())
+    }
+
+    pub fn record_failure(&self, address: IpAddr) {
+        self.record_failure_at(address, Instant::now())
+    }
+
+    fn is_limited_at(&self, address: IpAddr, now: Instant) -> bool {
+        let mut failures = self.failures.lock().unwrap();
+        match failures.get_mut(&address) {
+            Some(attempts) => {
+                self.expire(attempts, now);
+                attempts.len() >= self.max_failures
+            }
+            None => false,
+        }
+    }
+
+    fn record_failure_at(&self, address: IpAddr, now: Instant) {
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() >= PRUNE_THRESHOLD {
+            failures.retain(|_, attempts| {
+                self.expire(attempts, now);
+                !attempts.is_empty()
+            });
+        }
+
+        let attempts = failures.entry(address).or_default();
+        self.expire(attempts, now);
+        attempts.push_back(now);
+        // older failures than the limit do not affect the outcome
+        while attempts.len() > self.max_failures {
+            attempts.pop_front();
+        }
+    }
+
+    fn expire(&self, attempts: &mut VecDeque<Instant>, now: Instant) {
+        while attempts
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.window)
+        {
+            attempts.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::rate_limit::LoginRateLimiter;
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn limit_failed_logins_per_address() {
+        let limiter = LoginRateLimiter::new(3, Duration::from_secs(60));
+        let address: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        for i in 0..3 {
+            assert!(!limiter.is_limited_at(address, start));
+            limiter.record_failure_at(address, start + Duration::from_secs(i * 10));
+        }
+        assert!(limiter.is_limited_at(address, start + Duration::from_secs(30)));
+        assert!(!limiter.is_limited_at(other, start + Duration::from_secs(30)));
+
+        // the first failure expires from the window
+        assert!(limiter.is_limited_at(address, start + Duration::from_secs(59)));
+        assert!(!limiter.is_limited_at(address, start + Duration::from_secs(60)));
+
+        // a new failure reaches the limit again
+        limiter.record_failure_at(address, start + Duration::from_secs(61));
+        assert!(limiter.is_limited_at(address, start + Duration::from_secs(61)));
+    }
+}
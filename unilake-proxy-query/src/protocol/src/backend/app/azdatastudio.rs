@@ -1 +1,260 @@
+//! Probes sent by Azure Data Studio (and other clients built on the SQL Tools Service) when
+//! connecting and when opening the server dashboard.
+use crate::backend::app::generic::{FedResult, ResultSetBuilder};
+use crate::backend::app::interceptor::{
+    single_result, InterceptorHandler, InterceptorRuleConfig, QueryPattern,
+};
+use crate::backend::app::FedResultStream;
+use crate::frontend::sqlstring::SqlString;
+use crate::frontend::{BatchRequest, ColumnData, DataFlags, TypeInfo};
+use async_stream::stream;
 
+pub(crate) const HANDLERS: &[(&str, InterceptorHandler)] = &[
+    ("server_edition", server_edition),
+    ("backup_info", backup_info),
+    ("database_size_info", database_size_info),
+];
+
+/// The rules match the complete probe, so a batch which merely contains a fragment of a probe is
+/// still executed by the backend
+pub(crate) fn default_rules() -> Vec<InterceptorRuleConfig> {
+    vec![
+        // server version query executed on connect
+        InterceptorRuleConfig::new(
+            "server_edition",
+            QueryPattern::Regex(
+                concat!(
+                    r"^declare @edition sysname;",
+                    r"set @edition=cast\(serverproperty\(n'edition'\)as sysname\);",
+                    r"select [^;]*serverproperty\('engineedition'\)as databaseengineedition,",
+                    r"[^;]*@@microsoftversion as microsoftversion;",
+                    r"select host_platform from sys\.dm_os_host_info ",
+                    r"if @edition=n'sql azure' select 'tcp' as connectionprotocol ",
+                    r"else exec\('select convert\(nvarchar\(40\),",
+                    r"connectionproperty\(''net_transport''\)\) as connectionprotocol'\)$",
+                )
+                .to_string(),
+            ),
+            "server_edition",
+        ),
+        // backup status widget of the server dashboard
+        InterceptorRuleConfig::new(
+            "backup_info",
+            QueryPattern::Regex(
+                concat!(
+                    r"^declare @condition tinyint;set @condition=24;",
+                    r"with backupinsight_cte\([^;]*\)",
+                    r"select [^;]*as within 24hrs,[^;]*as older than 24hrs,",
+                    r"[^;]*as no backup found from backupinsight_cte$",
+                )
+                .to_string(),
+            ),
+            "backup_info",
+        ),
+        // database size widget of the server dashboard
+        InterceptorRuleConfig::new(
+            "database_size_info",
+            QueryPattern::Regex(
+                concat!(
+                    r"^with fs as\(select database_id,type,size\*8\.0/1024 size ",
+                    r"from sys\.master_files\)",
+                    r"select name,[^;]*\)datafilesizemb,[^;]*\)logfilesizemb ",
+                    r"from sys\.databases db$",
+                )
+                .to_string(),
+            ),
+            "database_size_info",
+        ),
+    ]
+}
+
+fn server_edition(_req: &BatchRequest) -> FedResultStream {
+    let stream = stream! {
+    let result_set = ResultSetBuilder::new()
+        .add_column(
+            Some("DatabaseEngineType"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("DatabaseEngineEdition"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("ProductVersion"),
+            TypeInfo::new_nvarchar(255),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("MicrosoftVersion"),
+            TypeInfo::new_nvarchar(255),
+            DataFlags::default(),
+        )
+        .add_row(&[
+            ColumnData::I32(1),
+            ColumnData::I32(3),
+            ColumnData::new_varchar("16.0.4140.4", 255),
+            ColumnData::I32(268439596),
+        ]);
+
+        // first resultset
+        yield Ok(FedResult::Tabular(result_set.result));
+
+       let result_set = ResultSetBuilder::new()
+            .add_column(Some("host_platform"), TypeInfo::new_nvarchar(255), DataFlags::default())
+            .add_row(&[ColumnData::new_varchar("Linux", 255)]);
+
+        // second resultset
+        yield Ok(FedResult::Tabular(result_set.result));
+
+        let result_set = ResultSetBuilder::new()
+            .add_column(Some("ConnectionProtocol"), TypeInfo::new_nvarchar(255), DataFlags::default())
+            .add_row(&[ColumnData::new_varchar("TCP", 255)]);
+
+        // third resultset
+        yield Ok(FedResult::Tabular(result_set.result));
+    };
+
+    FedResultStream::new(Box::pin(stream))
+}
+
+fn backup_info(_req: &BatchRequest) -> FedResultStream {
+    let result_set = ResultSetBuilder::new()
+        .add_column(
+            Some("Within 24hrs"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("Older than 24hrs"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("No backup found"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_row(&[ColumnData::I32(0), ColumnData::I32(0), ColumnData::I32(0)]);
+
+    single_result(FedResult::Tabular(result_set.result))
+}
+
+fn database_size_info(_: &BatchRequest) -> FedResultStream {
+    let result_set = ResultSetBuilder::new()
+        .add_column(
+            Some("name"),
+            TypeInfo::new_nvarchar(255),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("DataFileSizeMB"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_column(
+            Some("LogFileSizeMB"),
+            TypeInfo::new_int(false),
+            DataFlags::default(),
+        )
+        .add_row(&[
+            ColumnData::String(SqlString::from_string(
+                Some("default_catalog".to_string()),
+                255,
+            )),
+            ColumnData::I32(0),
+            ColumnData::I32(0),
+        ]);
+
+    single_result(FedResult::Tabular(result_set.result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::interceptor::intercept_query;
+
+    #[tokio::test]
+    async fn intercept_server_edition() {
+        let query = "DECLARE @edition sysname; SET @edition = cast(SERVERPROPERTY(N'EDITION') as sysname);
+            SELECT case when @edition = N'SQL Azure' then 2 else 1 end as 'DatabaseEngineType',
+            SERVERPROPERTY('EngineEdition') AS DatabaseEngineEdition,
+            SERVERPROPERTY('ProductVersion') AS ProductVersion,
+            @@MICROSOFTVERSION AS MicrosoftVersion;
+            select host_platform from sys.dm_os_host_info
+            if @edition = N'SQL Azure'
+              select 'TCP' as ConnectionProtocol
+            else
+              exec ('select CONVERT(nvarchar(40),CONNECTIONPROPERTY(''net_transport'')) as ConnectionProtocol')";
+        let (rule, result_sets) = intercept_query(query).await.unwrap();
+        assert_eq!(rule, "server_edition");
+        assert_eq!(
+            result_sets,
+            vec![
+                vec![
+                    "DatabaseEngineType",
+                    "DatabaseEngineEdition",
+                    "ProductVersion",
+                    "MicrosoftVersion"
+                ],
+                vec!["host_platform"],
+                vec!["ConnectionProtocol"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn intercept_backup_info() {
+        let query = "declare @condition tinyint;
+            SET @condition = 24;
+            with backupInsight_cte (database_id, last_backup, health_check) as (
+              select d.database_id, max(b.backup_start_date) AS last_backup,
+                case when (datediff(hh, max(b.backup_start_date), getdate()) < @condition) then 1 else 0 end
+              from sys.databases as d
+              left join msdb..backupset as b on d.name = b.database_name
+              group by d.database_id
+            )
+            select
+              sum(health_check) AS [Within 24hrs],
+              sum(case when health_check = 0 AND last_backup IS NOT NULL then 1 else 0 end) AS [Older than 24hrs],
+              sum(case when health_check = 0 AND last_backup IS NULL then 1 else 0 end) AS [No backup found]
+            from backupInsight_cte";
+        let (rule, result_sets) = intercept_query(query).await.unwrap();
+        assert_eq!(rule, "backup_info");
+        assert_eq!(
+            result_sets,
+            vec![vec!["Within 24hrs", "Older than 24hrs", "No backup found"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn intercept_database_size_info() {
+        let query = "with fs as (
+              select database_id, type, size * 8.0 / 1024 size
+              from sys.master_files
+            )
+            select
+              name,
+              (select sum(size) from fs where type = 0 and fs.database_id = db.database_id) DataFileSizeMB,
+              (select sum(size) from fs where type = 1 and fs.database_id = db.database_id) LogFileSizeMB
+            from sys.databases db";
+        let (rule, result_sets) = intercept_query(query).await.unwrap();
+        assert_eq!(rule, "database_size_info");
+        assert_eq!(
+            result_sets,
+            vec![vec!["name", "DataFileSizeMB", "LogFileSizeMB"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_containing_probe_fragments_are_not_intercepted() {
+        let queries = [
+            "select 'within 24hrs', 'older than 24hrs', 'no backup found' from sales.backups",
+            "select DataFileSizeMB, LogFileSizeMB from sys.master_files",
+            "select DatabaseEngineEdition, @@MICROSOFTVERSION, host_platform from sales.servers",
+        ];
+        for query in queries {
+            assert!(intercept_query(query).await.is_none(), "{}", query);
+        }
+    }
+}
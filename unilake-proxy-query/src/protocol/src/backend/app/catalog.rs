@@ -0,0 +1,800 @@
+//! Emulated SQL Server system catalog, answering queries on `sys.*` and `INFORMATION_SCHEMA.*`
+//! views as sent by object explorers and BI tools. The catalog is read from the StarRocks
+//! catalog of the session and reduced to the tables and columns the user may see, after which
+//! the query is executed on the emulated views by the sql parser.
+//!
+//! Names are mapped the same way as for regular queries: the StarRocks catalog is the SQL Server
+//! database and a StarRocks database is a SQL Server schema.
+//!
+//! | View                          | Rows                                             |
+//! |-------------------------------|--------------------------------------------------|
+//! | sys.databases                 | the catalog of the session                       |
+//! | sys.schemas                   | databases with at least one visible table        |
+//! | sys.tables, sys.objects       | visible tables                                   |
+//! | sys.columns                   | visible columns                                  |
+//! | sys.types                     | types the StarRocks types are mapped to          |
+//! | INFORMATION_SCHEMA.SCHEMATA   | databases with at least one visible table        |
+//! | INFORMATION_SCHEMA.TABLES     | visible tables                                   |
+//! | INFORMATION_SCHEMA.COLUMNS    | visible columns                                  |
+use crate::backend::app::generic::{ResultSet, ResultSetBuilder};
+use crate::backend::app::interceptor::normalize_query;
+use crate::backend::engine::BackendConnection;
+use crate::frontend::sqlstring::SqlString;
+use crate::frontend::{ColumnData, DataFlags, TokenRow, TypeInfo};
+use bigdecimal::ToPrimitive;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_sql::{Catalog, CatalogOutput, CatalogValue};
+
+/// Schema id of the `sys` schema, which owns the system types
+const SYS_SCHEMA_ID: i64 = 4;
+/// Schema id of the first user schema, the ids before are reserved for the schemas of SQL
+/// Server itself (dbo, guest, INFORMATION_SCHEMA and sys)
+const FIRST_USER_SCHEMA_ID: i64 = 5;
+/// Maximum length of a (non max) nvarchar, in characters
+const MAX_NVARCHAR_LENGTH: i64 = 4000;
+/// Length of the type of max data types
+const MAX_LENGTH: usize = 0xFFFF;
+
+/// Returns true if the query references a view of the system catalog, string literals are
+/// not taken into account
+pub fn is_catalog_query(query: &str) -> bool {
+    static LITERAL: OnceLock<Regex> = OnceLock::new();
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let query = normalize_query(query);
+    let query = LITERAL
+        .get_or_init(|| Regex::new(r"'[^']*'").unwrap())
+        .replace_all(&query, "''");
+    PATTERN
+        .get_or_init(|| Regex::new(r"\b(sys|information_schema)\.[a-z_]").unwrap())
+        .is_match(&query)
+}
+
+/// Column of a table, as described by the StarRocks `information_schema.columns`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogColumn {
+    pub name: String,
+    /// StarRocks data type, e.g. `varchar`
+    pub data_type: String,
+    pub is_nullable: bool,
+    pub max_length: Option<i64>,
+    pub precision: Option<i64>,
+    pub scale: Option<i64>,
+}
+
+/// SQL Server type of a column
+pub(crate) struct SqlServerType {
+    pub(crate) name: &'static str,
+    pub(crate) system_type_id: i64,
+    /// Length in bytes, -1 for max types
+    pub(crate) max_length: i64,
+    pub(crate) precision: i64,
+    pub(crate) scale: i64,
+}
+
+impl SqlServerType {
+    const fn new(
+        name: &'static str,
+        system_type_id: i64,
+        max_length: i64,
+        precision: i64,
+        scale: i64,
+    ) -> Self {
+        SqlServerType {
+            name,
+            system_type_id,
+            max_length,
+            precision,
+            scale,
+        }
+    }
+
+    /// The type a StarRocks column is sent as, see the mapping of the MySQL backend
+    pub(crate) fn from_column(column: &CatalogColumn) -> Self {
+        match column.data_type.to_lowercase().as_str() {
+            "boolean" => Self::new("bit", 104, 1, 1, 0),
+            "tinyint" | "smallint" => Self::new("smallint", 52, 2, 5, 0),
+            "int" => Self::new("int", 56, 4, 10, 0),
+            "bigint" => Self::new("bigint", 127, 8, 19, 0),
+            "float" => Self::new("real", 59, 4, 24, 0),
+            "double" => Self::new("float", 62, 8, 53, 0),
+            "date" => Self::new("date", 40, 3, 10, 0),
+            "time" => Self::new("time", 41, 5, 16, 7),
+            "datetime" | "timestamp" => Self::new("datetime2", 42, 8, 27, 7),
+            "binary" | "varbinary" => Self::new("varbinary", 165, -1, 0, 0),
+            t if t.starts_with("decimal") => match (column.precision, column.scale) {
+                (Some(p), Some(s)) if p <= 38 => {
+                    let length = match p {
+                        1..=9 => 5,
+                        10..=19 => 9,
+                        20..=28 => 13,
+                        _ => 17,
+                    };
+                    Self::new("decimal", 106, length, p, s)
+                }
+                (p, _) => Self::nvarchar(p.map(|p| p + 2)),
+            },
+            "char" | "varchar" | "string" => Self::nvarchar(column.max_length),
+            "largeint" => Self::nvarchar(Some(40)),
+            _ => Self::nvarchar(None),
+        }
+    }
+
+    fn nvarchar(length: Option<i64>) -> Self {
+        match length {
+            Some(length) if length > 0 && length <= MAX_NVARCHAR_LENGTH => {
+                Self::new("nvarchar", 231, length * 2, 0, 0)
+            }
+            _ => Self::new("nvarchar", 231, -1, 0, 0),
+        }
+    }
+
+    pub(crate) fn is_text(&self) -> bool {
+        self.system_type_id == 231
+    }
+
+    pub(crate) fn is_numeric(&self) -> bool {
+        !self.is_text() && !matches!(self.system_type_id, 40..=42 | 165)
+    }
+
+    /// All types columns can be mapped to, for `sys.types`
+    fn all() -> [SqlServerType; 12] {
+        [
+            Self::new("bit", 104, 1, 1, 0),
+            Self::new("smallint", 52, 2, 5, 0),
+            Self::new("int", 56, 4, 10, 0),
+            Self::new("bigint", 127, 8, 19, 0),
+            Self::new("real", 59, 4, 24, 0),
+            Self::new("float", 62, 8, 53, 0),
+            Self::new("decimal", 106, 17, 38, 38),
+            Self::new("date", 40, 3, 10, 0),
+            Self::new("time", 41, 5, 16, 7),
+            Self::new("datetime2", 42, 8, 27, 7),
+            Self::new("nvarchar", 231, 8000, 0, 0),
+            Self::new("varbinary", 165, 8000, 0, 0),
+        ]
+    }
+}
+
+/// Tables and columns of a StarRocks catalog
+#[derive(Debug, Default)]
+pub struct VirtualCatalog {
+    name: String,
+    /// Columns by schema and table name, in ordinal order
+    tables: BTreeMap<(String, String), Vec<CatalogColumn>>,
+}
+
+impl VirtualCatalog {
+    pub fn new(name: &str) -> Self {
+        VirtualCatalog {
+            name: name.to_string(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Query describing all columns of the catalog
+    pub fn get_columns_query(name: &str) -> String {
+        format!(
+            "SELECT TABLE_SCHEMA, TABLE_NAME, COLUMN_NAME, DATA_TYPE, IS_NULLABLE, \
+            CHARACTER_MAXIMUM_LENGTH, NUMERIC_PRECISION, NUMERIC_SCALE \
+            FROM `{}`.information_schema.columns \
+            WHERE TABLE_SCHEMA NOT IN ('information_schema', 'sys', '_statistics_') \
+            ORDER BY TABLE_SCHEMA, TABLE_NAME, ORDINAL_POSITION",
+            name.replace('`', "``")
+        )
+    }
+
+    /// Read the tables and columns of the catalog from the backend
+    pub async fn read(
+        conn: &mut dyn BackendConnection,
+        name: &str,
+        cancellation_token: CancellationToken,
+    ) -> TdsWireResult<Self> {
+        let mut catalog = VirtualCatalog::new(name);
+        let mut result = conn
+            .execute(&Self::get_columns_query(name), cancellation_token)
+            .await?;
+        while let Some(row) = result.rows.next().await {
+            catalog.add_row(&row?)?;
+        }
+        Ok(catalog)
+    }
+
+    fn add_row(&mut self, row: &TokenRow) -> TdsWireResult<()> {
+        let text = |i: usize| row.get(i).and_then(get_text);
+        let number = |i: usize| row.get(i).and_then(get_number);
+        let (Some(schema), Some(table), Some(name), Some(data_type)) =
+            (text(0), text(1), text(2), text(3))
+        else {
+            return Err(TdsWireError::Protocol(
+                "Unexpected column description received from the backend".to_string(),
+            ));
+        };
+
+        self.add_column(
+            &schema,
+            &table,
+            CatalogColumn {
+                name,
+                data_type,
+                is_nullable: text(4).is_some_and(|n| n.eq_ignore_ascii_case("yes")),
+                max_length: number(5),
+                precision: number(6),
+                scale: number(7),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn add_column(&mut self, schema: &str, table: &str, column: CatalogColumn) {
+        self.tables
+            .entry((schema.to_string(), table.to_string()))
+            .or_default()
+            .push(column);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All tables by schema and name, ordered by schema and table name
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &str, &[CatalogColumn])> {
+        self.tables
+            .iter()
+            .map(|((schema, table), columns)| (schema.as_str(), table.as_str(), columns.as_slice()))
+    }
+
+    /// Names of all tables, expressed in `<catalog>.<schema>.<table>`
+    pub fn entities(&self) -> Vec<String> {
+        self.tables
+            .keys()
+            .map(|(schema, table)| format!("{}.{}.{}", self.name, schema, table))
+            .collect()
+    }
+
+    /// Only keep the tables and columns of the visible schema, tables without any visible
+    /// column are removed
+    pub fn retain_visible(&mut self, visible_schema: &HashMap<String, Catalog>) {
+        let visible = visible_schema.get(&self.name);
+        self.tables.retain(|(schema, table), columns| {
+            let Some(visible) = visible
+                .and_then(|c| c.db.get(schema))
+                .and_then(|d| d.table.get(table))
+            else {
+                return false;
+            };
+            columns.retain(|c| visible.columns.contains_key(&c.name));
+            !columns.is_empty()
+        });
+    }
+
+    /// The emulated views, in the form expected by the sql parser
+    pub fn to_json(&self) -> String {
+        let mut schema_ids = BTreeMap::new();
+        for (schema, _) in self.tables.keys() {
+            let next_id = schema_ids.len() as i64 + FIRST_USER_SCHEMA_ID;
+            schema_ids.entry(schema.as_str()).or_insert(next_id);
+        }
+
+        let schemas = schema_ids
+            .iter()
+            .map(|(name, id)| json!({"name": name, "schema_id": id, "principal_id": 1}))
+            .chain([json!({"name": "sys", "schema_id": SYS_SCHEMA_ID, "principal_id": 4})])
+            .collect::<Vec<_>>();
+        let schemata = schema_ids
+            .keys()
+            .map(|name| {
+                json!({
+                    "catalog_name": self.name,
+                    "schema_name": name,
+                    "schema_owner": "dbo",
+                    "default_character_set_catalog": Value::Null,
+                    "default_character_set_schema": Value::Null,
+                    "default_character_set_name": Value::Null,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut tables = Vec::new();
+        let mut information_schema_tables = Vec::new();
+        let mut columns = Vec::new();
+        let mut information_schema_columns = Vec::new();
+        for (i, ((schema, table), table_columns)) in self.tables.iter().enumerate() {
+            let object_id = i as i64 + 1;
+            tables.push(json!({
+                "name": table,
+                "object_id": object_id,
+                "principal_id": Value::Null,
+                "schema_id": schema_ids[schema.as_str()],
+                "parent_object_id": 0,
+                "type": "U",
+                "type_desc": "USER_TABLE",
+                "is_ms_shipped": false,
+            }));
+            information_schema_tables.push(json!({
+                "table_catalog": self.name,
+                "table_schema": schema,
+                "table_name": table,
+                "table_type": "BASE TABLE",
+            }));
+
+            for (j, column) in table_columns.iter().enumerate() {
+                let ty = SqlServerType::from_column(column);
+                columns.push(json!({
+                    "object_id": object_id,
+                    "name": column.name,
+                    "column_id": j + 1,
+                    "system_type_id": ty.system_type_id,
+                    "user_type_id": ty.system_type_id,
+                    "max_length": ty.max_length,
+                    "precision": ty.precision,
+                    "scale": ty.scale,
+                    "collation_name": ty.is_text().then_some("Latin1_General_100_CI_AS_SC_UTF8"),
+                    "is_nullable": column.is_nullable,
+                    "is_identity": false,
+                    "is_computed": false,
+                }));
+                information_schema_columns.push(json!({
+                    "table_catalog": self.name,
+                    "table_schema": schema,
+                    "table_name": table,
+                    "column_name": column.name,
+                    "ordinal_position": j + 1,
+                    "column_default": Value::Null,
+                    "is_nullable": if column.is_nullable { "YES" } else { "NO" },
+                    "data_type": ty.name,
+                    "character_maximum_length": ty.is_text()
+                        .then_some(if ty.max_length < 0 { -1 } else { ty.max_length / 2 }),
+                    "numeric_precision": ty.is_numeric().then_some(ty.precision),
+                    "numeric_scale": ty.is_numeric().then_some(ty.scale),
+                }));
+            }
+        }
+
+        let types = SqlServerType::all()
+            .iter()
+            .map(|ty| {
+                json!({
+                    "name": ty.name,
+                    "system_type_id": ty.system_type_id,
+                    "user_type_id": ty.system_type_id,
+                    "schema_id": SYS_SCHEMA_ID,
+                    "principal_id": Value::Null,
+                    "max_length": ty.max_length,
+                    "precision": ty.precision,
+                    "scale": ty.scale,
+                    "is_nullable": true,
+                    "is_user_defined": false,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "sys": {
+                "databases": [{
+                    "name": self.name,
+                    "database_id": 1,
+                    "compatibility_level": 160,
+                    "collation_name": "Latin1_General_100_CI_AS_SC_UTF8",
+                    "user_access_desc": "MULTI_USER",
+                    "is_read_only": false,
+                    "state": 0,
+                    "state_desc": "ONLINE",
+                }],
+                "schemas": schemas,
+                "tables": tables.clone(),
+                "objects": tables,
+                "columns": columns,
+                "types": types,
+            },
+            "information_schema": {
+                "schemata": schemata,
+                "tables": information_schema_tables,
+                "columns": information_schema_columns,
+            },
+        })
+        .to_string()
+    }
+}
+
+/// Result set of a catalog query, the type of each column is derived from its values
+pub fn to_result_set(output: CatalogOutput) -> ResultSet {
+    #[derive(PartialEq)]
+    enum ValueType {
+        Unknown,
+        Bool,
+        Int,
+        Float,
+        Text(usize),
+    }
+
+    let mut types = output
+        .columns
+        .iter()
+        .map(|_| ValueType::Unknown)
+        .collect::<Vec<_>>();
+    for row in &output.rows {
+        for (ty, value) in types.iter_mut().zip(row) {
+            *ty = match (&*ty, value) {
+                (ty, None) => match ty {
+                    ValueType::Unknown => ValueType::Unknown,
+                    ValueType::Bool => ValueType::Bool,
+                    ValueType::Int => ValueType::Int,
+                    ValueType::Float => ValueType::Float,
+                    ValueType::Text(l) => ValueType::Text(*l),
+                },
+                (ValueType::Unknown | ValueType::Bool, Some(CatalogValue::Bool(_))) => {
+                    ValueType::Bool
+                }
+                (ValueType::Unknown | ValueType::Int, Some(CatalogValue::Int(_))) => ValueType::Int,
+                (
+                    ValueType::Unknown | ValueType::Int | ValueType::Float,
+                    Some(CatalogValue::Int(_) | CatalogValue::Float(_)),
+                ) => ValueType::Float,
+                (ty, Some(value)) => {
+                    let length = to_text(value).encode_utf16().count();
+                    match ty {
+                        ValueType::Text(l) => ValueType::Text(length.max(*l)),
+                        _ => ValueType::Text(length),
+                    }
+                }
+            };
+        }
+    }
+
+    let mut builder = ResultSetBuilder::new();
+    let mut lengths = Vec::with_capacity(types.len());
+    for (name, ty) in output.columns.iter().zip(&types) {
+        let (type_info, length) = match ty {
+            ValueType::Bool => (TypeInfo::new_bit(true), 0),
+            ValueType::Int => (TypeInfo::new_bigint(true), 0),
+            ValueType::Float => (TypeInfo::new_float_64(true), 0),
+            ValueType::Unknown | ValueType::Text(_) => {
+                let length = match ty {
+                    ValueType::Text(l) if *l as i64 <= MAX_NVARCHAR_LENGTH => (*l).max(1) * 2,
+                    ValueType::Text(_) => MAX_LENGTH,
+                    _ => 2,
+                };
+                match length {
+                    MAX_LENGTH => (TypeInfo::new_string(), length),
+                    _ => (TypeInfo::new_nvarchar(length), length),
+                }
+            }
+        };
+        let flags = DataFlags {
+            is_nullable: true,
+            ..Default::default()
+        };
+        builder = builder.add_column(Some(name), type_info, flags);
+        lengths.push(length);
+    }
+
+    for row in output.rows {
+        let cells = row
+            .into_iter()
+            .zip(types.iter().zip(&lengths))
+            .map(|(value, (ty, length))| match ty {
+                ValueType::Bool => ColumnData::BitN(match value {
+                    Some(CatalogValue::Bool(b)) => Some(b),
+                    _ => None,
+                }),
+                ValueType::Int => ColumnData::I64N(match value {
+                    Some(CatalogValue::Int(i)) => Some(i),
+                    _ => None,
+                }),
+                ValueType::Float => ColumnData::F64N(match value {
+                    Some(CatalogValue::Int(i)) => Some(i as f64),
+                    Some(CatalogValue::Float(f)) => Some(f),
+                    _ => None,
+                }),
+                ValueType::Unknown | ValueType::Text(_) => {
+                    ColumnData::String(SqlString::from_string(value.as_ref().map(to_text), *length))
+                }
+            })
+            .collect::<Vec<_>>();
+        builder = builder.add_row(&cells);
+    }
+    builder.result
+}
+
+fn to_text(value: &CatalogValue) -> String {
+    match value {
+        CatalogValue::Bool(b) => (*b as u8).to_string(),
+        CatalogValue::Int(i) => i.to_string(),
+        CatalogValue::Float(f) => f.to_string(),
+        CatalogValue::Text(s) => s.clone(),
+    }
+}
+
+pub(crate) fn get_text(value: &ColumnData) -> Option<String> {
+    match value {
+        ColumnData::String(s) => s.as_str().map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_number(value: &ColumnData) -> Option<i64> {
+    match value {
+        ColumnData::U8(v) => Some(*v as i64),
+        ColumnData::U8N(v) => v.map(|v| v as i64),
+        ColumnData::I16(v) => Some(*v as i64),
+        ColumnData::I16N(v) => v.map(|v| v as i64),
+        ColumnData::I32(v) => Some(*v as i64),
+        ColumnData::I32N(v) => v.map(|v| v as i64),
+        ColumnData::I64(v) => Some(*v),
+        ColumnData::I64N(v) => *v,
+        ColumnData::Numeric(v) => v.as_ref().and_then(|v| v.to_i64()),
+        ColumnData::String(s) => s.as_str().and_then(|s| s.parse().ok()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::catalog::{
+        get_number, get_text, is_catalog_query, to_result_set, CatalogColumn, VirtualCatalog,
+    };
+    use crate::backend::engine::{MockBackend, MockResult, QueryBackend};
+    use crate::frontend::sqlstring::SqlString;
+    use crate::frontend::{ColumnData, TokenColMetaData, TypeInfo, VarLenType};
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use tokio_util::sync::CancellationToken;
+    use unilake_sql::{Catalog, CatalogOutput, CatalogValue, Database, Table};
+
+    fn text(value: &str) -> ColumnData {
+        ColumnData::String(SqlString::from_string(Some(value.to_string()), 200))
+    }
+
+    fn column_row(
+        table: &str,
+        column: &str,
+        data_type: &str,
+        length: Option<i64>,
+        precision: Option<i64>,
+    ) -> Vec<ColumnData> {
+        vec![
+            text("sales"),
+            text(table),
+            text(column),
+            text(data_type),
+            text("YES"),
+            ColumnData::I64N(length),
+            ColumnData::I64N(precision),
+            ColumnData::I64N(precision.map(|_| 2)),
+        ]
+    }
+
+    fn visible(tables: &[(&str, &[&str])]) -> HashMap<String, Catalog> {
+        let table = tables
+            .iter()
+            .map(|(name, columns)| {
+                let columns = columns
+                    .iter()
+                    .map(|c| (c.to_string(), "varchar".to_string()))
+                    .collect();
+                (name.to_string(), Table { columns })
+            })
+            .collect();
+        let db = HashMap::from([("sales".to_string(), Database { table })]);
+        HashMap::from([("lake".to_string(), Catalog { db })])
+    }
+
+    #[test]
+    fn detect_catalog_queries() {
+        assert!(is_catalog_query(
+            "SELECT name FROM [master].[sys].[databases]"
+        ));
+        assert!(is_catalog_query(
+            "select * from INFORMATION_SCHEMA.COLUMNS where TABLE_NAME = 'orders'"
+        ));
+        assert!(!is_catalog_query("select * from sales.orders"));
+        assert!(!is_catalog_query("select 'sys.tables' as name"));
+    }
+
+    #[tokio::test]
+    async fn read_visible_catalog() {
+        let columns = [
+            "schema", "table", "column", "type", "nullable", "len", "p", "s",
+        ]
+        .iter()
+        .map(|c| (c.to_string(), TypeInfo::new_nvarchar(200)))
+        .collect();
+        let backend = MockBackend::new().with_result(
+            &VirtualCatalog::get_columns_query("lake"),
+            MockResult::Rows {
+                columns,
+                rows: vec![
+                    column_row("customers", "id", "bigint", None, Some(19)),
+                    column_row("customers", "email", "varchar", Some(200), None),
+                    column_row("customers", "notes", "string", Some(65533), None),
+                    column_row("orders", "amount", "decimal", None, Some(10)),
+                    column_row("secrets", "value", "varchar", Some(20), None),
+                ],
+            },
+        );
+        let mut conn = backend.connect("user").await.unwrap();
+        let mut catalog = VirtualCatalog::read(conn.as_mut(), "lake", CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            catalog.entities(),
+            vec![
+                "lake.sales.customers",
+                "lake.sales.orders",
+                "lake.sales.secrets"
+            ]
+        );
+
+        catalog.retain_visible(&visible(&[
+            ("customers", &["id", "email"]),
+            ("orders", &["amount"]),
+        ]));
+        assert_eq!(
+            catalog.entities(),
+            vec!["lake.sales.customers", "lake.sales.orders"]
+        );
+
+        let views: Value = serde_json::from_str(&catalog.to_json()).unwrap();
+        assert_eq!(views["sys"]["databases"][0]["name"], "lake");
+        assert_eq!(views["sys"]["schemas"][0]["name"], "sales");
+        assert_eq!(views["sys"]["tables"][1]["name"], "orders");
+        let columns = views["sys"]["columns"].as_array().unwrap();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns[1]["name"], "email");
+        assert_eq!(columns[1]["system_type_id"], 231);
+        assert_eq!(columns[1]["max_length"], 400);
+        assert_eq!(columns[2]["system_type_id"], 106);
+        assert_eq!(columns[2]["precision"], 10);
+        let columns = views["information_schema"]["columns"].as_array().unwrap();
+        assert_eq!(columns[0]["data_type"], "bigint");
+        assert_eq!(columns[1]["character_maximum_length"], 200);
+        assert_eq!(columns[2]["numeric_scale"], 2);
+    }
+
+    #[test]
+    fn user_schema_ids_after_system_schemas() {
+        let mut catalog = VirtualCatalog::new("lake");
+        for schema in ["a", "b", "c", "d"] {
+            catalog.add_column(
+                schema,
+                "t",
+                CatalogColumn {
+                    name: "id".to_string(),
+                    data_type: "int".to_string(),
+                    is_nullable: false,
+                    max_length: None,
+                    precision: Some(10),
+                    scale: Some(0),
+                },
+            );
+        }
+
+        // the system types only join to the sys schema
+        let views: Value = serde_json::from_str(&catalog.to_json()).unwrap();
+        let schemas = views["sys"]["schemas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                (
+                    s["name"].as_str().unwrap(),
+                    s["schema_id"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            schemas,
+            vec![("a", 5), ("b", 6), ("c", 7), ("d", 8), ("sys", 4)]
+        );
+        let tables = views["sys"]["tables"].as_array().unwrap();
+        assert_eq!(tables[3]["schema_id"], 8);
+        assert!(views["sys"]["types"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|t| t["schema_id"] == 4));
+    }
+
+    #[test]
+    fn map_starrocks_types() {
+        let mut catalog = VirtualCatalog::new("lake");
+        for (name, data_type, length, precision) in [
+            ("a", "boolean", None, None),
+            ("b", "largeint", None, None),
+            ("c", "decimal", None, Some(76)),
+            ("d", "string", Some(65533), None),
+            ("e", "json", None, None),
+            ("f", "datetime", None, None),
+        ] {
+            catalog.add_column(
+                "sales",
+                "t",
+                CatalogColumn {
+                    name: name.to_string(),
+                    data_type: data_type.to_string(),
+                    is_nullable: true,
+                    max_length: length,
+                    precision,
+                    scale: precision.map(|_| 0),
+                },
+            );
+        }
+
+        let views: Value = serde_json::from_str(&catalog.to_json()).unwrap();
+        let types = views["sys"]["columns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["system_type_id"].as_i64(), c["max_length"].as_i64()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                (Some(104), Some(1)),
+                (Some(231), Some(80)),
+                (Some(231), Some(156)),
+                (Some(231), Some(-1)),
+                (Some(231), Some(-1)),
+                (Some(42), Some(8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn result_set_types_follow_values() {
+        let mut result = to_result_set(CatalogOutput {
+            columns: vec!["name".into(), "id".into(), "flag".into(), "empty".into()],
+            rows: vec![
+                vec![
+                    Some(CatalogValue::Text("orders".into())),
+                    Some(CatalogValue::Int(1)),
+                    Some(CatalogValue::Bool(false)),
+                    None,
+                ],
+                vec![
+                    Some(CatalogValue::Text("customers".into())),
+                    None,
+                    Some(CatalogValue::Bool(true)),
+                    None,
+                ],
+            ],
+            is_catalog_query: true,
+            error: None,
+        });
+
+        let metadata = TokenColMetaData::from(&mut result);
+        let types = metadata
+            .columns
+            .iter()
+            .map(|c| match &c.base.ty {
+                TypeInfo::VarLenSized(ctx) => (ctx.r#type(), ctx.len()),
+                ty => panic!("unexpected type {:?}", ty),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                (VarLenType::NVarchar, 18),
+                (VarLenType::Intn, 8),
+                (VarLenType::Bitn, 1),
+                (VarLenType::NVarchar, 2),
+            ]
+        );
+
+        let rows = result.collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[1].get(0).and_then(get_text),
+            Some("customers".to_string())
+        );
+        assert_eq!(rows[0].get(1).and_then(get_number), Some(1));
+        assert_eq!(rows[1].get(1).and_then(get_number), None);
+        assert!(matches!(rows[1].get(2), Some(ColumnData::BitN(Some(true)))));
+    }
+}
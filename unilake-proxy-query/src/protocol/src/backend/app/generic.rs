@@ -1,183 +1,70 @@
-use crate::backend::app::{FedResultStream, FederatedRequestType};
+use crate::backend::app::interceptor::{
+    single_result, InterceptorHandler, InterceptorRuleConfig, QueryPattern,
+};
+use crate::backend::app::FedResultStream;
 use crate::frontend::sqlstring::SqlString;
 use crate::frontend::{
     BaseMetaDataColumn, BatchRequest, ColumnData, DataFlags, MetaDataColumn, TokenColMetaData,
-    TokenInfo, TokenRow, TokenSessionState, TypeInfo,
+    TokenEnvChange, TokenInfo, TokenRow, TypeInfo,
 };
-use async_stream::stream;
 use std::collections::VecDeque;
-use unilake_common::error::TdsWireResult;
-
-pub(crate) fn process_static(hash: u64, req: &FederatedRequestType) -> Option<FedResultStream> {
-    // hash based
-    if let FederatedRequestType::Query(req) = req {
-        let found = match hash {
-            10359985016278064883 => Some(engine_edition(req)),
-            17700992380341451191 => Some(session_properties(req)),
-            5755979048921116848 => Some(databases(req)),
-            6768217174072757231 => Some(context_info(req)),
-            9848272818868536402 => Some(database_size_info(req)),
-            7919239051011949721 => Some(backup_info(req)),
-            _ => None,
-        };
-        if let Some(result_set) = found {
-            let stream = stream! {yield result_set;};
-            return Some(FedResultStream::new(Box::pin(stream)));
-        }
 
-        if hash == 3415367573379425041 {
-            return Some(server_edition(req));
-        }
-    }
-
-    None
-    // non-hash
-    // toreturn = match req {
-    //     n if n.starts_with("set", true) => set_statement(req),
-    //     _ => toreturn,
-    // };
-    //
-    // toreturn
-}
-
-fn server_edition(_req: &BatchRequest) -> FedResultStream {
-    let stream = stream! {
-    let result_set = ResultSetBuilder::new()
-        .add_column(
-            Some("DatabaseEngineType"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("DatabaseEngineEdition"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("ProductVersion"),
-            TypeInfo::new_nvarchar(255),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("MicrosoftVersion"),
-            TypeInfo::new_nvarchar(255),
-            DataFlags::default(),
-        )
-        .add_row(&[
-            ColumnData::I32(1),
-            ColumnData::I32(3),
-            ColumnData::new_varchar("16.0.4140.4", 255),
-            ColumnData::I32(268439596),
-        ]);
-
-        // first resultset
-        yield Ok(FedResult::Tabular(result_set.result));
-
-       let result_set = ResultSetBuilder::new()
-            .add_column(Some("host_platform"), TypeInfo::new_nvarchar(255), DataFlags::default())
-            .add_row(&[ColumnData::new_varchar("Linux", 255)]);
-
-        // second resultset
-        yield Ok(FedResult::Tabular(result_set.result));
-
-        let result_set = ResultSetBuilder::new()
-            .add_column(Some("ConnectionProtocol"), TypeInfo::new_nvarchar(255), DataFlags::default())
-            .add_row(&[ColumnData::new_varchar("TCP", 255)]);
-
-        // third resultset
-        yield Ok(FedResult::Tabular(result_set.result));
-    };
-
-    FedResultStream::new(Box::pin(stream))
-}
-
-fn set_statement(req: &BatchRequest) -> Option<FedResult> {
-    tracing::info!("Received SET statement: {}", req.query);
-    Some(FedResult::Empty)
-}
-
-fn backup_info(_req: &BatchRequest) -> TdsWireResult<FedResult> {
-    let result_set = ResultSetBuilder::new()
-        .add_column(
-            Some("Within 24hrs"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("Older than 24hrs"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("No backup found"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_row(&[ColumnData::I32(0), ColumnData::I32(0), ColumnData::I32(0)]);
-
-    Ok(FedResult::Tabular(result_set.result))
-}
-
-fn database_size_info(_: &BatchRequest) -> TdsWireResult<FedResult> {
-    let result_set = ResultSetBuilder::new()
-        .add_column(
-            Some("name"),
-            TypeInfo::new_nvarchar(255),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("DataFileSizeMB"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_column(
-            Some("LogFileSizeMB"),
-            TypeInfo::new_int(false),
-            DataFlags::default(),
-        )
-        .add_row(&[
-            ColumnData::String(SqlString::from_string(
-                Some("default_catalog".to_string()),
-                255,
-            )),
-            ColumnData::I32(0),
-            ColumnData::I32(0),
-        ]);
-
-    Ok(FedResult::Tabular(result_set.result))
-}
-
-fn context_info(_: &BatchRequest) -> TdsWireResult<FedResult> {
+/// Handlers for probes sent by SQL Server clients in general
+pub(crate) const HANDLERS: &[(&str, InterceptorHandler)] = &[
+    ("engine_edition", engine_edition),
+    ("session_properties", session_properties),
+    ("context_info", context_info),
+];
+
+/// The rules match the complete probe, so a batch which merely contains a fragment of a probe is
+/// still executed by the backend
+pub(crate) fn default_rules() -> Vec<InterceptorRuleConfig> {
+    vec![
+        InterceptorRuleConfig::new(
+            "engine_edition",
+            QueryPattern::Regex(
+                concat!(
+                    r"^select serverproperty\('engineedition'\),n'[^']*',",
+                    r"serverproperty\('productlevel'\),serverproperty\('edition'\),",
+                    r"serverproperty\('machinename'\),serverproperty\('servername'\),",
+                    r"serverproperty\('isclustered'\)$",
+                )
+                .to_string(),
+            ),
+            "engine_edition",
+        ),
+        InterceptorRuleConfig::new(
+            "session_properties",
+            QueryPattern::Regex(
+                r"^select sessionproperty\('\w+'\),sessionproperty\('\w+'\)$".to_string(),
+            ),
+            "session_properties",
+        ),
+        InterceptorRuleConfig::new(
+            "context_info",
+            QueryPattern::Regex(r"^select context_info\(\)( ?as \w+)?$".to_string()),
+            "context_info",
+        ),
+    ]
+}
+
+fn context_info(_: &BatchRequest) -> FedResultStream {
     let result_set = ResultSetBuilder::new()
         .add_column(None, TypeInfo::new_nvarchar(100), DataFlags::default())
         .add_row(&[ColumnData::String(SqlString::from_string(None, 100))]);
-    Ok(FedResult::Tabular(result_set.result))
-}
-
-fn databases(_: &BatchRequest) -> TdsWireResult<FedResult> {
-    let result_set = ResultSetBuilder::new()
-        .add_column(
-            Some("name"),
-            TypeInfo::new_nvarchar(100),
-            DataFlags::default(),
-        )
-        .add_row(&[ColumnData::String(SqlString::from_string(
-            Some("dwh".to_string()),
-            100,
-        ))]);
-    Ok(FedResult::Tabular(result_set.result))
+    single_result(FedResult::Tabular(result_set.result))
 }
 
-fn session_properties(_: &BatchRequest) -> TdsWireResult<FedResult> {
+fn session_properties(_: &BatchRequest) -> FedResultStream {
     let result_set = ResultSetBuilder::new()
         .add_column(None, TypeInfo::new_int(false), DataFlags::default())
         .add_column(None, TypeInfo::new_int(false), DataFlags::default())
         .add_row(&[ColumnData::I32(1), ColumnData::I32(1)]);
 
-    Ok(FedResult::Tabular(result_set.result))
+    single_result(FedResult::Tabular(result_set.result))
 }
 
-fn engine_edition(_: &BatchRequest) -> TdsWireResult<FedResult> {
+fn engine_edition(_: &BatchRequest) -> FedResultStream {
     let result_set = ResultSetBuilder::new()
         .add_column(None, TypeInfo::new_int(false), DataFlags::default())
         .add_column(None, TypeInfo::new_nvarchar(100), DataFlags::default())
@@ -208,7 +95,7 @@ fn engine_edition(_: &BatchRequest) -> TdsWireResult<FedResult> {
             ColumnData::I32(1),
         ]);
 
-    Ok(FedResult::Tabular(result_set.result))
+    single_result(FedResult::Tabular(result_set.result))
 }
 
 impl From<&mut ResultSet> for TokenColMetaData {
@@ -238,11 +125,10 @@ impl Iterator for ResultSet {
     }
 }
 
-// todo(mrhamburg): we need to check where we do the set commands for sessions and which one we support
 pub enum FedResult {
     Tabular(ResultSet),
     Info(TokenInfo),
-    State(TokenSessionState),
+    State(TokenEnvChange),
     Empty,
 }
 
@@ -260,8 +146,8 @@ impl ResultSet {
     }
 }
 
-struct ResultSetBuilder {
-    result: ResultSet,
+pub(crate) struct ResultSetBuilder {
+    pub(crate) result: ResultSet,
 }
 
 impl ResultSetBuilder {
@@ -305,3 +191,62 @@ impl BatchRequest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::interceptor::intercept_query;
+
+    #[tokio::test]
+    async fn intercept_engine_edition() {
+        let query = "SELECT SERVERPROPERTY('EngineEdition'), N'Microsoft SQL Server',
+            SERVERPROPERTY('ProductLevel'), SERVERPROPERTY('Edition'),
+            SERVERPROPERTY('MachineName'), SERVERPROPERTY('ServerName'),
+            SERVERPROPERTY('IsClustered')";
+        let (rule, result_sets) = intercept_query(query).await.unwrap();
+        assert_eq!(rule, "engine_edition");
+        assert_eq!(result_sets, vec![vec![""; 7]]);
+    }
+
+    #[tokio::test]
+    async fn intercept_session_properties() {
+        let query = "select SESSIONPROPERTY ('ANSI_NULLS'), SESSIONPROPERTY ('QUOTED_IDENTIFIER')";
+        let (rule, result_sets) = intercept_query(query).await.unwrap();
+        assert_eq!(rule, "session_properties");
+        assert_eq!(result_sets, vec![vec![""; 2]]);
+    }
+
+    #[tokio::test]
+    async fn intercept_context_info() {
+        let (rule, result_sets) = intercept_query("SELECT CONTEXT_INFO()").await.unwrap();
+        assert_eq!(rule, "context_info");
+        assert_eq!(result_sets, vec![vec![""]]);
+        let (rule, _) = intercept_query("select context_info() as ctx")
+            .await
+            .unwrap();
+        assert_eq!(rule, "context_info");
+    }
+
+    #[tokio::test]
+    async fn regular_queries_are_not_intercepted() {
+        // queries on the system catalog are answered by the emulated catalog
+        assert!(
+            intercept_query("SELECT name FROM [master].[sys].[databases] ORDER BY name")
+                .await
+                .is_none()
+        );
+        assert!(intercept_query("SELECT name FROM sales.databases")
+            .await
+            .is_none());
+        assert!(intercept_query("SELECT SERVERPROPERTY('EngineEdition')")
+            .await
+            .is_none());
+
+        // the probe is only intercepted as a whole, the rest of the batch would be lost
+        for query in [
+            "SELECT CONTEXT_INFO(), col FROM t",
+            "SELECT CONTEXT_INFO(); SELECT * FROM sales",
+        ] {
+            assert!(intercept_query(query).await.is_none(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Registry of rules intercepting the queries client tools send to discover the server, e.g. on
+//! connect or when opening a dashboard. Such queries target SQL Server system objects and are
+//! answered by the proxy instead of the backend.
+//!
+//! Rules are matched against the normalized query text (see [`normalize_query`]), which makes
+//! them independent of casing, comments, delimited identifiers and whitespace. The first
+//! matching rule wins, rules loaded from the configuration file precede the built-in rules.
+//!
+//! Configuration file example:
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "bi_tool_context",
+//!     "pattern": { "regex": "^select context_info\\(\\)as \\w+$" },
+//!     "handler": "context_info"
+//!   },
+//!   {
+//!     "name": "bi_tool_edition",
+//!     "pattern": { "contains": ["serverproperty('engineedition')", "@@version"] },
+//!     "handler": "engine_edition"
+//!   }
+//! ]
+//! ```
+use crate::backend::app::generic::FedResult;
+use crate::backend::app::{azdatastudio, generic, FedResultStream, FederatedRequestType};
+use crate::frontend::BatchRequest;
+use async_stream::stream;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::Arc;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::settings::settings_server_interceptors_file;
+
+/// Produces the result of an intercepted query
+pub type InterceptorHandler = fn(&BatchRequest) -> FedResultStream;
+
+/// How a rule matches the normalized query text
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryPattern {
+    /// The query equals the (normalized) text
+    Exact(String),
+    /// The query contains all (normalized) fragments
+    Contains(Vec<String>),
+    /// The regular expression matches the query
+    Regex(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InterceptorRuleConfig {
+    pub name: String,
+    pub pattern: QueryPattern,
+    /// Name of the built-in handler producing the result
+    pub handler: String,
+}
+
+impl InterceptorRuleConfig {
+    pub fn new(name: &str, pattern: QueryPattern, handler: &str) -> Self {
+        InterceptorRuleConfig {
+            name: name.to_string(),
+            pattern,
+            handler: handler.to_string(),
+        }
+    }
+}
+
+enum QueryMatcher {
+    Exact(String),
+    Contains(Vec<String>),
+    Regex(Regex),
+}
+
+impl QueryMatcher {
+    fn is_match(&self, query: &str) -> bool {
+        match self {
+            QueryMatcher::Exact(text) => query == text,
+            QueryMatcher::Contains(fragments) => fragments.iter().all(|f| query.contains(f)),
+            QueryMatcher::Regex(regex) => regex.is_match(query),
+        }
+    }
+}
+
+pub struct InterceptorRule {
+    name: String,
+    matcher: QueryMatcher,
+    handler: InterceptorHandler,
+}
+
+impl InterceptorRule {
+    pub fn from_config(config: InterceptorRuleConfig) -> TdsWireResult<Self> {
+        let handler = builtin_handler(&config.handler).ok_or_else(|| {
+            TdsWireError::Input(format!(
+                "Unknown handler '{}' for interceptor rule '{}'",
+                config.handler, config.name
+            ))
+        })?;
+        let matcher = match config.pattern {
+            QueryPattern::Exact(text) => QueryMatcher::Exact(normalize_query(&text)),
+            QueryPattern::Contains(fragments) if !fragments.is_empty() => {
+                QueryMatcher::Contains(fragments.iter().map(|f| normalize_query(f)).collect())
+            }
+            QueryPattern::Contains(_) => {
+                return Err(TdsWireError::Input(format!(
+                    "Interceptor rule '{}' has no fragments to match",
+                    config.name
+                )))
+            }
+            QueryPattern::Regex(pattern) => {
+                QueryMatcher::Regex(Regex::new(&pattern).map_err(|e| {
+                    TdsWireError::Input(format!(
+                        "Invalid pattern for interceptor rule '{}': {}",
+                        config.name, e
+                    ))
+                })?)
+            }
+        };
+
+        Ok(InterceptorRule {
+            name: config.name,
+            matcher,
+            handler,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct InterceptorRegistry {
+    rules: Vec<InterceptorRule>,
+}
+
+impl Default for InterceptorRegistry {
+    /// Registry with the built-in rules for the probes of common client tools
+    fn default() -> Self {
+        let rules = azdatastudio::default_rules()
+            .into_iter()
+            .chain(generic::default_rules())
+            .map(|config| InterceptorRule::from_config(config).expect("valid built-in rule"))
+            .collect();
+        InterceptorRegistry { rules }
+    }
+}
+
+impl InterceptorRegistry {
+    /// Registry with the given rules, followed by the built-in rules
+    pub fn new(rules: Vec<InterceptorRuleConfig>) -> TdsWireResult<Self> {
+        let mut registry = InterceptorRegistry::default();
+        let rules = rules
+            .into_iter()
+            .map(InterceptorRule::from_config)
+            .collect::<TdsWireResult<Vec<_>>>()?;
+        registry.rules.splice(0..0, rules);
+        Ok(registry)
+    }
+
+    pub fn from_file(path: &str) -> TdsWireResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            TdsWireError::Input(format!(
+                "Failed to read interceptors file '{}': {}",
+                path, e
+            ))
+        })?;
+        let rules = serde_json::from_str::<Vec<InterceptorRuleConfig>>(&content).map_err(|e| {
+            TdsWireError::Input(format!(
+                "Failed to parse interceptors file '{}': {}",
+                path, e
+            ))
+        })?;
+        Self::new(rules)
+    }
+
+    /// Find the first rule matching the query
+    pub fn find(&self, query: &str) -> Option<&InterceptorRule> {
+        let query = normalize_query(query);
+        self.rules.iter().find(|rule| rule.matcher.is_match(&query))
+    }
+
+    /// Returns the result of the request if it is intercepted
+    pub fn intercept(&self, request: &FederatedRequestType) -> Option<FedResultStream> {
+        let FederatedRequestType::Query(request) = request else {
+            return None;
+        };
+        let rule = self.find(&request.query)?;
+        tracing::trace!("Query intercepted by rule '{}'", rule.name);
+        Some((rule.handler)(request))
+    }
+}
+
+/// Creates the interceptor registry, extended with the rules of the configured file, if any
+pub fn interceptor_registry_from_settings() -> TdsWireResult<Arc<InterceptorRegistry>> {
+    match settings_server_interceptors_file() {
+        Some(path) => Ok(Arc::new(InterceptorRegistry::from_file(&path)?)),
+        None => Ok(Arc::new(InterceptorRegistry::default())),
+    }
+}
+
+fn builtin_handler(name: &str) -> Option<InterceptorHandler> {
+    azdatastudio::HANDLERS
+        .iter()
+        .chain(generic::HANDLERS)
+        .find(|(handler, _)| *handler == name)
+        .map(|(_, handler)| *handler)
+}
+
+/// Result stream of a handler producing a single result
+pub(crate) fn single_result(result: FedResult) -> FedResultStream {
+    let stream = stream! {yield Ok(result);};
+    FedResultStream::new(Box::pin(stream))
+}
+
+/// Normalizes the query text for matching: lowercased, without comments, identifier brackets,
+/// trailing semicolons and insignificant whitespace. String literals are only lowercased.
+pub fn normalize_query(query: &str) -> String {
+    let mut normalized = String::with_capacity(query.len());
+    let mut chars = query.chars().peekable();
+    let mut in_string = false;
+    let mut pending_space = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            normalized.extend(c.to_lowercase());
+            in_string = c != '\'';
+            continue;
+        }
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                chars.by_ref().find(|&c| c == '\n');
+                pending_space = true;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                chars.by_ref().find(|&c| {
+                    let end = previous == '*' && c == '/';
+                    previous = c;
+                    end
+                });
+                pending_space = true;
+            }
+            '[' | ']' => {}
+            c if c.is_whitespace() => pending_space = true,
+            c => {
+                let separated = normalized.ends_with(is_operator) || is_operator(c);
+                if pending_space && !normalized.is_empty() && !separated {
+                    normalized.push(' ');
+                }
+                pending_space = false;
+                in_string = c == '\'';
+                normalized.extend(c.to_lowercase());
+            }
+        }
+    }
+
+    let trimmed = normalized.trim_end_matches([';', ' ']).len();
+    normalized.truncate(trimmed);
+    normalized
+}
+
+/// Characters which need no surrounding whitespace
+fn is_operator(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | ',' | ';' | '.' | '=' | '<' | '>' | '+' | '-' | '*' | '/' | '%'
+    )
+}
+
+/// Intercept the query with the built-in rules, returns the name of the matching rule and the
+/// column names of each result set produced by its handler
+#[cfg(test)]
+pub(crate) async fn intercept_query(query: &str) -> Option<(String, Vec<Vec<String>>)> {
+    use crate::frontend::TokenColMetaData;
+    use tokio_stream::StreamExt;
+
+    let request = BatchRequest {
+        query: query.to_string(),
+        query_lowercased: query.to_lowercase(),
+        transaction_descriptor: vec![],
+    };
+    let registry = InterceptorRegistry::default();
+    let name = registry.find(query)?.name().to_string();
+    let mut stream = registry.intercept(&FederatedRequestType::Query(&request))?;
+
+    let mut result_sets = vec![];
+    while let Some(result) = stream.next().await {
+        if let FedResult::Tabular(mut result_set) = result.unwrap() {
+            let metadata = TokenColMetaData::from(&mut result_set);
+            result_sets.push(metadata.columns.into_iter().map(|c| c.col_name).collect());
+        }
+    }
+    Some((name, result_sets))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::interceptor::{
+        normalize_query, InterceptorRegistry, InterceptorRuleConfig, QueryPattern,
+    };
+
+    #[test]
+    fn normalize_query_text() {
+        assert_eq!(
+            normalize_query("SELECT  name\n FROM [sys].[databases] -- all databases\n;"),
+            "select name from sys.databases"
+        );
+        assert_eq!(
+            normalize_query("select /* columns */ a , b\tFROM t WHERE  x = 'Two  Words' ;;"),
+            "select a,b from t where x='two  words'"
+        );
+        assert_eq!(
+            normalize_query("SELECT SERVERPROPERTY ( 'EngineEdition' )"),
+            "select serverproperty('engineedition')"
+        );
+        assert_eq!(
+            normalize_query("select 'it''s -- [x]'"),
+            "select 'it''s -- [x]'"
+        );
+    }
+
+    #[test]
+    fn configured_rules_precede_builtin_rules() {
+        let registry = InterceptorRegistry::new(vec![
+            InterceptorRuleConfig::new(
+                "exact",
+                QueryPattern::Exact("SELECT name FROM sys.databases".to_string()),
+                "context_info",
+            ),
+            InterceptorRuleConfig::new(
+                "regex",
+                QueryPattern::Regex(r"^select @@version$".to_string()),
+                "engine_edition",
+            ),
+        ])
+        .unwrap();
+
+        let found = |query| registry.find(query).map(|r| r.name().to_string());
+        assert_eq!(
+            found("select name\nfrom [sys].[databases];"),
+            Some("exact".into())
+        );
+        assert_eq!(found("SELECT @@VERSION"), Some("regex".into()));
+        assert_eq!(found("select context_info()"), Some("context_info".into()));
+        assert_eq!(found("select * from sales.orders"), None);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let rule = |pattern, handler| {
+            InterceptorRegistry::new(vec![InterceptorRuleConfig::new("rule", pattern, handler)])
+        };
+        assert!(rule(QueryPattern::Exact("select 1".into()), "unknown").is_err());
+        assert!(rule(QueryPattern::Regex("select (".into()), "context_info").is_err());
+        assert!(rule(QueryPattern::Contains(vec![]), "context_info").is_err());
+    }
+
+    #[test]
+    fn load_rules_from_file() {
+        let path = std::env::temp_dir().join(format!("interceptors-{}.json", ulid::Ulid::new()));
+        std::fs::write(
+            &path,
+            r#"[{"name": "tool", "pattern": {"contains": ["SELECT  1", "FROM dual"]}, "handler": "context_info"}]"#,
+        )
+        .unwrap();
+
+        let registry = InterceptorRegistry::from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            registry.find("select 1 from dual").map(|r| r.name()),
+            Some("tool")
+        );
+        assert!(InterceptorRegistry::from_file("/nonexistent/interceptors.json").is_err());
+    }
+}
@@ -1,34 +1,26 @@
 use crate::backend::app::generic::FedResult;
 use crate::frontend::BatchRequest;
 use crate::frontend::RpcRequest;
+use crate::frontend::{TdsBackendResponse, TdsToken, TokenColMetaData, TokenDone};
+use futures::{Sink, SinkExt};
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use tokio_stream::Stream;
-use unilake_common::error::TdsWireResult;
+use tokio_stream::{Stream, StreamExt};
+use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
+use unilake_common::metrics::metrics;
 
 pub mod azdatastudio;
+pub mod catalog;
 pub mod generic;
-// Hashmap with key being the SQL query to search for and a function to execute it
-// Make use of: https://docs.rs/ahash/latest/ahash/struct.AHashMap.html
-// Generic approaches accommodate the above
-// Also: I think this mod should be placed elsewhere (sql perhaps?), or not
+pub mod interceptor;
+pub mod procedures;
+pub mod session_statement;
 
 pub enum FederatedRequestType<'a> {
     Query(&'a BatchRequest),
     Rpc(&'a RpcRequest),
 }
 
-pub struct FederatedFrontendHandler {}
-
-impl FederatedFrontendHandler {
-    pub fn exec_request(
-        hash: u64,
-        request: FederatedRequestType,
-    ) -> TdsWireResult<Option<FedResultStream>> {
-        Ok(generic::process_static(hash, &request))
-    }
-}
-
 pub struct FedResultStream {
     it: Pin<Box<dyn Stream<Item = TdsWireResult<FedResult>> + Send>>,
 }
@@ -46,3 +38,185 @@ impl Stream for FedResultStream {
         Pin::new(&mut self.it).poll_next(cx)
     }
 }
+
+/// Send the results of an intercepted request to the client. Each result set is completed by
+/// its own DONE token, which has DONE_MORE set unless it is the last result of the response. A
+/// failing handler is reported as an error and ends the response.
+pub(crate) async fn send_fed_result<C>(
+    client: &mut C,
+    mut fed_result: FedResultStream,
+    server_name: &str,
+) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse> + Unpin + Send,
+{
+    let mut completed = false;
+    let mut next = fed_result.next().await;
+    while let Some(result) = next {
+        next = fed_result.next().await;
+        let has_more = next.is_some();
+        completed = false;
+        match result {
+            Ok(FedResult::Tabular(mut result)) => {
+                send(client, TokenColMetaData::from(&mut result)).await?;
+                let mut count = 0;
+                for row in result {
+                    send(client, row).await?;
+                    count += 1;
+                }
+                let mut done = TokenDone::new_count(0, count);
+                done.set_status_more(has_more);
+                send(client, done).await?;
+                completed = true;
+            }
+            Ok(FedResult::Info(token)) => send(client, token).await?,
+            Ok(FedResult::State(token)) => send(client, token).await?,
+            Ok(FedResult::Empty) => {
+                let mut done = TokenDone::new_count(0, 0);
+                done.set_status_more(has_more);
+                send(client, done).await?;
+                completed = true;
+            }
+            Err(e) => {
+                tracing::error!("Intercepted request failed: {}", e);
+                let mut token = match e {
+                    TdsWireError::Server(token) => token,
+                    e => {
+                        TokenError::new(0, 1, 16, e.to_string(), "".to_string(), "".to_string(), 0)
+                    }
+                };
+                metrics()
+                    .query_errors
+                    .with_label_values(&[&token.code.to_string()])
+                    .inc();
+                token.server = server_name.to_string();
+                send(client, token).await?;
+                return send(client, TokenDone::new_error(0)).await;
+            }
+        }
+    }
+
+    // info and state changes still need to complete the response
+    if !completed {
+        send(client, TokenDone::new_final()).await?;
+    }
+    Ok(())
+}
+
+async fn send<C, T>(client: &mut C, token: T) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse> + Unpin + Send,
+    T: Into<TdsToken>,
+{
+    client
+        .send(TdsBackendResponse::Token(token.into()))
+        .await
+        .map_err(|_| TdsWireError::Protocol("Failed to feed token".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::generic::{FedResult, ResultSetBuilder};
+    use crate::backend::app::{send_fed_result, FedResultStream};
+    use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::{
+        ColumnData, DataFlags, DoneStatus, TdsBackendResponse, TdsToken, TokenEnvChange, TokenInfo,
+        TypeInfo,
+    };
+    use async_stream::stream;
+    use futures::channel::mpsc;
+    use unilake_common::error::{TdsWireError, TdsWireResult};
+
+    fn get_stream(results: Vec<TdsWireResult<FedResult>>) -> FedResultStream {
+        FedResultStream::new(Box::pin(stream! {
+            for result in results {
+                yield result;
+            }
+        }))
+    }
+
+    /// Sends the results and returns the tokens received by the client
+    async fn get_tokens(results: Vec<TdsWireResult<FedResult>>) -> Vec<TdsToken> {
+        let (mut client, mut receiver) = mpsc::unbounded::<TdsBackendResponse>();
+        send_fed_result(&mut client, get_stream(results), "server")
+            .await
+            .unwrap();
+        let mut tokens = Vec::new();
+        while let Ok(response) = receiver.try_recv() {
+            if let TdsBackendResponse::Token(token) = response {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+
+    #[tokio::test]
+    async fn send_fed_result_tabular() {
+        let result_set = |value: i32| {
+            ResultSetBuilder::new()
+                .add_column(None, TypeInfo::new_int(false), DataFlags::default())
+                .add_row(&[ColumnData::I32(value)])
+                .result
+        };
+        let tokens = get_tokens(vec![
+            Ok(FedResult::Tabular(result_set(1))),
+            Ok(FedResult::Tabular(result_set(2))),
+        ])
+        .await;
+
+        // only the last DONE token ends the response
+        match &tokens[..] {
+            [TdsToken::ColMetaData(_), TdsToken::Row(_), TdsToken::Done(first), TdsToken::ColMetaData(_), TdsToken::Row(_), TdsToken::Done(last)] =>
+            {
+                assert_eq!(first.done_rows, 1);
+                assert!(first.status.contains(DoneStatus::More));
+                assert_eq!(last.done_rows, 1);
+                assert!(!last.status.contains(DoneStatus::More));
+            }
+            tokens => panic!("unexpected tokens: {:?}", tokens),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_fed_result_info() {
+        let info = TokenInfo::new(&ServerContext::new(), 5701, 2, 0, "info".to_string());
+        let tokens = get_tokens(vec![Ok(FedResult::Info(info))]).await;
+        match &tokens[..] {
+            [TdsToken::Info(info), TdsToken::Done(done)] => {
+                assert_eq!(info.message, "info");
+                assert!(done.status.is_empty());
+            }
+            tokens => panic!("unexpected tokens: {:?}", tokens),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_fed_result_state() {
+        let change = TokenEnvChange::new_database_change("a".to_string(), "b".to_string());
+        let tokens = get_tokens(vec![Ok(FedResult::State(change)), Ok(FedResult::Empty)]).await;
+        match &tokens[..] {
+            [TdsToken::EnvChange(TokenEnvChange::Database(..)), TdsToken::Done(done)] => {
+                assert!(done.status.contains(DoneStatus::Count))
+            }
+            tokens => panic!("unexpected tokens: {:?}", tokens),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_fed_result_error() {
+        let tokens = get_tokens(vec![
+            Err(TdsWireError::Protocol("failed".to_string())),
+            Ok(FedResult::Empty),
+        ])
+        .await;
+
+        // the error ends the response
+        match &tokens[..] {
+            [TdsToken::Error(error), TdsToken::Done(done)] => {
+                assert_eq!(error.server, "server");
+                assert!(done.status.contains(DoneStatus::Error));
+            }
+            tokens => panic!("unexpected tokens: {:?}", tokens),
+        }
+    }
+}
@@ -0,0 +1,1264 @@
+//! Emulated system stored procedures, as called by drivers and tools to discover the server and
+//! its catalog, e.g. JDBC `DatabaseMetaData`, ODBC `SQLTables`/`SQLColumns` and Excel. The
+//! procedures are called through RPC or in a batch (`EXEC sp_columns N'orders'`) and return
+//! result sets with the same layout as SQL Server.
+//!
+//! | Procedure      | Answered from                                                  |
+//! |----------------|----------------------------------------------------------------|
+//! | sp_tables      | visible tables of the session catalog                          |
+//! | sp_columns     | visible columns of the session catalog                         |
+//! | sp_help        | visible tables and columns of the session catalog              |
+//! | sp_who         | active sessions of the server instance, of the same user only  |
+//! | sp_server_info | server context                                                 |
+use crate::backend::app::catalog::{CatalogColumn, SqlServerType, VirtualCatalog};
+use crate::backend::app::generic::{ResultSet, ResultSetBuilder};
+use crate::frontend::prot::ActiveSession;
+use crate::frontend::rpc::to_literal;
+use crate::frontend::sqlstring::SqlString;
+use crate::frontend::tds::server_context::ServerContext;
+use crate::frontend::{ColumnData, DataFlags, RpcRequest, TypeInfo};
+use ulid::Ulid;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Length of a sysname (nvarchar(128)) column, in bytes
+const SYSNAME_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SystemProcedure {
+    Tables,
+    Columns,
+    Help,
+    Who,
+    ServerInfo,
+}
+
+impl SystemProcedure {
+    /// Resolve the procedure by its name, which may be qualified, e.g. `[master].[dbo].[sp_who]`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.replace(['[', ']'], "").to_lowercase();
+        Some(match name.rsplit('.').next().unwrap_or_default() {
+            "sp_tables" => SystemProcedure::Tables,
+            "sp_columns" => SystemProcedure::Columns,
+            "sp_help" => SystemProcedure::Help,
+            "sp_who" => SystemProcedure::Who,
+            "sp_server_info" => SystemProcedure::ServerInfo,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemProcedure::Tables => "sp_tables",
+            SystemProcedure::Columns => "sp_columns",
+            SystemProcedure::Help => "sp_help",
+            SystemProcedure::Who => "sp_who",
+            SystemProcedure::ServerInfo => "sp_server_info",
+        }
+    }
+
+    /// Procedures listing tables or columns, which require the visible catalog of the session
+    pub fn requires_catalog(&self) -> bool {
+        matches!(
+            self,
+            SystemProcedure::Tables | SystemProcedure::Columns | SystemProcedure::Help
+        )
+    }
+}
+
+/// Call of a system procedure with its arguments
+#[derive(Debug, PartialEq)]
+pub struct ProcedureCall {
+    pub procedure: SystemProcedure,
+    /// Arguments by lowercase name without the `@`, positional arguments have no name. A
+    /// missing value is either NULL or the default of the parameter.
+    arguments: Vec<(Option<String>, Option<String>)>,
+}
+
+impl ProcedureCall {
+    pub fn new(procedure: SystemProcedure, arguments: Vec<(Option<&str>, Option<&str>)>) -> Self {
+        ProcedureCall {
+            procedure,
+            arguments: arguments
+                .into_iter()
+                .map(|(n, v)| (n.map(|n| n.to_lowercase()), v.map(str::to_string)))
+                .collect(),
+        }
+    }
+
+    /// The call of a system procedure through RPC, none for any other procedure
+    pub fn from_rpc(request: &RpcRequest) -> Option<TdsWireResult<Self>> {
+        let procedure = SystemProcedure::from_name(request.procedure_name()?)?;
+        let arguments = request
+            .parameters()
+            .iter()
+            .map(|p| {
+                let name = p.name().trim_start_matches('@').to_lowercase();
+                let value = match p.value() {
+                    _ if p.is_default() => None,
+                    ColumnData::String(s) => s.as_str().map(str::to_string),
+                    value => Some(to_literal(value)?).filter(|v| v != "NULL"),
+                };
+                Ok(((!name.is_empty()).then_some(name), value))
+            })
+            .collect::<TdsWireResult<Vec<_>>>();
+        Some(arguments.map(|arguments| ProcedureCall {
+            procedure,
+            arguments,
+        }))
+    }
+
+    /// Parse the statement if it executes a system procedure, e.g.
+    /// `EXEC sp_tables @table_name = N'orders'`, returns none for any other statement
+    pub fn parse(statement: &str) -> Option<TdsWireResult<Self>> {
+        let statement = statement.trim().trim_end_matches(';').trim_end();
+        let (keyword, rest) = split_word(statement);
+        let (name, rest) =
+            if keyword.eq_ignore_ascii_case("exec") || keyword.eq_ignore_ascii_case("execute") {
+                split_word(rest)
+            } else {
+                (keyword, rest)
+            };
+        let procedure = SystemProcedure::from_name(name)?;
+        Some(parse_arguments(rest).map(|arguments| ProcedureCall {
+            procedure,
+            arguments,
+        }))
+    }
+
+    /// Value of the argument, passed by name or on its position
+    fn argument(&self, position: usize, name: &str) -> Option<&str> {
+        self.arguments
+            .iter()
+            .find(|(n, _)| n.as_deref() == Some(name))
+            .or_else(|| self.arguments.get(position).filter(|(n, _)| n.is_none()))
+            .and_then(|(_, v)| v.as_deref())
+    }
+}
+
+/// Data the procedures are answered from
+pub struct ProcedureContext<'a> {
+    pub server: &'a ServerContext,
+    /// Catalog of the session reduced to the visible tables and columns, only required for
+    /// procedures listing tables or columns
+    pub catalog: Option<&'a VirtualCatalog>,
+    /// All active sessions of the server instance
+    pub sessions: Vec<ActiveSession>,
+    pub session_id: Ulid,
+    /// Database of the session, which is the default schema for unqualified names
+    pub schema: Option<&'a str>,
+}
+
+/// Execute the procedure, returns its result sets. Invalid arguments result in an input error.
+pub fn execute_procedure(
+    call: &ProcedureCall,
+    context: &ProcedureContext,
+) -> TdsWireResult<Vec<ResultSet>> {
+    let catalog = || {
+        context.catalog.ok_or_else(|| {
+            TdsWireError::Protocol(format!(
+                "Catalog is required for procedure {}",
+                call.procedure.name()
+            ))
+        })
+    };
+    match call.procedure {
+        SystemProcedure::Tables => Ok(vec![sp_tables(call, catalog()?)]),
+        SystemProcedure::Columns => sp_columns(call, catalog()?).map(|r| vec![r]),
+        SystemProcedure::Help => sp_help(call, catalog()?, context.schema),
+        SystemProcedure::Who => Ok(vec![sp_who(call, &context.sessions, context.session_id)]),
+        SystemProcedure::ServerInfo => sp_server_info(call, context.server).map(|r| vec![r]),
+    }
+}
+
+/// Type of a result set column
+#[derive(Debug, Clone, Copy)]
+enum Col {
+    SysName,
+    NVarChar(usize),
+    TinyInt,
+    SmallInt,
+    Int,
+    DateTime,
+}
+
+/// Value of a result set cell
+enum Value {
+    Null,
+    Text(String),
+    Int(i64),
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+/// Result set with a fixed layout
+struct Table {
+    layout: &'static [(&'static str, Col)],
+    builder: ResultSetBuilder,
+}
+
+impl Table {
+    fn new(layout: &'static [(&'static str, Col)]) -> Self {
+        let mut builder = ResultSetBuilder::new();
+        for (name, col) in layout {
+            let ty = match col {
+                Col::SysName => TypeInfo::new_nvarchar(SYSNAME_LENGTH),
+                Col::NVarChar(length) => TypeInfo::new_nvarchar(length * 2),
+                Col::TinyInt => TypeInfo::new_tinyint(true),
+                Col::SmallInt => TypeInfo::new_smallint(true),
+                Col::Int => TypeInfo::new_int(true),
+                Col::DateTime => TypeInfo::new_datetime2(),
+            };
+            let flags = DataFlags {
+                is_nullable: true,
+                ..Default::default()
+            };
+            builder = builder.add_column(Some(name), ty, flags);
+        }
+        Table { layout, builder }
+    }
+
+    fn add_row(mut self, values: Vec<Value>) -> Self {
+        let cells = self
+            .layout
+            .iter()
+            .zip(values)
+            .map(|((_, col), value)| {
+                let text = match &value {
+                    Value::Null => None,
+                    Value::Text(s) => Some(s.clone()),
+                    Value::Int(i) => Some(i.to_string()),
+                };
+                let int = match &value {
+                    Value::Int(i) => Some(*i),
+                    _ => None,
+                };
+                match col {
+                    Col::SysName => {
+                        ColumnData::String(SqlString::from_string(text, SYSNAME_LENGTH))
+                    }
+                    Col::NVarChar(length) => {
+                        ColumnData::String(SqlString::from_string(text, length * 2))
+                    }
+                    Col::TinyInt => ColumnData::U8N(int.map(|i| i as u8)),
+                    Col::SmallInt => ColumnData::I16N(int.map(|i| i as i16)),
+                    Col::Int => ColumnData::I32N(int.map(|i| i as i32)),
+                    Col::DateTime => ColumnData::DateTime2(None),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.builder = self.builder.add_row(&cells);
+        self
+    }
+
+    fn build(self) -> ResultSet {
+        self.builder.result
+    }
+}
+
+const SP_TABLES_LAYOUT: &[(&str, Col)] = &[
+    ("TABLE_QUALIFIER", Col::SysName),
+    ("TABLE_OWNER", Col::SysName),
+    ("TABLE_NAME", Col::SysName),
+    ("TABLE_TYPE", Col::NVarChar(32)),
+    ("REMARKS", Col::NVarChar(254)),
+];
+
+/// Tables matching the name, owner and qualifier patterns. As with ODBC `SQLTables`, a `%`
+/// pattern with all other arguments empty enumerates the qualifiers, owners or table types.
+fn sp_tables(call: &ProcedureCall, catalog: &VirtualCatalog) -> ResultSet {
+    let name = call.argument(0, "table_name");
+    let owner = call.argument(1, "table_owner");
+    let qualifier = call.argument(2, "table_qualifier");
+    let table_type = call.argument(3, "table_type");
+
+    let mut table = Table::new(SP_TABLES_LAYOUT);
+    match (qualifier, owner, name, table_type) {
+        (Some("%"), Some(""), Some(""), _) => {
+            return table
+                .add_row(vec![
+                    catalog.name().into(),
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                ])
+                .build();
+        }
+        (Some(""), Some("%"), Some(""), _) => {
+            let mut schemas = catalog.tables().map(|(s, _, _)| s).collect::<Vec<_>>();
+            schemas.dedup();
+            for schema in schemas {
+                table = table.add_row(vec![
+                    Value::Null,
+                    schema.into(),
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                ]);
+            }
+            return table.build();
+        }
+        (Some(""), Some(""), Some(""), Some("%")) => {
+            return table
+                .add_row(vec![
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                    "TABLE".into(),
+                    Value::Null,
+                ])
+                .build();
+        }
+        _ => {}
+    }
+
+    // only tables exist, views and system tables are not listed
+    let is_table_type = table_type.is_none_or(|types| {
+        types
+            .split(',')
+            .map(|t| t.trim().trim_matches(|c| c == '\'' || c == '"'))
+            .any(|t| t.eq_ignore_ascii_case("table"))
+    });
+    if !is_table_type || !qualifier.is_none_or(|q| is_like(q, catalog.name())) {
+        return table.build();
+    }
+    for (schema, name_, _) in catalog.tables() {
+        if owner.is_none_or(|o| is_like(o, schema)) && name.is_none_or(|n| is_like(n, name_)) {
+            table = table.add_row(vec![
+                catalog.name().into(),
+                schema.into(),
+                name_.into(),
+                "TABLE".into(),
+                Value::Null,
+            ]);
+        }
+    }
+    table.build()
+}
+
+const SP_COLUMNS_LAYOUT: &[(&str, Col)] = &[
+    ("TABLE_QUALIFIER", Col::SysName),
+    ("TABLE_OWNER", Col::SysName),
+    ("TABLE_NAME", Col::SysName),
+    ("COLUMN_NAME", Col::SysName),
+    ("DATA_TYPE", Col::SmallInt),
+    ("TYPE_NAME", Col::SysName),
+    ("PRECISION", Col::Int),
+    ("LENGTH", Col::Int),
+    ("SCALE", Col::SmallInt),
+    ("RADIX", Col::SmallInt),
+    ("NULLABLE", Col::SmallInt),
+    ("REMARKS", Col::NVarChar(254)),
+    ("COLUMN_DEF", Col::NVarChar(4000)),
+    ("SQL_DATA_TYPE", Col::SmallInt),
+    ("SQL_DATETIME_SUB", Col::SmallInt),
+    ("CHAR_OCTET_LENGTH", Col::Int),
+    ("ORDINAL_POSITION", Col::Int),
+    ("IS_NULLABLE", Col::NVarChar(254)),
+    ("SS_DATA_TYPE", Col::TinyInt),
+];
+
+/// Columns of the tables matching the name, owner and qualifier patterns
+fn sp_columns(call: &ProcedureCall, catalog: &VirtualCatalog) -> TdsWireResult<ResultSet> {
+    let name = call
+        .argument(0, "table_name")
+        .ok_or_else(|| missing_parameter(call.procedure, "@table_name"))?;
+    let owner = call.argument(1, "table_owner");
+    let qualifier = call.argument(2, "table_qualifier");
+    let column_name = call.argument(3, "column_name");
+
+    let mut table = Table::new(SP_COLUMNS_LAYOUT);
+    if !qualifier.is_none_or(|q| is_like(q, catalog.name())) {
+        return Ok(table.build());
+    }
+    for (schema, table_name, columns) in catalog.tables() {
+        if !is_like(name, table_name) || !owner.is_none_or(|o| is_like(o, schema)) {
+            continue;
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if !column_name.is_none_or(|c| is_like(c, &column.name)) {
+                continue;
+            }
+            table = table.add_row(column_row(catalog.name(), schema, table_name, i, column));
+        }
+    }
+    Ok(table.build())
+}
+
+fn column_row(
+    catalog: &str,
+    schema: &str,
+    table: &str,
+    index: usize,
+    column: &CatalogColumn,
+) -> Vec<Value> {
+    let ty = SqlServerType::from_column(column);
+    let odbc = OdbcType::from(&ty);
+    vec![
+        catalog.into(),
+        schema.into(),
+        table.into(),
+        column.name.as_str().into(),
+        odbc.data_type.into(),
+        ty.name.into(),
+        odbc.precision.into(),
+        odbc.length.into(),
+        odbc.scale.into(),
+        odbc.radix.into(),
+        (column.is_nullable as i64).into(),
+        Value::Null,
+        Value::Null,
+        odbc.sql_data_type.into(),
+        odbc.datetime_sub.into(),
+        (ty.is_text() || ty.system_type_id == 165)
+            .then_some(odbc.length)
+            .into(),
+        (index as i64 + 1).into(),
+        if column.is_nullable { "YES" } else { "NO" }.into(),
+        odbc.ss_data_type.into(),
+    ]
+}
+
+/// Description of a type as reported to ODBC and JDBC drivers
+struct OdbcType {
+    data_type: i64,
+    precision: i64,
+    length: i64,
+    scale: Option<i64>,
+    radix: Option<i64>,
+    sql_data_type: i64,
+    datetime_sub: Option<i64>,
+    ss_data_type: Option<i64>,
+}
+
+impl From<&SqlServerType> for OdbcType {
+    fn from(ty: &SqlServerType) -> Self {
+        let variable = |data_type, ss_data_type| {
+            let (precision, length) = match ty.max_length {
+                -1 => (1073741823, 2147483646),
+                length if ty.is_text() => (length / 2, length),
+                length => (length, length),
+            };
+            OdbcType {
+                data_type,
+                precision,
+                length,
+                scale: None,
+                radix: None,
+                sql_data_type: data_type,
+                datetime_sub: None,
+                ss_data_type: Some(ss_data_type),
+            }
+        };
+        let numeric = |data_type, precision, length, radix, ss_data_type| OdbcType {
+            data_type,
+            precision,
+            length,
+            scale: Some(ty.scale),
+            radix: Some(radix),
+            sql_data_type: data_type,
+            datetime_sub: None,
+            ss_data_type: Some(ss_data_type),
+        };
+        let temporal = |data_type, precision, length, scale, sub| OdbcType {
+            data_type,
+            precision,
+            length,
+            scale,
+            radix: None,
+            sql_data_type: 9,
+            datetime_sub: Some(sub),
+            ss_data_type: Some(0),
+        };
+        match ty.name {
+            "bit" => OdbcType {
+                radix: None,
+                scale: None,
+                ..numeric(-7, 1, 1, 10, 50)
+            },
+            "smallint" => numeric(5, 5, 2, 10, 38),
+            "int" => numeric(4, 10, 4, 10, 38),
+            "bigint" => numeric(-5, 19, 8, 10, 108),
+            "real" => OdbcType {
+                scale: None,
+                ..numeric(7, 24, 4, 2, 109)
+            },
+            "float" => OdbcType {
+                scale: None,
+                ..numeric(6, 53, 8, 2, 109)
+            },
+            "decimal" => numeric(3, ty.precision, ty.precision + 2, 10, 106),
+            "date" => temporal(91, 10, 6, None, 1),
+            "time" => temporal(92, 16, 12, Some(7), 2),
+            "datetime2" => temporal(93, 27, 16, Some(7), 3),
+            "varbinary" => variable(-3, 37),
+            _ => variable(-9, 39),
+        }
+    }
+}
+
+const SP_HELP_OBJECTS_LAYOUT: &[(&str, Col)] = &[
+    ("Name", Col::SysName),
+    ("Owner", Col::SysName),
+    ("Object_type", Col::NVarChar(31)),
+];
+
+const SP_HELP_OBJECT_LAYOUT: &[(&str, Col)] = &[
+    ("Name", Col::SysName),
+    ("Owner", Col::SysName),
+    ("Type", Col::NVarChar(31)),
+    ("Created_datetime", Col::DateTime),
+];
+
+const SP_HELP_COLUMNS_LAYOUT: &[(&str, Col)] = &[
+    ("Column_name", Col::SysName),
+    ("Type", Col::SysName),
+    ("Computed", Col::NVarChar(35)),
+    ("Length", Col::Int),
+    ("Prec", Col::NVarChar(5)),
+    ("Scale", Col::NVarChar(5)),
+    ("Nullable", Col::NVarChar(35)),
+    ("TrimTrailingBlanks", Col::NVarChar(35)),
+    ("FixedLenNullInSource", Col::NVarChar(35)),
+    ("Collation", Col::SysName),
+];
+
+/// Without an object, lists all tables. Otherwise describes the table and its columns, the
+/// result sets on identity columns, indexes and constraints are omitted as these do not exist.
+fn sp_help(
+    call: &ProcedureCall,
+    catalog: &VirtualCatalog,
+    default_schema: Option<&str>,
+) -> TdsWireResult<Vec<ResultSet>> {
+    let Some(object) = call.argument(0, "objname") else {
+        let mut table = Table::new(SP_HELP_OBJECTS_LAYOUT);
+        for (schema, name, _) in catalog.tables() {
+            table = table.add_row(vec![name.into(), schema.into(), "user table".into()]);
+        }
+        return Ok(vec![table.build()]);
+    };
+
+    let parts = object.replace(['[', ']'], "");
+    let mut parts = parts.rsplit('.');
+    let name = parts.next().unwrap_or_default();
+    let schema = parts.next().filter(|s| !s.is_empty());
+    let candidates = catalog
+        .tables()
+        .filter(|(s, n, _)| {
+            n.eq_ignore_ascii_case(name)
+                && schema.is_none_or(|schema| s.eq_ignore_ascii_case(schema))
+        })
+        .collect::<Vec<_>>();
+    // unqualified names are resolved in the database of the session first
+    let found = candidates
+        .iter()
+        .find(|(s, _, _)| schema.is_none() && Some(*s) == default_schema)
+        .or(candidates.first())
+        .copied();
+    let Some((schema, name, columns)) = found else {
+        return Err(TdsWireError::Input(format!(
+            "The object '{}' does not exist in database '{}' or is invalid for this operation.",
+            object,
+            catalog.name()
+        )));
+    };
+
+    let object = Table::new(SP_HELP_OBJECT_LAYOUT).add_row(vec![
+        name.into(),
+        schema.into(),
+        "user table".into(),
+        Value::Null,
+    ]);
+    let mut table = Table::new(SP_HELP_COLUMNS_LAYOUT);
+    for column in columns {
+        let ty = SqlServerType::from_column(column);
+        let (precision, scale) = if ty.is_numeric() {
+            (format!("{:>5}", ty.precision), format!("{:>5}", ty.scale))
+        } else {
+            (" ".repeat(5), " ".repeat(5))
+        };
+        table = table.add_row(vec![
+            column.name.as_str().into(),
+            ty.name.into(),
+            "no".into(),
+            ty.max_length.into(),
+            precision.as_str().into(),
+            scale.as_str().into(),
+            if column.is_nullable { "yes" } else { "no" }.into(),
+            "(n/a)".into(),
+            "(n/a)".into(),
+            ty.is_text()
+                .then_some("Latin1_General_100_CI_AS_SC_UTF8")
+                .into(),
+        ]);
+    }
+    Ok(vec![object.build(), table.build()])
+}
+
+const SP_WHO_LAYOUT: &[(&str, Col)] = &[
+    ("spid", Col::SmallInt),
+    ("ecid", Col::SmallInt),
+    ("status", Col::NVarChar(30)),
+    ("loginame", Col::NVarChar(128)),
+    ("hostname", Col::NVarChar(128)),
+    ("blk", Col::NVarChar(5)),
+    ("dbname", Col::NVarChar(128)),
+    ("cmd", Col::NVarChar(16)),
+    ("request_id", Col::Int),
+];
+
+/// Sessions of the user, as users lack the permission to view the sessions of others. The
+/// optional login argument is a login name, a session id or `active`.
+fn sp_who(call: &ProcedureCall, sessions: &[ActiveSession], session_id: Ulid) -> ResultSet {
+    let user = sessions
+        .iter()
+        .find(|s| s.session_id == session_id)
+        .map(|s| s.user.as_str());
+    let filter = call.argument(0, "loginame");
+
+    let mut table = Table::new(SP_WHO_LAYOUT);
+    for session in sessions.iter().filter(|s| Some(s.user.as_str()) == user) {
+        let is_current = session.session_id == session_id;
+        let is_match = match filter {
+            None => true,
+            Some(f) if f.eq_ignore_ascii_case("active") => is_current,
+            Some(f) => match f.parse::<u16>() {
+                Ok(spid) => session.spid == spid,
+                Err(_) => session.user.eq_ignore_ascii_case(f),
+            },
+        };
+        if !is_match {
+            continue;
+        }
+        let (status, cmd) = if is_current {
+            ("runnable", "SELECT")
+        } else {
+            ("sleeping", "AWAITING COMMAND")
+        };
+        table = table.add_row(vec![
+            (session.spid as i64).into(),
+            Value::Int(0),
+            status.into(),
+            session.user.as_str().into(),
+            session.hostname.as_str().into(),
+            "0".into(),
+            Some(session.database.as_str())
+                .filter(|d| !d.is_empty())
+                .into(),
+            cmd.into(),
+            Value::Int(0),
+        ]);
+    }
+    table.build()
+}
+
+const SP_SERVER_INFO_LAYOUT: &[(&str, Col)] = &[
+    ("attribute_id", Col::Int),
+    ("attribute_name", Col::SysName),
+    ("attribute_value", Col::NVarChar(255)),
+];
+
+/// Attributes of the server, optionally only the attribute with the given id
+fn sp_server_info(call: &ProcedureCall, server: &ServerContext) -> TdsWireResult<ResultSet> {
+    let attribute_id = match call.argument(0, "attribute_id") {
+        Some(id) => Some(id.parse::<i64>().map_err(|_| {
+            TdsWireError::Input(format!(
+                "Error converting data type nvarchar to int for value '{}'.",
+                id
+            ))
+        })?),
+        None => None,
+    };
+
+    let version = format!(
+        "Microsoft SQL Server - {}",
+        server.get_server_version_string()
+    );
+    let attributes: &[(i64, &str, &str)] = &[
+        (1, "DBMS_NAME", "Microsoft SQL Server"),
+        (2, "DBMS_VER", &version),
+        (10, "OWNER_TERM", "owner"),
+        (11, "TABLE_TERM", "table"),
+        (12, "MAX_OWNER_NAME_LENGTH", "128"),
+        (13, "TABLE_LENGTH", "128"),
+        (14, "MAX_QUAL_LENGTH", "128"),
+        (15, "COLUMN_LENGTH", "128"),
+        (16, "IDENTIFIER_CASE", "MIXED"),
+        (17, "TX_ISOLATION", "2"),
+        (
+            18,
+            "COLLATION_SEQ",
+            "charset=iso_1 sort_order=nocase_iso charset_num=1 sort_order_num=52",
+        ),
+        (19, "SAVEPOINT_SUPPORT", "N"),
+        (20, "MULTI_RESULT_SETS", "Y"),
+        (22, "ACCESSIBLE_TABLES", "Y"),
+        (100, "USERID_LENGTH", "128"),
+        (101, "QUALIFIER_TERM", "database"),
+        (102, "NAMED_TRANSACTIONS", "N"),
+        (103, "SPROC_AS_LANGUAGE", "Y"),
+        (104, "ACCESSIBLE_SPROC", "Y"),
+        (105, "MAX_INDEX_COLS", "16"),
+        (106, "RENAME_TABLE", "N"),
+        (107, "RENAME_COLUMN", "N"),
+        (108, "DROP_COLUMN", "N"),
+        (109, "INCREASE_COLUMN_LENGTH", "N"),
+        (110, "DDL_IN_TRANSACTION", "N"),
+        (111, "DESCENDING_INDEXES", "N"),
+        (112, "SP_RENAME", "N"),
+        (113, "REMOTE_SPROC", "N"),
+        (500, "SYS_SPROC_VERSION", "16.00.4135"),
+    ];
+
+    let mut table = Table::new(SP_SERVER_INFO_LAYOUT);
+    for (id, name, value) in attributes {
+        if attribute_id.is_none_or(|a| a == *id) {
+            table = table.add_row(vec![(*id).into(), (*name).into(), (*value).into()]);
+        }
+    }
+    Ok(table.build())
+}
+
+fn missing_parameter(procedure: SystemProcedure, parameter: &str) -> TdsWireError {
+    TdsWireError::Input(format!(
+        "Procedure or function '{}' expects parameter '{}', which was not supplied.",
+        procedure.name(),
+        parameter
+    ))
+}
+
+/// Match the value against a LIKE pattern, case insensitive. Supports the `%` and `_` wildcards
+/// and `[...]` character sets, which drivers use to escape wildcards in names. Patterns are
+/// supplied by the client, matching is therefore linear in the size of the pattern times the
+/// size of the value.
+fn is_like(pattern: &str, value: &str) -> bool {
+    enum Token {
+        Any,
+        One,
+        Set(Vec<char>),
+        Char(char),
+    }
+
+    let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+    let value = value.to_lowercase().chars().collect::<Vec<_>>();
+
+    // runs of % are collapsed, they match the same as a single %
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pattern.len() {
+        let token = match pattern[i] {
+            '%' if matches!(tokens.last(), Some(Token::Any)) => None,
+            '%' => Some(Token::Any),
+            '_' => Some(Token::One),
+            '[' => match pattern[i + 1..].iter().position(|&c| c == ']') {
+                Some(end) => {
+                    let set = pattern[i + 1..i + 1 + end].to_vec();
+                    i += end + 1;
+                    Some(Token::Set(set))
+                }
+                None => Some(Token::Char('[')),
+            },
+            c => Some(Token::Char(c)),
+        };
+        tokens.extend(token);
+        i += 1;
+    }
+
+    // matched[j] is true if the tokens processed so far match the first j characters
+    let mut matched = vec![false; value.len() + 1];
+    matched[0] = true;
+    for token in &tokens {
+        let mut next = vec![false; value.len() + 1];
+        for j in 0..=value.len() {
+            next[j] = match token {
+                Token::Any => matched[j] || (j > 0 && next[j - 1]),
+                _ if j == 0 => false,
+                Token::One => matched[j - 1],
+                Token::Set(set) => matched[j - 1] && set.contains(&value[j - 1]),
+                Token::Char(c) => matched[j - 1] && value[j - 1] == *c,
+            };
+        }
+        matched = next;
+    }
+    matched[value.len()]
+}
+
+/// Split the leading word from the remainder of the statement
+fn split_word(statement: &str) -> (&str, &str) {
+    let end = statement
+        .find(char::is_whitespace)
+        .unwrap_or(statement.len());
+    (&statement[..end], statement[end..].trim_start())
+}
+
+/// Arguments of an `EXEC` statement, e.g. `N'orders', @table_owner = 'sales'`
+fn parse_arguments(arguments: &str) -> TdsWireResult<Vec<(Option<String>, Option<String>)>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    for (i, c) in arguments.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            ('[', None) => quote = Some(']'),
+            (c, Some(q)) if c == q => quote = None,
+            (',', None) => {
+                parts.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&arguments[start..]);
+    if parts.len() == 1 && parts[0].trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    parts
+        .into_iter()
+        .map(|part| {
+            let part = part.trim();
+            let (name, value) = match part.strip_prefix('@') {
+                Some(rest) => {
+                    let (name, value) = rest.split_once('=').ok_or_else(|| syntax_error(part))?;
+                    (Some(name.trim().to_lowercase()), value.trim())
+                }
+                None => (None, part),
+            };
+            Ok((name, parse_value(value)?))
+        })
+        .collect()
+}
+
+fn parse_value(value: &str) -> TdsWireResult<Option<String>> {
+    let unprefixed = value
+        .strip_prefix(['N', 'n'])
+        .filter(|v| v.starts_with('\''))
+        .unwrap_or(value);
+    match unprefixed.chars().next() {
+        _ if value.eq_ignore_ascii_case("null") || value.eq_ignore_ascii_case("default") => {
+            Ok(None)
+        }
+        Some('\'') if unprefixed.len() > 1 && unprefixed.ends_with('\'') => {
+            Ok(Some(unprefixed[1..unprefixed.len() - 1].replace("''", "'")))
+        }
+        Some('"') if value.len() > 1 && value.ends_with('"') => {
+            Ok(Some(value[1..value.len() - 1].replace("\"\"", "\"")))
+        }
+        Some('[') if value.ends_with(']') => Ok(Some(value[1..value.len() - 1].to_string())),
+        Some(_) if !value.contains(|c: char| c.is_whitespace() || c == '\'') => {
+            Ok(Some(value.to_string()))
+        }
+        _ => Err(syntax_error(value)),
+    }
+}
+
+fn syntax_error(near: &str) -> TdsWireError {
+    TdsWireError::Input(format!("Incorrect syntax near '{}'.", near))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::catalog::{get_number, get_text, CatalogColumn, VirtualCatalog};
+    use crate::backend::app::generic::ResultSet;
+    use crate::backend::app::procedures::{
+        execute_procedure, is_like, ProcedureCall, ProcedureContext, SystemProcedure,
+    };
+    use crate::frontend::prot::ActiveSession;
+    use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::{TokenColMetaData, TypeInfo};
+    use ulid::Ulid;
+
+    fn get_catalog() -> VirtualCatalog {
+        let mut catalog = VirtualCatalog::new("lake");
+        for (schema, table, name, data_type, length, precision) in [
+            ("sales", "orders", "id", "bigint", None, Some(19)),
+            ("sales", "orders", "amount", "decimal", None, Some(10)),
+            (
+                "sales",
+                "order_lines",
+                "product",
+                "varchar",
+                Some(100),
+                None,
+            ),
+            ("crm", "customers", "email", "varchar", Some(200), None),
+        ] {
+            catalog.add_column(
+                schema,
+                table,
+                CatalogColumn {
+                    name: name.to_string(),
+                    data_type: data_type.to_string(),
+                    is_nullable: name != "id",
+                    max_length: length,
+                    precision,
+                    scale: precision.map(|_| 2),
+                },
+            );
+        }
+        catalog
+    }
+
+    fn get_session(spid: u16, user: &str) -> ActiveSession {
+        ActiveSession {
+            session_id: Ulid::new(),
+            spid,
+            socket_addr: "10.0.0.1:50000".parse().unwrap(),
+            hostname: "laptop".to_string(),
+            user: user.to_string(),
+            database: "sales".to_string(),
+        }
+    }
+
+    fn execute(call: &ProcedureCall, sessions: Vec<ActiveSession>) -> Vec<ResultSet> {
+        let server = ServerContext::default();
+        let catalog = get_catalog();
+        let session_id = sessions.first().map(|s| s.session_id).unwrap_or_default();
+        let context = ProcedureContext {
+            server: &server,
+            catalog: Some(&catalog),
+            sessions,
+            session_id,
+            schema: Some("sales"),
+        };
+        execute_procedure(call, &context).unwrap()
+    }
+
+    /// Columns of the result set, as `<name> <type>(<length>)`
+    fn get_layout(result_set: &mut ResultSet) -> Vec<String> {
+        TokenColMetaData::from(result_set)
+            .columns
+            .iter()
+            .map(|c| match &c.base.ty {
+                TypeInfo::VarLenSized(ctx) => {
+                    format!("{} {:?}({})", c.col_name, ctx.r#type(), ctx.len())
+                }
+                ty => format!("{} {:?}", c.col_name, ty),
+            })
+            .collect()
+    }
+
+    /// Values of the first column of each row
+    fn get_first_values(result_set: ResultSet) -> Vec<String> {
+        result_set
+            .map(|row| {
+                let value = row.get(0).unwrap();
+                get_text(value)
+                    .or_else(|| get_number(value).map(|n| n.to_string()))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn call(procedure: SystemProcedure, arguments: &[(Option<&str>, &str)]) -> ProcedureCall {
+        ProcedureCall::new(
+            procedure,
+            arguments.iter().map(|(n, v)| (*n, Some(*v))).collect(),
+        )
+    }
+
+    #[test]
+    fn parse_procedure_calls() {
+        assert_eq!(
+            ProcedureCall::parse("sp_who").unwrap().unwrap(),
+            ProcedureCall::new(SystemProcedure::Who, vec![])
+        );
+        assert_eq!(
+            ProcedureCall::parse(
+                "EXEC [master].[dbo].[sp_columns] N'orders', @table_owner = 'sa''les', NULL;"
+            )
+            .unwrap()
+            .unwrap(),
+            ProcedureCall::new(
+                SystemProcedure::Columns,
+                vec![
+                    (None, Some("orders")),
+                    (Some("table_owner"), Some("sa'les")),
+                    (None, None)
+                ]
+            )
+        );
+        assert_eq!(
+            ProcedureCall::parse("execute sp_tables @table_type = \"'TABLE','VIEW'\"")
+                .unwrap()
+                .unwrap(),
+            ProcedureCall::new(
+                SystemProcedure::Tables,
+                vec![(Some("table_type"), Some("'TABLE','VIEW'"))]
+            )
+        );
+        assert!(ProcedureCall::parse("exec sp_help @objname")
+            .unwrap()
+            .is_err());
+        assert!(ProcedureCall::parse("exec sp_rename 'a', 'b'").is_none());
+        assert!(ProcedureCall::parse("select * from sp_who").is_none());
+    }
+
+    #[test]
+    fn match_like_patterns() {
+        assert!(is_like("%", "orders"));
+        assert!(is_like("ORD%", "orders"));
+        assert!(is_like("order_lines", "order_lines"));
+        assert!(is_like("order[_]lines", "order_lines"));
+        assert!(!is_like("order[_]lines", "orderXlines"));
+        assert!(is_like("o_ders", "orders"));
+        assert!(!is_like("orders%x", "orders"));
+        assert!(is_like("%%or%%s", "orders"));
+        assert!(is_like("[ab", "[ab"));
+    }
+
+    #[test]
+    fn like_pathological_pattern() {
+        // backtracking on every % would not finish in any reasonable time
+        let pattern = format!("{}x", "%".repeat(40));
+        let value = "a".repeat(5000);
+        let start = std::time::Instant::now();
+        assert!(!is_like(&pattern, &value));
+        assert!(!is_like(&"%a".repeat(200), &value[..100]));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn sp_tables_layout_and_rows() {
+        let mut result = execute(&call(SystemProcedure::Tables, &[]), vec![]).remove(0);
+        assert_eq!(
+            get_layout(&mut result),
+            vec![
+                "TABLE_QUALIFIER NVarchar(256)",
+                "TABLE_OWNER NVarchar(256)",
+                "TABLE_NAME NVarchar(256)",
+                "TABLE_TYPE NVarchar(64)",
+                "REMARKS NVarchar(508)",
+            ]
+        );
+        assert_eq!(get_first_values(result), vec!["lake"; 3]);
+
+        let tables = |arguments: &[(Option<&str>, &str)]| {
+            let result = execute(&call(SystemProcedure::Tables, arguments), vec![]).remove(0);
+            result
+                .map(|row| row.get(2).and_then(get_text).unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tables(&[(None, "order%")]), vec!["order_lines", "orders"]);
+        assert_eq!(
+            tables(&[
+                (Some("table_owner"), "crm"),
+                (Some("table_type"), "'TABLE'")
+            ]),
+            vec!["customers"]
+        );
+        assert!(tables(&[(Some("table_type"), "'VIEW'")]).is_empty());
+        assert!(tables(&[(Some("table_qualifier"), "other")]).is_empty());
+
+        // enumeration of the owners
+        let result = execute(
+            &call(
+                SystemProcedure::Tables,
+                &[(None, ""), (None, "%"), (None, "")],
+            ),
+            vec![],
+        )
+        .remove(0);
+        let owners = result
+            .map(|row| row.get(1).and_then(get_text).unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(owners, vec!["crm", "sales"]);
+    }
+
+    #[test]
+    fn sp_columns_layout_and_rows() {
+        let mut result = execute(
+            &call(SystemProcedure::Columns, &[(Some("table_name"), "orders")]),
+            vec![],
+        )
+        .remove(0);
+        assert_eq!(
+            get_layout(&mut result),
+            vec![
+                "TABLE_QUALIFIER NVarchar(256)",
+                "TABLE_OWNER NVarchar(256)",
+                "TABLE_NAME NVarchar(256)",
+                "COLUMN_NAME NVarchar(256)",
+                "DATA_TYPE Intn(2)",
+                "TYPE_NAME NVarchar(256)",
+                "PRECISION Intn(4)",
+                "LENGTH Intn(4)",
+                "SCALE Intn(2)",
+                "RADIX Intn(2)",
+                "NULLABLE Intn(2)",
+                "REMARKS NVarchar(508)",
+                "COLUMN_DEF NVarchar(8000)",
+                "SQL_DATA_TYPE Intn(2)",
+                "SQL_DATETIME_SUB Intn(2)",
+                "CHAR_OCTET_LENGTH Intn(4)",
+                "ORDINAL_POSITION Intn(4)",
+                "IS_NULLABLE NVarchar(508)",
+                "SS_DATA_TYPE Intn(1)",
+            ]
+        );
+
+        let rows = result.collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        let values = |i: usize| {
+            (0..19)
+                .map(|j| {
+                    let value = rows[i].get(j).unwrap();
+                    get_text(value).or_else(|| get_number(value).map(|n| n.to_string()))
+                })
+                .collect::<Vec<_>>()
+        };
+        let amount = values(1);
+        assert_eq!(amount[3].as_deref(), Some("amount"));
+        assert_eq!(amount[4].as_deref(), Some("3"));
+        assert_eq!(amount[5].as_deref(), Some("decimal"));
+        assert_eq!(amount[6].as_deref(), Some("10"));
+        assert_eq!(amount[8].as_deref(), Some("2"));
+        assert_eq!(amount[16].as_deref(), Some("2"));
+        assert_eq!(values(0)[10].as_deref(), Some("0"));
+
+        let server = ServerContext::default();
+        let context = ProcedureContext {
+            server: &server,
+            catalog: Some(&get_catalog()),
+            sessions: vec![],
+            session_id: Ulid::new(),
+            schema: None,
+        };
+        assert!(execute_procedure(&call(SystemProcedure::Columns, &[]), &context).is_err());
+    }
+
+    #[test]
+    fn sp_help_layout_and_rows() {
+        let mut result = execute(&call(SystemProcedure::Help, &[]), vec![]).remove(0);
+        assert_eq!(
+            get_layout(&mut result),
+            vec![
+                "Name NVarchar(256)",
+                "Owner NVarchar(256)",
+                "Object_type NVarchar(62)",
+            ]
+        );
+        assert_eq!(
+            get_first_values(result),
+            vec!["customers", "order_lines", "orders"]
+        );
+
+        let mut results = execute(&call(SystemProcedure::Help, &[(None, "[orders]")]), vec![]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            get_layout(&mut results[0]),
+            vec![
+                "Name NVarchar(256)",
+                "Owner NVarchar(256)",
+                "Type NVarchar(62)",
+                "Created_datetime Datetime2(7)",
+            ]
+        );
+        assert_eq!(
+            get_layout(&mut results[1]),
+            vec![
+                "Column_name NVarchar(256)",
+                "Type NVarchar(256)",
+                "Computed NVarchar(70)",
+                "Length Intn(4)",
+                "Prec NVarchar(10)",
+                "Scale NVarchar(10)",
+                "Nullable NVarchar(70)",
+                "TrimTrailingBlanks NVarchar(70)",
+                "FixedLenNullInSource NVarchar(70)",
+                "Collation NVarchar(256)",
+            ]
+        );
+        assert_eq!(get_first_values(results.remove(1)), vec!["id", "amount"]);
+
+        let server = ServerContext::default();
+        let context = ProcedureContext {
+            server: &server,
+            catalog: Some(&get_catalog()),
+            sessions: vec![],
+            session_id: Ulid::new(),
+            schema: None,
+        };
+        let hidden = call(SystemProcedure::Help, &[(None, "secrets")]);
+        assert!(execute_procedure(&hidden, &context).is_err());
+    }
+
+    #[test]
+    fn sp_who_lists_sessions_of_user() {
+        let sessions = vec![
+            get_session(52, "alice"),
+            get_session(51, "bob"),
+            get_session(53, "alice"),
+        ];
+        let mut result = execute(&call(SystemProcedure::Who, &[]), sessions.clone()).remove(0);
+        assert_eq!(
+            get_layout(&mut result),
+            vec![
+                "spid Intn(2)",
+                "ecid Intn(2)",
+                "status NVarchar(60)",
+                "loginame NVarchar(256)",
+                "hostname NVarchar(256)",
+                "blk NVarchar(10)",
+                "dbname NVarchar(256)",
+                "cmd NVarchar(32)",
+                "request_id Intn(4)",
+            ]
+        );
+        assert_eq!(get_first_values(result), vec!["52", "53"]);
+
+        let who = |argument: &str| {
+            let result = execute(
+                &call(SystemProcedure::Who, &[(None, argument)]),
+                sessions.clone(),
+            );
+            get_first_values(result.into_iter().next().unwrap())
+        };
+        assert_eq!(who("active"), vec!["52"]);
+        assert_eq!(who("53"), vec!["53"]);
+        assert!(who("51").is_empty());
+        assert!(who("bob").is_empty());
+    }
+
+    #[test]
+    fn sp_server_info_layout_and_rows() {
+        let mut result = execute(&call(SystemProcedure::ServerInfo, &[]), vec![]).remove(0);
+        assert_eq!(
+            get_layout(&mut result),
+            vec![
+                "attribute_id Intn(4)",
+                "attribute_name NVarchar(256)",
+                "attribute_value NVarchar(510)",
+            ]
+        );
+        assert_eq!(result.count(), 29);
+
+        let result = execute(
+            &call(SystemProcedure::ServerInfo, &[(Some("attribute_id"), "2")]),
+            vec![],
+        )
+        .remove(0);
+        let rows = result.collect::<Vec<_>>();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0].get(2).and_then(get_text),
+            Some("Microsoft SQL Server - 16.0.4135.0".to_string())
+        );
+    }
+}
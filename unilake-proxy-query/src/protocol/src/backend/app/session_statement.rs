@@ -0,0 +1,296 @@
+//! Statements changing the state of the session, which are handled by the proxy instead of the
+//! backend: `USE <database>`, `SET` of the proxy session variables and the T-SQL `SET` options
+//! clients send when connecting. Any other `SET` is left to the backend. Of the T-SQL options,
+//! only NOCOUNT and XACT_ABORT change the behavior of the proxy, the others are accepted so
+//! client connections succeed, but have no effect on the queries executed.
+use crate::session::{
+    SESSION_VARIABLE_CATALOG, SESSION_VARIABLE_DATABASE, SESSION_VARIABLE_DIALECT,
+    SESSION_VARIABLE_NOCOUNT, SESSION_VARIABLE_SECURITY_IMPERSONATE,
+    SESSION_VARIABLE_SEND_TELEMETRY, SESSION_VARIABLE_XACT_ABORT,
+};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// T-SQL options set to ON or OFF, stored under their lowercase name
+const ON_OFF_OPTIONS: &[&str] = &[
+    "nocount",
+    "ansi_nulls",
+    "ansi_null_dflt_on",
+    "ansi_null_dflt_off",
+    "ansi_padding",
+    "ansi_warnings",
+    "arithabort",
+    "arithignore",
+    "concat_null_yields_null",
+    "cursor_close_on_commit",
+    "implicit_transactions",
+    "numeric_roundabort",
+    "quoted_identifier",
+    "xact_abort",
+];
+
+/// T-SQL options with a value, stored under their lowercase name
+const VALUE_OPTIONS: &[&str] = &["textsize", "lock_timeout", "dateformat", "datefirst"];
+
+/// T-SQL options applied by the proxy
+const APPLIED_OPTIONS: &[&str] = &[SESSION_VARIABLE_NOCOUNT, SESSION_VARIABLE_XACT_ABORT];
+
+/// Session variables of the proxy which can be set by the client
+const PROXY_VARIABLES: &[&str] = &[
+    SESSION_VARIABLE_DIALECT,
+    SESSION_VARIABLE_CATALOG,
+    SESSION_VARIABLE_DATABASE,
+    SESSION_VARIABLE_SECURITY_IMPERSONATE,
+    SESSION_VARIABLE_SEND_TELEMETRY,
+];
+
+const DATE_FORMATS: &[&str] = &["mdy", "dmy", "ymd", "ydm", "myd", "dym"];
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStatement {
+    /// Change the database of the session
+    Use(String),
+    /// Set the session variables, by name and value
+    Set(Vec<(String, String)>),
+}
+
+/// Parse the statement if it changes the session, returns none for any other statement. Known
+/// options with an invalid value result in an input error.
+pub fn parse_session_statement(statement: &str) -> Option<TdsWireResult<SessionStatement>> {
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let (keyword, rest) = split_name(statement);
+    if keyword.eq_ignore_ascii_case("use") {
+        return Some(parse_identifier(rest).map(SessionStatement::Use));
+    }
+    if !keyword.eq_ignore_ascii_case("set") {
+        return None;
+    }
+
+    let (name, value) = split_name(rest);
+    let name = name.to_lowercase();
+    if ON_OFF_OPTIONS.contains(&name.as_str()) {
+        return parse_on_off_options(rest);
+    }
+
+    let value = value.strip_prefix('=').unwrap_or(value).trim();
+    if name == SESSION_VARIABLE_DATABASE {
+        return Some(parse_identifier(&unquote(value)).map(SessionStatement::Use));
+    }
+    let value = if VALUE_OPTIONS.contains(&name.as_str()) {
+        parse_option_value(&name, value)
+    } else if PROXY_VARIABLES.contains(&name.as_str()) {
+        parse_variable_value(&name, value)
+    } else {
+        return None;
+    };
+    Some(value.map(|value| SessionStatement::Set(vec![(name, value)])))
+}
+
+/// Whether the variable is a T-SQL option which is accepted, but has no effect
+pub fn is_ignored_option(name: &str) -> bool {
+    (ON_OFF_OPTIONS.contains(&name) || VALUE_OPTIONS.contains(&name))
+        && !APPLIED_OPTIONS.contains(&name)
+}
+
+/// Split the leading name from the remainder of the statement
+fn split_name(statement: &str) -> (&str, &str) {
+    let end = statement
+        .find(|c: char| c.is_whitespace() || c == '=' || c == ',')
+        .unwrap_or(statement.len());
+    (&statement[..end], statement[end..].trim_start())
+}
+
+/// Options set at once, e.g. `SET ANSI_NULLS, QUOTED_IDENTIFIER ON`. Lists containing other
+/// options are left to the backend.
+fn parse_on_off_options(rest: &str) -> Option<TdsWireResult<SessionStatement>> {
+    let (options, value) = rest.rsplit_once(char::is_whitespace)?;
+    let options = options
+        .split(',')
+        .map(|option| option.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    if !options
+        .iter()
+        .all(|option| ON_OFF_OPTIONS.contains(&option.as_str()))
+    {
+        return None;
+    }
+
+    let value = value.to_lowercase();
+    if value != "on" && value != "off" {
+        return Some(Err(invalid_value(&options.join(", "), &value)));
+    }
+    Some(Ok(SessionStatement::Set(
+        options
+            .into_iter()
+            .map(|option| (option, value.clone()))
+            .collect(),
+    )))
+}
+
+fn parse_option_value(name: &str, value: &str) -> TdsWireResult<String> {
+    let value = unquote(value);
+    let is_valid = match name {
+        "textsize" => value.parse::<i32>().is_ok_and(|v| v >= -1),
+        "lock_timeout" => value.parse::<i64>().is_ok_and(|v| v >= -1),
+        "datefirst" => value.parse::<u8>().is_ok_and(|v| (1..=7).contains(&v)),
+        "dateformat" => DATE_FORMATS.contains(&value.to_lowercase().as_str()),
+        _ => false,
+    };
+    match is_valid {
+        true => Ok(value.to_lowercase()),
+        false => Err(invalid_value(name, &value)),
+    }
+}
+
+fn parse_variable_value(name: &str, value: &str) -> TdsWireResult<String> {
+    let value = unquote(value);
+    let is_valid = match name {
+        SESSION_VARIABLE_SEND_TELEMETRY => value == "true" || value == "false",
+        _ => !value.is_empty(),
+    };
+    match is_valid {
+        true => Ok(value),
+        false => Err(invalid_value(name, &value)),
+    }
+}
+
+/// Name of a database, optionally delimited
+fn parse_identifier(value: &str) -> TdsWireResult<String> {
+    let name = match value.chars().next() {
+        Some('[') if value.ends_with(']') => value[1..value.len() - 1].replace("]]", "]"),
+        Some(c @ ('"' | '`')) if value.len() > 1 && value.ends_with(c) => {
+            let quote = c.to_string();
+            value[1..value.len() - 1].replace(&quote.repeat(2), &quote)
+        }
+        _ if value.contains(char::is_whitespace) => String::new(),
+        _ => value.to_string(),
+    };
+    if name.is_empty() {
+        return Err(TdsWireError::Input(format!(
+            "Incorrect syntax near '{}'.",
+            value
+        )));
+    }
+    Ok(name)
+}
+
+/// Value without its string quotes, if any
+fn unquote(value: &str) -> String {
+    let unprefixed = value
+        .strip_prefix('N')
+        .or_else(|| value.strip_prefix('n'))
+        .filter(|v| v.starts_with('\''))
+        .unwrap_or(value);
+    match unprefixed.chars().next() {
+        Some(c @ ('\'' | '"')) if unprefixed.len() > 1 && unprefixed.ends_with(c) => {
+            let quote = c.to_string();
+            unprefixed[1..unprefixed.len() - 1].replace(&quote.repeat(2), &quote)
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn invalid_value(name: &str, value: &str) -> TdsWireError {
+    TdsWireError::Input(format!(
+        "Invalid value '{}' for SET {}.",
+        value,
+        name.to_uppercase()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::app::session_statement::{
+        is_ignored_option, parse_session_statement, SessionStatement,
+    };
+
+    fn parse(statement: &str) -> Option<SessionStatement> {
+        parse_session_statement(statement).map(|r| r.unwrap())
+    }
+
+    fn set(values: &[(&str, &str)]) -> Option<SessionStatement> {
+        Some(SessionStatement::Set(
+            values
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn parse_use() {
+        let expected = Some(SessionStatement::Use("sales".to_string()));
+        assert_eq!(parse("USE sales"), expected);
+        assert_eq!(parse("use [sales];"), expected);
+        assert_eq!(parse("  USE \"sales\"  "), expected);
+        assert_eq!(parse("SET proxy_database = 'sales'"), expected);
+        assert_eq!(
+            parse("USE [my]]db]"),
+            Some(SessionStatement::Use("my]db".to_string()))
+        );
+        assert!(parse_session_statement("USE").unwrap().is_err());
+        assert!(parse_session_statement("USE my db").unwrap().is_err());
+        assert_eq!(parse("users"), None);
+    }
+
+    #[test]
+    fn parse_on_off_options() {
+        assert_eq!(parse("SET NOCOUNT ON"), set(&[("nocount", "on")]));
+        assert_eq!(
+            parse("set ansi_nulls, Quoted_Identifier off;"),
+            set(&[("ansi_nulls", "off"), ("quoted_identifier", "off")])
+        );
+        assert!(parse_session_statement("SET XACT_ABORT MAYBE")
+            .unwrap()
+            .is_err());
+        // lists with options not handled by the proxy go to the backend
+        assert_eq!(parse("SET NOCOUNT, STATISTICS IO ON"), None);
+    }
+
+    #[test]
+    fn parse_value_options() {
+        assert_eq!(
+            parse("SET TEXTSIZE 2147483647"),
+            set(&[("textsize", "2147483647")])
+        );
+        assert_eq!(parse("SET LOCK_TIMEOUT -1"), set(&[("lock_timeout", "-1")]));
+        assert_eq!(parse("SET DATEFORMAT 'DMY'"), set(&[("dateformat", "dmy")]));
+        assert_eq!(parse("SET DATEFIRST 7"), set(&[("datefirst", "7")]));
+        assert!(parse_session_statement("SET DATEFORMAT xyz")
+            .unwrap()
+            .is_err());
+        assert!(parse_session_statement("SET LOCK_TIMEOUT soon")
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_proxy_variables() {
+        assert_eq!(
+            parse("SET proxy_dialect = 'snowflake'"),
+            set(&[("proxy_dialect", "snowflake")])
+        );
+        assert_eq!(
+            parse("set PROXY_CATALOG=N'lake'"),
+            set(&[("proxy_catalog", "lake")])
+        );
+        assert_eq!(
+            parse("SET proxy_send_telemetry true"),
+            set(&[("proxy_send_telemetry", "true")])
+        );
+        assert!(parse_session_statement("SET proxy_send_telemetry = 'yes'")
+            .unwrap()
+            .is_err());
+        // other variables are set on the backend
+        assert_eq!(parse("SET query_timeout = 300"), None);
+        assert_eq!(parse("SET @count = 1"), None);
+    }
+
+    #[test]
+    fn ignored_options() {
+        assert!(!is_ignored_option("nocount"));
+        assert!(!is_ignored_option("xact_abort"));
+        assert!(is_ignored_option("quoted_identifier"));
+        assert!(is_ignored_option("lock_timeout"));
+        assert!(!is_ignored_option("proxy_dialect"));
+    }
+}
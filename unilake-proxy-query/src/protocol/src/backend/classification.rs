@@ -0,0 +1,113 @@
+//! Data classification of result sets, derived from the tags of the attributes selected by the
+//! query. Result columns are matched to attributes by name, so computed or renamed columns are
+//! not classified.
+use crate::frontend::{MetaDataColumn, SensitivityProperty, TokenDataClassification};
+use std::collections::HashMap;
+
+/// Sensitivity label of all tagged columns
+const SENSITIVITY_LABEL: &str = "Confidential";
+const SENSITIVITY_LABEL_ID: &str = "confidential";
+/// Tag categories up to this length are treated as acronyms (pii, phi, pci)
+const MAX_ACRONYM_LENGTH: usize = 3;
+
+/// Classify each column by the tags of the attributes with the same name, `attribute_tags` is
+/// keyed by lowercase attribute name
+pub(crate) fn classify_columns(
+    columns: &[MetaDataColumn],
+    attribute_tags: &HashMap<String, Vec<String>>,
+) -> TokenDataClassification {
+    let label = SensitivityProperty::new(
+        SENSITIVITY_LABEL.to_string(),
+        SENSITIVITY_LABEL_ID.to_string(),
+    );
+    let mut classification = TokenDataClassification::new();
+    for column in columns {
+        let sources = attribute_tags
+            .get(&column.col_name.to_lowercase())
+            .map(|tags| {
+                tags.iter()
+                    .map(|tag| (label.clone(), information_type(tag)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        classification.add_column(sources);
+    }
+    classification
+}
+
+/// Information type of a tag, identified by the tag itself. The name is the category followed
+/// by the tag, `pii::email` and `pii_email` are both named `PII email`.
+fn information_type(tag: &str) -> SensitivityProperty {
+    let name = match tag.split_once("::").or_else(|| tag.split_once('_')) {
+        Some((category, name)) => {
+            let category = if category.len() <= MAX_ACRONYM_LENGTH {
+                category.to_uppercase()
+            } else {
+                let mut chars = category.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            };
+            format!("{} {}", category, name.replace('_', " "))
+        }
+        None => tag.replace('_', " "),
+    };
+    SensitivityProperty::new(name, tag.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::classification::{classify_columns, information_type};
+    use crate::frontend::{
+        BaseMetaDataColumn, DataFlags, MetaDataColumn, SensitivityClassification, TypeInfo,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn name_information_types() {
+        assert_eq!(information_type("pii::email").name, "PII email");
+        assert_eq!(information_type("pii_email").name, "PII email");
+        assert_eq!(
+            information_type("pii::home_address").name,
+            "PII home address"
+        );
+        assert_eq!(information_type("finance::revenue").name, "Finance revenue");
+        assert_eq!(information_type("internal").name, "internal");
+        assert_eq!(information_type("pii::email").id, "pii::email");
+    }
+
+    #[test]
+    fn classify_columns_by_attribute_name() {
+        let column = |name: &str| MetaDataColumn {
+            base: BaseMetaDataColumn {
+                flags: DataFlags::default(),
+                ty: TypeInfo::new_nvarchar(100),
+            },
+            col_name: name.to_string(),
+        };
+        let columns = [column("id"), column("Email"), column("full_name")];
+        let attribute_tags = HashMap::from([
+            ("email".to_string(), vec!["pii::email".to_string()]),
+            (
+                "full_name".to_string(),
+                vec!["pii::firstname".to_string(), "pii::lastname".to_string()],
+            ),
+        ]);
+
+        let classification = classify_columns(&columns, &attribute_tags);
+        assert!(classification.is_classified());
+        assert_eq!(classification.labels().len(), 1);
+        assert_eq!(classification.labels()[0].name, "Confidential");
+        assert_eq!(classification.information_types().len(), 3);
+
+        let source = |information_type| SensitivityClassification {
+            label: Some(0),
+            information_type: Some(information_type),
+        };
+        assert_eq!(
+            classification.columns(),
+            &[vec![], vec![source(0)], vec![source(1), source(2)]]
+        );
+    }
+}
@@ -10,6 +10,7 @@ use std::hash::Hash;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 use unilake_common::model::{
     AccessPolicyModel, AppInfoModel, EntityModel, GroupModel, IpInfoModel, UserModel,
 };
@@ -143,6 +144,7 @@ pub struct BackendHandler {
     instances: RwLock<HashMap<String, Arc<BackendInstance>>>,
     redis_client: Option<Arc<ClusterClient>>,
     backend_running: RwLock<bool>,
+    sse_consumer_stopped: CancellationToken,
     rest_client: Option<reqwest::Client>,
 }
 
@@ -153,6 +155,7 @@ impl BackendHandler {
             redis_client,
             instances: RwLock::new(HashMap::new()),
             backend_running: RwLock::new(false),
+            sse_consumer_stopped: CancellationToken::new(),
             rest_client: Some(reqwest::Client::new()),
         }
     }
@@ -369,10 +372,24 @@ impl BackendHandler {
                 settings_server_api_endpoint()
             );
             tracing::info!("Starting SSE consumer at {}", endpoint);
+            let stopped = backend_handler.sse_consumer_stopped.clone();
             loop {
-                tokio::time::sleep(std::time::Duration::from_secs(backoff)).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(backoff)) => {}
+                    _ = stopped.cancelled() => break,
+                }
                 let mut es = EventSource::get(endpoint.clone());
-                while let Some(event) = es.next().await {
+                loop {
+                    let event = tokio::select! {
+                        event = es.next() => match event {
+                            Some(event) => event,
+                            None => break,
+                        },
+                        _ = stopped.cancelled() => {
+                            es.close();
+                            break;
+                        }
+                    };
                     match event {
                         Ok(Event::Open) => {
                             tracing::info!(
@@ -405,7 +422,17 @@ impl BackendHandler {
                         }
                     }
                 }
+                if stopped.is_cancelled() {
+                    break;
+                }
             }
+            *backend_handler.backend_running.write().await = false;
+            tracing::info!("SSE consumer stopped");
         });
     }
+
+    /// Stops the SSE consumer, local caches are no longer invalidated afterwards
+    pub fn stop_sse_consumer(&self) {
+        self.sse_consumer_stopped.cancel();
+    }
 }
@@ -0,0 +1,152 @@
+use crate::backend::engine::{BackendConnection, QueryBackend, QueryResult};
+use crate::frontend::{
+    BaseMetaDataColumn, ColumnData, DataFlags, MetaDataColumn, TokenRow, TypeInfo,
+};
+use async_stream::try_stream;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
+
+/// Result returned by the mock backend for a query
+#[derive(Clone, Debug)]
+pub enum MockResult {
+    /// A result set with the given columns and rows
+    Rows {
+        columns: Vec<(String, TypeInfo)>,
+        rows: Vec<Vec<ColumnData>>,
+    },
+    /// The query fails with the given error
+    Error(TokenError),
+    /// The query keeps running until it is cancelled
+    Blocking,
+}
+
+/// In-process backend returning predefined results, so the handler can be used without a
+/// running engine. Queries without a predefined result fail.
+#[derive(Default)]
+pub struct MockBackend {
+    results: Arc<HashMap<String, MockResult>>,
+    executed: Arc<Mutex<Vec<String>>>,
+    connected: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_result(mut self, query: &str, result: MockResult) -> Self {
+        Arc::make_mut(&mut self.results).insert(query.to_string(), result);
+        self
+    }
+
+    /// All queries executed on connections of this backend, in order of execution
+    pub fn get_executed_queries(&self) -> Vec<String> {
+        self.executed.lock().unwrap().clone()
+    }
+
+    /// Users with an open connection
+    pub fn get_connected_users(&self) -> Vec<String> {
+        self.connected.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl QueryBackend for MockBackend {
+    async fn connect(&self, user_id: &str) -> TdsWireResult<Box<dyn BackendConnection>> {
+        self.connected.lock().unwrap().push(user_id.to_string());
+        Ok(Box::new(MockConnection {
+            results: self.results.clone(),
+            executed: self.executed.clone(),
+        }))
+    }
+
+    async fn disconnect(&self, user_id: &str) {
+        let mut connected = self.connected.lock().unwrap();
+        if let Some(index) = connected.iter().position(|u| u == user_id) {
+            connected.remove(index);
+        }
+    }
+}
+
+struct MockConnection {
+    results: Arc<HashMap<String, MockResult>>,
+    executed: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockConnection {
+    fn get_result(&self, query: &str) -> TdsWireResult<MockResult> {
+        self.results.get(query).cloned().ok_or_else(|| {
+            TdsWireError::Server(TokenError::new(
+                0,
+                1,
+                16,
+                format!("No result defined for query: {}", query),
+                "".to_string(),
+                "".to_string(),
+                1,
+            ))
+        })
+    }
+}
+
+fn to_metadata(columns: &[(String, TypeInfo)]) -> Vec<MetaDataColumn> {
+    columns
+        .iter()
+        .map(|(name, ty)| MetaDataColumn {
+            col_name: name.clone(),
+            base: BaseMetaDataColumn {
+                flags: DataFlags {
+                    is_nullable: true,
+                    ..DataFlags::default()
+                },
+                ty: ty.clone(),
+            },
+        })
+        .collect()
+}
+
+#[async_trait]
+impl BackendConnection for MockConnection {
+    async fn execute(
+        &mut self,
+        query: &str,
+        cancellation_token: CancellationToken,
+    ) -> TdsWireResult<QueryResult<'_>> {
+        self.executed.lock().unwrap().push(query.to_string());
+        let (columns, rows) = match self.get_result(query)? {
+            MockResult::Rows { columns, rows } => (columns, rows),
+            MockResult::Error(error) => return Err(TdsWireError::Server(error)),
+            MockResult::Blocking => {
+                cancellation_token.cancelled().await;
+                return Err(TdsWireError::Cancelled);
+            }
+        };
+
+        let rows = try_stream! {
+            for cells in rows {
+                if cancellation_token.is_cancelled() {
+                    Err(TdsWireError::Cancelled)?;
+                }
+                let mut row = TokenRow::new(cells.len(), false);
+                cells.into_iter().for_each(|cell| row.push_row(cell));
+                yield row;
+            }
+        };
+
+        Ok(QueryResult {
+            columns: to_metadata(&columns),
+            rows: Box::pin(rows),
+        })
+    }
+
+    async fn describe(&mut self, query: &str) -> TdsWireResult<Vec<MetaDataColumn>> {
+        match self.get_result(query)? {
+            MockResult::Rows { columns, .. } => Ok(to_metadata(&columns)),
+            MockResult::Error(error) => Err(TdsWireError::Server(error)),
+            MockResult::Blocking => Ok(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Query engines the proxy can front. The TDS handler only drives a [`QueryBackend`], each
+//! implementation converts the results of its engine to TDS column metadata and rows.
+mod mock;
+mod mysql;
+
+pub use mock::{MockBackend, MockResult};
+pub use mysql::{MySqlBackend, MySqlConnection};
+
+use crate::backend::classification::classify_columns;
+use crate::frontend::{
+    ColumnData, MetaDataColumn, TdsBackendResponse, TdsToken, TokenColMetaData, TokenRow, TypeInfo,
+    VarLenType,
+};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{Sink, SinkExt, StreamExt};
+use std::collections::HashMap;
+use tokio_util::sync::CancellationToken;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Result of a query, rows are streamed from the backend while they are being sent
+pub struct QueryResult<'a> {
+    pub columns: Vec<MetaDataColumn>,
+    pub rows: BoxStream<'a, TdsWireResult<TokenRow>>,
+}
+
+/// Engine queries are executed on, shared by all sessions using the same cluster
+#[async_trait]
+pub trait QueryBackend: Send + Sync {
+    /// Open a connection to the backend on behalf of the given user
+    async fn connect(&self, user_id: &str) -> TdsWireResult<Box<dyn BackendConnection>>;
+
+    /// The session of the user has been closed, its connection is no longer in use
+    async fn disconnect(&self, _user_id: &str) {}
+
+    /// Called for every request of a session using this backend
+    async fn register_activity(&self) {}
+}
+
+/// Connection to a backend, owned by a single session
+#[async_trait]
+pub trait BackendConnection: Send {
+    /// Execute the query and return its columns, with the rows to be read from the stream.
+    /// Once the cancellation token is cancelled, the query is cancelled on the backend and
+    /// [`TdsWireError::Cancelled`] is returned, either by this call or by the row stream.
+    async fn execute(
+        &mut self,
+        query: &str,
+        cancellation_token: CancellationToken,
+    ) -> TdsWireResult<QueryResult<'_>>;
+
+    /// Describe the columns returned by the query, without executing it
+    async fn describe(&mut self, query: &str) -> TdsWireResult<Vec<MetaDataColumn>>;
+}
+
+/// Send the column metadata and all rows of the query result to the client. Returns the
+/// number of rows sent and their size in bytes. Character data is sent as UTF-8 varchar
+/// instead of nvarchar, if the client supports UTF-8. The columns are classified by the given
+/// attribute tags, if the client supports data classification.
+pub(crate) async fn send_query_result<C>(
+    client: &mut C,
+    result: QueryResult<'_>,
+    utf8_support: bool,
+    attribute_tags: Option<&HashMap<String, Vec<String>>>,
+) -> TdsWireResult<(u64, usize)>
+where
+    C: Sink<TdsBackendResponse> + Unpin + Send,
+{
+    let QueryResult {
+        mut columns,
+        mut rows,
+    } = result;
+    let classification = attribute_tags
+        .map(|tags| classify_columns(&columns, tags))
+        .filter(|c| c.is_classified());
+    let utf8_columns = if utf8_support {
+        to_utf8_columns(&mut columns)
+    } else {
+        Vec::new()
+    };
+    send(client, TokenColMetaData { columns }).await?;
+    if let Some(classification) = classification {
+        send(client, classification).await?;
+    }
+
+    let mut record_count = 0;
+    let mut record_bytes = 0;
+    while let Some(row) = rows.next().await {
+        let mut row = row?;
+        for (index, max_length) in &utf8_columns {
+            if let Some(ColumnData::String(value)) = row.get_mut(*index) {
+                value.set_utf8(*max_length);
+            }
+        }
+        record_count += 1;
+        record_bytes += row.size_in_bytes();
+        // todo: add exclude time for send_token (telemetry), so we don't include network time
+        send(client, row).await?;
+    }
+    Ok((record_count, record_bytes))
+}
+
+/// Change the type of nvarchar columns to UTF-8 varchar, returns the index and max length
+/// in bytes of each changed column. A UTF-8 character takes up to twice the bytes of a UTF-16
+/// code unit, so the max length is twice that of the nvarchar.
+fn to_utf8_columns(columns: &mut [MetaDataColumn]) -> Vec<(usize, usize)> {
+    let mut utf8_columns = Vec::new();
+    for (index, column) in columns.iter_mut().enumerate() {
+        if let TypeInfo::VarLenSized(cx) = &column.base.ty {
+            if cx.r#type() == VarLenType::NVarchar {
+                column.base.ty = TypeInfo::new_varchar_utf8(cx.len().saturating_mul(2));
+                if let TypeInfo::VarLenSized(cx) = &column.base.ty {
+                    utf8_columns.push((index, cx.len()));
+                }
+            }
+        }
+    }
+    utf8_columns
+}
+
+async fn send<C, T>(client: &mut C, token: T) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse> + Unpin + Send,
+    T: Into<TdsToken>,
+{
+    client
+        .send(TdsBackendResponse::Token(token.into()))
+        .await
+        .map_err(|_| TdsWireError::Protocol("Failed to feed token".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::engine::{send_query_result, MockBackend, MockResult, QueryBackend};
+    use crate::frontend::sqlstring::SqlString;
+    use crate::frontend::{ColumnData, TdsBackendResponse, TdsToken, TypeInfo, VarLenType};
+    use futures::channel::mpsc;
+    use std::time::Duration;
+    use tokio_util::bytes::BytesMut;
+    use tokio_util::sync::CancellationToken;
+    use unilake_common::error::{TdsWireError, TokenError};
+
+    fn get_backend() -> MockBackend {
+        MockBackend::new()
+            .with_result(
+                "select id, name from users",
+                MockResult::Rows {
+                    columns: vec![
+                        ("id".to_string(), TypeInfo::new_int(true)),
+                        ("name".to_string(), TypeInfo::new_nvarchar(50)),
+                    ],
+                    rows: vec![
+                        vec![
+                            ColumnData::I32N(Some(1)),
+                            ColumnData::String(SqlString::from_string(
+                                Some("alice".to_string()),
+                                50,
+                            )),
+                        ],
+                        vec![
+                            ColumnData::I32N(Some(2)),
+                            ColumnData::String(SqlString::from_string(Some("bob".to_string()), 50)),
+                        ],
+                    ],
+                },
+            )
+            .with_result(
+                "select * from missing",
+                MockResult::Error(TokenError::new(
+                    208,
+                    1,
+                    16,
+                    "Invalid object name 'missing'.".to_string(),
+                    "".to_string(),
+                    "".to_string(),
+                    1,
+                )),
+            )
+            .with_result(
+                "select name from users",
+                MockResult::Rows {
+                    columns: vec![("name".to_string(), TypeInfo::new_nvarchar(100))],
+                    rows: vec![
+                        vec![ColumnData::String(SqlString::from_string(
+                            Some("ÿ".to_string()),
+                            100,
+                        ))],
+                        vec![ColumnData::String(SqlString::from_string(None, 100))],
+                    ],
+                },
+            )
+            .with_result("select sleep(60)", MockResult::Blocking)
+    }
+
+    /// Tokens sent to the client so far
+    fn get_tokens(mut receiver: mpsc::UnboundedReceiver<TdsBackendResponse>) -> Vec<TdsToken> {
+        let mut tokens = Vec::new();
+        while let Ok(response) = receiver.try_recv() {
+            if let TdsBackendResponse::Token(token) = response {
+                tokens.push(token);
+            }
+        }
+        tokens
+    }
+
+    #[tokio::test]
+    async fn send_result_set() {
+        let backend = get_backend();
+        let mut conn = backend.connect("user").await.unwrap();
+        let (mut client, receiver) = mpsc::unbounded();
+
+        let result = conn
+            .execute("select id, name from users", CancellationToken::new())
+            .await
+            .unwrap();
+        let (count, bytes) = send_query_result(&mut client, result, false, None)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(bytes > 0);
+
+        let tokens = get_tokens(receiver);
+        assert_eq!(tokens.len(), 3);
+        match &tokens[0] {
+            TdsToken::ColMetaData(meta) => {
+                assert_eq!(meta.len(), 2);
+                assert_eq!(meta.columns[1].col_name, "name");
+            }
+            t => panic!("unexpected token: {:?}", t),
+        }
+        assert!(matches!(&tokens[1], TdsToken::Row(row) if row.len() == 2));
+        assert_eq!(backend.get_connected_users(), vec!["user".to_string()]);
+        assert_eq!(
+            backend.get_executed_queries(),
+            vec!["select id, name from users".to_string()]
+        );
+
+        backend.disconnect("user").await;
+        assert!(backend.get_connected_users().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_result_set_as_utf8() {
+        let backend = get_backend();
+        let mut conn = backend.connect("user").await.unwrap();
+        let (mut client, receiver) = mpsc::unbounded();
+
+        let result = conn
+            .execute("select name from users", CancellationToken::new())
+            .await
+            .unwrap();
+        let (_, bytes) = send_query_result(&mut client, result, true, None)
+            .await
+            .unwrap();
+        assert_eq!(bytes, 2);
+
+        let tokens = get_tokens(receiver);
+        match &tokens[0] {
+            TdsToken::ColMetaData(meta) => match &meta.columns[0].base.ty {
+                TypeInfo::VarLenSized(cx) => {
+                    assert_eq!(cx.r#type(), VarLenType::BigVarChar);
+                    assert_eq!(cx.len(), 200);
+                    assert!(cx.collation().is_some_and(|c| c.is_utf8()));
+                }
+                ty => panic!("unexpected type: {:?}", ty),
+            },
+            t => panic!("unexpected token: {:?}", t),
+        }
+        let mut data = BytesMut::new();
+        for token in &tokens[1..] {
+            match token {
+                TdsToken::Row(row) => row.get(0).unwrap().encode(&mut data).unwrap(),
+                t => panic!("unexpected token: {:?}", t),
+            }
+        }
+        assert_eq!(&data[..], &[0x02, 0x00, 0xc3, 0xbf, 0xff, 0xff]);
+    }
+
+    #[tokio::test]
+    async fn return_backend_error() {
+        let backend = get_backend();
+        let mut conn = backend.connect("user").await.unwrap();
+        match conn
+            .execute("select * from missing", CancellationToken::new())
+            .await
+        {
+            Err(TdsWireError::Server(token)) => assert_eq!(token.code, 208),
+            _ => panic!("expected a server error"),
+        }
+        assert!(conn.describe("select * from unknown").await.is_err());
+        assert_eq!(
+            conn.describe("select id, name from users")
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_running_query() {
+        let backend = get_backend();
+        let mut conn = backend.connect("user").await.unwrap();
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cancel.cancel();
+        });
+
+        let result = conn.execute("select sleep(60)", token).await;
+        assert!(matches!(result, Err(TdsWireError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn cancel_while_sending_rows() {
+        let backend = get_backend();
+        let mut conn = backend.connect("user").await.unwrap();
+        let (mut client, receiver) = mpsc::unbounded();
+        let token = CancellationToken::new();
+
+        let result = conn
+            .execute("select id, name from users", token.clone())
+            .await
+            .unwrap();
+        token.cancel();
+        let result = send_query_result(&mut client, result, false, None).await;
+        assert!(matches!(result, Err(TdsWireError::Cancelled)));
+
+        // only the column metadata has been sent
+        let tokens = get_tokens(receiver);
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(&tokens[0], TdsToken::ColMetaData(_)));
+    }
+}
@@ -0,0 +1,199 @@
+mod types;
+
+use crate::backend::engine::{BackendConnection, QueryBackend, QueryResult};
+use crate::frontend::MetaDataColumn;
+use async_stream::try_stream;
+use async_trait::async_trait;
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, Opts, Pool};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use types::{to_token_row, ColumnMapping};
+use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
+
+/// Time given to connect to the backend and kill a running query
+const KILL_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Backend for any engine speaking the MySQL protocol, using a single connection pool
+pub struct MySqlBackend {
+    opts: Opts,
+    pool: Pool,
+}
+
+impl MySqlBackend {
+    pub fn new<O: Into<Opts>>(opts: O) -> Self {
+        let opts = opts.into();
+        MySqlBackend {
+            pool: Pool::new(opts.clone()),
+            opts,
+        }
+    }
+}
+
+#[async_trait]
+impl QueryBackend for MySqlBackend {
+    async fn connect(&self, _user_id: &str) -> TdsWireResult<Box<dyn BackendConnection>> {
+        let conn = self.pool.get_conn().await.map_err(map_error)?;
+        Ok(Box::new(MySqlConnection::new(conn, self.opts.clone())))
+    }
+}
+
+/// Connection to a MySQL protocol backend. Running queries are killed using a dedicated side
+/// connection opened with the given options, as the connection itself is busy and the pool it
+/// belongs to might be exhausted by the queries being cancelled.
+pub struct MySqlConnection {
+    conn: Conn,
+    opts: Opts,
+}
+
+impl MySqlConnection {
+    pub fn new(conn: Conn, opts: Opts) -> Self {
+        MySqlConnection { conn, opts }
+    }
+}
+
+#[async_trait]
+impl BackendConnection for MySqlConnection {
+    async fn execute(
+        &mut self,
+        query: &str,
+        cancellation_token: CancellationToken,
+    ) -> TdsWireResult<QueryResult<'_>> {
+        let connection_id = self.conn.id();
+        tracing::debug!("Connection id: {}", connection_id);
+
+        // on cancellation the query is killed on the backend, after which the running query returns
+        let opts = self.opts.clone();
+        let query_result = {
+            let query_future = self.conn.query_iter(query.to_string());
+            tokio::pin!(query_future);
+            tokio::select! {
+                result = &mut query_future => result,
+                _ = cancellation_token.cancelled() => {
+                    kill_query(&opts, connection_id).await;
+                    query_future.await
+                }
+            }
+        };
+        let mut result = match query_result {
+            Ok(result) => result,
+            Err(_) if cancellation_token.is_cancelled() => return Err(TdsWireError::Cancelled),
+            Err(e) => return Err(map_error(e)),
+        };
+
+        let mappings = result
+            .columns_ref()
+            .iter()
+            .map(ColumnMapping::from_column)
+            .collect::<TdsWireResult<Vec<_>>>()?;
+        let columns = mappings.iter().map(ColumnMapping::to_metadata).collect();
+        let rows = try_stream! {
+            loop {
+                let row = {
+                    let next = result.next();
+                    tokio::pin!(next);
+                    tokio::select! {
+                        row = &mut next => row,
+                        _ = cancellation_token.cancelled() => {
+                            kill_query(&opts, connection_id).await;
+                            next.await
+                        }
+                    }
+                };
+                if cancellation_token.is_cancelled() {
+                    break;
+                }
+                match row.map_err(map_error)? {
+                    Some(row) => yield to_token_row(row.unwrap(), &mappings)?,
+                    None => break,
+                }
+            }
+
+            if cancellation_token.is_cancelled() {
+                // consume the remainder of the interrupted result, so the connection can be reused
+                if let Err(e) = result.drop_result().await {
+                    tracing::debug!("Dropping cancelled result failed: {}", e);
+                }
+                Err(TdsWireError::Cancelled)?;
+            }
+        };
+
+        Ok(QueryResult {
+            columns,
+            rows: Box::pin(rows),
+        })
+    }
+
+    async fn describe(&mut self, query: &str) -> TdsWireResult<Vec<MetaDataColumn>> {
+        let statement = self.conn.prep(query).await.map_err(map_error)?;
+        let mappings = statement
+            .columns()
+            .iter()
+            .map(ColumnMapping::from_column)
+            .collect::<TdsWireResult<Vec<_>>>();
+        self.conn.close(statement).await.map_err(map_error)?;
+        Ok(mappings?.iter().map(ColumnMapping::to_metadata).collect())
+    }
+}
+
+/// Kill the query running on the given connection, using a dedicated connection which is
+/// closed afterwards. Gives up once the timeout passes, so the cancellation is never blocked.
+async fn kill_query(opts: &Opts, connection_id: u32) {
+    let result = tokio::time::timeout(KILL_QUERY_TIMEOUT, async {
+        let mut conn = Conn::new(opts.clone()).await?;
+        let result = conn
+            .query_drop(format!("KILL QUERY {}", connection_id))
+            .await;
+        conn.disconnect().await?;
+        result
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!(
+            "Failed to kill query on connection {}: {}",
+            connection_id,
+            e
+        ),
+        Err(_) => tracing::error!("Timed out killing query on connection {}", connection_id),
+    }
+}
+
+/// Errors reported by the server are passed on to the client as is
+fn map_error(e: mysql_async::Error) -> TdsWireError {
+    match e {
+        mysql_async::Error::Server(e) => TdsWireError::Server(TokenError::new(
+            e.code as u32,
+            1,
+            16,
+            e.message,
+            "".to_string(),
+            "".to_string(),
+            1,
+        )),
+        e => TdsWireError::Protocol(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::engine::mysql::map_error;
+    use unilake_common::error::TdsWireError;
+
+    #[test]
+    fn map_server_error() {
+        let error = mysql_async::Error::Server(mysql_async::ServerError {
+            code: 1064,
+            message: "You have an error in your SQL syntax".to_string(),
+            state: "42000".to_string(),
+        });
+        match map_error(error) {
+            TdsWireError::Server(token) => {
+                assert_eq!(token.code, 1064);
+                assert_eq!(token.class, 16);
+                assert_eq!(token.message, "You have an error in your SQL syntax");
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
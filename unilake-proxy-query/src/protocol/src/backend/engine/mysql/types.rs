@@ -0,0 +1,564 @@
+//! Mapping of MySQL protocol column types to TDS types. The column metadata and the row values
+//! are both derived from the [`TdsType`] of a column, so they always agree.
+//!
+//! | MySQL (StarRocks)                                  | TDS                               |
+//! |----------------------------------------------------|-----------------------------------|
+//! | TINYINT(1), BOOLEAN, BIT(1)                        | bit                               |
+//! | TINYINT UNSIGNED                                   | tinyint                           |
+//! | TINYINT, SMALLINT, YEAR                            | smallint                          |
+//! | SMALLINT UNSIGNED, MEDIUMINT, INT                  | int                               |
+//! | INT UNSIGNED, BIGINT, BIT(n)                       | bigint                            |
+//! | BIGINT UNSIGNED                                    | decimal(20, 0)                    |
+//! | DECIMAL(p, s), p <= 38                             | decimal(p, s)                     |
+//! | DECIMAL(p, s), p > 38                              | nvarchar(p + 2)                   |
+//! | FLOAT / DOUBLE                                     | real / float                      |
+//! | DATE                                               | date                              |
+//! | TIME                                               | time(7)                           |
+//! | DATETIME, TIMESTAMP                                | datetime2(7)                      |
+//! | CHAR, VARCHAR, ENUM, SET, LARGEINT                 | nvarchar(n), or max if n > 4000   |
+//! | TEXT, JSON, ARRAY, MAP, STRUCT                     | nvarchar(max)                     |
+//! | BINARY, VARBINARY, BLOB, GEOMETRY                  | varbinary(max)                    |
+//! | NULL                                               | int                               |
+//!
+//! StarRocks sends LARGEINT and complex types (ARRAY, MAP, STRUCT) as strings.
+//! Clients supporting UTF-8 receive nvarchar columns as UTF-8 varchar instead, see
+//! [`send_query_result`](crate::backend::engine::send_query_result).
+use crate::frontend::sqlbinary::SqlBinary;
+use crate::frontend::{
+    sqlstring::SqlString, BaseMetaDataColumn, ColumnData, DataFlags, MetaDataColumn, TokenRow,
+    TypeInfo, UpdatableFlags,
+};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use mysql_async::consts::{ColumnFlags, ColumnType};
+use mysql_async::prelude::FromValue;
+use mysql_async::{from_value_opt, Column, Value};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Collation of binary strings
+const BINARY_CHARACTER_SET: u16 = 63;
+/// Length of the type of max data types, which are sent in chunks
+const MAX_LENGTH: usize = 0xFFFF;
+/// Maximum length of a (non max) nvarchar, in characters
+const MAX_NVARCHAR_LENGTH: usize = 4000;
+/// Maximum precision of a TDS decimal
+const MAX_DECIMAL_PRECISION: u32 = 38;
+
+/// TDS type a MySQL column is sent as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TdsType {
+    /// Integer values of 0 or 1
+    Bit,
+    /// BIT(n) values, sent as bit if n is 1, as bigint otherwise
+    BitField(u32),
+    TinyInt,
+    SmallInt,
+    Int,
+    BigInt,
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    Real,
+    Float,
+    Date,
+    Time,
+    DateTime2,
+    /// Length in characters, none for nvarchar(max)
+    NVarChar(Option<usize>),
+    VarBinary,
+}
+
+impl TdsType {
+    pub(crate) fn from_column(column: &Column) -> TdsWireResult<Self> {
+        let is_unsigned = column.flags().contains(ColumnFlags::UNSIGNED_FLAG);
+        let is_binary = column.character_set() == BINARY_CHARACTER_SET;
+        let length = column.column_length();
+
+        Ok(match column.column_type() {
+            ColumnType::MYSQL_TYPE_TINY if length == 1 => TdsType::Bit,
+            ColumnType::MYSQL_TYPE_TINY if is_unsigned => TdsType::TinyInt,
+            ColumnType::MYSQL_TYPE_TINY | ColumnType::MYSQL_TYPE_YEAR => TdsType::SmallInt,
+            ColumnType::MYSQL_TYPE_SHORT if !is_unsigned => TdsType::SmallInt,
+            ColumnType::MYSQL_TYPE_SHORT
+            | ColumnType::MYSQL_TYPE_INT24
+            | ColumnType::MYSQL_TYPE_NULL => TdsType::Int,
+            ColumnType::MYSQL_TYPE_LONG if !is_unsigned => TdsType::Int,
+            ColumnType::MYSQL_TYPE_LONG => TdsType::BigInt,
+            ColumnType::MYSQL_TYPE_LONGLONG if !is_unsigned => TdsType::BigInt,
+            ColumnType::MYSQL_TYPE_LONGLONG => TdsType::Decimal {
+                precision: 20,
+                scale: 0,
+            },
+            ColumnType::MYSQL_TYPE_BIT => TdsType::BitField(length),
+            ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => {
+                // the length includes the decimal point and the sign of signed columns
+                let scale = column.decimals() as u32;
+                let precision = length
+                    .saturating_sub((scale > 0) as u32)
+                    .saturating_sub(!is_unsigned as u32)
+                    .max(scale)
+                    .max(1);
+                if precision > MAX_DECIMAL_PRECISION {
+                    TdsType::NVarChar(Some(precision as usize + 2))
+                } else {
+                    TdsType::Decimal {
+                        precision: precision as u8,
+                        scale: scale as u8,
+                    }
+                }
+            }
+            ColumnType::MYSQL_TYPE_FLOAT => TdsType::Real,
+            ColumnType::MYSQL_TYPE_DOUBLE => TdsType::Float,
+            ColumnType::MYSQL_TYPE_DATE | ColumnType::MYSQL_TYPE_NEWDATE => TdsType::Date,
+            ColumnType::MYSQL_TYPE_TIME | ColumnType::MYSQL_TYPE_TIME2 => TdsType::Time,
+            ColumnType::MYSQL_TYPE_DATETIME
+            | ColumnType::MYSQL_TYPE_DATETIME2
+            | ColumnType::MYSQL_TYPE_TIMESTAMP
+            | ColumnType::MYSQL_TYPE_TIMESTAMP2 => TdsType::DateTime2,
+            ColumnType::MYSQL_TYPE_GEOMETRY => TdsType::VarBinary,
+            ColumnType::MYSQL_TYPE_VARCHAR
+            | ColumnType::MYSQL_TYPE_VAR_STRING
+            | ColumnType::MYSQL_TYPE_STRING
+                if is_binary =>
+            {
+                TdsType::VarBinary
+            }
+            ColumnType::MYSQL_TYPE_VARCHAR
+            | ColumnType::MYSQL_TYPE_VAR_STRING
+            | ColumnType::MYSQL_TYPE_STRING
+            | ColumnType::MYSQL_TYPE_ENUM
+            | ColumnType::MYSQL_TYPE_SET => {
+                let length = length as usize / max_bytes_per_char(column.character_set());
+                match length {
+                    1..=MAX_NVARCHAR_LENGTH => TdsType::NVarChar(Some(length)),
+                    _ => TdsType::NVarChar(None),
+                }
+            }
+            ColumnType::MYSQL_TYPE_TINY_BLOB
+            | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | ColumnType::MYSQL_TYPE_LONG_BLOB
+            | ColumnType::MYSQL_TYPE_BLOB
+                if is_binary =>
+            {
+                TdsType::VarBinary
+            }
+            ColumnType::MYSQL_TYPE_TINY_BLOB
+            | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+            | ColumnType::MYSQL_TYPE_LONG_BLOB
+            | ColumnType::MYSQL_TYPE_BLOB
+            | ColumnType::MYSQL_TYPE_JSON => TdsType::NVarChar(None),
+            ty => {
+                return Err(TdsWireError::Conversion(format!(
+                    "Unsupported type {:?} of column '{}'",
+                    ty,
+                    column.name_str()
+                )))
+            }
+        })
+    }
+
+    /// The TDS type, all backend columns are nullable
+    pub(crate) fn type_info(&self) -> TypeInfo {
+        match *self {
+            TdsType::Bit | TdsType::BitField(1) => TypeInfo::new_bit(true),
+            TdsType::BitField(_) => TypeInfo::new_bigint(true),
+            TdsType::TinyInt => TypeInfo::new_tinyint(true),
+            TdsType::SmallInt => TypeInfo::new_smallint(true),
+            TdsType::Int => TypeInfo::new_int(true),
+            TdsType::BigInt => TypeInfo::new_bigint(true),
+            TdsType::Decimal { precision, scale } => TypeInfo::new_decimal(precision, scale),
+            TdsType::Real => TypeInfo::new_float_32(true),
+            TdsType::Float => TypeInfo::new_float_64(true),
+            TdsType::Date => TypeInfo::new_date(),
+            TdsType::Time => TypeInfo::new_time(),
+            TdsType::DateTime2 => TypeInfo::new_datetime2(),
+            TdsType::NVarChar(Some(length)) => TypeInfo::new_nvarchar(length * 2),
+            TdsType::NVarChar(None) => TypeInfo::new_string(),
+            TdsType::VarBinary => TypeInfo::new_varbinary(MAX_LENGTH),
+        }
+    }
+
+    /// Convert a value of a column of this type
+    pub(crate) fn to_column_data(self, value: Value) -> Result<ColumnData, Value> {
+        Ok(match self {
+            TdsType::Bit => ColumnData::BitN(get::<i64>(value)?.map(|v| v != 0)),
+            TdsType::BitField(length) => {
+                let bits = get::<Vec<u8>>(value)?
+                    .map(|b| b.iter().fold(0u64, |bits, b| bits << 8 | *b as u64));
+                if length == 1 {
+                    ColumnData::BitN(bits.map(|v| v != 0))
+                } else {
+                    ColumnData::I64N(bits.map(|v| v as i64))
+                }
+            }
+            TdsType::TinyInt => ColumnData::U8N(get(value)?),
+            TdsType::SmallInt => ColumnData::I16N(get(value)?),
+            TdsType::Int => ColumnData::I32N(get(value)?),
+            TdsType::BigInt => ColumnData::I64N(get(value)?),
+            TdsType::Decimal { scale, .. } => ColumnData::Numeric(
+                // values are sent with the scale of the column
+                get::<BigDecimal>(value)?.map(|v| v.with_scale(scale as i64)),
+            ),
+            TdsType::Real => ColumnData::F32N(get(value)?),
+            TdsType::Float => ColumnData::F64N(get(value)?),
+            TdsType::Date => ColumnData::Date(get::<NaiveDate>(value)?),
+            TdsType::Time => ColumnData::Time(get::<NaiveTime>(value)?),
+            TdsType::DateTime2 => ColumnData::DateTime2(get::<NaiveDateTime>(value)?),
+            TdsType::NVarChar(length) => ColumnData::String(SqlString::from_string(
+                get::<String>(value)?,
+                length.map(|l| l * 2).unwrap_or(MAX_LENGTH),
+            )),
+            TdsType::VarBinary => {
+                ColumnData::Binary(SqlBinary::from_bytes(get::<Vec<u8>>(value)?, MAX_LENGTH))
+            }
+        })
+    }
+}
+
+/// Get the value as the given type, returns the value if it cannot be converted
+fn get<T: FromValue>(value: Value) -> Result<Option<T>, Value> {
+    from_value_opt::<Option<T>>(value).map_err(|e| e.0)
+}
+
+/// Maximum number of bytes used per character for the given character set
+fn max_bytes_per_char(character_set: u16) -> usize {
+    match character_set {
+        // utf8mb3
+        33 | 83 | 192..=215 => 3,
+        // utf8mb4
+        45 | 46 | 224..=247 | 255.. => 4,
+        _ => 1,
+    }
+}
+
+/// TDS representation of a backend column
+#[derive(Debug, Clone)]
+pub(crate) struct ColumnMapping {
+    name: String,
+    ty: TdsType,
+    is_key: bool,
+}
+
+impl ColumnMapping {
+    pub(crate) fn from_column(column: &Column) -> TdsWireResult<Self> {
+        Ok(ColumnMapping {
+            name: column.name_str().to_string(),
+            ty: TdsType::from_column(column)?,
+            is_key: column.flags().contains(ColumnFlags::PART_KEY_FLAG),
+        })
+    }
+
+    pub(crate) fn to_metadata(&self) -> MetaDataColumn {
+        MetaDataColumn {
+            base: BaseMetaDataColumn {
+                flags: DataFlags {
+                    is_key: self.is_key,
+                    updatable: UpdatableFlags::NotUpdatable,
+                    is_nullable: true,
+                    ..DataFlags::default()
+                },
+                ty: self.ty.type_info(),
+            },
+            col_name: self.name.clone(),
+        }
+    }
+}
+
+/// Convert the values of a row to a TDS row, null values are sent using a null bitmap
+pub(crate) fn to_token_row(
+    values: Vec<Value>,
+    columns: &[ColumnMapping],
+) -> TdsWireResult<TokenRow> {
+    if values.len() != columns.len() {
+        return Err(TdsWireError::Conversion(format!(
+            "Expected {} values in row, got {}",
+            columns.len(),
+            values.len()
+        )));
+    }
+
+    let mut row = TokenRow::new(columns.len(), false);
+    for (value, column) in values.into_iter().zip(columns) {
+        row.nbc_row |= value == Value::NULL;
+        let data = column.ty.to_column_data(value).map_err(|value| {
+            TdsWireError::Conversion(format!(
+                "Cannot convert value {} of column '{}' to {:?}",
+                value.as_sql(true),
+                column.name,
+                column.ty
+            ))
+        })?;
+        row.push_row(data);
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::engine::mysql::types::{to_token_row, ColumnMapping, TdsType};
+    use crate::frontend::{ColumnData, TypeInfo, VarLenType};
+    use bigdecimal::BigDecimal;
+    use mysql_async::consts::{ColumnFlags, ColumnType};
+    use mysql_async::{Column, Value};
+    use std::str::FromStr;
+    use tokio_util::bytes::BytesMut;
+
+    fn column(ty: ColumnType, length: u32, flags: ColumnFlags) -> Column {
+        Column::new(ty)
+            .with_name(b"c")
+            .with_column_length(length)
+            .with_flags(flags)
+            .with_character_set(33)
+    }
+
+    fn map(column: &Column) -> TdsType {
+        TdsType::from_column(column).unwrap()
+    }
+
+    /// Encode the value as part of a row with the metadata of the column
+    fn encode(column: &Column, value: Value) -> (Vec<u8>, Vec<u8>) {
+        let mapping = ColumnMapping::from_column(column).unwrap();
+        let mut meta = BytesMut::new();
+        mapping.to_metadata().base.ty.encode(&mut meta);
+        let row = to_token_row(vec![value], &[mapping]).unwrap();
+        let mut data = BytesMut::new();
+        for value in row {
+            value.encode(&mut data).unwrap();
+        }
+        (meta.to_vec(), data.to_vec())
+    }
+
+    #[test]
+    fn map_integer_types() {
+        let none = ColumnFlags::empty();
+        let unsigned = ColumnFlags::UNSIGNED_FLAG;
+        let cases = [
+            (ColumnType::MYSQL_TYPE_TINY, 1, none, TdsType::Bit),
+            (ColumnType::MYSQL_TYPE_TINY, 4, none, TdsType::SmallInt),
+            (ColumnType::MYSQL_TYPE_TINY, 3, unsigned, TdsType::TinyInt),
+            (ColumnType::MYSQL_TYPE_SHORT, 6, none, TdsType::SmallInt),
+            (ColumnType::MYSQL_TYPE_SHORT, 5, unsigned, TdsType::Int),
+            (ColumnType::MYSQL_TYPE_INT24, 9, none, TdsType::Int),
+            (ColumnType::MYSQL_TYPE_LONG, 11, none, TdsType::Int),
+            (ColumnType::MYSQL_TYPE_LONG, 10, unsigned, TdsType::BigInt),
+            (ColumnType::MYSQL_TYPE_LONGLONG, 20, none, TdsType::BigInt),
+            (ColumnType::MYSQL_TYPE_YEAR, 4, unsigned, TdsType::SmallInt),
+            (
+                ColumnType::MYSQL_TYPE_BIT,
+                1,
+                unsigned,
+                TdsType::BitField(1),
+            ),
+            (ColumnType::MYSQL_TYPE_NULL, 0, none, TdsType::Int),
+            (
+                ColumnType::MYSQL_TYPE_LONGLONG,
+                20,
+                unsigned,
+                TdsType::Decimal {
+                    precision: 20,
+                    scale: 0,
+                },
+            ),
+        ];
+        for (ty, length, flags, expected) in cases {
+            assert_eq!(map(&column(ty, length, flags)), expected, "{:?}", ty);
+        }
+    }
+
+    #[test]
+    fn map_decimal_types() {
+        let decimal = |length, decimals| {
+            map(&column(
+                ColumnType::MYSQL_TYPE_NEWDECIMAL,
+                length,
+                ColumnFlags::empty(),
+            )
+            .with_decimals(decimals))
+        };
+        assert_eq!(
+            decimal(12, 2),
+            TdsType::Decimal {
+                precision: 10,
+                scale: 2
+            }
+        );
+        assert_eq!(
+            decimal(28, 0),
+            TdsType::Decimal {
+                precision: 27,
+                scale: 0
+            }
+        );
+        // decimal256 exceeds the maximum precision
+        assert_eq!(decimal(78, 10), TdsType::NVarChar(Some(78)));
+    }
+
+    #[test]
+    fn map_string_and_binary_types() {
+        let none = ColumnFlags::empty();
+        let varchar = column(ColumnType::MYSQL_TYPE_VAR_STRING, 300, none);
+        assert_eq!(map(&varchar), TdsType::NVarChar(Some(100)));
+        let varchar = column(ColumnType::MYSQL_TYPE_VAR_STRING, 196599, none);
+        assert_eq!(map(&varchar), TdsType::NVarChar(None));
+        let json = column(ColumnType::MYSQL_TYPE_JSON, 0, none);
+        assert_eq!(map(&json), TdsType::NVarChar(None));
+        let text = column(ColumnType::MYSQL_TYPE_BLOB, 65535, none);
+        assert_eq!(map(&text), TdsType::NVarChar(None));
+        let enumeration = column(ColumnType::MYSQL_TYPE_STRING, 3, ColumnFlags::ENUM_FLAG);
+        assert_eq!(map(&enumeration), TdsType::NVarChar(Some(1)));
+
+        let binary = ColumnFlags::BINARY_FLAG;
+        for ty in [
+            ColumnType::MYSQL_TYPE_STRING,
+            ColumnType::MYSQL_TYPE_VAR_STRING,
+            ColumnType::MYSQL_TYPE_BLOB,
+        ] {
+            let column = column(ty, 16, binary).with_character_set(63);
+            assert_eq!(map(&column), TdsType::VarBinary);
+        }
+        let geometry = column(ColumnType::MYSQL_TYPE_GEOMETRY, 0, binary);
+        assert_eq!(map(&geometry), TdsType::VarBinary);
+    }
+
+    #[test]
+    fn map_temporal_types() {
+        let none = ColumnFlags::empty();
+        assert_eq!(
+            map(&column(ColumnType::MYSQL_TYPE_DATE, 10, none)),
+            TdsType::Date
+        );
+        assert_eq!(
+            map(&column(ColumnType::MYSQL_TYPE_TIME, 10, none)),
+            TdsType::Time
+        );
+        for ty in [
+            ColumnType::MYSQL_TYPE_DATETIME,
+            ColumnType::MYSQL_TYPE_TIMESTAMP,
+        ] {
+            assert_eq!(map(&column(ty, 26, none)), TdsType::DateTime2);
+        }
+    }
+
+    #[test]
+    fn reject_unsupported_types() {
+        let column = column(ColumnType::MYSQL_TYPE_UNKNOWN, 0, ColumnFlags::empty());
+        assert!(TdsType::from_column(&column).is_err());
+    }
+
+    #[test]
+    fn metadata_matches_values() {
+        let none = ColumnFlags::empty();
+        let bytes = |v: &str| Value::Bytes(v.as_bytes().to_vec());
+
+        // signed tinyint is sent as smallint
+        let (meta, data) = encode(&column(ColumnType::MYSQL_TYPE_TINY, 4, none), bytes("-5"));
+        assert_eq!(meta, [0x26, 0x02]);
+        assert_eq!(data, [0x02, 0xFB, 0xFF]);
+
+        // unsigned tinyint
+        let tinyint = column(ColumnType::MYSQL_TYPE_TINY, 3, ColumnFlags::UNSIGNED_FLAG);
+        let (meta, data) = encode(&tinyint, bytes("255"));
+        assert_eq!(meta, [0x26, 0x01]);
+        assert_eq!(data, [0x01, 0xFF]);
+
+        // boolean
+        let (meta, data) = encode(&column(ColumnType::MYSQL_TYPE_TINY, 1, none), bytes("1"));
+        assert_eq!(meta, [0x68, 0x01]);
+        assert_eq!(data, [0x01, 0x01]);
+
+        // bit(10) is sent as bigint
+        let bit = column(ColumnType::MYSQL_TYPE_BIT, 10, ColumnFlags::UNSIGNED_FLAG);
+        let (meta, data) = encode(&bit, Value::Bytes(vec![0x02, 0x01]));
+        assert_eq!(meta, [0x26, 0x08]);
+        assert_eq!(data, [0x08, 0x01, 0x02, 0, 0, 0, 0, 0, 0]);
+
+        // decimal values use the scale of the column
+        let decimal = column(ColumnType::MYSQL_TYPE_NEWDECIMAL, 12, none).with_decimals(2);
+        let (meta, data) = encode(&decimal, bytes("1.5"));
+        assert_eq!(meta, [0x6A, 0x09, 0x0A, 0x02]);
+        assert_eq!(data, [0x05, 0x01, 0x96, 0x00, 0x00, 0x00]);
+
+        // time and datetime are sent with a scale of 7
+        let (meta, data) = encode(
+            &column(ColumnType::MYSQL_TYPE_TIME, 10, none),
+            bytes("01:02:03"),
+        );
+        assert_eq!(meta, [0x29, 0x07]);
+        assert_eq!(data, [0x05, 0x80, 0xB7, 0x14, 0xAB, 0x08]);
+        let (meta, data) = encode(
+            &column(ColumnType::MYSQL_TYPE_DATETIME, 19, none),
+            bytes("2003-12-31 01:02:03"),
+        );
+        assert_eq!(meta, [0x2A, 0x07]);
+        assert_eq!(data, [0x08, 0x80, 0xB7, 0x14, 0xAB, 0x08, 0xBB, 0x29, 0x0B]);
+
+        // varchar(2) in utf8
+        let varchar = column(ColumnType::MYSQL_TYPE_VAR_STRING, 6, none);
+        let (meta, data) = encode(&varchar, bytes("ab"));
+        assert_eq!(&meta[..3], [0xE7, 0x04, 0x00]);
+        assert_eq!(data, [0x04, 0x00, 0x61, 0x00, 0x62, 0x00]);
+
+        // varbinary(max) is sent in chunks
+        let binary = column(ColumnType::MYSQL_TYPE_BLOB, 16, none).with_character_set(63);
+        let (meta, data) = encode(&binary, Value::Bytes(vec![0xAB]));
+        assert_eq!(meta, [0xA5, 0xFF, 0xFF]);
+        assert_eq!(data, [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0xAB, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn convert_row_values() {
+        let columns = [
+            ColumnMapping::from_column(&column(
+                ColumnType::MYSQL_TYPE_LONGLONG,
+                20,
+                ColumnFlags::UNSIGNED_FLAG,
+            ))
+            .unwrap(),
+            ColumnMapping::from_column(&column(
+                ColumnType::MYSQL_TYPE_DATE,
+                10,
+                ColumnFlags::empty(),
+            ))
+            .unwrap(),
+        ];
+        let row = to_token_row(
+            vec![Value::Bytes(b"18446744073709551615".to_vec()), Value::NULL],
+            &columns,
+        )
+        .unwrap();
+        assert!(row.nbc_row);
+        match row.get(0) {
+            Some(ColumnData::Numeric(Some(v))) => {
+                assert_eq!(*v, BigDecimal::from_str("18446744073709551615").unwrap())
+            }
+            v => panic!("unexpected value: {:?}", v),
+        }
+        assert!(matches!(row.get(1), Some(ColumnData::Date(None))));
+        assert!(matches!(
+            columns[0].to_metadata().base.ty,
+            TypeInfo::VarLenSizedPrecision {
+                ty: VarLenType::Decimaln,
+                precision: 20,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        let columns = [ColumnMapping::from_column(&column(
+            ColumnType::MYSQL_TYPE_TIME,
+            10,
+            ColumnFlags::empty(),
+        ))
+        .unwrap()];
+        // time values outside of a day cannot be represented
+        let result = to_token_row(vec![Value::Bytes(b"-25:00:00".to_vec())], &columns);
+        assert!(result.is_err());
+        let result = to_token_row(vec![Value::Bytes(b"12:00:00".to_vec())], &columns);
+        assert!(result.is_ok());
+        // values must match the columns
+        assert!(to_token_row(Vec::new(), &columns).is_err());
+    }
+}
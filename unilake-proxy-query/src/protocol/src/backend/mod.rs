@@ -1,4 +1,6 @@
 pub mod app;
+pub(crate) mod classification;
 pub(crate) mod data;
+pub mod engine;
 pub mod starrocks;
 pub mod telemetry;
@@ -0,0 +1,331 @@
+use async_trait::async_trait;
+use mysql_async::{OptsBuilder, PoolConstraints, PoolOpts};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::settings::{
+    settings_backend_starrocks_clusters, settings_backend_starrocks_clusters_file,
+    settings_server_api_endpoint, settings_server_api_timeout_in_ms,
+};
+
+/// Frontend (FE) node of a StarRocks cluster
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StarRocksNodeConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+/// Connection settings of a StarRocks cluster, used by a tenant for a compute id. Connections
+/// are load-balanced over all FE nodes of the cluster.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StarRocksClusterConfig {
+    pub cluster_id: String,
+    pub tenant_id: String,
+    /// The compute id served by this cluster, none if this is the default cluster of the tenant
+    #[serde(default)]
+    pub compute_id: Option<String>,
+    pub nodes: Vec<StarRocksNodeConfig>,
+    pub user: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Minimum number of connections kept open per FE node
+    #[serde(default = "default_pool_min")]
+    pub pool_min: usize,
+    /// Maximum number of connections per FE node
+    #[serde(default = "default_pool_max")]
+    pub pool_max: usize,
+    /// Idle connections above the pool minimum are closed after this timeout
+    #[serde(default = "default_idle_timeout_in_seconds")]
+    pub idle_timeout_in_seconds: u64,
+    /// The cluster can be shutdown when it has not been used for this amount of time
+    #[serde(default = "default_activity_timeout_in_minutes")]
+    pub activity_timeout_in_minutes: u16,
+}
+
+fn default_port() -> u16 {
+    9030
+}
+
+fn default_pool_min() -> usize {
+    1
+}
+
+fn default_pool_max() -> usize {
+    100
+}
+
+fn default_idle_timeout_in_seconds() -> u64 {
+    300
+}
+
+fn default_activity_timeout_in_minutes() -> u16 {
+    60
+}
+
+impl StarRocksClusterConfig {
+    pub fn validate(&self) -> TdsWireResult<()> {
+        if self.nodes.is_empty() {
+            return Err(TdsWireError::Input(format!(
+                "No nodes configured for cluster '{}'",
+                self.cluster_id
+            )));
+        }
+        if self.pool_max == 0 || self.pool_min > self.pool_max {
+            return Err(TdsWireError::Input(format!(
+                "Invalid pool size for cluster '{}', min {} max {}",
+                self.cluster_id, self.pool_min, self.pool_max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Connection options for the given FE node
+    pub fn get_opts(&self, node: &StarRocksNodeConfig) -> OptsBuilder {
+        let constraints = PoolConstraints::new(self.pool_min, self.pool_max).unwrap_or_default();
+        let pool_opts = PoolOpts::default()
+            .with_constraints(constraints)
+            .with_inactive_connection_ttl(Duration::from_secs(self.idle_timeout_in_seconds));
+
+        OptsBuilder::default()
+            .ip_or_hostname(node.host.as_str())
+            .tcp_port(node.port)
+            .user(Some(self.user.as_str()))
+            .pass(self.password.as_deref())
+            .prefer_socket(Some(false))
+            .pool_opts(pool_opts)
+    }
+}
+
+/// Resolves the StarRocks cluster to use for a tenant and compute id
+#[async_trait]
+pub trait ClusterResolver: Send + Sync {
+    async fn resolve(
+        &self,
+        tenant_id: &str,
+        compute_id: &str,
+    ) -> TdsWireResult<Option<StarRocksClusterConfig>>;
+}
+
+/// Resolves clusters from a local JSON file with a list of clusters
+pub struct FileClusterResolver {
+    clusters: Vec<StarRocksClusterConfig>,
+}
+
+impl FileClusterResolver {
+    pub fn new(clusters: Vec<StarRocksClusterConfig>) -> TdsWireResult<Self> {
+        for cluster in &clusters {
+            cluster.validate()?;
+        }
+        Ok(FileClusterResolver { clusters })
+    }
+
+    pub fn from_file(path: &str) -> TdsWireResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            TdsWireError::Input(format!("Failed to read clusters file '{}': {}", path, e))
+        })?;
+        let clusters =
+            serde_json::from_str::<Vec<StarRocksClusterConfig>>(&content).map_err(|e| {
+                TdsWireError::Input(format!("Failed to parse clusters file '{}': {}", path, e))
+            })?;
+        Self::new(clusters)
+    }
+}
+
+#[async_trait]
+impl ClusterResolver for FileClusterResolver {
+    async fn resolve(
+        &self,
+        tenant_id: &str,
+        compute_id: &str,
+    ) -> TdsWireResult<Option<StarRocksClusterConfig>> {
+        // prefer the cluster of the compute id, fallback to the default cluster of the tenant
+        let tenant_clusters = self.clusters.iter().filter(|c| c.tenant_id == tenant_id);
+        let found = tenant_clusters
+            .clone()
+            .find(|c| c.compute_id.as_deref() == Some(compute_id))
+            .or_else(|| tenant_clusters.clone().find(|c| c.compute_id.is_none()));
+        Ok(found.cloned())
+    }
+}
+
+/// Resolves clusters using the Unilake API
+pub struct ApiClusterResolver {
+    api_endpoint: String,
+    client: reqwest::Client,
+}
+
+impl ApiClusterResolver {
+    pub fn new(api_endpoint: String, client: reqwest::Client) -> Self {
+        ApiClusterResolver {
+            api_endpoint,
+            client,
+        }
+    }
+
+    fn get_path(&self, tenant_id: &str, compute_id: &str) -> String {
+        format!(
+            "{}/tenants/{}/compute/{}/proxy/backend",
+            self.api_endpoint, tenant_id, compute_id
+        )
+    }
+}
+
+#[async_trait]
+impl ClusterResolver for ApiClusterResolver {
+    async fn resolve(
+        &self,
+        tenant_id: &str,
+        compute_id: &str,
+    ) -> TdsWireResult<Option<StarRocksClusterConfig>> {
+        let response = self
+            .client
+            .get(self.get_path(tenant_id, compute_id))
+            .send()
+            .await
+            .map_err(|e| {
+                TdsWireError::Protocol(format!("Failed to send cluster request: {}", e))
+            })?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let cluster = response
+                    .json::<StarRocksClusterConfig>()
+                    .await
+                    .map_err(|e| {
+                        TdsWireError::Protocol(format!("Failed to parse cluster response: {}", e))
+                    })?;
+                cluster.validate()?;
+                Ok(Some(cluster))
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            status => Err(TdsWireError::Protocol(format!(
+                "Unexpected cluster response status: {}",
+                status
+            ))),
+        }
+    }
+}
+
+/// Creates the configured cluster resolver, either "api" (default) or "file"
+pub fn cluster_resolver_from_settings() -> TdsWireResult<Arc<dyn ClusterResolver>> {
+    match settings_backend_starrocks_clusters()
+        .to_lowercase()
+        .as_str()
+    {
+        "api" => {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_millis(settings_server_api_timeout_in_ms()))
+                .build()
+                .map_err(|e| {
+                    TdsWireError::Input(format!("Failed to create cluster api client: {}", e))
+                })?;
+            Ok(Arc::new(ApiClusterResolver::new(
+                settings_server_api_endpoint(),
+                client,
+            )))
+        }
+        "file" => {
+            let path = settings_backend_starrocks_clusters_file().ok_or_else(|| {
+                TdsWireError::Input(
+                    "File cluster resolution requires 'backend_starrocks_clusters_file'"
+                        .to_string(),
+                )
+            })?;
+            Ok(Arc::new(FileClusterResolver::from_file(&path)?))
+        }
+        other => Err(TdsWireError::Input(format!(
+            "Unknown cluster resolution method '{}'",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::starrocks::cluster::{
+        ApiClusterResolver, ClusterResolver, FileClusterResolver, StarRocksClusterConfig,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const CLUSTERS: &str = r#"[
+        {"cluster_id": "default", "tenant_id": "t1", "user": "root",
+         "nodes": [{"host": "fe1"}, {"host": "fe2", "port": 9031}]},
+        {"cluster_id": "reporting", "tenant_id": "t1", "compute_id": "c1", "user": "root",
+         "password": "secret", "pool_min": 2, "pool_max": 4, "idle_timeout_in_seconds": 60,
+         "nodes": [{"host": "fe3"}]}
+    ]"#;
+
+    fn get_resolver() -> FileClusterResolver {
+        FileClusterResolver::new(serde_json::from_str(CLUSTERS).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_clusters_file() {
+        let clusters = serde_json::from_str::<Vec<StarRocksClusterConfig>>(CLUSTERS).unwrap();
+        assert_eq!(clusters[0].nodes[0].port, 9030);
+        assert_eq!(clusters[0].nodes[1].port, 9031);
+        assert_eq!(clusters[0].pool_max, 100);
+        assert_eq!(clusters[1].pool_min, 2);
+        assert_eq!(clusters[1].password.as_deref(), Some("secret"));
+
+        let opts = mysql_async::Opts::from(clusters[1].get_opts(&clusters[1].nodes[0]));
+        assert_eq!(opts.ip_or_hostname(), "fe3");
+        assert_eq!(opts.pool_opts().constraints().max(), 4);
+    }
+
+    #[test]
+    fn reject_invalid_cluster() {
+        let mut clusters = serde_json::from_str::<Vec<StarRocksClusterConfig>>(CLUSTERS).unwrap();
+        clusters[1].pool_min = 10;
+        assert!(FileClusterResolver::new(clusters.clone()).is_err());
+        clusters[1].pool_min = 1;
+        clusters[1].nodes.clear();
+        assert!(FileClusterResolver::new(clusters).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_compute_cluster() {
+        let resolver = get_resolver();
+        let cluster = resolver.resolve("t1", "c1").await.unwrap().unwrap();
+        assert_eq!(cluster.cluster_id, "reporting");
+    }
+
+    #[tokio::test]
+    async fn resolve_default_cluster() {
+        let resolver = get_resolver();
+        let cluster = resolver.resolve("t1", "unknown").await.unwrap().unwrap();
+        assert_eq!(cluster.cluster_id, "default");
+        assert!(resolver.resolve("t2", "c1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn resolve_using_api() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let len = socket.read(&mut buffer).await.unwrap();
+            let body = r#"{"cluster_id":"c","tenant_id":"t1","user":"u","nodes":[{"host":"fe"}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buffer[..len]).to_string()
+        });
+
+        let resolver = ApiClusterResolver::new(endpoint, reqwest::Client::new());
+        let cluster = resolver.resolve("t1", "c1").await.unwrap().unwrap();
+        let request = handle.await.unwrap();
+
+        assert_eq!(cluster.cluster_id, "c");
+        assert_eq!(cluster.nodes[0].port, 9030);
+        assert!(request.starts_with("GET /tenants/t1/compute/c1/proxy/backend"));
+    }
+}
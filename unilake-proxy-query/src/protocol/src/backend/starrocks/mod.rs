@@ -1,45 +1,102 @@
-mod extensions;
+mod cluster;
 mod query;
+mod recovery;
 mod session;
 
-use crate::backend::app::generic::FedResult;
-use crate::backend::app::{FedResultStream, FederatedFrontendHandler, FederatedRequestType};
+pub use cluster::{
+    cluster_resolver_from_settings, ApiClusterResolver, ClusterResolver, FileClusterResolver,
+    StarRocksClusterConfig, StarRocksNodeConfig,
+};
+
+use crate::auth::{AuthenticationResult, Authenticator, JwtValidator, LoginRateLimiter};
+use crate::backend::app::catalog::{is_catalog_query, to_result_set, VirtualCatalog};
+use crate::backend::app::interceptor::InterceptorRegistry;
+use crate::backend::app::procedures::{
+    execute_procedure, ProcedureCall, ProcedureContext, SystemProcedure,
+};
+use crate::backend::app::session_statement::{
+    is_ignored_option, parse_session_statement, SessionStatement,
+};
+use crate::backend::app::{send_fed_result, FederatedRequestType};
 use crate::backend::data::BackendInstance;
-use crate::backend::starrocks::session::StarRocksSession;
+use crate::backend::engine::{send_query_result, BackendConnection, MySqlConnection, QueryBackend};
+use crate::backend::starrocks::recovery::RecoveryState;
+use crate::backend::starrocks::session::{StarRocksSession, DEFAULT_DATABASE};
 use crate::backend::telemetry::{QueryTelemetry, QueryTelemetryHandler};
 use crate::frontend::{
     prot::{
         ServerInstance, ServerInstanceMessage, SessionAuditMessage, SessionUserInfo,
         TdsWireHandlerFactory,
     },
-    tds::server_context::ServerContext,
-    BatchRequest, LoginMessage, OptionFlag2, PreloginMessage, TdsBackendResponse, TokenColMetaData,
-    TokenDone, TokenEnvChange, TokenInfo, TokenLoginAck, TokenPreLoginFedAuthRequiredOption,
-    TokenRow,
+    rpc::{escape_backslashes, resolve_rpc_request, RpcAction},
+    tds::{collation::Collation, server_context::ServerContext},
+    BatchRequest, FeatureAck, FedAuthLibrary, FedAuthTokenMessage, LoginMessage, OptionFlag2,
+    PreloginMessage, RpcRequest, TdsBackendResponse, TokenColMetaData, TokenDone, TokenEnvChange,
+    TokenFeatureExtAck, TokenFedAuth, TokenInfo, TokenLoginAck, TokenPreLoginFedAuthRequiredOption,
+    TokenReturnStatus, TokenSessionState, DATA_CLASSIFICATION_VERSION,
 };
 use crate::session::{
-    SessionInfo, SESSION_VARIABLE_CATALOG, SESSION_VARIABLE_DATABASE, SESSION_VARIABLE_DIALECT,
-    SESSION_VARIABLE_SEND_TELEMETRY,
+    SessionInfo, SessionVariable, SESSION_VARIABLE_CATALOG, SESSION_VARIABLE_DATABASE,
+    SESSION_VARIABLE_DIALECT, SESSION_VARIABLE_NOCOUNT, SESSION_VARIABLE_SEND_TELEMETRY,
+    SESSION_VARIABLE_XACT_ABORT,
 };
 use async_trait::async_trait;
 use chrono::{DateTime, TimeDelta, Utc};
 use futures::{Sink, StreamExt};
-use mysql_async::{prelude::Queryable, Conn, Error, OptsBuilder, Pool};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use mysql_async::{prelude::Queryable, Conn, Opts, Pool};
+use ring::constant_time;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use std::{collections::HashMap, net::SocketAddr};
 use tokio::sync::{Mutex, RwLock};
 use tokio_util::sync::CancellationToken;
+use ulid::Ulid;
 use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
+use unilake_common::metrics::metrics;
 use unilake_common::settings::{
+    settings_backend_health_check_interval_in_seconds,
     settings_backend_register_activity_timeout_in_seconds, settings_server_transparent_mode,
 };
-use unilake_security::handler::{HandleResult, SecurityHandler, SecurityHandlerError};
+use unilake_security::handler::{
+    access_denied_messages, HandleResult, SecurityHandler, SecurityHandlerError,
+    ACCESS_DENIED_ERROR_CODE, SEVERITY_ACCESS_ERROR,
+};
 use unilake_security::repository::RepoRest;
-use unilake_sql::{PolicyAccessRequestUrl, TranspilerDenyCause};
+use unilake_sql::{
+    run_catalog_operation, run_split_operation, PolicyAccessRequestUrl, TranspilerDenyCause,
+};
+
+/// Frontend (FE) node of a StarRocks cluster, each node has its own connection pool
+struct StarRocksNode {
+    address: String,
+    /// Options of the node, used to open connections outside the pool
+    opts: Opts,
+    pool: Pool,
+    healthy: AtomicBool,
+}
+
+impl StarRocksNode {
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                tracing::info!("StarRocks node {} is healthy again", self.address);
+            } else {
+                tracing::warn!("StarRocks node {} is unhealthy", self.address);
+            }
+        }
+    }
+}
 
 pub(crate) struct StarRocksBackend {
-    /// todo(mrhamburg): we actually need multiple pools, for multiple FE nodes (so 3 FE nodes, is 3 pools and load-balance connections)?
     cluster_id: String,
-    mysql_pool: Pool,
+    /// connections are load-balanced over all FE nodes of the cluster
+    nodes: Vec<StarRocksNode>,
+    next_node: AtomicUsize,
     last_activity_reported: Mutex<Option<DateTime<Utc>>>,
     activity_timeout_in_minutes: u16,
     server_instance: Arc<ServerInstance>,
@@ -49,6 +106,32 @@ pub(crate) struct StarRocksBackend {
 }
 
 impl StarRocksBackend {
+    fn new(config: &StarRocksClusterConfig, server_instance: Arc<ServerInstance>) -> Self {
+        let nodes = config
+            .nodes
+            .iter()
+            .map(|node| {
+                let opts: Opts = config.get_opts(node).into();
+                StarRocksNode {
+                    address: format!("{}:{}", node.host, node.port),
+                    pool: Pool::new(opts.clone()),
+                    opts,
+                    healthy: AtomicBool::new(true),
+                }
+            })
+            .collect();
+
+        StarRocksBackend {
+            cluster_id: config.cluster_id.clone(),
+            nodes,
+            next_node: AtomicUsize::new(0),
+            last_activity_reported: Mutex::new(None),
+            activity_timeout_in_minutes: config.activity_timeout_in_minutes,
+            server_instance,
+            session_count: Mutex::new(HashMap::new()),
+        }
+    }
+
     /// Checks if the current connection pool has not been used and has timed out. If so, the connection pool can be removed and the backend instance can be shutdown.
     // todo(mrhamburg): determine if this is necessary or if we can remove it entirely
     pub async fn is_timed_out(&self) -> bool {
@@ -62,28 +145,89 @@ impl StarRocksBackend {
         }
     }
 
-    pub async fn get_conn(&self, userid: &str) -> TdsWireResult<Conn> {
-        match self.mysql_pool.get_conn().await {
-            Ok(conn) => {
-                let mut session_counter = self.session_count.lock().await;
-                if let Some(session_count) = session_counter.get_mut(userid) {
-                    *session_count += 1;
-                } else {
-                    session_counter.insert(userid.to_string(), 1);
-                }
+    /// Get a connection from the next FE node in line, returns the index of the node used
+    async fn get_conn(&self, userid: &str) -> TdsWireResult<(usize, Conn)> {
+        let start = self.next_node.fetch_add(1, Ordering::Relaxed);
+        let healthy = self
+            .nodes
+            .iter()
+            .map(|n| n.is_healthy())
+            .collect::<Vec<_>>();
+        for index in get_node_order(start, &healthy) {
+            let node = &self.nodes[index];
+            match node.pool.get_conn().await {
+                Ok(conn) => {
+                    node.set_healthy(true);
+                    metrics()
+                        .backend_connections
+                        .with_label_values(&[&self.cluster_id])
+                        .inc();
+                    let mut session_counter = self.session_count.lock().await;
+                    if let Some(session_count) = session_counter.get_mut(userid) {
+                        *session_count += 1;
+                    } else {
+                        session_counter.insert(userid.to_string(), 1);
+                    }
 
-                Ok(conn)
+                    return Ok((index, conn));
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to get connection from StarRocks node {} of cluster {}: {}",
+                        node.address,
+                        self.cluster_id,
+                        e
+                    );
+                    metrics()
+                        .backend_connection_errors
+                        .with_label_values(&[&self.cluster_id])
+                        .inc();
+                    node.set_healthy(false);
+                }
             }
-            Err(_) => Err(TdsWireError::Protocol(
-                "Failed to get connection from pool".to_string(),
-            )),
         }
+
+        Err(TdsWireError::Protocol(
+            "Failed to get connection from pool".to_string(),
+        ))
+    }
+
+    /// Checks if all FE nodes are reachable, unhealthy nodes are skipped when possible
+    async fn check_health(&self) {
+        for node in &self.nodes {
+            let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, async {
+                let mut conn = node.pool.get_conn().await?;
+                conn.ping().await
+            })
+            .await;
+            node.set_healthy(matches!(result, Ok(Ok(()))));
+        }
+    }
+}
+
+#[async_trait]
+impl QueryBackend for StarRocksBackend {
+    /// Running queries are killed through a side connection to the FE node the connection
+    /// belongs to
+    async fn connect(&self, user_id: &str) -> TdsWireResult<Box<dyn BackendConnection>> {
+        let (node, conn) = self.get_conn(user_id).await?;
+        Ok(Box::new(MySqlConnection::new(
+            conn,
+            self.nodes[node].opts.clone(),
+        )))
     }
 
-    pub async fn drop_conn(&self, userid: &str) {
+    async fn disconnect(&self, user_id: &str) {
         let mut sessions = self.session_count.lock().await;
-        if let Some(count) = sessions.get_mut(userid) {
-            *count -= 1;
+        if let Some(count) = sessions.get_mut(user_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                sessions.remove(user_id);
+            }
+            metrics()
+                .backend_connections
+                .with_label_values(&[&self.cluster_id])
+                .dec();
         }
     }
 
@@ -109,66 +253,144 @@ impl StarRocksBackend {
     }
 }
 
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Order in which the nodes are tried, round-robin starting at the given node. Unhealthy nodes
+/// are only tried as a last resort, as they might have recovered since the last health check.
+fn get_node_order(start: usize, healthy: &[bool]) -> Vec<usize> {
+    let count = healthy.len();
+    let (mut order, unhealthy): (Vec<usize>, Vec<usize>) = (0..count)
+        .map(|i| (start + i) % count)
+        .partition(|i| healthy[*i]);
+    order.extend(unhealthy);
+    order
+}
+
+/// Periodically checks the health of the nodes of the backend, until the backend is dropped
+async fn run_health_checks(backend: Weak<StarRocksBackend>) {
+    let interval = Duration::from_secs(settings_backend_health_check_interval_in_seconds().max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        match backend.upgrade() {
+            Some(backend) => backend.check_health().await,
+            None => break,
+        }
+    }
+}
+
+/// Outcome of a single statement of a batch
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatementResult {
+    /// The statement completed, with the number of rows sent
+    Done(u64),
+    /// The statement completed without returning rows
+    Completed,
+    /// The statement failed and its error has been sent. Failures of the connection to the
+    /// backend abort the batch, other failures only the statement unless XACT_ABORT is on.
+    Failed { abort_batch: bool },
+    /// The statement was cancelled by the client, which aborts the batch
+    Cancelled,
+}
+
+impl StatementResult {
+    fn aborts_batch(&self, xact_abort: bool) -> bool {
+        match self {
+            StatementResult::Done(_) | StatementResult::Completed => false,
+            StatementResult::Failed { abort_batch } => *abort_batch || xact_abort,
+            StatementResult::Cancelled => true,
+        }
+    }
+
+    /// DONE token completing the statement, none if cancelled as the attention acknowledgement
+    /// completes the request instead. With NOCOUNT on, the row count is omitted.
+    fn get_done_token(&self, has_more: bool, nocount: bool) -> Option<TokenDone> {
+        let mut token = match self {
+            StatementResult::Done(_) if nocount => TokenDone::new_done(0),
+            StatementResult::Done(count) => TokenDone::new_count(0, *count),
+            StatementResult::Completed => TokenDone::new_done(0),
+            StatementResult::Failed { .. } => TokenDone::new_error(0),
+            StatementResult::Cancelled => return None,
+        };
+        token.set_status_more(has_more);
+        Some(token)
+    }
+}
+
 struct StarRocksTdsHandlerFactoryInnnerState {
+    /// backends by tenant and compute id, compute ids served by the same cluster share a backend
     backends: RwLock<HashMap<String, Arc<StarRocksBackend>>>,
+    cluster_resolver: Arc<dyn ClusterResolver>,
     server_instance: Arc<ServerInstance>,
-    // Pool is needed, functions to handle pool (add, get, disconnect and remove)
     // Backend actions are needed, handle a down cluster, spin up etc...
     // Probably also best to implement our own sessioninfo for starrocks for policy caching and things like that?
 }
 
 impl StarRocksTdsHandlerFactoryInnnerState {
-    pub fn new(server_instance: Arc<ServerInstance>) -> Self {
+    pub fn new(
+        server_instance: Arc<ServerInstance>,
+        cluster_resolver: Arc<dyn ClusterResolver>,
+    ) -> Self {
         Self {
             backends: RwLock::new(HashMap::new()),
+            cluster_resolver,
             server_instance,
         }
     }
 
-    pub async fn get_or_add_backend<F>(&self, cluster_id: &str, f: F) -> Arc<StarRocksBackend>
-    where
-        F: FnOnce() -> OptsBuilder,
-    {
-        {
-            let found = self.get_backend(cluster_id, true).await;
-            if let Some(backend) = found {
-                return backend.clone();
-            }
-        }
-        {
-            let mut backends = self.backends.write().await;
-            let opts = f();
-            let pool = Pool::new(opts);
-
-            // todo(mrhamburg): also requires pooloptions and constraints (min max pool size for example)
-            backends.insert(
-                cluster_id.to_string(),
-                Arc::new(StarRocksBackend {
-                    cluster_id: cluster_id.to_string(),
-                    mysql_pool: pool,
-                    last_activity_reported: Mutex::new(None),
-                    //todo(mrhamburg): determine this, don't think 60 minutes is a good fit, should be configurable (global config)
-                    activity_timeout_in_minutes: 60, // Default to 60 minutes
-                    server_instance: self.server_instance.clone(),
-                    session_count: Mutex::new(HashMap::new()),
-                }),
-            );
+    /// Get the backend for the tenant and compute id, the cluster is resolved when the backend
+    /// is requested for the first time
+    pub async fn get_or_add_backend(
+        &self,
+        tenant_id: &str,
+        compute_id: &str,
+    ) -> TdsWireResult<Arc<StarRocksBackend>> {
+        let key = format!("{}/{}", tenant_id, compute_id);
+        if let Some(backend) = self.get_backend(&key, true).await {
+            return Ok(backend);
         }
 
-        self.get_backend(cluster_id, false).await.unwrap()
+        let config = self
+            .cluster_resolver
+            .resolve(tenant_id, compute_id)
+            .await?
+            .ok_or_else(|| {
+                TdsWireError::Protocol(format!(
+                    "No StarRocks cluster configured for tenant '{}' and compute '{}'",
+                    tenant_id, compute_id
+                ))
+            })?;
+
+        let mut backends = self.backends.write().await;
+        // the backend might have been added in the meantime
+        if let Some(backend) = backends.get(&key) {
+            return Ok(backend.clone());
+        }
+        let backend = match backends
+            .values()
+            .find(|b| b.cluster_id == config.cluster_id)
+        {
+            Some(backend) => backend.clone(),
+            None => {
+                tracing::info!(
+                    "Setting up StarRocks backend for cluster {}",
+                    config.cluster_id
+                );
+                let backend =
+                    Arc::new(StarRocksBackend::new(&config, self.server_instance.clone()));
+                tokio::spawn(run_health_checks(Arc::downgrade(&backend)));
+                backend
+            }
+        };
+        backends.insert(key, backend.clone());
+        Ok(backend)
     }
 
     pub async fn get_backend(
         &self,
-        cluster_name: &str,
+        key: &str,
         register_activity: bool,
     ) -> Option<Arc<StarRocksBackend>> {
-        let backend = self
-            .backends
-            .read()
-            .await
-            .get(cluster_name)
-            .map(|x| x.clone());
+        let backend = self.backends.read().await.get(key).map(|x| x.clone());
         if register_activity && backend.is_some() {
             if let Some(ref backend) = backend {
                 backend.register_activity().await
@@ -192,6 +414,16 @@ impl StarRocksTdsHandlerFactoryInnnerState {
         }
     }
 
+    /// Send login events to the audit system
+    fn audit_on_login(&self, message: SessionAuditMessage) {
+        if let Err(e) = self
+            .server_instance
+            .process_message(ServerInstanceMessage::Audit(message))
+        {
+            tracing::error!("Failed to send audit login event: {}", e);
+        }
+    }
+
     // async fn query_event(&self, query_id: Ulid, event_type: QueryEventType) {
     //     let time = std::time::SystemTime::now();
     //     // probably best to be implemented in the new query.rs environment
@@ -199,15 +431,390 @@ impl StarRocksTdsHandlerFactoryInnnerState {
     // }
 }
 
+/// Name used in login errors and audit events for federated authentication, the user is only
+/// known after the access token has been validated
+const FED_AUTH_PRINCIPAL: &str = "<token-identified principal>";
+
 pub struct StarRocksTdsHandlerFactory {
     inner: StarRocksTdsHandlerFactoryInnnerState,
+    authenticator: Arc<dyn Authenticator>,
+    jwt_validator: Option<Arc<JwtValidator>>,
+    login_rate_limiter: Option<Arc<LoginRateLimiter>>,
+    interceptors: Arc<InterceptorRegistry>,
 }
 
 impl StarRocksTdsHandlerFactory {
-    pub fn new(server_instance: Arc<ServerInstance>) -> Self {
+    pub fn new(
+        server_instance: Arc<ServerInstance>,
+        authenticator: Arc<dyn Authenticator>,
+        cluster_resolver: Arc<dyn ClusterResolver>,
+    ) -> Self {
         StarRocksTdsHandlerFactory {
-            inner: StarRocksTdsHandlerFactoryInnnerState::new(server_instance),
+            inner: StarRocksTdsHandlerFactoryInnnerState::new(server_instance, cluster_resolver),
+            authenticator,
+            jwt_validator: None,
+            login_rate_limiter: None,
+            interceptors: Arc::new(InterceptorRegistry::default()),
+        }
+    }
+
+    /// Answer the queries matched by the rules of the given registry, instead of the built-in rules
+    pub fn with_interceptors(mut self, interceptors: Arc<InterceptorRegistry>) -> Self {
+        self.interceptors = interceptors;
+        self
+    }
+
+    /// Accept federated authentication access tokens validated by the given validator
+    pub fn with_jwt_validator(mut self, jwt_validator: Arc<JwtValidator>) -> Self {
+        self.jwt_validator = Some(jwt_validator);
+        self
+    }
+
+    /// Reject logins from client addresses with too many recently failed logins
+    pub fn with_login_rate_limiter(mut self, login_rate_limiter: Arc<LoginRateLimiter>) -> Self {
+        self.login_rate_limiter = Some(login_rate_limiter);
+        self
+    }
+
+    /// Checked before authenticating, rejects the login when the session limits have been
+    /// reached or too many logins from the client address failed recently
+    fn admit_login(
+        &self,
+        session_info: &mut StarRocksSession,
+        username: &str,
+    ) -> TdsWireResult<()> {
+        if let Err(e) = self
+            .inner
+            .server_instance
+            .check_session_limits(session_info.session_id())
+        {
+            return self.reject_login(session_info, username, e);
+        }
+        if let Some(limiter) = &self.login_rate_limiter {
+            if limiter.is_limited(session_info.socket_addr().ip()) {
+                tracing::warn!(
+                    "Too many failed logins from {}, rejecting login for user '{}'",
+                    session_info.socket_addr().ip(),
+                    username
+                );
+                // not recorded as a failure, retries must not extend the lockout
+                let error = TdsWireError::Server(TokenError::new(
+                    18456,
+                    1,
+                    14,
+                    format!(
+                        "Login failed for user '{}'. Too many failed logins from the client address, try again later.",
+                        username
+                    ),
+                    session_info.tds_server_context().server_name.clone(),
+                    "".to_string(),
+                    1,
+                ));
+                return self.reject_login(session_info, username, error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the access token received using federated authentication
+    fn validate_access_token(
+        &self,
+        session_info: &StarRocksSession,
+        token: &str,
+        nonce: Option<&[u8]>,
+    ) -> TdsWireResult<AuthenticationResult> {
+        let validator = self.jwt_validator.as_ref().ok_or_else(|| {
+            TdsWireError::Protocol("Federated authentication is not configured".to_string())
+        })?;
+
+        if !Self::is_nonce_echoed(session_info.get_server_nonce(), nonce) {
+            return Ok(AuthenticationResult::Denied);
+        }
+
+        validator.validate(token)
+    }
+
+    /// The client must echo the nonce we sent in the pre-login response, compared in constant
+    /// time. Without a nonce sent by us, there is nothing to echo.
+    fn is_nonce_echoed(expected: Option<[u8; 32]>, nonce: Option<&[u8]>) -> bool {
+        match (expected, nonce) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(expected), Some(nonce)) => {
+                constant_time::verify_slices_are_equal(&expected, nonce).is_ok()
+            }
+        }
+    }
+
+    /// Register the authenticated user with the session. On failure, a login failed error is
+    /// returned, which is reported to the client before the connection is closed.
+    fn handle_authentication_result(
+        &self,
+        session_info: &mut StarRocksSession,
+        username: &str,
+        result: TdsWireResult<AuthenticationResult>,
+    ) -> TdsWireResult<()> {
+        match result {
+            Ok(AuthenticationResult::Authenticated(user_id)) => {
+                session_info.set_sql_user_id(user_id);
+                // the limit per user can only be checked once the user is known
+                if let Err(e) = self
+                    .inner
+                    .server_instance
+                    .check_session_limits(session_info.session_id())
+                {
+                    return self.reject_login(session_info, username, e);
+                }
+                metrics().logins.with_label_values(&["success"]).inc();
+                self.inner
+                    .audit_on_login(SessionAuditMessage::LoginSucceeded(SessionUserInfo::from(
+                        &*session_info,
+                    )));
+                Ok(())
+            }
+            result => {
+                match result {
+                    Err(e) => tracing::error!("Authentication failed for '{}': {}", username, e),
+                    _ => tracing::info!("Login failed for user '{}'", username),
+                }
+                if let Some(limiter) = &self.login_rate_limiter {
+                    limiter.record_failure(session_info.socket_addr().ip());
+                }
+                let error = TdsWireError::Server(TokenError::new(
+                    18456,
+                    1,
+                    14,
+                    format!("Login failed for user '{}'.", username),
+                    session_info.tds_server_context().server_name.clone(),
+                    "".to_string(),
+                    1,
+                ));
+                self.reject_login(session_info, username, error)
+            }
+        }
+    }
+
+    /// Registers the failed login and returns the error, which is reported to the client
+    /// before the connection is closed
+    fn reject_login(
+        &self,
+        session_info: &StarRocksSession,
+        username: &str,
+        error: TdsWireError,
+    ) -> TdsWireResult<()> {
+        metrics().logins.with_label_values(&["failure"]).inc();
+        self.inner
+            .audit_on_login(SessionAuditMessage::LoginFailed(SessionUserInfo::new(
+                session_info.socket_addr(),
+                username.to_string(),
+            )));
+        Err(error)
+    }
+
+    /// Acknowledge session recovery if requested by the client, the session state received
+    /// when reconnecting is restored. A session which can not be restored fails the login.
+    fn recover_session(
+        &self,
+        session_info: &mut StarRocksSession,
+        msg: &LoginMessage,
+    ) -> TdsWireResult<Option<FeatureAck>> {
+        let server_context = session_info.tds_server_context();
+        let (Some(ext), Some(key)) = (&msg.session_recovery, &server_context.session_recovery_key)
+        else {
+            return Ok(None);
+        };
+        if !server_context.session_recovery_enabled {
+            return Ok(None);
+        }
+
+        if let Some(data) = &ext.recovery_data {
+            if let Err(e) =
+                RecoveryState::open(key, &data.states).and_then(|s| s.restore(session_info))
+            {
+                let user_id = session_info.get_sql_user_id().to_string();
+                tracing::warn!("Failed to recover session of user '{}': {}", user_id, e);
+                let error = TdsWireError::Server(TokenError::new(
+                    18456,
+                    1,
+                    14,
+                    format!("Login failed for user '{}'.", user_id),
+                    server_context.server_name.clone(),
+                    "".to_string(),
+                    1,
+                ));
+                return self
+                    .reject_login(session_info, &user_id, error)
+                    .map(|_| None);
+            }
+            tracing::info!(
+                "Recovered session of user '{}'",
+                session_info.get_sql_user_id()
+            );
+        }
+
+        session_info.enable_session_recovery();
+        let state = RecoveryState::from_session(session_info).seal(key)?;
+        Ok(Some(FeatureAck::new_session_recovery(vec![state])))
+    }
+
+    /// Send the changed session state to a client using session recovery
+    async fn send_session_state<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        if !session_info.is_session_recovery_enabled() {
+            return Ok(());
         }
+        let server_context = session_info.tds_server_context();
+        let Some(key) = &server_context.session_recovery_key else {
+            return Ok(());
+        };
+        let state = RecoveryState::from_session(session_info).seal(key)?;
+        let seq_no = session_info.next_session_state_seq_no();
+        self.send_token(client, TokenSessionState::new(seq_no, true, vec![state]))
+            .await
+    }
+
+    /// Send the login response to an authenticated client
+    async fn complete_login<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+        msg: &LoginMessage,
+        mut feature_acks: Vec<FeatureAck>,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        // restore the state of a recovered session, before its database is reported
+        if let Some(ack) = self.recover_session(session_info, msg)? {
+            feature_acks.push(ack);
+        }
+
+        // set database change
+        let old_database = if let Some(old_database) = session_info.get_database() {
+            old_database.clone().to_string()
+        } else {
+            "".to_string()
+        };
+        let new_database = match session_info.get_schema() {
+            Some(schema) => schema.to_string(),
+            None => msg
+                .db_name
+                .clone()
+                .unwrap_or_else(|| DEFAULT_DATABASE.to_string()),
+        };
+        self.send_token(
+            client,
+            TokenEnvChange::new_database_change(old_database, new_database.clone()),
+        )
+        .await?;
+        self.send_token(
+            client,
+            TokenInfo::new(
+                &*session_info.tds_server_context(),
+                5701,
+                2,
+                0,
+                format!("Changed database context to '{}'", &new_database),
+            ),
+        )
+        .await?;
+        session_info.set_schema(new_database);
+
+        // result sets are classified if the client supports data classification
+        if msg.data_classification.is_some() {
+            session_info.enable_data_classification();
+            feature_acks.push(FeatureAck::new_data_classification(
+                DATA_CLASSIFICATION_VERSION,
+            ));
+        }
+
+        // set collation change, character data is UTF-8 encoded if the client supports it
+        let collation = if msg.utf8_support {
+            session_info.enable_utf8_support();
+            feature_acks.push(FeatureAck::new_utf8_support());
+            Collation::utf8()
+        } else {
+            Collation::default()
+        };
+        self.send_token(
+            client,
+            TokenEnvChange::new_collation_change(None, Some(collation)),
+        )
+        .await?;
+
+        // set language change
+        self.send_token(
+            client,
+            TokenEnvChange::new_language_change("".to_string(), "us_english".to_string()),
+        )
+        .await?;
+        self.send_token(
+            client,
+            TokenInfo::new(
+                &*session_info.tds_server_context(),
+                5703,
+                1,
+                0,
+                format!("Changed language to '{}'", "us_english"),
+            ),
+        )
+        .await?;
+
+        // set packet size change, the new packet size applies from the next response onward
+        let old_packet_size = session_info.packet_size().load(Ordering::Relaxed);
+        let packet_size = session_info
+            .tds_server_context()
+            .negotiate_packet_size(msg.packet_size);
+        session_info
+            .packet_size()
+            .store(packet_size, Ordering::Relaxed);
+        self.send_token(
+            client,
+            TokenEnvChange::new_packet_size_change(
+                old_packet_size.to_string(),
+                packet_size.to_string(),
+            ),
+        )
+        .await?;
+        self.send_token(
+            client,
+            TokenInfo::new(
+                &*session_info.tds_server_context(),
+                5702,
+                1,
+                0,
+                format!("Changed packet size to {}", packet_size),
+            ),
+        )
+        .await?;
+
+        // keep this information
+        session_info.set_login_message(msg.clone());
+
+        // create login ack token
+        self.send_token(
+            client,
+            TokenLoginAck::new(session_info.tds_server_context()),
+        )
+        .await?;
+
+        if !feature_acks.is_empty() {
+            self.send_token(
+                client,
+                TokenFeatureExtAck {
+                    features: feature_acks,
+                },
+            )
+            .await?;
+        }
+
+        self.send_token(client, TokenDone::new_final()).await
     }
 
     async fn handle_frontend_error<C, TE>(
@@ -220,68 +827,55 @@ impl StarRocksTdsHandlerFactory {
         C: Sink<TdsBackendResponse> + Unpin + Send,
         TE: Into<TokenError>,
     {
-        //todo(mrhamburg): make sure this is also logged properly etc...
-        let mut token = e.into();
-        token.server = session_info.tds_server_context().server_name.clone();
-        self.send_token(client, token).await?;
+        self.send_error_token(client, session_info, e).await?;
         self.send_token(client, TokenDone::new_error(0)).await?;
         Ok(())
     }
 
-    async fn handle_backend_error<C>(
+    /// Sends the error without completing the request, so the caller can continue with the
+    /// next statement
+    async fn send_error_token<C, TE>(
         &self,
         client: &mut C,
         session_info: &StarRocksSession,
-        e: Error,
+        e: TE,
     ) -> TdsWireResult<()>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
+        TE: Into<TokenError>,
     {
         //todo(mrhamburg): make sure this is also logged properly etc...
-        let error_token = TokenError::new(
-            0,
-            0,
-            0,
-            e.to_string(),
-            session_info.tds_server_context().server_name.clone(),
-            "".to_string(),
-            0,
-        );
-        self.send_token(client, error_token).await?;
-        self.send_token(client, TokenDone::new_error(0)).await?;
-        Ok(())
+        let mut token = e.into();
+        metrics()
+            .query_errors
+            .with_label_values(&[&token.code.to_string()])
+            .inc();
+        token.server = session_info.tds_server_context().server_name.clone();
+        self.send_token(client, token).await
     }
 
-    async fn handle_fed_resultset<C>(
+    /// Errors reported by the backend engine are passed on and only fail the statement, other
+    /// failures are reported as a generic error and abort the batch
+    async fn handle_backend_error<C>(
         &self,
         client: &mut C,
-        mut fed_result: FedResultStream,
-    ) -> TdsWireResult<()>
+        session_info: &StarRocksSession,
+        e: TdsWireError,
+    ) -> TdsWireResult<StatementResult>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
-        while let Some(result) = fed_result.next().await {
-            match result {
-                Ok(fed_result) => match fed_result {
-                    FedResult::Tabular(mut result) => {
-                        self.send_token(client, TokenColMetaData::from(&mut result))
-                            .await?;
-                        let mut count = 0;
-                        for row in result {
-                            self.send_token(client, row).await?;
-                            count += 1;
-                        }
-                        let token_done = TokenDone::new_count(0, count);
-                        self.send_token(client, token_done).await?;
-                    }
-                    FedResult::Info(_) => todo!(),
-                    FedResult::State(_) => todo!(),
-                    FedResult::Empty => self.send_token(client, TokenDone::new_count(0, 0)).await?,
-                },
-                Err(_) => todo!(),
-            }
-        }
-        Ok(())
+        tracing::error!("Query failed on backend: {}", e);
+        let (error_token, abort_batch) = match e {
+            TdsWireError::Server(token) => (token, false),
+            e => (
+                TokenError::new(0, 1, 16, e.to_string(), "".to_string(), "".to_string(), 0),
+                true,
+            ),
+        };
+        self.send_error_token(client, session_info, error_token)
+            .await?;
+        Ok(StatementResult::Failed { abort_batch })
     }
 
     async fn get_backend_instance(&self, session_info: &StarRocksSession) -> Arc<BackendInstance> {
@@ -324,7 +918,7 @@ impl StarRocksTdsHandlerFactory {
         session_info: &StarRocksSession,
         query_telemetry: &mut QueryTelemetryHandler,
         query: &str,
-    ) -> TdsWireResult<Option<Arc<str>>>
+    ) -> TdsWireResult<Option<(Arc<str>, Option<HashMap<String, Vec<String>>>)>>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
@@ -356,13 +950,18 @@ impl StarRocksTdsHandlerFactory {
             start.elapsed()
         );
 
+        // the tags of the selected attributes classify the result columns
+        let attribute_tags = session_info
+            .is_data_classification_enabled()
+            .then(|| security_handler.get_attribute_tags().clone());
+
         self.inner
             .audit_on_query(session_info, security_handler)
             .await;
 
         match query {
             Ok(q) => match q {
-                HandleResult::Query(q) => Ok(Some(q)),
+                HandleResult::Query(q) => Ok(Some((q, attribute_tags))),
                 HandleResult::AccessDenied(cause, access_links) => {
                     self.handle_telemetry_request(
                         client,
@@ -370,15 +969,21 @@ impl StarRocksTdsHandlerFactory {
                         session_info,
                     )
                     .await?;
-                    self.handle_access_denied_result(client, cause, access_links)
-                        .await?;
+                    self.handle_access_denied_result(
+                        client,
+                        session_info,
+                        ulid,
+                        cause,
+                        access_links,
+                    )
+                    .await?;
                     Ok(None)
                 }
             },
             Err(e) => {
                 self.handle_telemetry_request(client, query_telemetry.end().await, session_info)
                     .await?;
-                self.handle_error_result(client, e).await?;
+                self.handle_error_result(client, session_info, e).await?;
                 Ok(None)
             }
         }
@@ -389,88 +994,447 @@ impl StarRocksTdsHandlerFactory {
         if cfg!(debug_assertions) {
             return settings_server_transparent_mode();
         }
-        false
+        false
+    }
+
+    /// Splits the batch into its statements, using the dialect of the session
+    fn split_batch(session_info: &StarRocksSession, query: &str) -> Vec<String> {
+        let values = session_info.get_values_or_default(&[SESSION_VARIABLE_DIALECT], true);
+        match run_split_operation(query, values[SESSION_VARIABLE_DIALECT].as_ref()) {
+            Ok(statements) => statements,
+            Err(e) => {
+                tracing::warn!("Failed to split batch, executing it as a whole: {}", e);
+                vec![query.to_string()]
+            }
+        }
+    }
+
+    fn is_option_on(session_info: &StarRocksSession, name: &str) -> bool {
+        session_info
+            .get_session_variable(name, false)
+            .get_value_or_default()
+            .eq_ignore_ascii_case("on")
+    }
+
+    /// Applies a statement changing the state of the session. A database change is executed on
+    /// the backend as well, so it fails for unknown databases and keeps the backend in sync.
+    async fn execute_session_statement<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+        cancellation_token: CancellationToken,
+        statement: TdsWireResult<SessionStatement>,
+    ) -> TdsWireResult<StatementResult>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let statement = match statement {
+            Ok(statement) => statement,
+            Err(e) => {
+                let message = match e {
+                    TdsWireError::Input(message) => message,
+                    e => e.to_string(),
+                };
+                let error = TokenError::new(0, 1, 16, message, "".to_string(), "".to_string(), 0);
+                self.send_error_token(client, session_info, error).await?;
+                return Ok(StatementResult::Failed { abort_batch: false });
+            }
+        };
+
+        match statement {
+            SessionStatement::Use(database) => {
+                let query = Self::use_database_query(session_info, &database);
+                match Self::execute_on_backend(session_info, &query, cancellation_token).await {
+                    Ok(()) => {}
+                    Err(TdsWireError::Cancelled) => return Ok(StatementResult::Cancelled),
+                    Err(e) => return self.handle_backend_error(client, session_info, e).await,
+                }
+
+                let old_database = session_info
+                    .get_schema()
+                    .map(|d| d.to_string())
+                    .unwrap_or_default();
+                session_info.set_session_variable(
+                    SESSION_VARIABLE_DATABASE.to_string(),
+                    SessionVariable::new(&database),
+                );
+                session_info.set_schema(database.clone());
+                self.send_token(
+                    client,
+                    TokenEnvChange::new_database_change(old_database, database.clone()),
+                )
+                .await?;
+                self.send_token(
+                    client,
+                    TokenInfo::new(
+                        &session_info.tds_server_context(),
+                        5701,
+                        2,
+                        0,
+                        format!("Changed database context to '{}'", database),
+                    ),
+                )
+                .await?;
+            }
+            SessionStatement::Set(values) => {
+                let mut ignored = Vec::new();
+                for (name, value) in values {
+                    tracing::debug!("Setting session variable {} to '{}'", name, value);
+                    if is_ignored_option(&name) {
+                        ignored.push(name.to_uppercase());
+                    }
+                    session_info.set_session_variable(name, SessionVariable::new(&value));
+                }
+                // the client must not assume options took effect which the backend never sees
+                if !ignored.is_empty() {
+                    self.send_token(
+                        client,
+                        TokenInfo::new(
+                            &session_info.tds_server_context(),
+                            0,
+                            1,
+                            0,
+                            format!(
+                                "SET {} is not supported by the proxy and has no effect.",
+                                ignored.join(", ")
+                            ),
+                        ),
+                    )
+                    .await?;
+                }
+            }
+        }
+        self.send_session_state(client, session_info).await?;
+        Ok(StatementResult::Completed)
+    }
+
+    fn use_database_query(session_info: &StarRocksSession, database: &str) -> String {
+        let values = session_info.get_values_or_default(&[SESSION_VARIABLE_CATALOG], true);
+        format!(
+            "USE `{}`.`{}`",
+            values[SESSION_VARIABLE_CATALOG].replace('`', "``"),
+            database.replace('`', "``")
+        )
+    }
+
+    /// Executes a statement on the backend, discarding any rows it returns
+    async fn execute_on_backend(
+        session_info: &StarRocksSession,
+        query: &str,
+        cancellation_token: CancellationToken,
+    ) -> TdsWireResult<()> {
+        let mut conn = session_info.get_conn().await?;
+        let mut result = conn.execute(query, cancellation_token).await?;
+        while let Some(row) = result.rows.next().await {
+            row?;
+        }
+        Ok(())
+    }
+
+    /// Executes the query on the backend and sends the result set to the client, without the
+    /// DONE token completing the statement. Errors have already been sent on failure.
+    async fn execute_query<C>(
+        &self,
+        client: &mut C,
+        cancellation_token: CancellationToken,
+        session: &StarRocksSession,
+        mut query_telemetry: QueryTelemetryHandler,
+        query: &str,
+    ) -> TdsWireResult<StatementResult>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let mut conn = session.get_conn().await?;
+
+        // for debugging purposes we only secure the query if transparent mode is disabled, the
+        // untranspiled query needs its backslashes escaped for the backend
+        let (query, attribute_tags) = if Self::get_transparent_mode_on() {
+            (Arc::from(escape_backslashes(query)), None)
+        } else {
+            match self
+                .secure_query(client, session, &mut query_telemetry, query)
+                .await?
+            {
+                None => return Ok(StatementResult::Failed { abort_batch: false }),
+                Some(q) => q,
+            }
+        };
+
+        query_telemetry.start_backend_timer();
+        let result = conn.execute(&query, cancellation_token).await;
+        query_telemetry.clock_backend_time();
+        let result = match result {
+            Ok(result) => {
+                send_query_result(
+                    client,
+                    result,
+                    session.is_utf8_support_enabled(),
+                    attribute_tags.as_ref(),
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok((record_count, record_bytes)) => {
+                // set and send telemetry
+                query_telemetry.set_processed_data(record_count, record_bytes as u64);
+                self.handle_telemetry_request(client, query_telemetry.end().await, session)
+                    .await?;
+                metrics().queries.with_label_values(&["success"]).inc();
+                Ok(StatementResult::Done(record_count))
+            }
+            Err(TdsWireError::Cancelled) => {
+                tracing::info!("Query of session {} was cancelled", session.session_id());
+                query_telemetry.end().await;
+                metrics().queries.with_label_values(&["cancelled"]).inc();
+                Ok(StatementResult::Cancelled)
+            }
+            Err(e) => {
+                self.handle_telemetry_request(client, query_telemetry.end().await, session)
+                    .await?;
+                metrics().queries.with_label_values(&["error"]).inc();
+                self.handle_backend_error(client, session, e).await
+            }
+        }
+    }
+
+    /// Executes an emulated system procedure and sends its result sets, each completed by a
+    /// DONEINPROC token. The return status and the DONE token are left to the caller.
+    async fn execute_procedure<C>(
+        &self,
+        client: &mut C,
+        cancellation_token: CancellationToken,
+        session: &StarRocksSession,
+        call: TdsWireResult<ProcedureCall>,
+    ) -> TdsWireResult<StatementResult>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let call = match call {
+            Ok(call) => call,
+            Err(e) => return self.handle_procedure_error(client, session, None, e).await,
+        };
+        tracing::debug!("Executing system procedure {}", call.procedure.name());
+
+        let catalog = if call.procedure.requires_catalog() {
+            match self
+                .read_visible_catalog(client, cancellation_token, session)
+                .await?
+            {
+                Ok(catalog) => Some(catalog),
+                Err(result) => return Ok(result),
+            }
+        } else {
+            None
+        };
+        let schema = session.get_schema();
+        let context = ProcedureContext {
+            server: &session.tds_server_context(),
+            catalog: catalog.as_ref(),
+            sessions: self.inner.server_instance.get_active_sessions(),
+            session_id: session.session_id(),
+            schema: schema.as_deref(),
+        };
+        let result_sets = match execute_procedure(&call, &context) {
+            Ok(result_sets) => result_sets,
+            Err(e) => {
+                return self
+                    .handle_procedure_error(client, session, Some(call.procedure), e)
+                    .await
+            }
+        };
+
+        for mut result_set in result_sets {
+            self.send_token(client, TokenColMetaData::from(&mut result_set))
+                .await?;
+            let mut count = 0;
+            for row in result_set {
+                self.send_token(client, row).await?;
+                count += 1;
+            }
+            let mut token = TokenDone::new_in_proc(0, count);
+            token.set_status_more(true);
+            self.send_token(client, token).await?;
+        }
+        Ok(StatementResult::Completed)
+    }
+
+    async fn handle_procedure_error<C>(
+        &self,
+        client: &mut C,
+        session: &StarRocksSession,
+        procedure: Option<SystemProcedure>,
+        e: TdsWireError,
+    ) -> TdsWireResult<StatementResult>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let message = match e {
+            TdsWireError::Input(message) => message,
+            e => e.to_string(),
+        };
+        let procedure = procedure.map(|p| p.name().to_string()).unwrap_or_default();
+        let error = TokenError::new(0, 1, 16, message, "".to_string(), procedure, 0);
+        self.send_error_token(client, session, error).await?;
+        Ok(StatementResult::Failed { abort_batch: false })
+    }
+
+    /// Reads the catalog of the session, reduced to the tables and columns visible to the user.
+    /// On failure the error has been sent and the result of the statement is returned instead.
+    async fn read_visible_catalog<C>(
+        &self,
+        client: &mut C,
+        cancellation_token: CancellationToken,
+        session: &StarRocksSession,
+    ) -> TdsWireResult<Result<VirtualCatalog, StatementResult>>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let values = session.get_values_or_default(&[SESSION_VARIABLE_CATALOG], true);
+        let catalog = {
+            let mut conn = session.get_conn().await?;
+            VirtualCatalog::read(
+                conn.as_mut(),
+                values[SESSION_VARIABLE_CATALOG].as_ref(),
+                cancellation_token,
+            )
+            .await
+        };
+        let mut catalog = match catalog {
+            Ok(catalog) => catalog,
+            Err(TdsWireError::Cancelled) => return Ok(Err(StatementResult::Cancelled)),
+            Err(e) => return self.handle_backend_error(client, session, e).await.map(Err),
+        };
+
+        // for debugging purposes the complete catalog is visible if transparent mode is enabled
+        if !Self::get_transparent_mode_on() {
+            let mut security_handler = self.get_new_security_handler(session).await?;
+            match security_handler
+                .get_visible_schema(&catalog.entities())
+                .await
+            {
+                Ok(visible_schema) => catalog.retain_visible(&visible_schema),
+                Err(e) => {
+                    self.handle_error_result(client, session, e).await?;
+                    return Ok(Err(StatementResult::Failed { abort_batch: false }));
+                }
+            }
+        }
+        Ok(Ok(catalog))
+    }
+
+    /// Answers a query on the system catalog from the tables and columns visible to the user.
+    /// Returns none if the statement is not a catalog query after all, in which case it is
+    /// executed as a regular query.
+    async fn execute_catalog_query<C>(
+        &self,
+        client: &mut C,
+        cancellation_token: CancellationToken,
+        session: &StarRocksSession,
+        query: &str,
+    ) -> TdsWireResult<Option<StatementResult>>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let catalog = match self
+            .read_visible_catalog(client, cancellation_token, session)
+            .await?
+        {
+            Ok(catalog) => catalog,
+            Err(result) => return Ok(Some(result)),
+        };
+
+        let values = session.get_values_or_default(&[SESSION_VARIABLE_DIALECT], true);
+        let output = match run_catalog_operation(
+            query,
+            values[SESSION_VARIABLE_DIALECT].as_ref(),
+            &catalog.to_json(),
+        ) {
+            Ok(output) if output.is_catalog_query => output,
+            Ok(_) => return Ok(None),
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to execute catalog query, sending it to the backend: {}",
+                    e
+                );
+                return Ok(None);
+            }
+        };
+        if let Some(message) = output.error {
+            let error = TokenError::new(0, 1, 16, message, "".to_string(), "".to_string(), 0);
+            self.send_error_token(client, session, error).await?;
+            return Ok(Some(StatementResult::Failed { abort_batch: false }));
+        }
+
+        let mut result = to_result_set(output);
+        self.send_token(client, TokenColMetaData::from(&mut result))
+            .await?;
+        let mut count = 0;
+        for row in result {
+            self.send_token(client, row).await?;
+            count += 1;
+        }
+        Ok(Some(StatementResult::Done(count)))
     }
 
-    async fn handle_batch_request<C>(
+    /// Executes a statement on the backend, unless it queries the system catalog which is
+    /// answered by the proxy
+    async fn execute_statement<C>(
         &self,
         client: &mut C,
         cancellation_token: CancellationToken,
         session: &StarRocksSession,
-        mut query_telemetry: QueryTelemetryHandler,
-        query: &str,
-    ) -> TdsWireResult<()>
+        statement: &str,
+    ) -> TdsWireResult<StatementResult>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
-        let mut conn = session.get_conn().await?;
-        tracing::debug!("Connection id: {}", conn.id());
-
-        // for debugging purposes we only secure the query if transparent mode is disabled
-        let query = if Self::get_transparent_mode_on() {
-            Arc::from(query)
-        } else {
-            match self
-                .secure_query(client, session, &mut query_telemetry, query)
+        if is_catalog_query(statement) {
+            if let Some(result) = self
+                .execute_catalog_query(client, cancellation_token.clone(), session, statement)
                 .await?
             {
-                None => return Ok(()),
-                Some(q) => q,
-            }
-        };
-
-        // todo(mrhamburg): handle query cancellation (either when dropping the connection or by sending an attention message to cancel)
-        query_telemetry.start_backend_timer();
-        let query_result = tokio::select! {
-            result = conn.query_iter(query) => {
-                query_telemetry.clock_backend_time();
-                match result {
-                    Ok(result) => {
-                        Some(result)
-                    }
-                    Err(e) => {
-                        self.handle_telemetry_request(client, query_telemetry.end().await, session).await?;
-                        self.handle_backend_error(client, session, e).await?;
-                        return Ok(())
-                    }
-                }
-            },
-            _ = cancellation_token.cancelled() => {
-                eprintln!("Query was canceled.");
-                None
+                return Ok(result);
             }
-        };
-
-        // send column metadata
-        let mut result = query_result.unwrap();
-        let mut columns = TokenColMetaData::new(result.columns_ref().len());
-        for column in result.columns_ref() {
-            columns.add_column(column);
         }
 
-        // todo: add exclude time for send_token (telemetry), so we don't include network time
-        self.send_token(client, columns).await?;
+        // set query telemetry, for keeping track of query execution time
+        let telemetry = QueryTelemetryHandler::new(self.inner.server_instance.clone());
+        self.execute_query(client, cancellation_token, session, telemetry, statement)
+            .await
+    }
 
-        // send rows
-        let mut record_count = 0;
-        let mut record_bytes = 0;
-        while let Ok(Some(row)) = result.next().await {
-            let token_row = TokenRow::from(row);
-            record_count += 1;
-            record_bytes += token_row.size_in_bytes();
+    /// Sets up the backend connection for this session, if not yet available
+    async fn ensure_backend_conn(&self, session_info: &mut StarRocksSession) -> TdsWireResult<()> {
+        if !session_info.has_conn() {
+            let backend = self
+                .inner
+                .get_or_add_backend(
+                    &session_info.get_tenant_id(),
+                    &session_info.get_compute_id(),
+                )
+                .await?;
 
-            // todo: add exclude time for send_token (telemetry), so we don't include network time
-            self.send_token(client, token_row).await?;
-        }
+            let conn = backend
+                .connect(session_info.get_sql_user_id().as_ref())
+                .await?;
+            session_info.set_backend(backend);
+            session_info.set_conn(Mutex::new(conn));
 
-        // set and send telemetry
-        query_telemetry.set_processed_data(record_count, record_bytes as u64);
-        self.handle_telemetry_request(client, query_telemetry.end().await, session)
-            .await?;
+            // a database changed earlier in the session, e.g. of a recovered session
+            if let SessionVariable::Some(database) =
+                session_info.get_session_variable(SESSION_VARIABLE_DATABASE, false)
+            {
+                let query = Self::use_database_query(session_info, database);
+                Self::execute_on_backend(session_info, &query, CancellationToken::new()).await?;
+            }
+        }
 
-        // send token done
-        self.send_token(client, TokenDone::new_count(0, record_count))
-            .await
+        // register activity to backend
+        session_info.register_activity().await;
+        Ok(())
     }
 
     async fn handle_telemetry_request<C>(
@@ -500,29 +1464,59 @@ impl StarRocksTdsHandlerFactory {
         Ok(())
     }
 
+    /// Sends the access denied error, followed by a message for each denied attribute and the
+    /// links where access can be requested. The caller completes the statement with an error.
     async fn handle_access_denied_result<C>(
         &self,
         client: &mut C,
+        session_info: &StarRocksSession,
+        query_id: Ulid,
         cause: Vec<TranspilerDenyCause>,
         access_links: Option<Vec<PolicyAccessRequestUrl>>,
     ) -> TdsWireResult<()>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
-        // todo: we want to present the user with at least the access link to request for access, the other ones are errors and messages?
-        todo!()
+        tracing::info!("Query {} denied: {:?}", query_id, cause);
+        metrics().queries.with_label_values(&["denied"]).inc();
+        let error = TokenError::new(
+            ACCESS_DENIED_ERROR_CODE,
+            1,
+            SEVERITY_ACCESS_ERROR,
+            format!(
+                "Access denied to one or more attributes requested by the query. Query Id: {}",
+                query_id
+            ),
+            "".to_string(),
+            "".to_string(),
+            0,
+        );
+        self.send_error_token(client, session_info, error).await?;
+        for message in access_denied_messages(&cause, access_links.as_deref().unwrap_or_default()) {
+            self.send_token(
+                client,
+                TokenInfo::new(&session_info.tds_server_context(), 0, 1, 0, message),
+            )
+            .await?;
+        }
+        Ok(())
     }
 
+    /// Sends the error raised while securing the query, e.g. when the user cannot be found. The
+    /// caller completes the statement with an error.
     async fn handle_error_result<C>(
         &self,
         client: &mut C,
+        session_info: &StarRocksSession,
         error: SecurityHandlerError,
     ) -> TdsWireResult<()>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
-        // todo: this could be an error like no user found
-        todo!()
+        let token = TokenError::from(error);
+        tracing::error!("Query failed in security handler: {}", token.message);
+        metrics().queries.with_label_values(&["error"]).inc();
+        self.send_error_token(client, session_info, token).await
     }
 }
 
@@ -606,9 +1600,47 @@ impl TdsWireHandlerFactory<StarRocksSession> for StarRocksTdsHandlerFactory {
 
         // todo(mrhamburg): check for tds version
 
+        // reject before authenticating, the fed auth principal is used until the user is known
+        let username = match msg.fed_auth_ext {
+            Some(_) => FED_AUTH_PRINCIPAL.to_string(),
+            None => msg.username.clone().unwrap_or_default(),
+        };
+        self.admit_login(session_info, &username)?;
+
         // check for fed auth
-        if let Some(ref _fed_auth_ext) = msg.fed_auth_ext {
-            todo!()
+        if let Some(ref fed_auth_ext) = msg.fed_auth_ext {
+            return match fed_auth_ext.library {
+                FedAuthLibrary::SecurityToken => {
+                    let result = self.validate_access_token(
+                        session_info,
+                        &fed_auth_ext.fed_auth_token,
+                        fed_auth_ext.nonce.as_deref(),
+                    );
+                    self.handle_authentication_result(session_info, FED_AUTH_PRINCIPAL, result)?;
+                    self.complete_login(client, session_info, msg, vec![FeatureAck::new_fed_auth()])
+                        .await
+                }
+                FedAuthLibrary::LiveIdCompactToken => {
+                    let result = Err(TdsWireError::Protocol(
+                        "Live ID compact tokens are not supported".to_string(),
+                    ));
+                    self.handle_authentication_result(session_info, FED_AUTH_PRINCIPAL, result)
+                }
+                FedAuthLibrary::Adal { .. } => {
+                    // the client acquires the token using the information from the FEDAUTHINFO
+                    // token and sends it in a separate message, login continues from there
+                    session_info.set_login_message(msg.clone());
+                    let server_context = session_info.tds_server_context();
+                    self.send_token(
+                        client,
+                        TokenFedAuth::new(
+                            &server_context.sts_url,
+                            &server_context.server_principal_name,
+                        ),
+                    )
+                    .await
+                }
+            };
         }
 
         // check for sspi (which we do not support)
@@ -618,161 +1650,442 @@ impl TdsWireHandlerFactory<StarRocksSession> for StarRocksTdsHandlerFactory {
             ));
         }
 
-        // expect this to be basic auth, which will be implemented later
-        // todo(mrhamburg): implement authentication
-        if let Some(ref client_id) = msg.client_id {
-            session_info.set_sql_user_id(client_id.clone());
-        }
+        // sql authentication
+        let password = msg.password.clone().unwrap_or_default();
+        let result = self
+            .authenticator
+            .authenticate(&session_info.get_tenant_id(), &username, &password)
+            .await;
+        self.handle_authentication_result(session_info, &username, result)?;
 
-        // set database change
-        let old_database = if let Some(old_database) = session_info.get_database() {
-            old_database.clone().to_string()
-        } else {
-            "".to_string()
-        };
-        let new_database = msg.db_name.clone().unwrap_or_else(|| "main".to_string());
-        self.send_token(
-            client,
-            TokenEnvChange::new_database_change(old_database, new_database.clone()),
-        )
-        .await?;
-        self.send_token(
-            client,
-            TokenInfo::new(
-                &*session_info.tds_server_context(),
-                5701,
-                2,
-                0,
-                format!("Changed database context to '{}'", &new_database),
-            ),
-        )
-        .await?;
-        session_info.set_schema(new_database);
+        self.complete_login(client, session_info, msg, Vec::new())
+            .await
+    }
 
-        // set collation change
-        // return_msg.add_token(TokenEnvChange::new_collation_change(
-        //     "".to_string(),
-        //     "".to_string(),
-        // ));
+    async fn on_federated_authentication_token_message<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+        msg: &FedAuthTokenMessage,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let login = session_info.get_login_message().cloned().ok_or_else(|| {
+            TdsWireError::Protocol("Missing login request for federated authentication".to_string())
+        })?;
 
-        // set language change
-        self.send_token(
-            client,
-            TokenEnvChange::new_language_change("".to_string(), "us_english".to_string()),
-        )
-        .await?;
-        self.send_token(
+        let result = self.validate_access_token(session_info, &msg.token, msg.nonce.as_deref());
+        self.handle_authentication_result(session_info, FED_AUTH_PRINCIPAL, result)?;
+        self.complete_login(
             client,
-            TokenInfo::new(
-                &*session_info.tds_server_context(),
-                5703,
-                1,
-                0,
-                format!("Changed language to '{}'", "us_english"),
-            ),
+            session_info,
+            &login,
+            vec![FeatureAck::new_fed_auth()],
         )
-        .await?;
+        .await
+    }
 
-        // set packet size change
-        self.send_token(
-            client,
-            TokenEnvChange::new_packet_size_change("4096".to_string(), "4096".to_string()),
-        )
-        .await?;
-        self.send_token(
-            client,
-            TokenInfo::new(
-                &*session_info.tds_server_context(),
-                5702,
-                1,
-                0,
-                format!("Changed packet size to {}", "4096"),
-            ),
-        )
-        .await?;
+    async fn on_sql_batch_request<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+        msg: &BatchRequest,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        tracing::info!("Received SQL batch request: {}", &msg.query);
 
-        // keep this information
-        session_info.set_login_message(msg.clone());
+        // check for federated query, matched on the complete batch
+        if let Some(handler) = self
+            .interceptors
+            .intercept(&FederatedRequestType::Query(msg))
+        {
+            return send_fed_result(
+                client,
+                handler,
+                &session_info.tds_server_context().server_name,
+            )
+            .await;
+        }
 
-        // create login ack token
-        self.send_token(
-            client,
-            TokenLoginAck::new(session_info.tds_server_context()),
-        )
-        .await?;
+        // handle initial session connection
+        self.ensure_backend_conn(session_info).await?;
 
-        // check if session recovery is enabled
-        if session_info.tds_server_context().session_recovery_enabled {
-            // msg.add_token(FeatureAck::new_session_recovery());
+        // handle batch request, each statement is completed by its own DONE token
+        let statements = Self::split_batch(session_info, &msg.query);
+        if statements.is_empty() {
+            return self.send_token(client, TokenDone::new_final()).await;
         }
 
-        self.send_token(client, TokenDone::new_final()).await
-    }
+        let cancellation_token = session_info.get_cancellation_token();
+        for (i, statement) in statements.iter().enumerate() {
+            let result = if let Some(statement) = parse_session_statement(statement) {
+                self.execute_session_statement(
+                    client,
+                    session_info,
+                    cancellation_token.clone(),
+                    statement,
+                )
+                .await?
+            } else if let Some(call) = ProcedureCall::parse(statement) {
+                let result = self
+                    .execute_procedure(client, cancellation_token.clone(), session_info, call)
+                    .await?;
+                if result == StatementResult::Completed {
+                    self.send_token(client, TokenReturnStatus::new(0)).await?;
+                }
+                result
+            } else {
+                self.execute_statement(client, cancellation_token.clone(), session_info, statement)
+                    .await?
+            };
 
-    fn on_federated_authentication_token_message(&self, _session: &StarRocksSession) {
-        todo!()
+            // options might have been changed by the statement itself
+            let xact_abort = Self::is_option_on(session_info, SESSION_VARIABLE_XACT_ABORT);
+            let nocount = Self::is_option_on(session_info, SESSION_VARIABLE_NOCOUNT);
+            let is_last = i == statements.len() - 1 || result.aborts_batch(xact_abort);
+            if let Some(token) = result.get_done_token(!is_last, nocount) {
+                self.send_token(client, token).await?;
+            }
+            if is_last {
+                break;
+            }
+        }
+        Ok(())
     }
 
-    async fn on_sql_batch_request<C>(
+    async fn on_rpc_request<C>(
         &self,
         client: &mut C,
         session_info: &mut StarRocksSession,
-        msg: &BatchRequest,
+        msg: &RpcRequest,
     ) -> TdsWireResult<()>
     where
         C: Sink<TdsBackendResponse> + Unpin + Send,
     {
-        tracing::info!("Received SQL batch request: {}", &msg.query);
+        tracing::info!("Received RPC request: {:?}", msg.procedure_type());
 
-        // set query telemetry, for keeping track of query execution time
-        let telemetry = QueryTelemetryHandler::new(self.inner.server_instance.clone());
+        if let Some(call) = ProcedureCall::from_rpc(msg) {
+            if call.as_ref().is_ok_and(|c| c.procedure.requires_catalog()) {
+                self.ensure_backend_conn(session_info).await?;
+            }
+            let cancellation_token = session_info.get_cancellation_token();
+            match self
+                .execute_procedure(client, cancellation_token, session_info, call)
+                .await?
+            {
+                StatementResult::Failed { .. } => {
+                    return self.send_token(client, TokenDone::new_error(0)).await
+                }
+                StatementResult::Cancelled => return Ok(()),
+                _ => {}
+            }
+            self.send_token(client, TokenReturnStatus::new(0)).await?;
+            return self.send_token(client, TokenDone::new_proc(0)).await;
+        }
 
-        // check for federated query
-        let hash = msg.get_hash();
-        if let Some(handler) =
-            FederatedFrontendHandler::exec_request(hash, FederatedRequestType::Query(msg))?
-        {
-            return self.handle_fed_resultset(client, handler).await;
+        let (query, return_values) = match resolve_rpc_request(session_info, msg) {
+            Ok(RpcAction::Execute {
+                query,
+                return_values,
+            }) => (Some(query), return_values),
+            Ok(RpcAction::Respond { return_values }) => (None, return_values),
+            Err(e) => {
+                let message = match e {
+                    TdsWireError::Input(message) => message,
+                    e => e.to_string(),
+                };
+                let procedure = msg
+                    .procedure_name()
+                    .map(str::to_string)
+                    .or_else(|| msg.procedure_type().map(|p| format!("{:?}", p)))
+                    .unwrap_or_default();
+                let error = TokenError::new(0, 1, 16, message, "".to_string(), procedure, 0);
+                return self
+                    .handle_frontend_error(client, session_info, error)
+                    .await;
+            }
+        };
+
+        if let Some(query) = query {
+            tracing::debug!("Bound RPC query: {}", &query);
+            let telemetry = QueryTelemetryHandler::new(self.inner.server_instance.clone());
+            self.ensure_backend_conn(session_info).await?;
+
+            let cancellation_token = session_info.get_cancellation_token();
+            match self
+                .execute_query(client, cancellation_token, session_info, telemetry, &query)
+                .await?
+            {
+                StatementResult::Done(count) => {
+                    self.send_token(client, TokenDone::new_in_proc(0, count))
+                        .await?
+                }
+                StatementResult::Completed => {}
+                StatementResult::Failed { .. } => {
+                    return self.send_token(client, TokenDone::new_error(0)).await
+                }
+                StatementResult::Cancelled => return Ok(()),
+            }
         }
-        tracing::trace!("No federated query found for: {}", hash);
 
-        // handle initial session connection
-        if !session_info.has_conn() {
-            let backend = self
-                .inner
-                .get_or_add_backend("testing", || {
-                    tracing::info!("Setting up StarRocks backend");
-                    OptsBuilder::default()
-                        .ip_or_hostname("10.255.255.17")
-                        .tcp_port(9030)
-                        .user(Some("root"))
-                        .prefer_socket(Some(false))
-                })
-                .await;
+        self.send_token(client, TokenReturnStatus::new(0)).await?;
+        for return_value in return_values {
+            self.send_token(client, return_value).await?;
+        }
+        self.send_token(client, TokenDone::new_proc(0)).await
+    }
 
-            let conn = backend
-                .get_conn(session_info.get_sql_user_id().as_ref())
-                .await?;
-            session_info.set_backend(backend.clone());
-            session_info.set_conn(Mutex::new(conn));
+    async fn on_reset_connection<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        let old_database = session_info
+            .get_schema()
+            .map(|d| d.to_string())
+            .unwrap_or_default();
+        session_info.reset().await;
+        tracing::debug!("Reset connection of session {}", session_info.session_id());
+
+        self.send_token(client, TokenEnvChange::new_reset_connection_ack())
+            .await?;
+        let database = session_info.get_login_database();
+        if old_database != database {
+            self.send_token(
+                client,
+                TokenEnvChange::new_database_change(old_database, database),
+            )
+            .await?;
         }
+        self.send_session_state(client, session_info).await
+    }
 
-        // register activity to backend
-        session_info.register_activity().await;
+    async fn on_attention<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut StarRocksSession,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send,
+    {
+        tracing::info!(
+            "Attention received for session: {}",
+            session_info.session_id()
+        );
+        self.send_token(client, TokenDone::new_attention(0)).await
+    }
+}
 
-        // handle batch request
-        let cancellation_token = CancellationToken::new();
-        self.handle_batch_request(
-            client,
-            cancellation_token,
-            session_info,
-            telemetry,
-            &msg.query,
+#[cfg(test)]
+mod tests {
+    use crate::auth::{LocalAuthenticator, LoginRateLimiter};
+    use crate::backend::app::session_statement::parse_session_statement;
+    use crate::backend::engine::QueryBackend;
+    use crate::backend::starrocks::session::StarRocksSession;
+    use crate::backend::starrocks::{
+        get_node_order, FileClusterResolver, StarRocksBackend, StarRocksClusterConfig,
+        StarRocksTdsHandlerFactory, StatementResult,
+    };
+    use crate::frontend::prot::ServerInstance;
+    use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::{DoneStatus, TdsBackendResponse, TdsToken};
+    use crate::session::{SessionInfo, SESSION_VARIABLE_NOCOUNT};
+    use enumflags2::BitFlags;
+    use futures::channel::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+    use unilake_common::error::TdsWireError;
+
+    fn get_factory(instance: &Arc<ServerInstance>) -> StarRocksTdsHandlerFactory {
+        StarRocksTdsHandlerFactory::new(
+            instance.clone(),
+            Arc::new(LocalAuthenticator::new(vec![]).unwrap()),
+            Arc::new(FileClusterResolver::new(vec![]).unwrap()),
         )
-        .await
     }
 
-    fn on_attention(&self, _session: &StarRocksSession) {
-        todo!()
+    fn get_session(instance: &Arc<ServerInstance>) -> StarRocksSession {
+        StarRocksSession::new(
+            "10.0.0.1:50001".parse().unwrap(),
+            instance.clone(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn node_order_round_robin() {
+        let healthy = [true, true, true];
+        assert_eq!(get_node_order(0, &healthy), vec![0, 1, 2]);
+        assert_eq!(get_node_order(1, &healthy), vec![1, 2, 0]);
+        assert_eq!(get_node_order(5, &healthy), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn node_order_unhealthy_last() {
+        assert_eq!(get_node_order(0, &[false, true, true]), vec![1, 2, 0]);
+        assert_eq!(get_node_order(2, &[true, false, true]), vec![2, 0, 1]);
+        assert_eq!(get_node_order(1, &[false, false]), vec![1, 0]);
+        assert!(get_node_order(3, &[]).is_empty());
+    }
+
+    #[test]
+    fn statement_done_tokens() {
+        let done = StatementResult::Done(3)
+            .get_done_token(true, false)
+            .unwrap();
+        assert_eq!(done.status, DoneStatus::Count | DoneStatus::More);
+        assert_eq!(done.done_rows, 3);
+
+        // the row count is omitted with nocount on
+        let done = StatementResult::Done(3)
+            .get_done_token(false, true)
+            .unwrap();
+        assert!(done.status.is_empty());
+        let done = StatementResult::Completed
+            .get_done_token(true, false)
+            .unwrap();
+        assert_eq!(done.status, BitFlags::from_flag(DoneStatus::More));
+
+        let failed = StatementResult::Failed { abort_batch: false };
+        let done = failed.get_done_token(false, false).unwrap();
+        assert_eq!(done.status, BitFlags::from_flag(DoneStatus::Error));
+        assert!(StatementResult::Cancelled
+            .get_done_token(true, false)
+            .is_none());
+    }
+
+    #[test]
+    fn statement_aborts_batch() {
+        let failed = StatementResult::Failed { abort_batch: false };
+        assert!(!failed.aborts_batch(false));
+        assert!(failed.aborts_batch(true));
+        assert!(StatementResult::Failed { abort_batch: true }.aborts_batch(false));
+        assert!(!StatementResult::Done(0).aborts_batch(true));
+        assert!(StatementResult::Cancelled.aborts_batch(false));
+    }
+
+    #[test]
+    fn nonce_must_be_echoed() {
+        let nonce = [7u8; 32];
+        assert!(StarRocksTdsHandlerFactory::is_nonce_echoed(
+            Some(nonce),
+            Some(&nonce)
+        ));
+        assert!(StarRocksTdsHandlerFactory::is_nonce_echoed(None, None));
+
+        // a missing or different nonce is a possible replay of the token
+        assert!(!StarRocksTdsHandlerFactory::is_nonce_echoed(
+            Some(nonce),
+            None
+        ));
+        assert!(!StarRocksTdsHandlerFactory::is_nonce_echoed(
+            Some(nonce),
+            Some(&[8u8; 32])
+        ));
+        assert!(!StarRocksTdsHandlerFactory::is_nonce_echoed(
+            Some(nonce),
+            Some(&nonce[..16])
+        ));
+    }
+
+    #[tokio::test]
+    async fn disconnect_removes_idle_users() {
+        let config: StarRocksClusterConfig = serde_json::from_value(serde_json::json!({
+            "cluster_id": "cluster",
+            "tenant_id": "tenant",
+            "nodes": [{"host": "127.0.0.1"}],
+            "user": "root",
+        }))
+        .unwrap();
+        let instance = Arc::new(ServerInstance::new(ServerContext::default()));
+        let backend = StarRocksBackend::new(&config, instance);
+        backend
+            .session_count
+            .lock()
+            .await
+            .insert("user".to_string(), 2);
+
+        backend.disconnect("user").await;
+        assert_eq!(backend.session_count.lock().await.get("user"), Some(&1));
+        backend.disconnect("user").await;
+        assert!(backend.session_count.lock().await.is_empty());
+        // a disconnect without a connection must not underflow the count
+        backend.disconnect("user").await;
+        assert!(backend.session_count.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rate_limited_login_is_not_recorded() {
+        let instance = Arc::new(ServerInstance::new(ServerContext::default()));
+        let limiter = Arc::new(LoginRateLimiter::new(1, Duration::from_millis(200)));
+        let factory = get_factory(&instance).with_login_rate_limiter(limiter.clone());
+        let mut session = get_session(&instance);
+
+        limiter.record_failure(session.socket_addr().ip());
+        for _ in 0..3 {
+            match factory.admit_login(&mut session, "user") {
+                Err(TdsWireError::Server(token)) => {
+                    assert_eq!(token.code, 18456);
+                    assert!(token.message.contains("Too many failed logins"));
+                }
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        // the rejected logins did not extend the lockout
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(factory.admit_login(&mut session, "user").is_ok());
+    }
+
+    #[tokio::test]
+    async fn set_options_are_applied_or_reported() {
+        let instance = Arc::new(ServerInstance::new(ServerContext::default()));
+        let factory = get_factory(&instance);
+        let mut session = get_session(&instance);
+        let (mut client, mut receiver) = mpsc::unbounded::<TdsBackendResponse>();
+
+        // NOCOUNT is applied by the proxy, the row count of the next query is omitted
+        let result = factory
+            .execute_session_statement(
+                &mut client,
+                &mut session,
+                CancellationToken::new(),
+                parse_session_statement("SET NOCOUNT ON").unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, StatementResult::Completed));
+        assert!(receiver.try_recv().is_err());
+        let nocount = StarRocksTdsHandlerFactory::is_option_on(&session, SESSION_VARIABLE_NOCOUNT);
+        let done = StatementResult::Done(3)
+            .get_done_token(false, nocount)
+            .unwrap();
+        assert!(!done.status.contains(DoneStatus::Count));
+
+        // options which never reach the backend are reported to the client
+        factory
+            .execute_session_statement(
+                &mut client,
+                &mut session,
+                CancellationToken::new(),
+                parse_session_statement("SET ANSI_NULLS, QUOTED_IDENTIFIER OFF").unwrap(),
+            )
+            .await
+            .unwrap();
+        match receiver.try_recv() {
+            Ok(TdsBackendResponse::Token(TdsToken::Info(info))) => assert_eq!(
+                info.message,
+                "SET ANSI_NULLS, QUOTED_IDENTIFIER is not supported by the proxy and has no effect."
+            ),
+            _ => panic!("expected an info token"),
+        }
     }
 }
@@ -0,0 +1,123 @@
+use crate::backend::starrocks::session::StarRocksSession;
+use crate::frontend::SessionStateData;
+use crate::session::{SessionInfo, SessionVariable};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// State id of the proxy session state, the complete state is kept in a single state value
+pub const SESSION_STATE_ID: u8 = 0;
+
+/// Length of the HMAC-SHA256 signature appended to the state
+const SIGNATURE_LEN: usize = 32;
+
+/// Session state the client keeps for us, so the session can be restored when the client
+/// reconnects after a connection drop. The state is signed, as the client can not be trusted
+/// with for example the impersonated user.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryState {
+    pub user_id: String,
+    pub schema: Option<String>,
+    /// Session variables explicitly set during the session, defaults are not included
+    pub variables: Vec<(String, String)>,
+}
+
+impl RecoveryState {
+    pub fn from_session(session: &StarRocksSession) -> Self {
+        let mut variables = session
+            .get_session_variables()
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                SessionVariable::Some(value) => Some((name.to_string(), value.to_string())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        variables.sort();
+
+        RecoveryState {
+            user_id: session.get_sql_user_id().to_string(),
+            schema: session.get_schema().map(|s| s.to_string()),
+            variables,
+        }
+    }
+
+    /// Restore the session, the user of the recovered session must match the authenticated user
+    pub fn restore(self, session: &mut StarRocksSession) -> TdsWireResult<()> {
+        if self.user_id != session.get_sql_user_id().as_ref() {
+            return Err(TdsWireError::Input(
+                "Session state belongs to a different user".to_string(),
+            ));
+        }
+        if let Some(schema) = self.schema {
+            session.set_schema(schema);
+        }
+        for (name, value) in self.variables {
+            session.set_session_variable(name, SessionVariable::new(&value));
+        }
+        Ok(())
+    }
+
+    /// The signed state as sent to the client
+    pub fn seal(&self, key: &hmac::Key) -> TdsWireResult<SessionStateData> {
+        let mut value = serde_json::to_vec(self).map_err(|e| {
+            TdsWireError::Protocol(format!("Failed to serialize session state: {}", e))
+        })?;
+        let signature = hmac::sign(key, &value);
+        value.extend_from_slice(signature.as_ref());
+        Ok(SessionStateData::new(SESSION_STATE_ID, value))
+    }
+
+    /// Read the state from the session recovery data, fails if the signature does not match
+    pub fn open(key: &hmac::Key, states: &[SessionStateData]) -> TdsWireResult<Self> {
+        let value = states
+            .iter()
+            .find(|s| s.state_id == SESSION_STATE_ID)
+            .map(|s| s.value.as_slice())
+            .ok_or_else(|| TdsWireError::Input("Missing session state".to_string()))?;
+        if value.len() < SIGNATURE_LEN {
+            return Err(TdsWireError::Input("Invalid session state".to_string()));
+        }
+
+        let (state, signature) = value.split_at(value.len() - SIGNATURE_LEN);
+        hmac::verify(key, state, signature)
+            .map_err(|_| TdsWireError::Input("Invalid session state signature".to_string()))?;
+        serde_json::from_slice(state)
+            .map_err(|e| TdsWireError::Input(format!("Invalid session state: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::starrocks::recovery::RecoveryState;
+    use ring::hmac;
+
+    #[test]
+    fn seal_and_open_session_state() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let state = RecoveryState {
+            user_id: "user-1".to_string(),
+            schema: Some("sales".to_string()),
+            variables: vec![(
+                "proxy_security_impersonate".to_string(),
+                "user-2".to_string(),
+            )],
+        };
+
+        let sealed = state.seal(&key).unwrap();
+        assert_eq!(
+            RecoveryState::open(&key, std::slice::from_ref(&sealed)).unwrap(),
+            state
+        );
+
+        // signed using a different key
+        let other_key = hmac::Key::new(hmac::HMAC_SHA256, b"other");
+        assert!(RecoveryState::open(&other_key, std::slice::from_ref(&sealed)).is_err());
+
+        // tampered with by the client
+        let mut tampered = sealed.clone();
+        tampered.value[2] ^= 0x01;
+        assert!(RecoveryState::open(&key, &[tampered]).is_err());
+
+        assert!(RecoveryState::open(&key, &[]).is_err());
+    }
+}
@@ -1,26 +1,30 @@
-use crate::backend::starrocks::StarRocksBackend;
+use crate::backend::engine::{BackendConnection, QueryBackend};
 use crate::frontend::prot::{ServerInstance, TdsSessionState};
-use crate::frontend::tds::server_context::ServerContext;
+use crate::frontend::tds::server_context::{ServerContext, DEFAULT_PACKET_SIZE};
 use crate::frontend::LoginMessage;
 use crate::session::{
-    SessionInfo, SessionVariable, SESSION_VARIABLE_CATALOG, SESSION_VARIABLE_DATABASE,
-    SESSION_VARIABLE_DIALECT, SESSION_VARIABLE_SECURITY_IMPERSONATE,
-    SESSION_VARIABLE_SEND_TELEMETRY,
+    PreparedStatement, SessionInfo, SessionVariable, SESSION_VARIABLE_CATALOG,
+    SESSION_VARIABLE_DATABASE, SESSION_VARIABLE_DIALECT, SESSION_VARIABLE_NOCOUNT,
+    SESSION_VARIABLE_SECURITY_IMPERSONATE, SESSION_VARIABLE_SEND_TELEMETRY,
+    SESSION_VARIABLE_XACT_ABORT,
 };
 use casbin::{Cache, DefaultModel};
 use chrono::Datelike;
-use mysql_async::Conn;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::atomic::AtomicU16;
 use std::sync::Arc;
 use tokio::sync::{Mutex, MutexGuard};
+use tokio_util::sync::CancellationToken;
 use ulid::Ulid;
 use unilake_common::error::{TdsWireError, TdsWireResult};
 use unilake_common::model::{AppInfoModel, IpInfoModel, SessionModel};
 use unilake_security::caching::layered_cache::MultiLayeredCache;
 use unilake_security::HitRule;
 
+/// Database of a session when none has been requested at login
+pub const DEFAULT_DATABASE: &str = "main";
+
 pub struct StarRocksSession {
     socket_addr: SocketAddr,
     state: TdsSessionState,
@@ -40,18 +44,28 @@ pub struct StarRocksSession {
     workspace_id: Arc<str>,
     domain_id: Arc<str>,
     endpoint: Arc<str>,
-    backend: Option<Arc<StarRocksBackend>>,
-    conn: Option<Mutex<Conn>>,
+    backend: Option<Arc<dyn QueryBackend>>,
+    conn: Option<Mutex<Box<dyn BackendConnection>>>,
     cached_rules: Option<Arc<Box<dyn Cache<u64, (String, HitRule)>>>>,
     server_instance: Arc<ServerInstance>,
     login_message: Option<LoginMessage>,
+    prepared_statements: HashMap<i32, Arc<PreparedStatement>>,
+    next_prepared_handle: i32,
+    cancellation_token: CancellationToken,
+    /// Whether session recovery has been acknowledged, the session state is sent to the client
+    session_recovery: bool,
+    session_state_seq_no: u32,
+    /// Whether UTF-8 support has been acknowledged, character data is sent as UTF-8 varchar
+    utf8_support: bool,
+    /// Whether data classification has been acknowledged, result sets are classified
+    data_classification: bool,
 }
 
 impl StarRocksSession {
     pub fn new(
         socket_addr: SocketAddr,
         server_instance: Arc<ServerInstance>,
-        conn: Option<Mutex<Conn>>,
+        conn: Option<Mutex<Box<dyn BackendConnection>>>,
         cached_rules: Option<Arc<Box<dyn Cache<u64, (String, HitRule)>>>>,
     ) -> Self {
         // let e = Arc::new(Mutex::new(
@@ -59,9 +73,9 @@ impl StarRocksSession {
         // ));
         StarRocksSession {
             socket_addr,
-            packet_size: Arc::new(AtomicU16::new(server_instance.ctx.packet_size)),
+            packet_size: Arc::new(AtomicU16::new(DEFAULT_PACKET_SIZE)),
             session_id: server_instance.next_session_id(),
-            sql_user_id: Some(Arc::from("500efbea-0bfd-49b3-88ab-090cff23cab6")),
+            sql_user_id: None,
             state: TdsSessionState::default(),
             database: None,
             schema: None,
@@ -81,21 +95,40 @@ impl StarRocksSession {
             server_instance,
             backend: None,
             login_message: None,
+            prepared_statements: HashMap::new(),
+            next_prepared_handle: 1,
+            cancellation_token: CancellationToken::new(),
+            session_recovery: false,
+            session_state_seq_no: 0,
+            utf8_support: false,
+            data_classification: false,
         }
     }
 
-    pub fn set_conn(&mut self, conn: Mutex<Conn>) {
+    pub fn set_conn(&mut self, conn: Mutex<Box<dyn BackendConnection>>) {
         self.conn = Some(conn);
     }
 
     pub fn set_login_message(&mut self, login_message: LoginMessage) {
+        if let Some(hostname) = login_message.hostname.clone().filter(|h| !h.is_empty()) {
+            self.server_instance
+                .update_session(self.session_id, |s| s.hostname = hostname);
+        }
         self.login_message = Some(login_message);
     }
 
+    pub fn get_login_message(&self) -> Option<&LoginMessage> {
+        self.login_message.as_ref()
+    }
+
     pub fn get_tenant_id(&self) -> Arc<str> {
         self.tenant_id.clone()
     }
 
+    pub fn get_compute_id(&self) -> Arc<str> {
+        self.compute_id.clone()
+    }
+
     pub fn has_conn(&self) -> bool {
         self.conn.is_some()
     }
@@ -106,11 +139,15 @@ impl StarRocksSession {
         }
     }
 
-    pub fn set_backend(&mut self, backend: Arc<StarRocksBackend>) {
+    pub fn set_backend(&mut self, backend: Arc<dyn QueryBackend>) {
         self.backend = Some(backend);
     }
 
-    pub async fn get_conn(&self) -> TdsWireResult<MutexGuard<Conn>> {
+    pub fn get_backend(&self) -> Option<Arc<dyn QueryBackend>> {
+        self.backend.clone()
+    }
+
+    pub async fn get_conn(&self) -> TdsWireResult<MutexGuard<'_, Box<dyn BackendConnection>>> {
         if let Some(conn) = &self.conn {
             return Ok(conn.lock().await);
         }
@@ -149,17 +186,59 @@ impl StarRocksSession {
             SESSION_VARIABLE_SEND_TELEMETRY.to_string(),
             SessionVariable::new_default("false"),
         );
+        variables.insert(
+            SESSION_VARIABLE_XACT_ABORT.to_string(),
+            SessionVariable::new_default("off"),
+        );
+        // T-SQL options, only nocount and xact_abort change the behavior of the proxy
+        for (name, value) in [
+            (SESSION_VARIABLE_NOCOUNT, "off"),
+            ("ansi_nulls", "on"),
+            ("quoted_identifier", "on"),
+            ("textsize", "4096"),
+            ("lock_timeout", "-1"),
+            ("dateformat", "mdy"),
+            ("datefirst", "7"),
+        ] {
+            variables.insert(name.to_string(), SessionVariable::new_default(value));
+        }
         variables
     }
 
     pub async fn close(&self) {
-        if let Some(pool) = &self.backend {
+        if let (Some(pool), Some(_)) = (&self.backend, &self.conn) {
             if let Some(userid) = &self.sql_user_id {
-                pool.drop_conn(userid.as_ref()).await;
+                pool.disconnect(userid.as_ref()).await;
             }
         }
     }
 
+    /// Return the backend connection to its pool, which resets its session state. A new
+    /// connection is set up for the next query.
+    pub async fn release_conn(&mut self) {
+        self.close().await;
+        self.conn = None;
+    }
+
+    /// Database requested when logging in
+    pub fn get_login_database(&self) -> String {
+        self.login_message
+            .as_ref()
+            .and_then(|m| m.db_name.clone())
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| DEFAULT_DATABASE.to_string())
+    }
+
+    /// Restore the session to its state right after login, when a pooled connection is reused
+    pub async fn reset(&mut self) {
+        self.session_variables = StarRocksSession::get_default_session_variable();
+        self.prepared_statements.clear();
+        self.next_prepared_handle = 1;
+        self.set_schema(self.get_login_database());
+        self.release_conn().await;
+        self.connection_reset_request_count += 1;
+    }
+
     fn get_app_name(&self) -> String {
         let default = "unknown".to_string();
         if let Some(login_message) = &self.login_message {
@@ -232,6 +311,36 @@ impl StarRocksSession {
     pub fn get_abac_model(&self) -> Option<DefaultModel> {
         self.server_instance.get_abac_model()
     }
+
+    pub fn enable_session_recovery(&mut self) {
+        self.session_recovery = true;
+    }
+
+    pub fn is_session_recovery_enabled(&self) -> bool {
+        self.session_recovery
+    }
+
+    pub fn enable_utf8_support(&mut self) {
+        self.utf8_support = true;
+    }
+
+    pub fn is_utf8_support_enabled(&self) -> bool {
+        self.utf8_support
+    }
+
+    pub fn enable_data_classification(&mut self) {
+        self.data_classification = true;
+    }
+
+    pub fn is_data_classification_enabled(&self) -> bool {
+        self.data_classification
+    }
+
+    /// Sequence number of the next session state change sent to the client
+    pub fn next_session_state_seq_no(&mut self) -> u32 {
+        self.session_state_seq_no = self.session_state_seq_no.wrapping_add(1);
+        self.session_state_seq_no
+    }
 }
 
 impl StarRocksSession {}
@@ -258,10 +367,12 @@ impl SessionInfo for StarRocksSession {
     }
 
     fn get_sql_user_id(&self) -> Arc<str> {
-        Arc::from("")
+        self.sql_user_id.clone().unwrap_or_else(|| Arc::from(""))
     }
 
     fn set_sql_user_id(&mut self, sql_user_id: String) {
+        self.server_instance
+            .update_session(self.session_id, |s| s.user = sql_user_id.clone());
         self.sql_user_id = Some(Arc::from(sql_user_id));
     }
 
@@ -284,6 +395,8 @@ impl SessionInfo for StarRocksSession {
     }
 
     fn set_schema(&mut self, db_name: String) {
+        self.server_instance
+            .update_session(self.session_id, |s| s.database = db_name.clone());
         self.schema = Some(Arc::from(db_name));
     }
 
@@ -296,7 +409,7 @@ impl SessionInfo for StarRocksSession {
     }
 
     fn connection_reset_request_count(&self) -> usize {
-        todo!()
+        self.connection_reset_request_count
     }
 
     fn set_client_nonce(&mut self, nonce: [u8; 32]) {
@@ -325,4 +438,66 @@ impl SessionInfo for StarRocksSession {
             .map(|(k, v)| (k.as_ref(), v))
             .collect()
     }
+
+    fn add_prepared_statement(&mut self, statement: PreparedStatement) -> i32 {
+        let handle = self.next_prepared_handle;
+        self.next_prepared_handle = self.next_prepared_handle.wrapping_add(1).max(1);
+        self.prepared_statements.insert(handle, Arc::new(statement));
+        handle
+    }
+
+    fn get_prepared_statement(&self, handle: i32) -> Option<Arc<PreparedStatement>> {
+        self.prepared_statements.get(&handle).cloned()
+    }
+
+    fn remove_prepared_statement(&mut self, handle: i32) -> bool {
+        self.prepared_statements.remove(&handle).is_some()
+    }
+
+    fn get_cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation_token = token;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::starrocks::session::StarRocksSession;
+    use crate::frontend::prot::ServerInstance;
+    use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::LoginMessage;
+    use crate::session::{
+        PreparedStatement, SessionInfo, SessionVariable, SESSION_VARIABLE_SECURITY_IMPERSONATE,
+    };
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn reset_session() {
+        let instance = Arc::new(ServerInstance::new(ServerContext::default()));
+        let mut session =
+            StarRocksSession::new("10.0.0.1:50001".parse().unwrap(), instance, None, None);
+        let mut login = LoginMessage::new();
+        login.db_name = Some("sales".to_string());
+        session.set_login_message(login);
+
+        session.set_schema("finance".to_string());
+        session.set_session_variable(
+            SESSION_VARIABLE_SECURITY_IMPERSONATE.to_string(),
+            SessionVariable::new("user-2"),
+        );
+        let handle = session.add_prepared_statement(PreparedStatement::new("", "SELECT 1"));
+
+        session.reset().await;
+
+        assert_eq!(session.get_schema().as_deref(), Some("sales"));
+        assert!(matches!(
+            session.get_session_variable(SESSION_VARIABLE_SECURITY_IMPERSONATE, false),
+            SessionVariable::None
+        ));
+        assert!(session.get_prepared_statement(handle).is_none());
+        assert_eq!(session.connection_reset_request_count(), 1);
+    }
 }
@@ -3,6 +3,7 @@ use crate::frontend::tds::server_context::ServerContext;
 use crate::frontend::TokenInfo;
 use serde::Serialize;
 use std::sync::Arc;
+use unilake_common::metrics::metrics;
 
 #[derive(Serialize, Clone)]
 pub struct QueryTelemetry {
@@ -134,6 +135,18 @@ impl QueryTelemetryHandler {
             instance.proxy_time =
                 (instance.end_time_utc - instance.start_time_utc) - instance.backend_time;
 
+            let metrics = metrics();
+            metrics
+                .query_proxy_duration
+                .observe(instance.proxy_time as f64 / 1000.0);
+            if instance.start_backend_time_utc > 0 {
+                metrics
+                    .query_backend_duration
+                    .observe(instance.backend_time as f64 / 1000.0);
+            }
+            metrics.rows_streamed.inc_by(instance.records_processed);
+            metrics.bytes_streamed.inc_by(instance.bytes_processed);
+
             // emit telemetry
             if let Err(e) = self
                 .server_instance
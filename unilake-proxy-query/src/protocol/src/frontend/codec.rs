@@ -1,43 +1,58 @@
 use crate::frontend::prot::{ServerInstance, TdsSessionState, TdsWireHandlerFactory};
+use crate::frontend::state::TdsSessionEvent;
+use crate::frontend::tds::server_context::{ServerContext, DEFAULT_PACKET_SIZE};
+use crate::frontend::tds::EncryptionLevel;
+use crate::frontend::tls::TlsPreLoginWrapper;
 use crate::frontend::{
-    PacketHeader, TdsBackendResponse, TdsFrontendRequest, TdsMessage, ALL_HEADERS_LEN_TX,
-    MAX_PACKET_SIZE,
+    PacketHeader, TdsBackendResponse, TdsFrontendRequest, TdsMessage, TokenDone, ALL_HEADERS_LEN_TX,
 };
 use crate::session::SessionInfo;
-use derive_new::new;
-use futures::future::poll_fn;
-use futures::{SinkExt, StreamExt};
+use futures::future::pending;
+use futures::{Sink, SinkExt, StreamExt};
 use std::io::Error as IOError;
 use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
+use tokio::time::sleep;
 use tokio_rustls::TlsAcceptor;
 use tokio_util::bytes::{Buf, BytesMut};
 use tokio_util::codec::{Decoder, Encoder, Framed};
-use unilake_common::error::{TdsWireError, TdsWireResult};
+use tokio_util::sync::CancellationToken;
+use ulid::Ulid;
+use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
 
 #[non_exhaustive]
 #[derive(Debug)]
 pub struct TdsWireMessageServerCodec {
     packet_number: u8,
     current_response: BytesMut,
+    /// Negotiated packet size of the session, can change during the session
     packet_size: Arc<AtomicU16>,
+    /// Packet size of the response being sent, a changed packet size applies from the next
+    /// response onward
+    response_packet_size: usize,
 }
 
 impl TdsWireMessageServerCodec {
-    fn new(packet_size: Arc<AtomicU16>) -> Self {
+    pub fn new(packet_size: Arc<AtomicU16>) -> Self {
+        let response_packet_size = packet_size.load(Ordering::Relaxed) as usize;
         TdsWireMessageServerCodec {
             packet_number: 0,
             current_response: BytesMut::new(),
             packet_size,
+            response_packet_size,
         }
     }
 
     fn flush_response(&mut self, dst: &mut BytesMut, is_done: bool) -> Result<(), TdsWireError> {
-        while self.current_response.has_remaining() {
+        // only full packets are sent until the response is done
+        let max_packet_size = self.max_packet_size();
+        while self.current_response.len() > max_packet_size
+            || (is_done && self.current_response.has_remaining())
+        {
             // get the length (or maximum length of the packet)
-            let len = std::cmp::min(self.max_packet_size(), self.current_response.len());
+            let len = std::cmp::min(max_packet_size, self.current_response.len());
 
             // get the slice for given size
             let slice = &self.current_response.split_to(len);
@@ -45,20 +60,11 @@ impl TdsWireMessageServerCodec {
             // create header
             let mut header = self.get_next_header();
             header.length = (len + ALL_HEADERS_LEN_TX) as u16;
-            header.is_end_of_message = header.length != self.packet_size.load(Ordering::Relaxed);
+            header.is_end_of_message = is_done && !self.current_response.has_remaining();
             header.encode(dst)?;
 
             // get slice for given size
             dst.extend_from_slice(slice);
-
-            if header.is_end_of_message && self.current_response.has_remaining() {
-                panic!("Cannot send more data after sending EOM packet");
-            }
-
-            // wait for more data if we are not done yet
-            if !is_done {
-                break;
-            }
         }
 
         // only clear if we are done
@@ -69,7 +75,7 @@ impl TdsWireMessageServerCodec {
     }
 
     fn get_next_header(&mut self) -> PacketHeader {
-        self.packet_number = self.packet_number.saturating_add(1);
+        self.packet_number = self.packet_number.wrapping_add(1);
         PacketHeader::new(0, self.packet_number)
     }
 
@@ -79,7 +85,7 @@ impl TdsWireMessageServerCodec {
     }
 
     fn max_packet_size(&self) -> usize {
-        self.packet_size.load(Ordering::Relaxed) as usize - ALL_HEADERS_LEN_TX
+        self.response_packet_size - ALL_HEADERS_LEN_TX
     }
 }
 
@@ -88,40 +94,10 @@ impl Decoder for TdsWireMessageServerCodec {
     type Error = TdsWireError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // sanity checks on network level are done here, fully decoding is done afterward
-        if let Some(header) = src.get(..ALL_HEADERS_LEN_TX) {
-            // check if header is correct and as expected
-            let mut buff = BytesMut::from(header);
-            let header = PacketHeader::decode(&mut buff)?;
-            // check header length compared to a defined maximum
-            if header.length as usize > MAX_PACKET_SIZE || src.len() > MAX_PACKET_SIZE {
-                return Err(TdsWireError::Protocol(
-                    "Invalid packet size, too large".to_string(),
-                ));
-            } else if src.len() < (header.length as usize) {
-                // wait for more data
-                return Ok(None);
-            }
-        } else {
-            // wait for more data
-            return Ok(None);
-        }
-
-        // perform decoding
         let result = TdsFrontendRequest::decode(src);
         if let Err(ref e) = result {
             tracing::error!("Error decoding message: {}", e);
         }
-
-        // check if all data has been consumed
-        // todo(mrhamburg), in case of residual bytes close the connection and check protocol if this is expected behaviour
-        if !src.is_empty() {
-            let msg = format!(
-                "Incomplete packet received or processed ({} remaining), closing connection",
-                src.len()
-            );
-            tracing::error!(msg);
-        }
         result
     }
 }
@@ -130,6 +106,10 @@ impl Encoder<TdsBackendResponse> for TdsWireMessageServerCodec {
     type Error = TdsWireError;
 
     fn encode(&mut self, item: TdsBackendResponse, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if self.packet_number == 0 && self.current_response.is_empty() {
+            self.response_packet_size = self.packet_size.load(Ordering::Relaxed) as usize;
+        }
+
         match item {
             TdsBackendResponse::Token(t) => {
                 t.encode(&mut self.current_response)?;
@@ -144,8 +124,8 @@ impl Encoder<TdsBackendResponse> for TdsWireMessageServerCodec {
             }
         }
 
-        // flush when the current response exceeds the session packet size
-        if self.current_response.len() > self.packet_size.load(Ordering::Relaxed) as usize {
+        // flush when the current response exceeds the packet size
+        if self.current_response.len() > self.response_packet_size {
             self.flush_response(dst, false)?;
         }
 
@@ -153,76 +133,208 @@ impl Encoder<TdsBackendResponse> for TdsWireMessageServerCodec {
     }
 }
 
-async fn process_request<T, H, S>(
+/// Process a request, an error closes the session after it has been reported to the client
+async fn process_request<C, H, S>(
     request: TdsFrontendRequest,
-    socket: &mut Framed<T, TdsWireMessageServerCodec>,
+    socket: &mut C,
     session_info: &mut S,
     handlers: Arc<H>,
 ) -> TdsWireResult<()>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + Sync,
+    C: Sink<TdsBackendResponse, Error = TdsWireError> + Unpin + Send,
     S: SessionInfo,
     H: TdsWireHandlerFactory<S>,
 {
-    // todo(mrhamburg): implement state machine
-    let incorrect_state_error = |expected: String| {
-        TdsWireError::Protocol(format!(
-            "Invalid session state, expected {expected} message"
-        ))
-    };
+    match dispatch_request(request, socket, session_info, handlers.clone()).await {
+        Ok(()) => Ok(()),
+        Err(e) => close_with_error(socket, session_info, handlers, e).await,
+    }
+}
 
-    for (_header, message) in request.messages {
-        match session_info.state() {
-            TdsSessionState::Initial => {
-                if let TdsMessage::PreLogin(p) = message {
-                    handlers
-                        .on_prelogin_request(socket, session_info, &p)
-                        .await?;
-                    session_info.set_state(TdsSessionState::PreLoginProcessed);
-                } else {
-                    return Err(incorrect_state_error("PreLogin".to_string()));
-                }
+/// Report a message which could not be decoded to the client, closing the session
+async fn close_with_decode_error<C, H, S>(
+    socket: &mut C,
+    session_info: &S,
+    handlers: Arc<H>,
+    error: TdsWireError,
+) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse, Error = TdsWireError> + Unpin + Send,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    let token = protocol_error_token(session_info, error.to_string());
+    close_with_error(socket, session_info, handlers, TdsWireError::Server(token)).await
+}
+
+async fn dispatch_request<C, H, S>(
+    request: TdsFrontendRequest,
+    socket: &mut C,
+    session_info: &mut S,
+    handlers: Arc<H>,
+) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse, Error = TdsWireError> + Unpin + Send,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    for (header, message) in request.messages {
+        let event = match TdsSessionEvent::from_message(&message) {
+            Some(event) => event,
+            None => return reject_event(session_info, None),
+        };
+        if transition(session_info, event).is_err() {
+            return reject_event(session_info, Some(event));
+        }
+
+        // client-side connection pools reset the session with the first request after reuse,
+        // transactions are not supported so both reset flags are handled the same
+        let is_request = matches!(
+            message,
+            TdsMessage::BatchRequest(_) | TdsMessage::RemoteProcedureCall(_)
+        );
+        if is_request && (header.is_reset_connection || header.is_reset_connection_skip_tran) {
+            handlers.on_reset_connection(socket, session_info).await?;
+        }
+
+        match message {
+            TdsMessage::PreLogin(p) => {
+                handlers
+                    .on_prelogin_request(socket, session_info, &p)
+                    .await?
             }
-            TdsSessionState::PreLoginProcessed => {
-                if let TdsMessage::Login(l) = message {
-                    handlers.on_login7_request(socket, session_info, &l).await?;
-                    session_info.set_state(TdsSessionState::LoggedIn);
-                } else {
-                    return Err(incorrect_state_error("Login".to_string()));
+            TdsMessage::Login(l) => {
+                handlers.on_login7_request(socket, session_info, &l).await?;
+                if event == TdsSessionEvent::Login7 {
+                    transition(session_info, TdsSessionEvent::LoginCompleted)?;
                 }
             }
-            TdsSessionState::SSLNegotiationProcessed => todo!(),
-            TdsSessionState::CompleteLogin7Processed => todo!(),
-            TdsSessionState::Login7SPNEGOProcessed => todo!(),
-            TdsSessionState::Login7FederatedAuthenticationInformationRequestProcessed => todo!(),
-            TdsSessionState::LoggedIn => {
-                if let TdsMessage::BatchRequest(b) = message {
-                    handlers
-                        .on_sql_batch_request(socket, session_info, &b)
-                        .await?;
-                }
-                // todo(mrhamburg): implement error handling for specific message types which we do not expect here
+            TdsMessage::FedAuthToken(t) => {
+                handlers
+                    .on_federated_authentication_token_message(socket, session_info, &t)
+                    .await?;
+                transition(session_info, TdsSessionEvent::LoginCompleted)?;
+            }
+            TdsMessage::BatchRequest(b) => {
+                handlers
+                    .on_sql_batch_request(socket, session_info, &b)
+                    .await?;
+                transition(session_info, TdsSessionEvent::RequestCompleted)?;
+            }
+            TdsMessage::RemoteProcedureCall(r) => {
+                handlers.on_rpc_request(socket, session_info, &r).await?;
+                transition(session_info, TdsSessionEvent::RequestCompleted)?;
             }
-            TdsSessionState::RequestReceived => todo!(),
-            TdsSessionState::AttentionReceived => todo!(),
-            TdsSessionState::ReConnect => todo!(),
-            TdsSessionState::LogoutProcessed => todo!(),
-            TdsSessionState::Final => todo!(),
+            TdsMessage::Attention(_) => {
+                handlers.on_attention(socket, session_info).await?;
+                transition(session_info, TdsSessionEvent::AttentionAcknowledged)?;
+            }
+            // never sent by a client, rejected above
+            TdsMessage::Response(_) | TdsMessage::FedAuth(_) => {}
         }
     }
 
-    // todo(mrhamburg): improve this section
     handlers.flush(socket).await?;
-    let result = socket.flush().await;
-    if result.is_err() {
-        panic!("Error flushing socket: {}", result.unwrap_err());
+    socket.flush().await
+}
+
+/// Move the session to the next state, fails if the event is not allowed in the current state
+fn transition<S: SessionInfo>(session_info: &mut S, event: TdsSessionEvent) -> TdsWireResult<()> {
+    let state = *session_info.state();
+    match state.next(event) {
+        Some(next) => {
+            session_info.set_state(next);
+            Ok(())
+        }
+        None => Err(TdsWireError::Protocol(format!(
+            "Unexpected {:?} in session state {:?}",
+            event, state
+        ))),
     }
-    Ok(())
+}
+
+/// Reject a message which is not allowed in the current session state, the event is none for
+/// messages which are never sent by a client
+fn reject_event<S: SessionInfo>(
+    session_info: &S,
+    event: Option<TdsSessionEvent>,
+) -> TdsWireResult<()> {
+    let message_type = match event {
+        Some(event) => format!("{:?}", event),
+        None => "server".to_string(),
+    };
+    tracing::error!(
+        "Unexpected {} message in session state {:?}, closing connection",
+        message_type,
+        session_info.state()
+    );
+    Err(TdsWireError::Server(protocol_error_token(
+        session_info,
+        format!("Unexpected {} message.", message_type),
+    )))
+}
+
+fn protocol_error_token<S: SessionInfo>(session_info: &S, message: String) -> TokenError {
+    TokenError::new(
+        4002,
+        1,
+        20,
+        format!(
+            "The incoming tabular data stream (TDS) protocol stream is incorrect. {}",
+            message
+        ),
+        session_info.tds_server_context().server_name.clone(),
+        "".to_string(),
+        0,
+    )
+}
+
+/// Report the error which closes the session to the client. Server errors are sent as-is, any
+/// other error is reported as a fatal error. Always returns the original error.
+async fn close_with_error<C, H, S>(
+    socket: &mut C,
+    session_info: &S,
+    handlers: Arc<H>,
+    error: TdsWireError,
+) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse, Error = TdsWireError> + Unpin + Send,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    // a client which did not complete the pre-login cannot process a token stream
+    if *session_info.state() == TdsSessionState::Initial {
+        return Err(error);
+    }
+
+    let token = match error {
+        TdsWireError::Server(ref token) => token.clone(),
+        ref e => TokenError::new(
+            0,
+            1,
+            20,
+            e.to_string(),
+            session_info.tds_server_context().server_name.clone(),
+            "".to_string(),
+            0,
+        ),
+    };
+    let result = async {
+        handlers.send_token(socket, token).await?;
+        handlers.send_token(socket, TokenDone::new_error(0)).await?;
+        handlers.flush(socket).await?;
+        socket.flush().await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::debug!("Failed to send error to client: {}", e);
+    }
+    Err(error)
 }
 
 pub async fn process_socket<H, S>(
     tcp_socket: TcpStream,
-    _tls_acceptor: Option<Arc<TlsAcceptor>>,
+    tls_acceptor: Option<Arc<TlsAcceptor>>,
     handler: Arc<H>,
     instance: Arc<ServerInstance>,
 ) -> Result<(), IOError>
@@ -233,102 +345,442 @@ where
     let addr = tcp_socket.peer_addr()?;
     tcp_socket.set_nodelay(true)?;
 
-    let session_info = handler.open_session(&addr, instance.clone()).await;
-
+    // sessions are admitted before any message is processed, so excess connections are
+    // rejected before they can hold on to resources
+    let session_info = handler
+        .open_session(&addr, instance.clone())
+        .await
+        .and_then(|s| instance.admit_session(s.session_id(), addr).map(|_| s));
     let mut session_info = match session_info {
         Ok(s) => {
             instance.increment_session_counter();
             s
         }
-        Err(_) => {
-            // process_error(&mut socket, e).await?;
+        Err(e) => {
+            tracing::warn!("Rejected connection from {}: {}", addr, e);
+            reject_connection(tcp_socket, &instance, e).await;
             return Ok(());
         }
     };
 
-    let tcp_socket = Framed::new(
+    let session_id = session_info.session_id();
+    let result = tokio::select! {
+        result = process_connection(
+            tcp_socket,
+            tls_acceptor,
+            &mut session_info,
+            handler.clone(),
+            &instance,
+        ) => result,
+        _ = login_timeout_expired(&instance, session_id) => {
+            Err(TdsWireError::Protocol(format!(
+                "Login timeout expired for session {}",
+                session_id
+            )))
+        }
+    };
+    if let Err(e) = result {
+        tracing::error!("Error processing connection: {}", e);
+    }
+
+    // remove session
+    // closing is allowed in any state
+    let _ = transition(&mut session_info, TdsSessionEvent::ConnectionClosed);
+    handler.close_session(&mut session_info).await;
+    instance.unregister_session(session_info.session_id());
+    instance.decrement_session_counter();
+
+    Ok(())
+}
+
+/// Report why the connection is not admitted to the client, before the socket is closed
+async fn reject_connection(tcp_socket: TcpStream, instance: &ServerInstance, error: TdsWireError) {
+    let token = match error {
+        TdsWireError::Server(token) => token,
+        e => TokenError::new(
+            0,
+            1,
+            20,
+            e.to_string(),
+            instance.ctx.server_name.clone(),
+            "".to_string(),
+            0,
+        ),
+    };
+    let mut socket = Framed::new(
         tcp_socket,
+        TdsWireMessageServerCodec::new(Arc::new(AtomicU16::new(DEFAULT_PACKET_SIZE))),
+    );
+    let result = async {
+        socket.feed(TdsBackendResponse::Token(token.into())).await?;
+        socket
+            .feed(TdsBackendResponse::Token(TokenDone::new_error(0).into()))
+            .await?;
+        socket.send(TdsBackendResponse::Done).await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::debug!("Failed to send error to client: {}", e);
+    }
+}
+
+/// Completes once the login timeout passes before the session has logged in, never completes
+/// for sessions which logged in in time
+async fn login_timeout_expired(instance: &ServerInstance, session_id: Ulid) {
+    let login_timeout = instance.login_timeout();
+    if !login_timeout.is_zero() {
+        sleep(login_timeout).await;
+        let logged_in = instance
+            .get_active_session(session_id)
+            .is_some_and(|s| s.is_logged_in());
+        if !logged_in {
+            return;
+        }
+    }
+    pending().await
+}
+
+async fn process_connection<T, H, S>(
+    socket: T,
+    tls_acceptor: Option<Arc<TlsAcceptor>>,
+    session_info: &mut S,
+    handler: Arc<H>,
+    instance: &ServerInstance,
+) -> TdsWireResult<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + Sync,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    let mut socket = Framed::new(
+        socket,
         TdsWireMessageServerCodec::new(session_info.packet_size()),
     );
-    // let ssl = peek_for_sslrequest(&mut tcp_socket, tls_acceptor.is_some()).await?;
-
-    let ssl = false; // todo: implement ssl handshake and check for ssl request
-    if !ssl {
-        let mut socket = tcp_socket;
-
-        while let Some(packet) = socket.next().await {
-            match packet {
-                Ok(msg) => {
-                    if let Err(e) =
-                        process_request(msg, &mut socket, &mut session_info, handler.clone()).await
-                    {
-                        tracing::error!("Error processing request: {}", e);
-                        // todo(mrhamburg): error handling + close session on error
-                        // process_error(&mut socket, e).await?;
-                        todo!()
+
+    // the PRELOGIN exchange is never encrypted, it determines if a TLS handshake follows
+    let encryption = match process_prelogin(&mut socket, session_info, handler.clone()).await? {
+        Some(encryption) => encryption,
+        None => return Ok(()),
+    };
+
+    if !ServerContext::requires_tls_handshake(encryption) {
+        return process_messages(socket, session_info, handler, instance).await;
+    }
+
+    let tls_acceptor = tls_acceptor.ok_or_else(|| {
+        TdsWireError::Tls("Encryption negotiated, but no TLS certificate configured".to_string())
+    })?;
+
+    // during the handshake TLS records are wrapped in PRELOGIN packets
+    let packet_size = session_info.packet_size();
+    let wrapper = TlsPreLoginWrapper::new(
+        socket.into_inner(),
+        packet_size.load(Ordering::Relaxed) as usize,
+    );
+    let mut tls_socket = tls_acceptor
+        .accept(wrapper)
+        .await
+        .map_err(|e| TdsWireError::Tls(format!("TLS handshake failed: {}", e)))?;
+    tls_socket.get_mut().0.handshake_complete();
+    transition(session_info, TdsSessionEvent::TlsHandshakeCompleted)?;
+
+    let mut tls_socket = Framed::new(
+        tls_socket,
+        TdsWireMessageServerCodec::new(packet_size.clone()),
+    );
+    if encryption != EncryptionLevel::Off {
+        return process_messages(tls_socket, session_info, handler, instance).await;
+    }
+
+    // login-only encryption, only the LOGIN7 message is encrypted, everything else is not
+    let login = match tls_socket.next().await {
+        Some(Ok(login)) => login,
+        Some(Err(e)) => {
+            return close_with_decode_error(&mut tls_socket, session_info, handler, e).await
+        }
+        None => return Ok(()),
+    };
+    let (socket, _) = tls_socket.into_inner().into_inner();
+    let mut socket = Framed::new(
+        socket.into_inner(),
+        TdsWireMessageServerCodec::new(packet_size),
+    );
+    process_request(login, &mut socket, session_info, handler.clone()).await?;
+    process_messages(socket, session_info, handler, instance).await
+}
+
+/// Process the PRELOGIN message and return the negotiated encryption level, none if the
+/// connection was closed
+async fn process_prelogin<T, H, S>(
+    socket: &mut Framed<T, TdsWireMessageServerCodec>,
+    session_info: &mut S,
+    handler: Arc<H>,
+) -> TdsWireResult<Option<EncryptionLevel>>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + Sync,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    let request = match socket.next().await {
+        Some(Ok(request)) => request,
+        Some(Err(e)) => {
+            close_with_decode_error(socket, session_info, handler, e).await?;
+            return Ok(None);
+        }
+        None => return Ok(None),
+    };
+
+    let client_encryption = request.messages.iter().find_map(|(_, m)| match m {
+        TdsMessage::PreLogin(p) => p.encryption,
+        _ => None,
+    });
+    process_request(request, socket, session_info, handler).await?;
+
+    Ok(Some(ServerContext::encryption_response(
+        session_info.tds_server_context().as_ref(),
+        client_encryption,
+    )))
+}
+
+/// Process requests until the connection is closed. On shutdown, the session is closed once the
+/// running request completes, or is cancelled when the shutdown deadline passes.
+async fn process_messages<T, H, S>(
+    socket: Framed<T, TdsWireMessageServerCodec>,
+    session_info: &mut S,
+    handler: Arc<H>,
+    instance: &ServerInstance,
+) -> TdsWireResult<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + Sync,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    let shutdown = instance.shutdown_token();
+    let shutdown_deadline = instance.shutdown_deadline_token();
+
+    // split the socket, so an attention can be received while a request is being processed
+    let (mut sink, mut stream) = socket.split();
+    // a single packet is read ahead while a request runs, the stream is no longer polled once
+    // it holds a packet so a pipelining client cannot grow it
+    let mut pending = None;
+    loop {
+        if shutdown.is_cancelled() {
+            return close_for_shutdown(&mut sink, session_info, handler).await;
+        }
+        let packet = match pending.take() {
+            Some(packet) => packet,
+            None => tokio::select! {
+                packet = stream.next() => match packet {
+                    Some(packet) => packet,
+                    None => break,
+                },
+                _ = shutdown.cancelled() => continue,
+            },
+        };
+
+        match packet {
+            Ok(msg) if is_attention(&msg) => {
+                // no request is running, only acknowledge the attention
+                process_request(msg, &mut sink, session_info, handler.clone()).await?;
+            }
+            Ok(msg) => {
+                let cancellation_token = CancellationToken::new();
+                session_info.set_cancellation_token(cancellation_token.clone());
+
+                let mut attention = false;
+                let mut closed = false;
+                let mut deadline_passed = false;
+                let result = {
+                    let request = process_request(msg, &mut sink, session_info, handler.clone());
+                    tokio::pin!(request);
+                    loop {
+                        tokio::select! {
+                            result = &mut request => break result,
+                            packet = stream.next(),
+                                if !attention && !closed && pending.is_none() => match packet {
+                                Some(Ok(msg)) if is_attention(&msg) => {
+                                    tracing::debug!("Attention received, cancelling request");
+                                    attention = true;
+                                    cancellation_token.cancel();
+                                }
+                                Some(packet) => pending = Some(packet),
+                                None => {
+                                    closed = true;
+                                    cancellation_token.cancel();
+                                }
+                            },
+                            _ = shutdown_deadline.cancelled(), if !deadline_passed => {
+                                tracing::info!("Shutdown deadline passed, cancelling request");
+                                deadline_passed = true;
+                                cancellation_token.cancel();
+                            }
+                        }
                     }
+                };
+
+                // an error closes the session, it has already been reported to the client
+                result?;
+
+                if attention {
+                    transition(session_info, TdsSessionEvent::Attention)?;
+                    handler.on_attention(&mut sink, session_info).await?;
+                    transition(session_info, TdsSessionEvent::AttentionAcknowledged)?;
+                    handler.flush(&mut sink).await?;
                 }
-                Err(e) => {
-                    tracing::error!("Error reading packet: {}", e);
-                    // todo(mrhamburg): error handling + close session on error
-                    // session_info.close_session().await?;
-                    socket.close().await?;
+                if closed {
+                    break;
                 }
             }
+            Err(e) => {
+                return close_with_decode_error(&mut sink, session_info, handler, e).await;
+            }
         }
-
-        // remove session
-        handler.close_session(&mut session_info).await;
-        instance.decrement_session_counter();
     }
-
     Ok(())
 }
 
-#[non_exhaustive]
-#[derive(PartialEq, Eq, Debug, new)]
-pub struct SslRequest;
+/// Report the server shutdown to the client and close the session
+async fn close_for_shutdown<C, H, S>(
+    socket: &mut C,
+    session_info: &S,
+    handlers: Arc<H>,
+) -> TdsWireResult<()>
+where
+    C: Sink<TdsBackendResponse, Error = TdsWireError> + Unpin + Send,
+    S: SessionInfo,
+    H: TdsWireHandlerFactory<S>,
+{
+    tracing::info!(
+        "Closing session {} for server shutdown",
+        session_info.session_id()
+    );
+    let token = TokenError::new(
+        6005,
+        1,
+        14,
+        "SHUTDOWN is in progress.".to_string(),
+        session_info.tds_server_context().server_name.clone(),
+        "".to_string(),
+        0,
+    );
+    // the error is reported to the client, the session closes without an error
+    let _ = close_with_error(socket, session_info, handlers, TdsWireError::Server(token)).await;
+    Ok(())
+}
 
-impl SslRequest {
-    pub const BODY_MAGIC_NUMBER: i32 = -1;
-    pub const BODY_SIZE: usize = 8;
+fn is_attention(request: &TdsFrontendRequest) -> bool {
+    request
+        .messages
+        .iter()
+        .any(|(_, message)| matches!(message, TdsMessage::Attention(_)))
 }
 
-// async fn peek_for_sslrequest<S>(
-//     socket: &mut Framed<TcpStream, TdsWireMessageServerCodec>,
-//     ssl_supported: bool,
-// ) -> Result<bool, IOError>
-// where
-//     S: SessionInfo,
-// {
-//     let ssl = false;
-//     if is_sslrequest_pending(socket.get_ref()).await? {
-//         // consume request
-//         socket.next().await;
-//
-//         let _response = if ssl_supported {
-//             // ssl = true;
-//             // TdsWireBackendMessage::SslResponse(SslResponse::Accept)
-//             todo!()
-//         } else {
-//             // TdsWireBackendMessage::SslResponse(SslResponse::Refuse)
-//             todo!()
-//         };
-//         // socket.send(response).await?;
-//     }
-//     Ok(ssl)
-// }
-
-// async fn is_sslrequest_pending(tcp_socket: &TcpStream) -> Result<bool, IOError> {
-//     let mut buf = [0u8; SslRequest::BODY_SIZE];
-//     let mut buf = ReadBuf::new(&mut buf);
-//     while buf.filled().len() < SslRequest::BODY_SIZE {
-//         if poll_fn(|cx| tcp_socket.poll_peek(cx, &mut buf)).await? == 0 {
-//             // the tcp_stream has ended
-//             return Ok(false);
-//         }
-//     }
-//
-//     let _buf = BytesMut::from(buf.filled());
-//     Ok(false)
-// }
+#[cfg(test)]
+mod tests {
+    use crate::auth::LocalAuthenticator;
+    use crate::backend::starrocks::{FileClusterResolver, StarRocksTdsHandlerFactory};
+    use crate::frontend::codec::{process_socket, TdsWireMessageServerCodec};
+    use crate::frontend::prot::ServerInstance;
+    use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::{
+        PacketHeader, TdsBackendResponse, TdsToken, TdsTokenCodec, TdsTokenType, TokenInfo,
+        ALL_HEADERS_LEN_TX,
+    };
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::bytes::{Buf, BytesMut};
+    use tokio_util::codec::Encoder;
+    use unilake_common::error::TokenError;
+
+    /// Encode the tokens as a single response, returns the headers of the packets sent
+    fn encode_response(codec: &mut TdsWireMessageServerCodec, tokens: usize) -> Vec<PacketHeader> {
+        let ctx = ServerContext::default();
+        let mut dst = BytesMut::new();
+        for _ in 0..tokens {
+            let token = TokenInfo::new(&ctx, 0, 0, 0, "x".repeat(200));
+            codec
+                .encode(TdsBackendResponse::Token(token.into()), &mut dst)
+                .unwrap();
+        }
+        codec.encode(TdsBackendResponse::Done, &mut dst).unwrap();
+
+        let mut headers = Vec::new();
+        while !dst.is_empty() {
+            let header = PacketHeader::decode(&mut dst.clone()).unwrap();
+            let _ = dst.split_to(header.length as usize);
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn split_response_into_packets() {
+        let packet_size = Arc::new(AtomicU16::new(512));
+        let mut codec = TdsWireMessageServerCodec::new(packet_size.clone());
+
+        let headers = encode_response(&mut codec, 10);
+        assert!(headers.len() > 1);
+        for (i, header) in headers.iter().enumerate() {
+            let is_last = i == headers.len() - 1;
+            assert_eq!(header.is_end_of_message, is_last);
+            assert!(header.length as usize <= 512);
+            if !is_last {
+                assert_eq!(header.length, 512);
+            }
+            assert!(header.length as usize > ALL_HEADERS_LEN_TX);
+        }
+
+        // a changed packet size applies to the next response
+        packet_size.store(8000, Ordering::Relaxed);
+        let headers = encode_response(&mut codec, 10);
+        assert_eq!(headers.len(), 1);
+        assert!(headers[0].is_end_of_message);
+    }
+
+    #[tokio::test]
+    async fn reject_connection_over_limit() {
+        let instance = Arc::new(ServerInstance::new(
+            ServerContext::default().with_session_limits(0, 0, 1),
+        ));
+        instance.register_session(
+            instance.next_session_id(),
+            "127.0.0.1:50000".parse().unwrap(),
+        );
+        let handler = Arc::new(StarRocksTdsHandlerFactory::new(
+            instance.clone(),
+            Arc::new(LocalAuthenticator::new(vec![]).unwrap()),
+            Arc::new(FileClusterResolver::new(vec![]).unwrap()),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        process_socket(socket, None, handler, instance.clone())
+            .await
+            .unwrap();
+
+        // the login error is sent before the connection is closed
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let mut buff = BytesMut::from(&received[..]);
+        let header = PacketHeader::decode(&mut buff).unwrap();
+        assert!(header.is_end_of_message);
+        assert_eq!(buff.get_u8(), TdsTokenType::Error as u8);
+        match TokenError::decode(&mut buff).unwrap() {
+            TdsToken::Error(token) => {
+                assert_eq!(token.code, 17809);
+                assert!(token.message.contains("from address '127.0.0.1'"));
+            }
+            _ => panic!("unexpected token"),
+        }
+        assert_eq!(buff.get_u8(), TdsTokenType::Done as u8);
+
+        // the rejected session has not been registered
+        assert_eq!(instance.get_active_sessions().len(), 1);
+        assert_eq!(instance.active_session_count(), 0);
+    }
+}
@@ -7,7 +7,10 @@ mod macros;
 
 pub mod codec;
 pub mod prot;
+pub mod rpc;
+pub mod state;
 pub mod tds;
+pub mod tls;
 pub mod utils;
 
 pub(crate) fn get_driver_version() -> u64 {
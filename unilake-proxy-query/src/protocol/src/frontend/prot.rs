@@ -1,28 +1,35 @@
+use crate::audit::{AuditEvent, AuditPipeline};
 use crate::backend::data::BackendHandler;
 use crate::backend::telemetry::QueryTelemetry;
 use crate::frontend::{
-    tds::server_context::ServerContext, BatchRequest, LoginMessage, PreloginMessage,
-    TdsBackendResponse, TdsMessage, TdsToken,
+    tds::server_context::ServerContext, BatchRequest, FedAuthTokenMessage, LoginMessage,
+    PreloginMessage, RpcRequest, TdsBackendResponse, TdsMessage, TdsToken,
 };
 use crate::session::SessionInfo;
 use async_trait::async_trait;
 use casbin::{Adapter, DefaultModel};
 use futures::{Sink, SinkExt};
 use std::{
+    collections::HashMap,
     net::SocketAddr,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicU16, AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
     },
     time::Duration,
 };
-use tokio::{sync::Semaphore, time::sleep};
+use tokio::{
+    sync::Semaphore,
+    time::{sleep, Instant},
+};
+use tokio_util::sync::CancellationToken;
 use ulid::Ulid;
-use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::error::{TdsWireError, TdsWireResult, TokenError};
+use unilake_common::metrics::metrics;
 use unilake_security::handler::SecurityHandler;
 use unilake_security::ABAC_MODEL;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum TdsSessionState {
     #[default]
     /// Initial State
@@ -89,7 +96,14 @@ where
     /// Called when federated authentication token message arrives. Called only when
     /// such a message arrives in response to federated authentication info, not when the
     /// token is part of a login request.
-    fn on_federated_authentication_token_message(&self, session: &S);
+    async fn on_federated_authentication_token_message<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut S,
+        msg: &FedAuthTokenMessage,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send;
 
     /// Called when SQL batch request arrives
     async fn on_sql_batch_request<C>(
@@ -101,8 +115,32 @@ where
     where
         C: Sink<TdsBackendResponse> + Unpin + Send;
 
-    /// Called when attention arrives
-    fn on_attention(&self, session: &S);
+    /// Called when remote procedure call request arrives
+    async fn on_rpc_request<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut S,
+        msg: &RpcRequest,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send;
+
+    /// Called when attention arrives, after the running request (if any) has been cancelled.
+    /// The attention must be acknowledged with a DONE token with the attention bit set.
+    async fn on_attention<C>(&self, client: &mut C, session_info: &mut S) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send;
+
+    /// Called when a pooled connection is reused, before the request carrying the reset is
+    /// handled. The session is restored to its state right after login and the reset is
+    /// acknowledged with a RESETCONNECTION env change.
+    async fn on_reset_connection<C>(
+        &self,
+        client: &mut C,
+        session_info: &mut S,
+    ) -> TdsWireResult<()>
+    where
+        C: Sink<TdsBackendResponse> + Unpin + Send;
 
     /// Send message to the client
     async fn send_message<C, M>(&self, client: &mut C, msg: M) -> Result<(), TdsWireError>
@@ -168,6 +206,21 @@ pub struct SessionUserInfo {
 }
 
 impl SessionUserInfo {
+    pub fn new(socket_addr: SocketAddr, userid: String) -> Self {
+        SessionUserInfo {
+            socket_addr,
+            userid,
+        }
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.userid
+    }
+
     pub fn from(info: &dyn SessionInfo) -> Self {
         SessionUserInfo {
             socket_addr: info.socket_addr(),
@@ -185,6 +238,32 @@ impl From<&dyn SessionInfo> for SessionUserInfo {
     }
 }
 
+/// Session connected to the server instance, as listed by sp_who
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveSession {
+    pub session_id: Ulid,
+    /// Server process id, only used to identify the session towards clients
+    pub spid: u16,
+    pub socket_addr: SocketAddr,
+    pub hostname: String,
+    pub user: String,
+    pub database: String,
+}
+
+impl ActiveSession {
+    /// The user is only known once the session has logged in
+    pub fn is_logged_in(&self) -> bool {
+        !self.user.is_empty()
+    }
+}
+
+/// First server process id handed out, lower ids are reserved for system processes
+const FIRST_USER_SPID: u16 = 51;
+/// Maximum number of server messages processed in parallel
+const MAX_MESSAGE_WORKERS: usize = 4;
+/// Time sessions get to close once their running requests have been cancelled on shutdown
+const SHUTDOWN_CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 pub struct ServerInstance {
     pub ctx: Arc<ServerContext>,
     pub backend_handler: Arc<BackendHandler>,
@@ -196,12 +275,22 @@ pub struct InnerServerInstance {
     receiver: Option<tokio::sync::mpsc::UnboundedReceiver<ServerInstanceMessage>>,
     sender: Arc<tokio::sync::mpsc::UnboundedSender<ServerInstanceMessage>>,
     active_sessions: AtomicUsize,
+    sessions: RwLock<HashMap<Ulid, ActiveSession>>,
+    next_spid: AtomicU16,
     semaphore: Arc<Semaphore>,
+    audit: Option<AuditPipeline>,
+    /// Cancelled when the shutdown starts, sessions close once their running request completes
+    shutdown: CancellationToken,
+    /// Cancelled when the shutdown deadline has passed, running requests are cancelled
+    shutdown_deadline: CancellationToken,
+    /// Cancelled when all sessions are closed, the background jobs flush and stop
+    stopped: CancellationToken,
+    /// Audit events not written by this deadline are spooled, set once the shutdown started
+    audit_deadline: OnceLock<Instant>,
 }
 
 // todo: we might as well put serverinstance in own folder and handle all of this there (coordination of actions for example)
 impl ServerInstance {
-    // todo(mrhamburg): implement logic for processing messages to retraced api or kafka for audit logging and other informational purposes
     pub fn new(ctx: ServerContext) -> Self {
         let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<ServerInstanceMessage>();
         ServerInstance {
@@ -211,7 +300,14 @@ impl ServerInstance {
                 receiver: Some(receiver),
                 sender: Arc::new(sender),
                 active_sessions: AtomicUsize::new(0),
-                semaphore: Arc::new(Semaphore::new(4)),
+                sessions: RwLock::new(HashMap::new()),
+                next_spid: AtomicU16::new(FIRST_USER_SPID),
+                semaphore: Arc::new(Semaphore::new(MAX_MESSAGE_WORKERS)),
+                audit: None,
+                shutdown: CancellationToken::new(),
+                shutdown_deadline: CancellationToken::new(),
+                stopped: CancellationToken::new(),
+                audit_deadline: OnceLock::new(),
             },
             default_model: None,
         }
@@ -227,14 +323,28 @@ impl ServerInstance {
         self.default_model.clone()
     }
 
-    async fn inner_process_message(&self, _msg: ServerInstanceMessage) {
-        tracing::error!(message = "Received server instance message, processing has not been implemented, dropping message!".to_string());
+    /// Ship audit events and telemetry to the given pipeline, messages are dropped otherwise
+    pub fn with_audit_pipeline(mut self, pipeline: AuditPipeline) -> Self {
+        self.inner.audit = Some(pipeline);
+        self
+    }
+
+    async fn inner_process_message(&self, msg: ServerInstanceMessage) {
+        let Some(audit) = &self.inner.audit else {
+            tracing::trace!("No audit sinks configured, dropping server instance message");
+            return;
+        };
+        if let Some(event) = AuditEvent::from_message(msg, &self.ctx.server_name) {
+            audit.submit(event);
+        }
     }
 
     /// Starts the background job server instance for processing server messages.
     /// Currently, is set to max 4 messages being processed in parallel.
     /// In case 4 messages are already being processed, the process will check every 10 milliseconds for
     /// an open slot to process new messages.
+    /// The background job stops after [`ServerInstance::shutdown`], once all queued messages have
+    /// been processed and the audit pipeline has been flushed.
     /// Note: the server instance can only be started once, will panic in case the background process has
     /// already been started
     pub async fn start_instance(mut self) -> (Arc<Self>, tokio::task::JoinHandle<()>) {
@@ -250,7 +360,14 @@ impl ServerInstance {
             instance: Arc<ServerInstance>,
             mut receiver: tokio::sync::mpsc::UnboundedReceiver<ServerInstanceMessage>,
         ) {
-            while let Some(msg) = receiver.recv().await {
+            loop {
+                let msg = tokio::select! {
+                    msg = receiver.recv() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    _ = instance.inner.stopped.cancelled() => break,
+                };
                 let instance = instance.clone();
                 let semaphore = instance.inner.semaphore.clone();
                 while semaphore.available_permits() == 0 {
@@ -262,6 +379,27 @@ impl ServerInstance {
                     drop(semaphore);
                 });
             }
+
+            // flush the queued messages and wait for the running workers
+            receiver.close();
+            while let Some(msg) = receiver.recv().await {
+                instance.inner_process_message(msg).await;
+            }
+            let _ = instance
+                .inner
+                .semaphore
+                .acquire_many(MAX_MESSAGE_WORKERS as u32)
+                .await;
+            if let Some(audit) = &instance.inner.audit {
+                let deadline = instance
+                    .inner
+                    .audit_deadline
+                    .get()
+                    .copied()
+                    .unwrap_or_else(|| Instant::now() + SHUTDOWN_CANCEL_GRACE_PERIOD);
+                audit.shutdown(deadline).await;
+            }
+            tracing::info!("Server instance background jobs stopped");
         }
 
         if self.inner.receiver.is_none() {
@@ -278,6 +416,64 @@ impl ServerInstance {
         )
     }
 
+    /// Gracefully shuts down the server instance. Sessions are closed once their running request
+    /// completes, requests still running after the timeout are cancelled. Afterwards the
+    /// background jobs flush the queued messages and stop.
+    pub async fn shutdown(&self, timeout: Duration) {
+        tracing::info!(
+            "Shutting down server instance, waiting up to {:?} for {} sessions",
+            timeout,
+            self.active_session_count()
+        );
+        let deadline = Instant::now() + timeout;
+        self.inner.shutdown.cancel();
+        self.backend_handler.stop_sse_consumer();
+
+        if !self.wait_for_sessions(timeout).await {
+            tracing::warn!(
+                "Shutdown deadline passed, cancelling running requests of {} sessions",
+                self.active_session_count()
+            );
+            self.inner.shutdown_deadline.cancel();
+            if !self.wait_for_sessions(SHUTDOWN_CANCEL_GRACE_PERIOD).await {
+                tracing::warn!(
+                    "Stopping with {} sessions still open",
+                    self.active_session_count()
+                );
+            }
+        }
+
+        // the audit events are flushed until the deadline, or the grace period once it passed
+        let now = Instant::now();
+        let _ = self.inner.audit_deadline.set(if deadline > now {
+            deadline
+        } else {
+            now + SHUTDOWN_CANCEL_GRACE_PERIOD
+        });
+        self.inner.stopped.cancel();
+    }
+
+    /// Waits until all sessions are closed, returns false on timeout
+    async fn wait_for_sessions(&self, timeout: Duration) -> bool {
+        tokio::time::timeout(timeout, async {
+            while self.active_session_count() > 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Cancelled once the server instance is shutting down
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.inner.shutdown.clone()
+    }
+
+    /// Cancelled once the shutdown deadline has passed and running requests must be cancelled
+    pub fn shutdown_deadline_token(&self) -> CancellationToken {
+        self.inner.shutdown_deadline.clone()
+    }
+
     pub fn active_session_count(&self) -> usize {
         self.inner.active_sessions.load(Ordering::Relaxed)
     }
@@ -285,6 +481,7 @@ impl ServerInstance {
     pub fn increment_session_counter(&self) -> usize {
         self.inner.active_sessions.fetch_add(1, Ordering::Relaxed);
         let count = self.active_session_count();
+        metrics().active_sessions.set(count as i64);
         tracing::info!(
             message = "Increased session count",
             current_count = count,
@@ -296,6 +493,7 @@ impl ServerInstance {
     pub fn decrement_session_counter(&self) -> usize {
         self.inner.active_sessions.fetch_sub(1, Ordering::Relaxed);
         let count = self.active_session_count();
+        metrics().active_sessions.set(count as i64);
         tracing::info!(
             message = "Decreased session count",
             current_count = count,
@@ -304,10 +502,175 @@ impl ServerInstance {
         count
     }
 
+    /// Adds the session to the active sessions, returns the server process id assigned to it
+    pub fn register_session(&self, session_id: Ulid, socket_addr: SocketAddr) -> u16 {
+        let mut sessions = self.inner.sessions.write().unwrap();
+        self.insert_session(&mut sessions, session_id, socket_addr)
+    }
+
+    /// Adds the session to the active sessions, unless the limit of sessions which have not
+    /// logged in yet or the limit of the client address has been reached. Returns the server
+    /// process id assigned to the session.
+    pub fn admit_session(&self, session_id: Ulid, socket_addr: SocketAddr) -> TdsWireResult<u16> {
+        let mut sessions = self.inner.sessions.write().unwrap();
+        let reached = |limit: usize, count: usize| limit > 0 && count >= limit;
+
+        if reached(
+            self.ctx.pending_session_limit,
+            sessions.values().filter(|s| !s.is_logged_in()).count(),
+        ) {
+            return Err(self.not_admitted(
+                session_id,
+                format!(
+                    "Could not connect because the maximum number of '{}' pending logins has already been reached.",
+                    self.ctx.pending_session_limit
+                ),
+            ));
+        }
+        if reached(
+            self.ctx.session_limit_per_address,
+            sessions
+                .values()
+                .filter(|s| s.socket_addr.ip() == socket_addr.ip())
+                .count(),
+        ) {
+            return Err(self.not_admitted(
+                session_id,
+                format!(
+                    "Could not connect because the maximum number of '{}' connections from address '{}' has already been reached.",
+                    self.ctx.session_limit_per_address,
+                    socket_addr.ip()
+                ),
+            ));
+        }
+        Ok(self.insert_session(&mut sessions, session_id, socket_addr))
+    }
+
+    fn insert_session(
+        &self,
+        sessions: &mut HashMap<Ulid, ActiveSession>,
+        session_id: Ulid,
+        socket_addr: SocketAddr,
+    ) -> u16 {
+        let spid = loop {
+            let spid = self.inner.next_spid.fetch_add(1, Ordering::Relaxed);
+            if spid < FIRST_USER_SPID {
+                // wrapped around, start over at the first user process id
+                self.inner
+                    .next_spid
+                    .store(FIRST_USER_SPID, Ordering::Relaxed);
+                continue;
+            }
+            if !sessions.values().any(|s| s.spid == spid) {
+                break spid;
+            }
+        };
+        sessions.insert(
+            session_id,
+            ActiveSession {
+                session_id,
+                spid,
+                socket_addr,
+                hostname: socket_addr.ip().to_string(),
+                user: String::new(),
+                database: String::new(),
+            },
+        );
+        spid
+    }
+
+    /// Updates the details of an active session, unknown sessions are ignored
+    pub fn update_session(&self, session_id: Ulid, update: impl FnOnce(&mut ActiveSession)) {
+        if let Some(session) = self.inner.sessions.write().unwrap().get_mut(&session_id) {
+            update(session);
+        }
+    }
+
+    pub fn unregister_session(&self, session_id: Ulid) {
+        self.inner.sessions.write().unwrap().remove(&session_id);
+    }
+
+    pub fn get_active_session(&self, session_id: Ulid) -> Option<ActiveSession> {
+        self.inner
+            .sessions
+            .read()
+            .unwrap()
+            .get(&session_id)
+            .cloned()
+    }
+
+    /// All active sessions, ordered by server process id
+    pub fn get_active_sessions(&self) -> Vec<ActiveSession> {
+        let mut sessions = self
+            .inner
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|s| s.spid);
+        sessions
+    }
+
     pub fn session_limit(&self) -> usize {
         self.ctx.session_limit
     }
 
+    /// Checks the concurrent session limits of the server and the user for the given session,
+    /// only sessions which have logged in are counted besides the given session. The limit per
+    /// user is only checked once the user of the session is known.
+    pub fn check_session_limits(&self, session_id: Ulid) -> TdsWireResult<()> {
+        let sessions = self.inner.sessions.read().unwrap();
+        let Some(session) = sessions.get(&session_id) else {
+            return Ok(());
+        };
+        let exceeded = |limit: usize, count: usize| limit > 0 && count > limit;
+        let logged_in = || {
+            sessions
+                .values()
+                .filter(|s| s.is_logged_in() || s.session_id == session_id)
+        };
+
+        let message = if exceeded(self.ctx.session_limit, logged_in().count()) {
+            format!(
+                "Could not connect because the maximum number of '{}' user connections has already been reached.",
+                self.ctx.session_limit
+            )
+        } else if session.is_logged_in()
+            && exceeded(
+                self.ctx.session_limit_per_user,
+                logged_in().filter(|s| s.user == session.user).count(),
+            )
+        {
+            format!(
+                "Could not connect because the maximum number of '{}' connections for the user has already been reached.",
+                self.ctx.session_limit_per_user
+            )
+        } else {
+            return Ok(());
+        };
+        Err(self.not_admitted(session_id, message))
+    }
+
+    /// Time a session gets to log in, before the connection is closed
+    pub fn login_timeout(&self) -> Duration {
+        self.ctx.login_timeout
+    }
+
+    fn not_admitted(&self, session_id: Ulid, message: String) -> TdsWireError {
+        tracing::warn!("Session {} not admitted: {}", session_id, message);
+        TdsWireError::Server(TokenError::new(
+            17809,
+            1,
+            20,
+            format!("{} The connection has been closed.", message),
+            self.ctx.server_name.clone(),
+            "".to_string(),
+            1,
+        ))
+    }
+
     pub fn next_session_id(&self) -> Ulid {
         let session_id = Ulid::new();
         tracing::trace!("Generating new session ID: {}", session_id.to_string());
@@ -325,3 +688,92 @@ impl ServerInstance {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::prot::ServerInstance;
+    use crate::frontend::tds::server_context::ServerContext;
+    use std::time::Duration;
+    use unilake_common::error::TdsWireError;
+
+    fn check(instance: &ServerInstance, session_id: ulid::Ulid) -> Option<String> {
+        match instance.check_session_limits(session_id) {
+            Ok(()) => None,
+            Err(TdsWireError::Server(token)) => {
+                assert_eq!(token.code, 17809);
+                Some(token.message)
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn check_session_limits() {
+        let instance = ServerInstance::new(ServerContext::default().with_session_limits(2, 1, 0));
+        let register = |address: &str| {
+            let session_id = instance.next_session_id();
+            instance.register_session(session_id, address.parse().unwrap());
+            session_id
+        };
+
+        // sessions which have not logged in yet do not count towards the limits
+        let pending = [register("10.0.0.1:50001"), register("10.0.0.1:50002")];
+        let first = register("10.0.0.1:50003");
+        assert_eq!(check(&instance, first), None);
+        instance.update_session(first, |s| s.user = "u-1".to_string());
+        assert_eq!(check(&instance, first), None);
+        for session_id in pending {
+            assert_eq!(check(&instance, session_id), None);
+        }
+
+        // limit per user, once the user is known
+        let second = register("10.0.0.2:50001");
+        instance.update_session(second, |s| s.user = "u-1".to_string());
+        assert!(check(&instance, second).unwrap().contains("for the user"));
+        instance.update_session(second, |s| s.user = "u-2".to_string());
+        assert_eq!(check(&instance, second), None);
+
+        // limit of the server
+        let third = register("10.0.0.3:50001");
+        assert!(check(&instance, third)
+            .unwrap()
+            .contains("maximum number of '2' user connections"));
+    }
+
+    #[test]
+    fn admit_sessions() {
+        let instance = ServerInstance::new(
+            ServerContext::default()
+                .with_session_limits(0, 0, 2)
+                .with_pending_session_limits(3, Duration::from_secs(60)),
+        );
+        let admit = |address: &str| {
+            let session_id = instance.next_session_id();
+            instance
+                .admit_session(session_id, address.parse().unwrap())
+                .map(|_| session_id)
+                .map_err(|e| match e {
+                    TdsWireError::Server(token) => {
+                        assert_eq!(token.code, 17809);
+                        token.message
+                    }
+                    e => panic!("unexpected error: {}", e),
+                })
+        };
+
+        // limit per address, counting sessions which have not logged in
+        let first = admit("10.0.0.1:50001").unwrap();
+        admit("10.0.0.1:50002").unwrap();
+        assert!(admit("10.0.0.1:50003")
+            .unwrap_err()
+            .contains("from address '10.0.0.1'"));
+
+        // limit of sessions which have not logged in
+        admit("10.0.0.2:50001").unwrap();
+        assert!(admit("10.0.0.3:50001")
+            .unwrap_err()
+            .contains("'3' pending logins"));
+        instance.update_session(first, |s| s.user = "u-1".to_string());
+        admit("10.0.0.3:50001").unwrap();
+    }
+}
@@ -0,0 +1,596 @@
+use crate::frontend::{
+    BaseMetaDataColumn, ColumnData, DataFlags, ProcedureType, RpcParameter, RpcRequest,
+    TokenReturnValue, TypeInfo,
+};
+use crate::session::{PreparedStatement, SessionInfo};
+use std::collections::HashMap;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Action to take after resolving an RPC request
+#[derive(Debug)]
+pub enum RpcAction {
+    /// Execute the bound query, afterwards send the return values
+    Execute {
+        query: String,
+        return_values: Vec<TokenReturnValue>,
+    },
+    /// Nothing to execute, only send the return values
+    Respond {
+        return_values: Vec<TokenReturnValue>,
+    },
+}
+
+/// Declared parameter of a parameterized statement, e.g. "@p1 int OUTPUT"
+#[derive(Debug, PartialEq)]
+pub struct ParameterDeclaration {
+    pub name: String,
+    pub data_type: String,
+    pub is_output: bool,
+}
+
+/// Resolves an RPC request to the query to execute, registering or removing prepared statements
+/// on the session where needed. Only the sp_executesql and sp_prepare family is supported.
+pub fn resolve_rpc_request<S: SessionInfo + ?Sized>(
+    session: &mut S,
+    request: &RpcRequest,
+) -> TdsWireResult<RpcAction> {
+    let params = request.parameters();
+    let procedure = request.procedure_type().ok_or_else(|| {
+        TdsWireError::Input(format!(
+            "Could not find stored procedure '{}'.",
+            request.procedure_name().unwrap_or_default()
+        ))
+    })?;
+
+    match procedure {
+        ProcedureType::SpExecuteSql => {
+            let statement = get_string(params, 0, "@stmt")?;
+            let declaration = get_optional_string(params, 1);
+            let query = bind_parameters(&statement, &declaration, params.get(2..))?;
+            Ok(RpcAction::Execute {
+                query,
+                return_values: get_output_values(params, 2),
+            })
+        }
+        ProcedureType::SpPrepare => {
+            let declaration = get_optional_string(params, 1);
+            let statement = get_string(params, 2, "@stmt")?;
+            let handle =
+                session.add_prepared_statement(PreparedStatement::new(&declaration, &statement));
+            Ok(RpcAction::Respond {
+                return_values: vec![new_handle_return_value(params, handle)],
+            })
+        }
+        ProcedureType::SpPrepExec => {
+            let declaration = get_optional_string(params, 1);
+            let statement = get_string(params, 2, "@stmt")?;
+            let query = bind_parameters(&statement, &declaration, params.get(3..))?;
+            let handle =
+                session.add_prepared_statement(PreparedStatement::new(&declaration, &statement));
+            let mut return_values = vec![new_handle_return_value(params, handle)];
+            return_values.extend(get_output_values(params, 3));
+            Ok(RpcAction::Execute {
+                query,
+                return_values,
+            })
+        }
+        ProcedureType::SpExecute => {
+            let handle = get_handle(params)?;
+            let prepared = session.get_prepared_statement(handle).ok_or_else(|| {
+                TdsWireError::Input(format!(
+                    "Could not find prepared statement with handle {}.",
+                    handle
+                ))
+            })?;
+            let query =
+                bind_parameters(&prepared.statement, &prepared.declaration, params.get(1..))?;
+            Ok(RpcAction::Execute {
+                query,
+                return_values: get_output_values(params, 1),
+            })
+        }
+        ProcedureType::SpUnprepare => {
+            let handle = get_handle(params)?;
+            if !session.remove_prepared_statement(handle) {
+                tracing::warn!("Unprepare of unknown prepared statement handle {}", handle);
+            }
+            Ok(RpcAction::Respond {
+                return_values: Vec::new(),
+            })
+        }
+        _ => Err(TdsWireError::Input(format!(
+            "Procedure {:?} is not supported",
+            procedure
+        ))),
+    }
+}
+
+/// Parse a parameter declaration list, e.g. "@p1 int, @p2 decimal(10, 2) OUTPUT"
+pub fn parse_declaration(declaration: &str) -> TdsWireResult<Vec<ParameterDeclaration>> {
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in declaration.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                entries.push(&declaration[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&declaration[start..]);
+
+    entries
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, rest) = entry.split_once(char::is_whitespace).ok_or_else(|| {
+                TdsWireError::Input(format!("Invalid parameter declaration '{}'", entry))
+            })?;
+            if !name.starts_with('@') {
+                return Err(TdsWireError::Input(format!(
+                    "Invalid parameter name '{}'",
+                    name
+                )));
+            }
+
+            let rest = rest.trim();
+            let (data_type, is_output) = match rest.rsplit_once(char::is_whitespace) {
+                Some((data_type, modifier))
+                    if modifier.eq_ignore_ascii_case("output")
+                        || modifier.eq_ignore_ascii_case("out") =>
+                {
+                    (data_type.trim(), true)
+                }
+                _ => (rest, false),
+            };
+
+            Ok(ParameterDeclaration {
+                name: name.to_string(),
+                data_type: data_type.to_string(),
+                is_output,
+            })
+        })
+        .collect()
+}
+
+/// Bind the parameter values to the statement by replacing all parameter references with
+/// literals. Values are matched by name, unnamed values are matched on their position.
+pub fn bind_parameters(
+    statement: &str,
+    declaration: &str,
+    values: Option<&[RpcParameter]>,
+) -> TdsWireResult<String> {
+    let declared = parse_declaration(declaration)?;
+    let values = values.unwrap_or_default();
+    if values.len() > declared.len() {
+        return Err(TdsWireError::Input(format!(
+            "Too many arguments specified, expected {} but got {}",
+            declared.len(),
+            values.len()
+        )));
+    }
+
+    let mut literals = HashMap::new();
+    for (position, value) in values.iter().enumerate() {
+        let name = if value.name().is_empty() {
+            declared[position].name.as_str()
+        } else {
+            value.name()
+        };
+        literals.insert(name.to_lowercase(), to_literal(value.value())?);
+    }
+    for param in &declared {
+        if !literals.contains_key(&param.name.to_lowercase()) {
+            return Err(TdsWireError::Input(format!(
+                "The parameterized query expects the parameter '{}', which was not supplied.",
+                param.name
+            )));
+        }
+    }
+
+    Ok(substitute_parameters(statement, &literals))
+}
+
+/// Replace parameter references in the statement, skipping string literals, quoted identifiers,
+/// comments and system variables (@@...)
+fn substitute_parameters(statement: &str, literals: &HashMap<String, String>) -> String {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut output = String::with_capacity(statement.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let end = match c {
+            '\'' | '"' => find_closing(&chars, i + 1, c),
+            '[' => find_closing(&chars, i + 1, ']'),
+            '-' if next == Some('-') => chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map(|p| i + p + 1)
+                .unwrap_or(chars.len()),
+            '/' if next == Some('*') => chars[i + 2..]
+                .windows(2)
+                .position(|w| w == ['*', '/'])
+                .map(|p| i + p + 4)
+                .unwrap_or(chars.len()),
+            '@' => {
+                let start = if next == Some('@') { i + 2 } else { i + 1 };
+                let end = chars[start..]
+                    .iter()
+                    .position(|c| !is_identifier_char(*c))
+                    .map(|p| start + p)
+                    .unwrap_or(chars.len());
+                if next != Some('@') {
+                    let name: String = chars[i..end].iter().collect();
+                    if let Some(literal) = literals.get(&name.to_lowercase()) {
+                        output.push_str(literal);
+                        i = end;
+                        continue;
+                    }
+                }
+                end
+            }
+            _ => i + 1,
+        };
+        output.extend(&chars[i..end]);
+        i = end;
+    }
+    output
+}
+
+/// Escape the backslashes in the string literals of the query, for a backend which treats them
+/// as escape characters and executes the query without transpiling it first. Quoted identifiers
+/// and comments are left as is.
+pub fn escape_backslashes(query: &str) -> String {
+    let chars: Vec<char> = query.chars().collect();
+    let mut output = String::with_capacity(query.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        let end = match chars[i] {
+            '\'' => {
+                let end = find_closing(&chars, i + 1, '\'');
+                for c in &chars[i..end] {
+                    if *c == '\\' {
+                        output.push('\\');
+                    }
+                    output.push(*c);
+                }
+                i = end;
+                continue;
+            }
+            '"' => find_closing(&chars, i + 1, '"'),
+            '[' => find_closing(&chars, i + 1, ']'),
+            '-' if next == Some('-') => chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map(|p| i + p + 1)
+                .unwrap_or(chars.len()),
+            '/' if next == Some('*') => chars[i + 2..]
+                .windows(2)
+                .position(|w| w == ['*', '/'])
+                .map(|p| i + p + 4)
+                .unwrap_or(chars.len()),
+            _ => i + 1,
+        };
+        output.extend(&chars[i..end]);
+        i = end;
+    }
+    output
+}
+
+/// Find the position after the closing quote, doubled quotes are treated as escaped
+fn find_closing(chars: &[char], mut i: usize, quote: char) -> usize {
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '$')
+}
+
+/// Render a parameter value as a T-SQL literal. Floats which are not finite have no literal and
+/// are rejected.
+pub fn to_literal(value: &ColumnData) -> TdsWireResult<String> {
+    fn or_null<T>(value: &Option<T>, f: impl Fn(&T) -> String) -> String {
+        value.as_ref().map(f).unwrap_or_else(|| "NULL".to_string())
+    }
+    fn finite<T: ToString>(value: T, is_finite: bool) -> TdsWireResult<String> {
+        if !is_finite {
+            return Err(TdsWireError::Input(format!(
+                "The float value '{}' is out of range.",
+                value.to_string()
+            )));
+        }
+        Ok(value.to_string())
+    }
+
+    let literal = match value {
+        ColumnData::U8(v) => v.to_string(),
+        ColumnData::U8N(v) => or_null(v, u8::to_string),
+        ColumnData::I16(v) => v.to_string(),
+        ColumnData::I16N(v) => or_null(v, i16::to_string),
+        ColumnData::I32(v) => v.to_string(),
+        ColumnData::I32N(v) => or_null(v, i32::to_string),
+        ColumnData::I64(v) => v.to_string(),
+        ColumnData::I64N(v) => or_null(v, i64::to_string),
+        ColumnData::F32(v) => finite(v, v.is_finite())?,
+        ColumnData::F32N(Some(v)) => finite(v, v.is_finite())?,
+        ColumnData::F64(v) => finite(v, v.is_finite())?,
+        ColumnData::F64N(Some(v)) => finite(v, v.is_finite())?,
+        ColumnData::F32N(None) | ColumnData::F64N(None) => "NULL".to_string(),
+        ColumnData::Bit(v) => (*v as u8).to_string(),
+        ColumnData::BitN(v) => or_null(v, |v| (*v as u8).to_string()),
+        ColumnData::String(v) => match v.as_str() {
+            Some(v) => format!("N'{}'", v.replace('\'', "''")),
+            None => "NULL".to_string(),
+        },
+        ColumnData::Binary(v) => match v.as_bytes() {
+            Some(v) => format!(
+                "0x{}",
+                v.iter().map(|b| format!("{:02X}", b)).collect::<String>()
+            ),
+            None => "NULL".to_string(),
+        },
+        ColumnData::Numeric(v) => or_null(v, |v| v.to_string()),
+        ColumnData::Date(v) => or_null(v, |v| format!("'{}'", v.format("%Y-%m-%d"))),
+        ColumnData::Time(v) => or_null(v, |v| format!("'{}'", v.format("%H:%M:%S%.f"))),
+        ColumnData::DateTime(v) | ColumnData::SmallDateTime(v) | ColumnData::DateTime2(v) => {
+            or_null(v, |v| format!("'{}'", v.format("%Y-%m-%d %H:%M:%S%.f")))
+        }
+        // the value is decoded as UTC, so the offset has to be explicit
+        ColumnData::DateTimeOffset(v) => or_null(v, |v| {
+            format!("'{} +00:00'", v.format("%Y-%m-%d %H:%M:%S%.f"))
+        }),
+    };
+    Ok(literal)
+}
+
+fn get_optional_string(params: &[RpcParameter], index: usize) -> String {
+    params
+        .get(index)
+        .and_then(|p| match p.value() {
+            ColumnData::String(s) => s.as_str().map(str::to_string),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn get_string(params: &[RpcParameter], index: usize, name: &str) -> TdsWireResult<String> {
+    let value = get_optional_string(params, index);
+    if value.is_empty() {
+        return Err(TdsWireError::Input(format!(
+            "Procedure expects parameter '{}' of type 'ntext/nchar/nvarchar'.",
+            name
+        )));
+    }
+    Ok(value)
+}
+
+fn get_handle(params: &[RpcParameter]) -> TdsWireResult<i32> {
+    match params.first().map(|p| p.value()) {
+        Some(ColumnData::I32(v)) | Some(ColumnData::I32N(Some(v))) => Ok(*v),
+        _ => Err(TdsWireError::Input(
+            "Procedure expects parameter '@handle' of type 'int'.".to_string(),
+        )),
+    }
+}
+
+/// Return value for the handle output parameter of sp_prepare and sp_prepexec
+fn new_handle_return_value(params: &[RpcParameter], handle: i32) -> TokenReturnValue {
+    TokenReturnValue {
+        param_ordinal: 0,
+        param_name: params
+            .first()
+            .map(|p| p.name().to_string())
+            .unwrap_or_default(),
+        udf: false,
+        meta: BaseMetaDataColumn {
+            flags: DataFlags {
+                is_nullable: true,
+                ..DataFlags::default()
+            },
+            ty: TypeInfo::new_int(true),
+        },
+        value: ColumnData::I32N(Some(handle)),
+    }
+}
+
+/// Output parameters are not assigned by the backend, their input value is returned as-is
+fn get_output_values(params: &[RpcParameter], skip: usize) -> Vec<TokenReturnValue> {
+    params
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .filter(|(_, p)| p.is_output())
+        .map(|(ordinal, p)| TokenReturnValue {
+            param_ordinal: ordinal as u16,
+            param_name: p.name().to_string(),
+            udf: false,
+            meta: BaseMetaDataColumn {
+                flags: DataFlags {
+                    is_nullable: true,
+                    ..DataFlags::default()
+                },
+                ty: p.type_info().clone(),
+            },
+            value: p.value().clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        escape_backslashes, parse_declaration, substitute_parameters, to_literal,
+        ParameterDeclaration,
+    };
+    use crate::frontend::sqlbinary::SqlBinary;
+    use crate::frontend::{ColumnData, TypeInfo, VarLenContext, VarLenType};
+    use std::collections::HashMap;
+    use tokio_util::bytes::BytesMut;
+    use unilake_common::error::TdsWireError;
+    use unilake_sql::{
+        run_scan_operation, run_transpile_operation, TranspilerInput, VisibleSchemaBuilder,
+    };
+
+    #[test]
+    fn parse_declaration_with_precision_and_output() {
+        let declared =
+            parse_declaration("@p1 int, @p2 decimal(10, 2) OUTPUT,@p3 nvarchar(max) out").unwrap();
+        assert_eq!(
+            declared,
+            vec![
+                ParameterDeclaration {
+                    name: "@p1".to_string(),
+                    data_type: "int".to_string(),
+                    is_output: false
+                },
+                ParameterDeclaration {
+                    name: "@p2".to_string(),
+                    data_type: "decimal(10, 2)".to_string(),
+                    is_output: true
+                },
+                ParameterDeclaration {
+                    name: "@p3".to_string(),
+                    data_type: "nvarchar(max)".to_string(),
+                    is_output: true
+                },
+            ]
+        );
+        assert!(parse_declaration("").unwrap().is_empty());
+        assert!(parse_declaration("p1 int").is_err());
+    }
+
+    #[test]
+    fn substitute_skips_strings_comments_and_system_variables() {
+        let literals = HashMap::from([
+            ("@p1".to_string(), "1".to_string()),
+            ("@name".to_string(), "N'o''neil'".to_string()),
+        ]);
+        let query = substitute_parameters(
+            "select @P1, '@p1', [@p1], @@version, @p10 -- @p1\nfrom t where n = @name /* @p1 */",
+            &literals,
+        );
+        assert_eq!(
+            query,
+            "select 1, '@p1', [@p1], @@version, @p10 -- @p1\nfrom t where n = N'o''neil' /* @p1 */"
+        );
+    }
+
+    #[test]
+    fn render_literals() {
+        let literal = |value: ColumnData| to_literal(&value).unwrap();
+        assert_eq!(literal(ColumnData::I32N(None)), "NULL");
+        assert_eq!(literal(ColumnData::I64(-42)), "-42");
+        assert_eq!(literal(ColumnData::F64N(Some(1.5))), "1.5");
+        assert_eq!(literal(ColumnData::BitN(Some(true))), "1");
+        assert_eq!(literal(ColumnData::new_varchar("it's", 10)), "N'it''s'");
+        assert_eq!(
+            literal(ColumnData::Binary(SqlBinary::from_bytes(
+                Some(vec![0x00, 0xff]),
+                8000
+            ))),
+            "0x00FF"
+        );
+        assert_eq!(
+            literal(ColumnData::Date(chrono::NaiveDate::from_ymd_opt(
+                2024, 2, 29
+            ))),
+            "'2024-02-29'"
+        );
+    }
+
+    #[test]
+    fn render_datetimeoffset_as_utc() {
+        // 2024-02-29 14:00:00 +02:00, stored as 12:00:00 UTC with an offset of 120 minutes
+        let mut src = BytesMut::from(
+            &[
+                0x0a, 0x00, 0xe0, 0x34, 0x95, 0x64, 0x80, 0x46, 0x0b, 0x78, 0x00,
+            ][..],
+        );
+        let type_info =
+            TypeInfo::VarLenSized(VarLenContext::new(VarLenType::DatetimeOffsetn, 7, None));
+        let value = ColumnData::decode(&mut src, &type_info).unwrap();
+        assert_eq!(to_literal(&value).unwrap(), "'2024-02-29 12:00:00 +00:00'");
+        assert_eq!(
+            to_literal(&ColumnData::DateTimeOffset(None)).unwrap(),
+            "NULL"
+        );
+    }
+
+    #[test]
+    fn secured_query_keeps_bound_backslashes() {
+        let literals = HashMap::from([(
+            "@p1".to_string(),
+            to_literal(&ColumnData::new_varchar("a\\b", 10)).unwrap(),
+        )]);
+        let query = substitute_parameters("select name from employees where name = @p1", &literals);
+        assert_eq!(query, "select name from employees where name = N'a\\b'");
+
+        // the backslash is an ordinary character in T-SQL, the backend escapes it only once
+        let scan_result = run_scan_operation(&query, "tsql", "catalog", "database").unwrap();
+        let mut builder = VisibleSchemaBuilder::new();
+        builder
+            .get_or_add_catalog("catalog".to_string())
+            .get_or_add_database("database".to_string())
+            .get_or_add_table("employees".to_string())
+            .get_or_add_column("name".to_string(), "string".to_string());
+        let output = run_transpile_operation(
+            &TranspilerInput {
+                cause: None,
+                query: scan_result.query.unwrap(),
+                request_url: None,
+                rules: vec![],
+                filters: vec![],
+                visible_schema: Some(builder.catalog),
+            },
+            false,
+        )
+        .unwrap();
+        assert!(output.error.is_none());
+        assert!(output.sql_transformed.contains("'a\\\\b'"));
+        assert!(!output.sql_transformed.contains("a\\\\\\\\b"));
+    }
+
+    #[test]
+    fn transparent_query_escapes_backslashes() {
+        // the quote cannot be escaped by a backslash to break out of the literal
+        let literal = to_literal(&ColumnData::new_varchar("\\' OR 1=1 -- ", 20)).unwrap();
+        assert_eq!(literal, "N'\\'' OR 1=1 -- '");
+        assert_eq!(
+            escape_backslashes(&format!("select {} -- it's", literal)),
+            "select N'\\\\'' OR 1=1 -- ' -- it's"
+        );
+        assert_eq!(
+            escape_backslashes("select [a\\'b], \"c\\d\" /* ' */ from t where n = 'x\\y'"),
+            "select [a\\'b], \"c\\d\" /* ' */ from t where n = 'x\\\\y'"
+        );
+    }
+
+    #[test]
+    fn reject_non_finite_floats() {
+        for value in [
+            ColumnData::F64(f64::NAN),
+            ColumnData::F64N(Some(f64::INFINITY)),
+            ColumnData::F32(f32::NEG_INFINITY),
+        ] {
+            assert!(matches!(to_literal(&value), Err(TdsWireError::Input(_))));
+        }
+        assert_eq!(to_literal(&ColumnData::F32N(None)).unwrap(), "NULL");
+    }
+}
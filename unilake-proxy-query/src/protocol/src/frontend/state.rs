@@ -0,0 +1,309 @@
+use crate::frontend::prot::TdsSessionState;
+use crate::frontend::TdsMessage;
+
+/// Events that move a session from one state to the next [3.3.5]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TdsSessionEvent {
+    /// PRELOGIN message received
+    PreLogin,
+    /// TLS handshake, wrapped in PRELOGIN messages, completed
+    TlsHandshakeCompleted,
+    /// LOGIN7 message received containing all authentication information
+    Login7,
+    /// LOGIN7 message received, the client expects the FEDAUTHINFO token to acquire a token
+    Login7FederatedAuthenticationInformationRequest,
+    /// Federated authentication token message received
+    FederatedAuthenticationToken,
+    /// Login response sent to the client
+    LoginCompleted,
+    /// SQL batch or RPC request received
+    ClientRequest,
+    /// Response to the client request sent
+    RequestCompleted,
+    /// Attention received
+    Attention,
+    /// Attention acknowledged with a DONE token with the attention bit set
+    AttentionAcknowledged,
+    /// Connection closed by either side
+    ConnectionClosed,
+}
+
+use TdsSessionEvent as E;
+use TdsSessionState as S;
+
+/// Valid transitions (current state, event, next state), any event not listed for a state is a
+/// protocol violation
+const TRANSITIONS: &[(TdsSessionState, TdsSessionEvent, TdsSessionState)] = &[
+    (S::Initial, E::PreLogin, S::PreLoginProcessed),
+    (
+        S::PreLoginProcessed,
+        E::TlsHandshakeCompleted,
+        S::SSLNegotiationProcessed,
+    ),
+    (S::PreLoginProcessed, E::Login7, S::CompleteLogin7Processed),
+    (
+        S::PreLoginProcessed,
+        E::Login7FederatedAuthenticationInformationRequest,
+        S::Login7FederatedAuthenticationInformationRequestProcessed,
+    ),
+    (
+        S::SSLNegotiationProcessed,
+        E::Login7,
+        S::CompleteLogin7Processed,
+    ),
+    (
+        S::SSLNegotiationProcessed,
+        E::Login7FederatedAuthenticationInformationRequest,
+        S::Login7FederatedAuthenticationInformationRequestProcessed,
+    ),
+    (
+        S::Login7FederatedAuthenticationInformationRequestProcessed,
+        E::FederatedAuthenticationToken,
+        S::CompleteLogin7Processed,
+    ),
+    (S::CompleteLogin7Processed, E::LoginCompleted, S::LoggedIn),
+    (S::LoggedIn, E::ClientRequest, S::RequestReceived),
+    (S::LoggedIn, E::Attention, S::AttentionReceived),
+    (S::RequestReceived, E::RequestCompleted, S::LoggedIn),
+    (S::RequestReceived, E::Attention, S::AttentionReceived),
+    (S::AttentionReceived, E::AttentionAcknowledged, S::LoggedIn),
+];
+
+impl TdsSessionEvent {
+    /// Get the event for a message received from the client, none for messages that are never
+    /// sent by a client
+    pub fn from_message(message: &TdsMessage) -> Option<Self> {
+        match message {
+            TdsMessage::PreLogin(_) => Some(E::PreLogin),
+            TdsMessage::Login(l) if l.expects_fed_auth_token_message() => {
+                Some(E::Login7FederatedAuthenticationInformationRequest)
+            }
+            TdsMessage::Login(_) => Some(E::Login7),
+            TdsMessage::FedAuthToken(_) => Some(E::FederatedAuthenticationToken),
+            TdsMessage::BatchRequest(_) | TdsMessage::RemoteProcedureCall(_) => {
+                Some(E::ClientRequest)
+            }
+            TdsMessage::Attention(_) => Some(E::Attention),
+            TdsMessage::Response(_) | TdsMessage::FedAuth(_) => None,
+        }
+    }
+}
+
+impl TdsSessionState {
+    /// Get the next state for the given event, none if the event is not allowed in this state
+    pub fn next(self, event: TdsSessionEvent) -> Option<TdsSessionState> {
+        if event == E::ConnectionClosed {
+            return Some(S::Final);
+        }
+        TRANSITIONS
+            .iter()
+            .find(|(state, e, _)| *state == self && *e == event)
+            .map(|(_, _, next)| *next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TdsSessionEvent;
+    use crate::frontend::prot::TdsSessionState;
+    use crate::frontend::{TdsFrontendRequest, TdsMessage, TokenFedAuth};
+    use tokio_util::bytes::BytesMut;
+
+    const PRELOGIN: &[u8] = &[
+        0x12, 0x01, 0x00, 0x2F, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x1A, 0x00, 0x06, 0x01, 0x00,
+        0x20, 0x00, 0x01, 0x02, 0x00, 0x21, 0x00, 0x01, 0x03, 0x00, 0x22, 0x00, 0x04, 0x04, 0x00,
+        0x26, 0x00, 0x01, 0xFF, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    /// LOGIN7 using SQL authentication, user "sa" with password "pwd"
+    const LOGIN7: &[u8] = &[
+        0x10, 0x01, 0x00, 0x70, 0x00, 0x00, 0x01, 0x00, 0x68, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+        0x74, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xE0, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x04, 0x00, 0x00, 0x5E,
+        0x00, 0x00, 0x00, 0x5E, 0x00, 0x02, 0x00, 0x62, 0x00, 0x03, 0x00, 0x68, 0x00, 0x00, 0x00,
+        0x68, 0x00, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x68, 0x00, 0x00,
+        0x00, 0x68, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00,
+        0x68, 0x00, 0x00, 0x00, 0x68, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x73, 0x00, 0x61,
+        0x00, 0xA2, 0xA5, 0x23, 0xA5, 0x92, 0xA5,
+    ];
+
+    /// LOGIN7 with the FEDAUTH feature extension, using the ADAL/MSAL workflow
+    const LOGIN7_FEDAUTH_ADAL: &[u8] = &[
+        0x10, 0x01, 0x00, 0x72, 0x00, 0x00, 0x01, 0x00, 0x6A, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+        0x74, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xE0, 0x03, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x09, 0x04, 0x00, 0x00, 0x5E,
+        0x00, 0x00, 0x00, 0x5E, 0x00, 0x00, 0x00, 0x5E, 0x00, 0x00, 0x00, 0x5E, 0x00, 0x00, 0x00,
+        0x5E, 0x00, 0x00, 0x00, 0x5E, 0x00, 0x04, 0x00, 0x62, 0x00, 0x00, 0x00, 0x62, 0x00, 0x00,
+        0x00, 0x62, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x00, 0x00, 0x00,
+        0x62, 0x00, 0x00, 0x00, 0x62, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x00, 0x00,
+        0x00, 0x02, 0x02, 0x00, 0x00, 0x00, 0x04, 0x01, 0xFF,
+    ];
+
+    /// Federated authentication token message, token "ey"
+    const FEDAUTH_TOKEN: &[u8] = &[
+        0x08, 0x01, 0x00, 0x14, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00,
+        0x00, 0x65, 0x00, 0x79, 0x00,
+    ];
+
+    /// SQL batch "select 1"
+    const SQL_BATCH: &[u8] = &[
+        0x01, 0x01, 0x00, 0x2E, 0x00, 0x00, 0x01, 0x00, 0x16, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00,
+        0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x73, 0x00, 0x65, 0x00, 0x6C, 0x00, 0x65, 0x00, 0x63, 0x00, 0x74, 0x00, 0x20, 0x00, 0x31,
+        0x00,
+    ];
+
+    const ATTENTION: &[u8] = &[0x06, 0x01, 0x00, 0x08, 0x00, 0x00, 0x01, 0x00];
+
+    fn replay(capture: &[u8]) -> TdsSessionEvent {
+        let mut buff = BytesMut::from(capture);
+        let request = TdsFrontendRequest::decode(&mut buff).unwrap().unwrap();
+        assert!(buff.is_empty(), "capture not fully decoded");
+        TdsSessionEvent::from_message(&request.messages[0].1).unwrap()
+    }
+
+    /// Replays the captures in order, applying the internal events in between
+    fn run(steps: &[(&[u8], &[TdsSessionEvent])]) -> Option<TdsSessionState> {
+        let mut state = TdsSessionState::default();
+        for (capture, events) in steps {
+            state = state.next(replay(capture))?;
+            for event in events.iter() {
+                state = state.next(*event)?;
+            }
+        }
+        Some(state)
+    }
+
+    #[test]
+    fn sql_authentication_login() {
+        assert_eq!(
+            run(&[
+                (PRELOGIN, &[]),
+                (LOGIN7, &[TdsSessionEvent::LoginCompleted]),
+            ]),
+            Some(TdsSessionState::LoggedIn)
+        );
+    }
+
+    #[test]
+    fn tls_login() {
+        assert_eq!(
+            run(&[
+                (PRELOGIN, &[TdsSessionEvent::TlsHandshakeCompleted]),
+                (LOGIN7, &[]),
+            ]),
+            Some(TdsSessionState::CompleteLogin7Processed)
+        );
+    }
+
+    #[test]
+    fn federated_authentication_login() {
+        assert_eq!(
+            run(&[(PRELOGIN, &[]), (LOGIN7_FEDAUTH_ADAL, &[])]),
+            Some(TdsSessionState::Login7FederatedAuthenticationInformationRequestProcessed)
+        );
+        assert_eq!(
+            run(&[
+                (PRELOGIN, &[]),
+                (LOGIN7_FEDAUTH_ADAL, &[]),
+                (FEDAUTH_TOKEN, &[TdsSessionEvent::LoginCompleted]),
+            ]),
+            Some(TdsSessionState::LoggedIn)
+        );
+    }
+
+    #[test]
+    fn client_request() {
+        let login: &[(&[u8], &[TdsSessionEvent])] = &[
+            (PRELOGIN, &[]),
+            (LOGIN7, &[TdsSessionEvent::LoginCompleted]),
+        ];
+        assert_eq!(
+            run(&[login, &[(SQL_BATCH, &[])]].concat()),
+            Some(TdsSessionState::RequestReceived)
+        );
+        assert_eq!(
+            run(&[
+                login,
+                &[
+                    (SQL_BATCH, &[TdsSessionEvent::RequestCompleted]),
+                    (SQL_BATCH, &[TdsSessionEvent::RequestCompleted]),
+                ]
+            ]
+            .concat()),
+            Some(TdsSessionState::LoggedIn)
+        );
+    }
+
+    #[test]
+    fn attention() {
+        let login: &[(&[u8], &[TdsSessionEvent])] = &[
+            (PRELOGIN, &[]),
+            (LOGIN7, &[TdsSessionEvent::LoginCompleted]),
+        ];
+        // while a request is running
+        assert_eq!(
+            run(&[login, &[(SQL_BATCH, &[]), (ATTENTION, &[])]].concat()),
+            Some(TdsSessionState::AttentionReceived)
+        );
+        // after the request completed
+        assert_eq!(
+            run(&[
+                login,
+                &[
+                    (SQL_BATCH, &[TdsSessionEvent::RequestCompleted]),
+                    (ATTENTION, &[TdsSessionEvent::AttentionAcknowledged]),
+                ]
+            ]
+            .concat()),
+            Some(TdsSessionState::LoggedIn)
+        );
+    }
+
+    #[test]
+    fn reject_out_of_order_messages() {
+        // request before login
+        assert_eq!(run(&[(SQL_BATCH, &[])]), None);
+        assert_eq!(run(&[(PRELOGIN, &[]), (SQL_BATCH, &[])]), None);
+        // login before pre-login
+        assert_eq!(run(&[(LOGIN7, &[])]), None);
+        // attention before login
+        assert_eq!(run(&[(PRELOGIN, &[]), (ATTENTION, &[])]), None);
+        // token without FEDAUTHINFO request
+        assert_eq!(run(&[(PRELOGIN, &[]), (FEDAUTH_TOKEN, &[])]), None);
+        // second pre-login or login
+        let login: &[(&[u8], &[TdsSessionEvent])] = &[
+            (PRELOGIN, &[]),
+            (LOGIN7, &[TdsSessionEvent::LoginCompleted]),
+        ];
+        assert_eq!(run(&[login, &[(PRELOGIN, &[])]].concat()), None);
+        assert_eq!(run(&[login, &[(LOGIN7, &[])]].concat()), None);
+        // new request while a request is running
+        assert_eq!(
+            run(&[login, &[(SQL_BATCH, &[]), (SQL_BATCH, &[])]].concat()),
+            None
+        );
+    }
+
+    #[test]
+    fn connection_closed() {
+        for state in [
+            TdsSessionState::Initial,
+            TdsSessionState::LoggedIn,
+            TdsSessionState::RequestReceived,
+        ] {
+            assert_eq!(
+                state.next(TdsSessionEvent::ConnectionClosed),
+                Some(TdsSessionState::Final)
+            );
+        }
+        assert_eq!(TdsSessionState::Final.next(TdsSessionEvent::PreLogin), None);
+    }
+
+    #[test]
+    fn server_messages_are_not_events() {
+        let message = TdsMessage::FedAuth(TokenFedAuth::new("https://sts", "spn"));
+        assert_eq!(TdsSessionEvent::from_message(&message), None);
+    }
+}
@@ -1,8 +1,9 @@
 mod attention;
 mod batch_request;
 mod column_data;
-mod decode;
+pub(crate) mod decode;
 mod encode;
+mod fed_auth_token;
 mod guid;
 mod header;
 mod login;
@@ -11,6 +12,7 @@ mod pre_login;
 mod response;
 mod row;
 mod rpc_request;
+mod session_recovery;
 mod token;
 pub mod tokenfactory;
 mod type_info;
@@ -18,6 +20,7 @@ mod type_info;
 pub use attention::*;
 pub use batch_request::*;
 pub use column_data::*;
+pub use fed_auth_token::*;
 pub use header::*;
 pub use login::*;
 pub use message::*;
@@ -25,12 +28,15 @@ pub use pre_login::*;
 pub use response::*;
 pub use row::*;
 pub use rpc_request::*;
+pub use session_recovery::*;
 pub use token::*;
 pub use tokenfactory::*;
 pub use type_info::*;
 
 pub const ALL_HEADERS_LEN_TX: usize = 8;
 pub const MAX_PACKET_SIZE: usize = 32767;
+/// Maximum size of a message received from a client, spanning one or more packets
+pub const MAX_MESSAGE_SIZE: usize = 128 * 1024 * 1024;
 
 #[derive(Debug)]
 #[repr(u16)]
@@ -2,19 +2,55 @@ use crate::frontend::{TdsMessage, TdsMessageCodec};
 use tokio_util::bytes::BytesMut;
 use unilake_common::error::TdsWireResult;
 
-#[derive(Debug)]
+/// Attention signal [2.2.1.7]
+/// Sent by the client to cancel the currently executing request. The message has no data, only
+/// the packet header is sent.
+#[derive(Debug, Default)]
 pub struct AttentionSignal {}
-impl AttentionSignal {}
+
+impl AttentionSignal {
+    pub fn new() -> Self {
+        AttentionSignal {}
+    }
+}
 
 impl TdsMessageCodec for AttentionSignal {
-    fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage>
+    fn decode(_src: &mut BytesMut) -> TdsWireResult<TdsMessage>
     where
         Self: Sized,
     {
-        todo!()
+        Ok(TdsMessage::Attention(AttentionSignal::new()))
     }
 
     fn encode(&self, _: &mut BytesMut) -> TdsWireResult<()> {
-        todo!()
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        AttentionSignal, PacketType, TdsFrontendRequest, TdsMessage, TdsMessageCodec,
+    };
+    use tokio_util::bytes::BytesMut;
+
+    #[test]
+    fn decode_attention_packet() {
+        // header only, no data
+        let mut buff = BytesMut::from(&[0x06, 0x01, 0x00, 0x08, 0x00, 0x00, 0x01, 0x00][..]);
+
+        let request = TdsFrontendRequest::decode(&mut buff).unwrap().unwrap();
+
+        assert!(buff.is_empty());
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.messages[0].0.ty, PacketType::Attention);
+        assert!(matches!(request.messages[0].1, TdsMessage::Attention(_)));
+    }
+
+    #[test]
+    fn encode_attention_has_no_data() {
+        let mut buff = BytesMut::new();
+        AttentionSignal::new().encode(&mut buff).unwrap();
+        assert!(buff.is_empty());
     }
 }
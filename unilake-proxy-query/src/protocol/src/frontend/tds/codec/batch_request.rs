@@ -1,10 +1,14 @@
-use crate::frontend::tds::codec::{AllHeaderTy, ALL_HEADERS_LEN_TX};
-use crate::frontend::utils::ReadAndAdvance;
+use crate::frontend::tds::codec::decode::read_all_headers;
+use crate::frontend::tds::codec::AllHeaderTy;
 use crate::frontend::{TdsMessage, TdsMessageCodec};
 use byteorder::{ByteOrder, LittleEndian};
-use std::hash::{DefaultHasher, Hasher};
-use tokio_util::bytes::{Buf, BufMut, BytesMut};
-use unilake_common::error::{Error, TdsWireResult, TokenError};
+use tokio_util::bytes::{BufMut, BytesMut};
+use unilake_common::error::{Error, TdsWireResult};
+
+/// Maximum length of the query text in bytes
+const MAX_QUERY_LENGTH: usize = 100_000_000;
+/// Length of the ALL_HEADERS rule containing only the transaction descriptor header
+const TRANSACTION_DESCRIPTOR_HEADERS_LEN: usize = 22;
 
 /// SQLBatch Message [2.2.6.7]
 #[derive(Debug)]
@@ -14,50 +18,16 @@ pub struct BatchRequest {
     pub transaction_descriptor: Vec<u8>,
 }
 
-impl BatchRequest {
-    /// Returns a hash for the query in this batchrequest
-    // todo(mrhamburg): should this be for each query inside of this request? (not support multi-statement)
-    pub fn get_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        hasher.write(&self.query.as_bytes());
-        hasher.finish()
-    }
-}
-
 impl TdsMessageCodec for BatchRequest {
     fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage> {
-        let _headers = {
-            let mut headers = Vec::with_capacity(2);
-            headers.push(src.get_u32_le());
-            headers.push(src.get_u32_le() + 4);
-            headers
-        };
-
-        let _hty = src.get_u16_le();
-        let (_, tx_descriptor) = src.read_and_advance(8);
-        let _outstanding_requests = src.get_u32_le();
+        let (transaction_descriptor, _outstanding_requests) = read_all_headers(src)?;
 
         let qtx: Vec<_> = {
-            let max_len = 100_000_000;
-            let mut qtx: Vec<u8> = Vec::with_capacity(1024);
-            loop {
-                let (len, buff) = src.read_and_advance(1024);
-
-                qtx.extend_from_slice(&buff);
-                if len < 1024 {
-                    break;
-                } else if qtx.len() >= max_len {
-                    // TODO: set proper handling of this error and probably any other possible errors
-                    return Err(Error::Server(TokenError::new(
-                        12,
-                        12,
-                        12,
-                        String::from("QueryTooLong"),
-                        String::from("Some Server"),
-                        String::from("a"),
-                        12,
-                    )));
-                }
+            let qtx = src.split();
+            if qtx.len() % 2 == 1 {
+                return Err(Error::Protocol("Invalid SQL batch length".to_string()));
+            } else if qtx.len() > MAX_QUERY_LENGTH {
+                return Err(Error::Protocol("SQL batch too long".to_string()));
             }
             qtx.chunks(2).map(LittleEndian::read_u16).collect()
         };
@@ -67,13 +37,14 @@ impl TdsMessageCodec for BatchRequest {
         Ok(TdsMessage::BatchRequest(BatchRequest {
             query: query_text.clone(),
             query_lowercased: query_text.to_lowercase(),
-            transaction_descriptor: tx_descriptor.to_vec(),
+            transaction_descriptor: transaction_descriptor.to_le_bytes().to_vec(),
         }))
     }
 
     fn encode(&self, dst: &mut BytesMut) -> TdsWireResult<()> {
-        dst.put_u32_le(ALL_HEADERS_LEN_TX as u32);
-        dst.put_u32_le(ALL_HEADERS_LEN_TX as u32 - 4);
+        // total length, followed by a single transaction descriptor header
+        dst.put_u32_le(TRANSACTION_DESCRIPTOR_HEADERS_LEN as u32);
+        dst.put_u32_le(TRANSACTION_DESCRIPTOR_HEADERS_LEN as u32 - 4);
         dst.put_u16_le(AllHeaderTy::TransactionDescriptor as u16);
 
         dst.put(&self.transaction_descriptor[..]);
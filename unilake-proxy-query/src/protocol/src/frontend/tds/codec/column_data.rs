@@ -1,9 +1,10 @@
 use crate::frontend::{TypeInfo, VarLenType};
 use bigdecimal::BigDecimal;
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use sqlbinary::SqlBinary;
 use sqlstring::SqlString;
 use tokio_util::bytes::BytesMut;
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 mod date;
 mod datetime2;
@@ -11,6 +12,7 @@ pub mod decimal;
 mod fixed_len;
 mod numeric;
 mod plp;
+pub mod sqlbinary;
 pub mod sqlstring;
 mod var_len;
 
@@ -49,7 +51,7 @@ pub enum ColumnData {
     /// A string value.
     String(SqlString),
     /// Binary data.
-    Binary(Option<String>),
+    Binary(SqlBinary),
     /// Numeric value (a decimal).
     Numeric(Option<BigDecimal>),
     /// DateTime value.
@@ -132,19 +134,15 @@ impl ColumnData {
                 }
             }
             ColumnData::String(v) => {
-                if !v.is_empty() {
-                    v.len() * 2
-                } else {
+                if v.is_empty() {
                     0
-                }
-            }
-            ColumnData::Binary(v) => {
-                if v.is_some() {
-                    v.as_ref().unwrap().len()
+                } else if v.is_utf8() {
+                    v.len()
                 } else {
-                    0
+                    v.len() * 2
                 }
             }
+            ColumnData::Binary(v) => v.len(),
             ColumnData::Numeric(v) => {
                 if v.is_some() {
                     8
@@ -215,7 +213,9 @@ impl ColumnData {
             | ColumnData::F32N(_)
             | ColumnData::F64N(_) => var_len::encode(dest, &self)?,
             ColumnData::String(s) => s.encode(dest)?,
+            ColumnData::Binary(b) => b.encode(dest)?,
             ColumnData::Date(_) => date::encode(dest, &self),
+            ColumnData::Time(_) => datetime2::encode_time(dest, &self)?,
             ColumnData::DateTime2(_) => datetime2::encode(dest, &self)?,
             ColumnData::Numeric(n) => {
                 numeric::encode(dest, &n)?;
@@ -226,15 +226,20 @@ impl ColumnData {
         Ok(())
     }
 
-    // todo(mrhamburg): further implement these types
+    /// Decode a value of the given type, as used for RPC parameters.
     pub fn decode(src: &mut BytesMut, typeinfo: &TypeInfo) -> TdsWireResult<Self> {
         match typeinfo {
-            TypeInfo::FixedLen(fl) => todo!(),
-            TypeInfo::VarLenSized(vs) => match vs.r#type() {
-                VarLenType::NVarchar => Ok(ColumnData::String(SqlString::decode(src, vs.len())?)),
-                _ => todo!(),
+            TypeInfo::FixedLen(fl) => fixed_len::decode(src, fl),
+            TypeInfo::VarLenSized(vs) => var_len::decode(src, vs),
+            TypeInfo::VarLenSizedPrecision { ty, scale, .. } => match ty {
+                VarLenType::Decimaln | VarLenType::Numericn => {
+                    Ok(ColumnData::Numeric(numeric::decode(src, *scale)?))
+                }
+                _ => Err(TdsWireError::Protocol(format!(
+                    "Unsupported precision type: {:?}",
+                    ty
+                ))),
             },
-            TypeInfo::VarLenSizedPrecision { .. } => todo!(),
         }
     }
 }
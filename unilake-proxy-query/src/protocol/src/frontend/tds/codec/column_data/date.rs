@@ -1,6 +1,8 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::ColumnData;
 use chrono::NaiveDate;
-use tokio_util::bytes::{BufMut, BytesMut};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::TdsWireResult;
 
 const BASE_DATE: Option<NaiveDate> = NaiveDate::from_ymd_opt(1, 1, 1);
 
@@ -20,6 +22,13 @@ pub(crate) fn encode(dst: &mut BytesMut, data: &ColumnData) {
     }
 }
 
+/// Decode the 3 byte representation of a date, days since 0001-01-01
+pub(crate) fn decode(src: &mut BytesMut) -> TdsWireResult<NaiveDate> {
+    check_remaining(src, 3, "date")?;
+    let days = src.get_uint_le(3);
+    Ok(BASE_DATE.unwrap() + chrono::TimeDelta::days(days as i64))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::frontend::tds::codec::column_data::date;
@@ -41,4 +50,13 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_decode_date() {
+        let mut buf = BytesMut::from(&RAW_BYTES[1..]);
+        assert_eq!(
+            date::decode(&mut buf).unwrap(),
+            NaiveDate::from_ymd_opt(2003, 12, 31).unwrap()
+        );
+    }
 }
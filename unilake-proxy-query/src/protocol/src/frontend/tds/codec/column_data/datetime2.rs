@@ -1,6 +1,7 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::ColumnData;
-use chrono::{NaiveDate, Timelike};
-use tokio_util::bytes::{BufMut, BytesMut};
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::TdsWireResult;
 
 const BASE_DATE: Option<NaiveDate> = NaiveDate::from_ymd_opt(1, 1, 1);
@@ -53,6 +54,35 @@ pub(crate) fn encode(dst: &mut BytesMut, data: &ColumnData) -> TdsWireResult<()>
     Ok(())
 }
 
+/// Encode a time value, which is always sent with a scale of 7 (100 nanoseconds)
+pub(crate) fn encode_time(dst: &mut BytesMut, data: &ColumnData) -> TdsWireResult<()> {
+    match data {
+        ColumnData::Time(Some(val)) => {
+            let time_of_day_ns = (val.num_seconds_from_midnight() as u64 * 1_000_000_000)
+                + (val.nanosecond() as u64);
+            dst.put_u8(5);
+            dst.extend_from_slice(&(time_of_day_ns / 100).to_le_bytes()[..5]);
+        }
+        // send null
+        _ => dst.put_u8(0),
+    }
+
+    Ok(())
+}
+
+/// Decode the time part of a time, datetime2 or datetimeoffset value for the given scale
+pub(crate) fn decode_time(src: &mut BytesMut, scale: usize) -> TdsWireResult<NaiveTime> {
+    let time_bytes = match scale {
+        0..=2 => 3,
+        3..=4 => 4,
+        _ => 5,
+    };
+    check_remaining(src, time_bytes, "time")?;
+    let scaled_time = src.get_uint_le(time_bytes);
+    let nanoseconds = scaled_time * 10u64.pow(9 - std::cmp::min(scale, 7) as u32);
+    Ok(NaiveTime::MIN + TimeDelta::nanoseconds(nanoseconds as i64))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::frontend::{tds::codec::column_data::datetime2, ColumnData};
@@ -79,4 +109,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_encode_time() -> TdsWireResult<()> {
+        let mut buf = BytesMut::new();
+        let data = ColumnData::Time(chrono::NaiveTime::from_hms_opt(1, 2, 3));
+        datetime2::encode_time(&mut buf, &data)?;
+        assert_eq!(buf.to_vec(), [0x05, 0x80, 0xb7, 0x14, 0xab, 0x08]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_datetime2_time() {
+        let mut buf = BytesMut::from(&RAW_BYTES_SCALE_7[1..6]);
+        let time = datetime2::decode_time(&mut buf, 7).unwrap();
+        assert_eq!(time, chrono::NaiveTime::from_hms_opt(1, 2, 3).unwrap());
+    }
 }
@@ -1,6 +1,9 @@
-use crate::frontend::ColumnData;
-use tokio_util::bytes::{BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use crate::frontend::{ColumnData, FixedLenType};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+const BASE_DATE_DATETIME: Option<NaiveDate> = NaiveDate::from_ymd_opt(1900, 1, 1);
 
 /// Fixed length token [2.2.4.2.1.2]
 // todo(mrhamburg): remove result type, we are not responding with any errors
@@ -32,3 +35,58 @@ pub(crate) fn encode(dst: &mut BytesMut, data: &ColumnData) -> TdsWireResult<()>
 
     Ok(())
 }
+
+pub(crate) fn decode(src: &mut BytesMut, ty: &FixedLenType) -> TdsWireResult<ColumnData> {
+    let len = match ty {
+        FixedLenType::Null => 0,
+        FixedLenType::Int1 | FixedLenType::Bit => 1,
+        FixedLenType::Int2 => 2,
+        FixedLenType::Int4 | FixedLenType::Datetime4 | FixedLenType::Float4 => 4,
+        FixedLenType::Datetime | FixedLenType::Float8 | FixedLenType::Int8 => 8,
+    };
+    if src.remaining() < len {
+        return Err(TdsWireError::Protocol(format!(
+            "Not enough data to decode fixed length value of type {:?}",
+            ty
+        )));
+    }
+
+    Ok(match ty {
+        FixedLenType::Null => ColumnData::I32N(None),
+        FixedLenType::Int1 => ColumnData::U8(src.get_u8()),
+        FixedLenType::Bit => ColumnData::Bit(src.get_u8() != 0),
+        FixedLenType::Int2 => ColumnData::I16(src.get_i16_le()),
+        FixedLenType::Int4 => ColumnData::I32(src.get_i32_le()),
+        FixedLenType::Int8 => ColumnData::I64(src.get_i64_le()),
+        FixedLenType::Float4 => ColumnData::F32(src.get_f32_le()),
+        FixedLenType::Float8 => ColumnData::F64(src.get_f64_le()),
+        FixedLenType::Datetime4 => ColumnData::SmallDateTime(Some(decode_small_datetime(src))),
+        FixedLenType::Datetime => ColumnData::DateTime(Some(decode_datetime(src)?)),
+    })
+}
+
+/// Decode a smalldatetime value: days since 1900-01-01 and minutes since midnight
+pub(crate) fn decode_small_datetime(src: &mut BytesMut) -> NaiveDateTime {
+    let days = src.get_u16_le();
+    let minutes = src.get_u16_le();
+    BASE_DATE_DATETIME.unwrap().and_hms_opt(0, 0, 0).unwrap()
+        + TimeDelta::days(days as i64)
+        + TimeDelta::minutes(minutes as i64)
+}
+
+/// Decode a datetime value: days since 1900-01-01 and 1/300 of a second since midnight
+pub(crate) fn decode_datetime(src: &mut BytesMut) -> TdsWireResult<NaiveDateTime> {
+    let days = src.get_i32_le();
+    let fragments = src.get_u32_le();
+    BASE_DATE_DATETIME
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .checked_add_signed(TimeDelta::days(days as i64))
+        .and_then(|d| {
+            d.checked_add_signed(TimeDelta::nanoseconds(
+                fragments as i64 * 1_000_000_000 / 300,
+            ))
+        })
+        .ok_or_else(|| TdsWireError::Protocol("datetime value out of range".to_string()))
+}
@@ -1,7 +1,9 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::TdsTokenCodec;
+use bigdecimal::num_bigint::{BigInt, Sign};
 use bigdecimal::BigDecimal;
-use tokio_util::bytes::{BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 pub(crate) fn encode(dest: &mut BytesMut, data: &Option<BigDecimal>) -> TdsWireResult<()> {
     if let Some(n) = data {
@@ -12,3 +14,41 @@ pub(crate) fn encode(dest: &mut BytesMut, data: &Option<BigDecimal>) -> TdsWireR
     }
     Ok(())
 }
+
+pub(crate) fn decode(src: &mut BytesMut, scale: u8) -> TdsWireResult<Option<BigDecimal>> {
+    check_remaining(src, 1, "numeric length")?;
+    let len = src.get_u8() as usize;
+    if len == 0 {
+        return Ok(None);
+    }
+    if src.remaining() < len {
+        return Err(TdsWireError::Protocol(
+            "Not enough data to decode numeric value".to_string(),
+        ));
+    }
+
+    let sign = if src.get_u8() == 0 {
+        Sign::Minus
+    } else {
+        Sign::Plus
+    };
+    let magnitude = src.split_to(len - 1);
+    let value = BigInt::from_bytes_le(sign, &magnitude);
+    Ok(Some(BigDecimal::new(value, scale as i64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::tds::codec::column_data::numeric;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+    use tokio_util::bytes::BytesMut;
+
+    #[test]
+    fn test_decode_numeric() {
+        // -123.45 with a scale of 2
+        let mut buf = BytesMut::from(&[0x05, 0x00, 0x39, 0x30, 0x00, 0x00][..]);
+        let value = numeric::decode(&mut buf, 2).unwrap();
+        assert_eq!(value, Some(BigDecimal::from_str("-123.45").unwrap()));
+    }
+}
@@ -1,34 +1,44 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+const PLP_NULL: u64 = 0xFFFFFFFFFFFFFFFF;
 
 /// Variable length-prefixed token [2.2.5.2.2]
 pub(crate) fn encode(dest: &mut BytesMut, type_length: &usize, data: Option<&String>) {
+    let data = data.map(|data| {
+        data.encode_utf16()
+            .flat_map(|b| b.to_le_bytes())
+            .collect::<Vec<u8>>()
+    });
+    encode_bytes(dest, type_length, data.as_deref());
+}
+
+/// Encode raw bytes, length-prefixed or as chunks for max types (of 0xffff length)
+pub(crate) fn encode_bytes(dest: &mut BytesMut, type_length: &usize, data: Option<&[u8]>) {
     match data {
         // Encoding a NULL
-        None => {
+        None if *type_length < 0xffff => {
             dest.put_u16_le(0xffff);
         }
+        None => {
+            dest.put_u64_le(PLP_NULL);
+        }
         Some(data) => {
-            // Fixed size
-            let mut data = BytesMut::from_iter(data.encode_utf16().flat_map(|b| b.to_le_bytes()));
-
             if *type_length < 0xffff {
                 // Encode the length first
                 dest.put_u16_le(data.len() as u16);
 
                 // Encode the actual data
-                dest.extend_from_slice(&data);
+                dest.extend_from_slice(data);
             } else {
                 // Unknown size, length-prefixed blobs
                 dest.put_u64_le(data.len() as u64);
 
-                while data.has_remaining() {
-                    // Determine the size of the next chunk
-                    let chunk_size = std::cmp::min(data.len(), 4035);
-                    dest.put_u32_le(chunk_size as u32);
-
+                for chunk in data.chunks(4035) {
                     // Encode the chunk data
-                    dest.extend_from_slice(&data.split_to(chunk_size));
+                    dest.put_u32_le(chunk.len() as u32);
+                    dest.extend_from_slice(chunk);
                 }
 
                 // Write a zero-length chunk as a sentinel
@@ -39,17 +49,62 @@ pub(crate) fn encode(dest: &mut BytesMut, type_length: &usize, data: Option<&Str
 }
 
 pub(crate) fn decode(src: &mut BytesMut, type_length: &usize) -> TdsWireResult<Option<String>> {
-    match *type_length {
-        0 => Ok(None),
-        n if n < 0xffff => {
-            let length = src.get_u16_le() as usize / 2;
-            let iter = (0..length).map(|_| u16::from_le_bytes([src.get_u8(), src.get_u8()]));
+    match decode_bytes(src, type_length)? {
+        None => Ok(None),
+        Some(data) => {
+            let iter = data
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]));
             Ok(std::char::decode_utf16(iter)
                 .collect::<Result<String, _>>()
                 .ok())
         }
+    }
+}
+
+/// Decode the raw bytes of a (possibly partially length-prefixed) value, none in case of NULL
+pub(crate) fn decode_bytes(
+    src: &mut BytesMut,
+    type_length: &usize,
+) -> TdsWireResult<Option<BytesMut>> {
+    match *type_length {
+        0 => Ok(None),
+        n if n < 0xffff => {
+            check_remaining(src, 2, "value length")?;
+            let length = src.get_u16_le();
+            if length == 0xffff {
+                return Ok(None);
+            }
+            split_checked(src, length as usize).map(Some)
+        }
         _ => {
-            todo!()
+            // partially length-prefixed, total length (or unknown) followed by chunks
+            check_remaining(src, 8, "PLP length")?;
+            let total_length = src.get_u64_le();
+            if total_length == PLP_NULL {
+                return Ok(None);
+            }
+
+            let mut data = BytesMut::new();
+            loop {
+                check_remaining(src, 4, "PLP chunk length")?;
+                let chunk_size = src.get_u32_le() as usize;
+                if chunk_size == 0 {
+                    break;
+                }
+                data.extend_from_slice(&split_checked(src, chunk_size)?);
+            }
+            Ok(Some(data))
         }
     }
 }
+
+fn split_checked(src: &mut BytesMut, length: usize) -> TdsWireResult<BytesMut> {
+    if src.remaining() < length {
+        return Err(TdsWireError::Protocol(format!(
+            "Not enough data to decode value of {} bytes",
+            length
+        )));
+    }
+    Ok(src.split_to(length))
+}
@@ -0,0 +1,39 @@
+use tokio_util::bytes::BytesMut;
+use unilake_common::error::TdsWireResult;
+
+/// Binary value, with the maximum length of its type used for encoding
+#[derive(Debug, Clone)]
+pub struct SqlBinary {
+    max_length: usize,
+    value: Option<Vec<u8>>,
+}
+
+impl SqlBinary {
+    pub fn from_bytes(value: Option<Vec<u8>>, max_length: usize) -> SqlBinary {
+        SqlBinary { max_length, value }
+    }
+
+    pub(crate) fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        super::plp::encode_bytes(dest, &self.max_length, self.value.as_deref());
+        Ok(())
+    }
+
+    pub(crate) fn decode(src: &mut BytesMut, max_len: usize) -> TdsWireResult<Self> {
+        Ok(SqlBinary::from_bytes(
+            super::plp::decode_bytes(src, &max_len)?.map(|b| b.to_vec()),
+            max_len,
+        ))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.value.is_none()
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        self.value.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.value.as_ref().map(|b| b.len()).unwrap_or(0)
+    }
+}
@@ -5,15 +5,38 @@ use unilake_common::error::TdsWireResult;
 pub struct SqlString {
     max_length: usize,
     value: Option<String>,
+    /// Encoded as UTF-8 (varchar) instead of UTF-16 (nvarchar)
+    utf8: bool,
 }
 
 impl SqlString {
     pub fn from_string(value: Option<String>, max_length: usize) -> SqlString {
-        SqlString { max_length, value }
+        SqlString {
+            max_length,
+            value,
+            utf8: false,
+        }
+    }
+
+    pub fn from_utf8_string(value: Option<String>, max_length: usize) -> SqlString {
+        SqlString {
+            max_length,
+            value,
+            utf8: true,
+        }
+    }
+
+    /// Encode the value as UTF-8 instead, with the given max length in bytes
+    pub fn set_utf8(&mut self, max_length: usize) {
+        self.max_length = max_length;
+        self.utf8 = true;
     }
 
     pub(crate) fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
-        if let Some(ref str) = self.value {
+        if self.utf8 {
+            let value = self.value.as_deref().map(str::as_bytes);
+            super::plp::encode_bytes(dest, &self.max_length, value);
+        } else if let Some(ref str) = self.value {
             super::plp::encode(dest, &self.max_length, Some(str));
         } else {
             super::plp::encode(dest, &self.max_length, None);
@@ -32,15 +55,24 @@ impl SqlString {
         self.value.is_none()
     }
 
+    pub fn as_str(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
     pub fn len(&self) -> usize {
         self.value.as_ref().map(|s| s.len()).unwrap_or(0)
     }
 
+    pub fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+
     pub fn new_empty(ty: &crate::frontend::TypeInfo) -> SqlString {
         match ty {
             crate::frontend::TypeInfo::VarLenSized(l) => SqlString {
                 max_length: l.len(),
                 value: None,
+                utf8: l.collation().is_some_and(|c| c.is_utf8()),
             },
             _ => unreachable!(),
         }
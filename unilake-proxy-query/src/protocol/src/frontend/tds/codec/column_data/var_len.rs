@@ -1,8 +1,10 @@
+use super::{date, datetime2, fixed_len, plp};
+use crate::frontend::sqlbinary::SqlBinary;
 use crate::frontend::sqlstring::SqlString;
-use crate::frontend::tds::codec::decode::read_us_varchar;
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::{ColumnData, VarLenContext, VarLenType};
-use tokio_util::bytes::{BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 /// Variable length token [2.2.4.2.1.3]
 pub(crate) fn encode(dst: &mut BytesMut, data: &ColumnData) -> TdsWireResult<()> {
@@ -42,36 +44,104 @@ pub(crate) fn encode(dst: &mut BytesMut, data: &ColumnData) -> TdsWireResult<()>
     Ok(())
 }
 
-pub(crate) fn decode(
-    src: &mut BytesMut,
-    context: &VarLenContext,
-) -> TdsWireResult<Option<ColumnData>> {
-    // push null
-    match context.r#type() {
-        VarLenType::Intn => {}
-        VarLenType::Bitn => {}
-        VarLenType::Decimaln => {}
-        VarLenType::Numericn => {}
-        VarLenType::Floatn => {}
-        VarLenType::Datetimen => {}
-        VarLenType::Daten => {}
-        VarLenType::Timen => {}
-        VarLenType::Datetime2 => {}
-        VarLenType::DatetimeOffsetn => {}
-        VarLenType::BigVarBin => {}
-        VarLenType::BigVarChar => {}
-        VarLenType::BigBinary => {}
-        VarLenType::BigChar => {}
-        VarLenType::NVarchar => {
-            let string = read_us_varchar(src)?;
-            return Ok(Some(ColumnData::String(SqlString::from_string(
-                string.into(),
-                0,
-            ))));
-        }
-        VarLenType::NChar => {}
-        VarLenType::SSVariant => {}
-    }
+pub(crate) fn decode(src: &mut BytesMut, context: &VarLenContext) -> TdsWireResult<ColumnData> {
+    let ty = context.r#type();
+    let mut data = match ty {
+        VarLenType::NVarchar | VarLenType::NChar => {
+            return Ok(ColumnData::String(SqlString::decode(src, context.len())?))
+        }
+        VarLenType::BigVarChar | VarLenType::BigChar
+            if context.collation().is_some_and(|c| c.is_utf8()) =>
+        {
+            let value = plp::decode_bytes(src, &context.len())?
+                .map(|b| String::from_utf8(b.to_vec()))
+                .transpose()
+                .map_err(|_| TdsWireError::Protocol("Invalid UTF-8 character data".to_string()))?;
+            return Ok(ColumnData::String(SqlString::from_utf8_string(
+                value,
+                context.len(),
+            )));
+        }
+        VarLenType::BigVarChar | VarLenType::BigChar => {
+            // single byte character data, decoded as latin1
+            let value = plp::decode_bytes(src, &context.len())?
+                .map(|b| b.iter().map(|c| *c as char).collect::<String>());
+            return Ok(ColumnData::String(SqlString::from_string(
+                value,
+                context.len(),
+            )));
+        }
+        VarLenType::BigVarBin | VarLenType::BigBinary => {
+            return Ok(ColumnData::Binary(SqlBinary::decode(src, context.len())?));
+        }
+        VarLenType::SSVariant | VarLenType::Decimaln | VarLenType::Numericn => {
+            return Err(TdsWireError::Protocol(format!(
+                "Unsupported variable length type: {:?}",
+                ty
+            )))
+        }
+        _ => {
+            check_remaining(src, 1, "value length")?;
+            let len = src.get_u8() as usize;
+            if src.remaining() < len {
+                return Err(TdsWireError::Protocol(format!(
+                    "Not enough data to decode value of type {:?}",
+                    ty
+                )));
+            }
+            src.split_to(len)
+        }
+    };
 
-    Ok(None)
+    Ok(match (ty, data.len()) {
+        (VarLenType::Intn, 0) => match context.len() {
+            1 => ColumnData::U8N(None),
+            2 => ColumnData::I16N(None),
+            4 => ColumnData::I32N(None),
+            _ => ColumnData::I64N(None),
+        },
+        (VarLenType::Intn, 1) => ColumnData::U8N(Some(data.get_u8())),
+        (VarLenType::Intn, 2) => ColumnData::I16N(Some(data.get_i16_le())),
+        (VarLenType::Intn, 4) => ColumnData::I32N(Some(data.get_i32_le())),
+        (VarLenType::Intn, 8) => ColumnData::I64N(Some(data.get_i64_le())),
+        (VarLenType::Bitn, 0) => ColumnData::BitN(None),
+        (VarLenType::Bitn, 1) => ColumnData::BitN(Some(data.get_u8() != 0)),
+        (VarLenType::Floatn, 0) => match context.len() {
+            4 => ColumnData::F32N(None),
+            _ => ColumnData::F64N(None),
+        },
+        (VarLenType::Floatn, 4) => ColumnData::F32N(Some(data.get_f32_le())),
+        (VarLenType::Floatn, 8) => ColumnData::F64N(Some(data.get_f64_le())),
+        (VarLenType::Datetimen, 0) => ColumnData::DateTime(None),
+        (VarLenType::Datetimen, 4) => {
+            ColumnData::SmallDateTime(Some(fixed_len::decode_small_datetime(&mut data)))
+        }
+        (VarLenType::Datetimen, 8) => {
+            ColumnData::DateTime(Some(fixed_len::decode_datetime(&mut data)?))
+        }
+        (VarLenType::Daten, 0) => ColumnData::Date(None),
+        (VarLenType::Daten, 3) => ColumnData::Date(Some(date::decode(&mut data)?)),
+        (VarLenType::Timen, 0) => ColumnData::Time(None),
+        (VarLenType::Timen, 3..=5) => {
+            ColumnData::Time(Some(datetime2::decode_time(&mut data, context.len())?))
+        }
+        (VarLenType::Datetime2, 0) => ColumnData::DateTime2(None),
+        (VarLenType::Datetime2, 6..=8) => {
+            let time = datetime2::decode_time(&mut data, context.len())?;
+            ColumnData::DateTime2(Some(date::decode(&mut data)?.and_time(time)))
+        }
+        (VarLenType::DatetimeOffsetn, 0) => ColumnData::DateTimeOffset(None),
+        (VarLenType::DatetimeOffsetn, 8..=10) => {
+            // value is stored as UTC, the offset only tells the time zone of the client and is
+            // ignored. The value must therefore be rendered as UTC.
+            let time = datetime2::decode_time(&mut data, context.len())?;
+            ColumnData::DateTimeOffset(Some(date::decode(&mut data)?.and_time(time)))
+        }
+        (ty, len) => {
+            return Err(TdsWireError::Protocol(format!(
+                "Invalid value length {} for type {:?}",
+                len, ty
+            )))
+        }
+    })
 }
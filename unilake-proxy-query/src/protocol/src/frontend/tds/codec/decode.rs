@@ -1,18 +1,63 @@
+use crate::frontend::tds::codec::AllHeaderTy;
 use tokio_util::bytes::{Buf, BytesMut};
 use unilake_common::error::{TdsWireError, TdsWireResult};
 
+/// Check if enough bytes remain to read the given length, a message which ends prematurely is
+/// malformed and cannot be decoded
+pub fn check_remaining<B: Buf>(src: &B, length: usize, what: &str) -> TdsWireResult<()> {
+    if src.remaining() < length {
+        return Err(TdsWireError::Protocol(format!(
+            "Unexpected end of message reading {}, expected {} bytes, {} remaining",
+            what,
+            length,
+            src.remaining()
+        )));
+    }
+    Ok(())
+}
+
+/// Read the ALL_HEADERS rule [2.2.5.3], returns the transaction descriptor and the outstanding
+/// request count
+pub fn read_all_headers(src: &mut BytesMut) -> TdsWireResult<(u64, u32)> {
+    check_remaining(src, 4, "headers length")?;
+    let total_length = src.get_u32_le() as usize;
+    if total_length < 4 || src.remaining() < total_length - 4 {
+        return Err(TdsWireError::Protocol("invalid headers length".to_string()));
+    }
+
+    let mut headers = src.split_to(total_length - 4);
+    let mut transaction_descriptor = 0;
+    let mut outstanding_requests = 1;
+    while headers.remaining() >= 6 {
+        let header_length = headers.get_u32_le() as usize;
+        if header_length < 6 || headers.remaining() < header_length - 4 {
+            return Err(TdsWireError::Protocol("invalid header length".to_string()));
+        }
+        let header_type = headers.get_u16_le();
+        let mut header = headers.split_to(header_length - 6);
+        if header_type == AllHeaderTy::TransactionDescriptor as u16 && header.remaining() >= 12 {
+            transaction_descriptor = header.get_u64_le();
+            outstanding_requests = header.get_u32_le();
+        }
+    }
+    Ok((transaction_descriptor, outstanding_requests))
+}
+
 pub fn read_us_varchar(src: &mut BytesMut) -> TdsWireResult<String> {
+    check_remaining(src, 2, "varchar length")?;
     let length = src.get_u16_le() as usize;
     read_string(src, length)
 }
 
 pub fn read_b_varchar(src: &mut BytesMut) -> TdsWireResult<String> {
+    check_remaining(src, 1, "varchar length")?;
     let length = src.get_u8() as usize;
     read_string(src, length)
 }
 
-fn read_string(src: &mut BytesMut, length: usize) -> TdsWireResult<String> {
+pub fn read_string(src: &mut BytesMut, length: usize) -> TdsWireResult<String> {
     if length > 0 {
+        check_remaining(src, length * 2, "varchar")?;
         // Read the UTF-16 encoded bytes and decode them into a String
         let mut utf16_data = Vec::with_capacity(length * 2);
         (0..length).for_each(|_| utf16_data.push(src.get_u16_le()));
@@ -0,0 +1,100 @@
+use crate::frontend::tds::codec::decode::read_string;
+use crate::frontend::{TdsMessage, TdsMessageCodec};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Federated Authentication Token message [2.2.6.3]
+/// Sent by the client in response to the FEDAUTHINFO token, contains the access token the client
+/// acquired using the ADAL/MSAL workflow.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct FedAuthTokenMessage {
+    pub token: String,
+    /// Nonce echoed back from the pre-login response
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl FedAuthTokenMessage {
+    pub fn new(token: String, nonce: Option<Vec<u8>>) -> Self {
+        FedAuthTokenMessage { token, nonce }
+    }
+}
+
+impl TdsMessageCodec for FedAuthTokenMessage {
+    fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage>
+    where
+        Self: Sized,
+    {
+        if src.remaining() < 8 {
+            return Err(TdsWireError::Protocol(
+                "Federated authentication token message too short".to_string(),
+            ));
+        }
+        let data_len = src.get_u32_le() as usize;
+        let token_len = src.get_u32_le() as usize;
+        if token_len % 2 == 1 || token_len + 4 > data_len || src.remaining() < data_len - 4 {
+            return Err(TdsWireError::Protocol(
+                "Invalid federated authentication token length".to_string(),
+            ));
+        }
+
+        let token = read_string(src, token_len / 2)?;
+        let nonce = match data_len - 4 - token_len {
+            0 => None,
+            32 => Some(src.split_to(32).to_vec()),
+            _ => {
+                return Err(TdsWireError::Protocol(
+                    "Invalid federated authentication nonce length".to_string(),
+                ))
+            }
+        };
+
+        Ok(TdsMessage::FedAuthToken(FedAuthTokenMessage {
+            token,
+            nonce,
+        }))
+    }
+
+    fn encode(&self, dst: &mut BytesMut) -> TdsWireResult<()> {
+        let token = self
+            .token
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let nonce = self.nonce.as_deref().unwrap_or_default();
+
+        dst.put_u32_le((4 + token.len() + nonce.len()) as u32);
+        dst.put_u32_le(token.len() as u32);
+        dst.put_slice(&token);
+        dst.put_slice(nonce);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{FedAuthTokenMessage, TdsMessage, TdsMessageCodec};
+    use tokio_util::bytes::BytesMut;
+
+    #[test]
+    fn fed_auth_token_round_trip() {
+        let input = FedAuthTokenMessage::new("eyJ0eXAiOiJKV1QifQ".to_string(), Some(vec![7u8; 32]));
+        let mut buff = BytesMut::new();
+        input.encode(&mut buff).unwrap();
+
+        let result = FedAuthTokenMessage::decode(&mut buff).unwrap();
+
+        assert!(buff.is_empty());
+        match result {
+            TdsMessage::FedAuthToken(result) => assert_eq!(result, input),
+            _ => panic!("unexpected message type"),
+        }
+    }
+
+    #[test]
+    fn fed_auth_token_invalid_length() {
+        // data length does not cover the token length
+        let mut buff = BytesMut::from(&[0x06, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00][..]);
+        assert!(FedAuthTokenMessage::decode(&mut buff).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use enumflags2::{bitflags, BitFlags};
 use std::fmt;
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
@@ -134,6 +135,7 @@ impl PacketHeader {
     }
 
     pub fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        check_remaining(src, 8, "packet header")?;
         let raw_ty = src.get_u8();
         let ty = PacketType::try_from(raw_ty).map_err(|_| {
             unilake_common::error::Error::Protocol(
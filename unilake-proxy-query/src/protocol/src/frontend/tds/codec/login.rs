@@ -1,9 +1,9 @@
+use crate::frontend::tds::codec::decode::{check_remaining, read_string};
+use crate::frontend::tds::codec::SessionRecoveryExt;
 use crate::frontend::{utils::ReadAndAdvance, TdsMessage, TdsMessageCodec};
 use byteorder::{ByteOrder, LittleEndian};
-use core::panic;
 use enumflags2::{bitflags, BitFlags};
 use std::fmt::Debug;
-use std::ops::Index;
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::{TdsWireError, TdsWireResult};
 
@@ -146,14 +146,103 @@ pub enum LoginTypeFlag {
 }
 
 const FIXED_LEN: usize = 94;
+const FED_AUTH_LIBRARY_LIVEID: u8 = 0x00;
+const FED_AUTH_LIBRARY_SECURITYTOKEN: u8 = 0x01;
+const FED_AUTH_LIBRARY_ADAL: u8 = 0x02;
+
+/// The library used by the client to obtain the federated authentication token
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FedAuthLibrary {
+    /// Live ID compact token, the token is part of the login request (not supported)
+    LiveIdCompactToken,
+    /// The token is part of the login request
+    #[default]
+    SecurityToken,
+    /// The token is acquired by the client (ADAL/MSAL) after receiving the FEDAUTHINFO token and
+    /// is sent in a separate federated authentication token message
+    Adal { workflow: u8 },
+}
 
 /// https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-tds/773a62b6-ee89-4c02-9e5e-344882630aac
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct FedAuthExt {
-    fed_auth_echo: bool,
-    fed_auth_token: String,
-    nonce: Option<Vec<u8>>,
+    /// Set if the client received FEDAUTHREQUIRED in the pre-login response
+    pub fed_auth_echo: bool,
+    pub library: FedAuthLibrary,
+    /// Access token, only available for the security token library
+    pub fed_auth_token: String,
+    /// Nonce echoed back from the pre-login response
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl FedAuthExt {
+    fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        if !src.has_remaining() {
+            return Err(TdsWireError::Protocol(
+                "FedAuth feature extension is empty".to_string(),
+            ));
+        }
+        let options = src.get_u8();
+        let fed_auth_echo = (options & 1) == 1;
+
+        match options >> 1 {
+            library @ (FED_AUTH_LIBRARY_LIVEID | FED_AUTH_LIBRARY_SECURITYTOKEN) => {
+                if src.remaining() < 4 {
+                    return Err(TdsWireError::Protocol(
+                        "FedAuth token length is missing".to_string(),
+                    ));
+                }
+                let token_len = src.get_u32_le() as usize;
+                if token_len % 2 == 1 || src.remaining() < token_len {
+                    return Err(TdsWireError::Protocol(
+                        "Invalid FedAuth token length".to_string(),
+                    ));
+                }
+                let fed_auth_token = read_string(src, token_len / 2)?;
+                // the nonce can be followed by a channel binding token and signature, which we ignore
+                let nonce = match src.remaining() {
+                    0 => None,
+                    n if n >= 32 => Some(src.split_to(32).to_vec()),
+                    _ => {
+                        return Err(TdsWireError::Protocol(
+                            "Invalid FedAuth nonce length".to_string(),
+                        ))
+                    }
+                };
+
+                Ok(FedAuthExt {
+                    fed_auth_echo,
+                    library: if library == FED_AUTH_LIBRARY_LIVEID {
+                        FedAuthLibrary::LiveIdCompactToken
+                    } else {
+                        FedAuthLibrary::SecurityToken
+                    },
+                    fed_auth_token,
+                    nonce,
+                })
+            }
+            FED_AUTH_LIBRARY_ADAL => {
+                if !src.has_remaining() {
+                    return Err(TdsWireError::Protocol(
+                        "FedAuth workflow is missing".to_string(),
+                    ));
+                }
+                Ok(FedAuthExt {
+                    fed_auth_echo,
+                    library: FedAuthLibrary::Adal {
+                        workflow: src.get_u8(),
+                    },
+                    fed_auth_token: String::new(),
+                    nonce: None,
+                })
+            }
+            library => Err(TdsWireError::Protocol(format!(
+                "Unsupported FedAuth library: {}",
+                library
+            ))),
+        }
+    }
 }
 
 /// Login7 Message [2.2.6.4]
@@ -193,9 +282,15 @@ pub struct LoginMessage {
     /// the default database to connect to
     pub db_name: Option<String>,
     pub fed_auth_ext: Option<FedAuthExt>,
+    /// set when the client supports connection resiliency
+    pub session_recovery: Option<SessionRecoveryExt>,
+    /// set when the client supports UTF-8 encoded character data
+    pub utf8_support: bool,
+    /// highest data classification version supported by the client, if any
+    pub data_classification: Option<u8>,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 enum VariableProperty {
     HostName,
     UserName,
@@ -209,8 +304,6 @@ enum VariableProperty {
     SSPI,
     AttachedDatabaseFile,
     ChangePassword,
-    ClientId,
-    Unused,
 }
 
 impl LoginMessage {
@@ -230,59 +323,149 @@ impl LoginMessage {
 
         self.fed_auth_ext = Some(FedAuthExt {
             fed_auth_echo,
+            library: FedAuthLibrary::SecurityToken,
             fed_auth_token: token.into(),
             nonce,
         })
     }
+
+    /// Returns true if the client will send the access token in a separate federated
+    /// authentication token message, after receiving the FEDAUTHINFO token
+    pub fn expects_fed_auth_token_message(&self) -> bool {
+        matches!(
+            self.fed_auth_ext,
+            Some(FedAuthExt {
+                library: FedAuthLibrary::Adal { .. },
+                ..
+            })
+        )
+    }
+}
+
+/// Write the offset and length of a variable length string to the offset table and the
+/// UTF-16 encoded string to the data, passwords are obfuscated [2.2.6.4]
+fn put_variable_string(
+    offsets: &mut BytesMut,
+    data: &mut BytesMut,
+    value: &Option<String>,
+    obfuscate: bool,
+) {
+    let value = value.as_deref().unwrap_or_default();
+    offsets.put_u16_le((FIXED_LEN + data.len()) as u16);
+    offsets.put_u16_le(value.encode_utf16().count() as u16);
+    for byte in value.encode_utf16().flat_map(|x| x.to_le_bytes()) {
+        if obfuscate {
+            data.put_u8(((byte << 4) & 0xf0 | (byte >> 4) & 0x0f) ^ 0xA5);
+        } else {
+            data.put_u8(byte);
+        }
+    }
 }
 
 impl TdsMessageCodec for LoginMessage {
-    #[rustfmt::skip]
-    fn encode(&self, dst: &mut BytesMut) -> TdsWireResult<()>
-    {
-        let get_length = |opt: &Option<String>| opt.as_ref().map_or(0, |s| s.len());
-        let mut total_length = FIXED_LEN + // fixed packet length
-            get_length(&self.hostname) +
-            get_length(&self.username) +
-            get_length(&self.password) +
-            get_length(&self.app_name) +
-            get_length(&self.server_name) +
-            get_length(&self.library_name) +
-            get_length(&self.language) +
-            get_length(&self.db_name) +
-            get_length(&self.attached_database) +
-            get_length(&self.change_password) +
-            0 + // sspi, not needed
-            0; // extensions
-
-        let mut fed_auth_buf = BytesMut::new();
-        if let Some(ext) = &self.fed_auth_ext {
-            fed_auth_buf.put_u8(FeatureExt::FedAuth as u8);
-
-            // TODO: missing here are, ChannelBindingToken, Signature and MSAL, decide if needed
-            let token = ext.fed_auth_token.encode_utf16().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
-            let feature_ext_length = 1 + 4 + token.len() + if ext.nonce.is_some() { 32 } else { 0 };
-            fed_auth_buf.put_u32_le(feature_ext_length as u32);
-
-            // let mut options: u8 = FED_AUTH_LIBRARY_SECURITYTOKEN << 1;
-            // if ext.fed_auth_echo {
-            //     options |= 1;
-            // }
-            // fed_auth_buf.put_u8(options);
-
-            fed_auth_buf.put_u32_le(token.len() as u32);
-            fed_auth_buf.put_slice(&token);
-
-            if let Some(nonce) = &ext.nonce {
-                fed_auth_buf.put_slice(&nonce);
-            }
+    fn encode(&self, dst: &mut BytesMut) -> TdsWireResult<()> {
+        if self.integrated_security.is_some() {
+            return Err(TdsWireError::Protocol(
+                "Integrated security is not supported".to_string(),
+            ));
+        }
 
-            fed_auth_buf.put_u8(FeatureExt::Terminator as u8);
+        // variable length data, the offsets are relative to the start of the message
+        let mut offsets = BytesMut::with_capacity(FIXED_LEN);
+        let mut data = BytesMut::new();
+        put_variable_string(&mut offsets, &mut data, &self.hostname, false);
+        put_variable_string(&mut offsets, &mut data, &self.username, false);
+        put_variable_string(&mut offsets, &mut data, &self.password, true);
+        put_variable_string(&mut offsets, &mut data, &self.app_name, false);
+        put_variable_string(&mut offsets, &mut data, &self.server_name, false);
+
+        // the extension holds the offset of the feature extensions, which are placed at the end
+        let extension_used = self.option_flags_3.contains(OptionFlag3::ExtensionUsed);
+        let feature_ext_pointer = data.len();
+        offsets.put_u16_le((FIXED_LEN + data.len()) as u16);
+        if extension_used {
+            offsets.put_u16_le(4);
+            data.put_u32_le(0);
+        } else {
+            offsets.put_u16_le(0);
         }
-        total_length += fed_auth_buf.len();
 
+        put_variable_string(&mut offsets, &mut data, &self.library_name, false);
+        put_variable_string(&mut offsets, &mut data, &self.language, false);
+        put_variable_string(&mut offsets, &mut data, &self.db_name, false);
+
+        // client id
+        offsets.put_u32_le(0); // TODO: get real client id
+        offsets.put_u16_le(42);
+
+        // sspi, not supported
+        offsets.put_u16_le((FIXED_LEN + data.len()) as u16);
+        offsets.put_u16_le(0);
+
+        put_variable_string(&mut offsets, &mut data, &self.attached_database, false);
+        put_variable_string(&mut offsets, &mut data, &self.change_password, true);
+
+        // skip long SSPI
+        offsets.put_u32_le(0);
+
+        if extension_used {
+            let position = ((FIXED_LEN + data.len()) as u32).to_le_bytes();
+            data[feature_ext_pointer..feature_ext_pointer + 4].copy_from_slice(&position);
+
+            if let Some(ext) = &self.fed_auth_ext {
+                data.put_u8(FeatureExt::FedAuth as u8);
+
+                // TODO: missing here are, ChannelBindingToken and Signature, decide if needed
+                let echo = ext.fed_auth_echo as u8;
+                match ext.library {
+                    FedAuthLibrary::LiveIdCompactToken | FedAuthLibrary::SecurityToken => {
+                        let library = if ext.library == FedAuthLibrary::LiveIdCompactToken {
+                            FED_AUTH_LIBRARY_LIVEID
+                        } else {
+                            FED_AUTH_LIBRARY_SECURITYTOKEN
+                        };
+                        let token = ext
+                            .fed_auth_token
+                            .encode_utf16()
+                            .flat_map(|x| x.to_le_bytes())
+                            .collect::<Vec<u8>>();
+                        let nonce_length = ext.nonce.as_ref().map_or(0, |n| n.len());
+                        data.put_u32_le((1 + 4 + token.len() + nonce_length) as u32);
+                        data.put_u8(library << 1 | echo);
+                        data.put_u32_le(token.len() as u32);
+                        data.put_slice(&token);
+
+                        if let Some(nonce) = &ext.nonce {
+                            data.put_slice(nonce);
+                        }
+                    }
+                    FedAuthLibrary::Adal { workflow } => {
+                        data.put_u32_le(2);
+                        data.put_u8(FED_AUTH_LIBRARY_ADAL << 1 | echo);
+                        data.put_u8(workflow);
+                    }
+                }
+            }
+            if let Some(ext) = &self.session_recovery {
+                let mut ext_data = BytesMut::new();
+                ext.encode(&mut ext_data)?;
+                data.put_u8(FeatureExt::SessionRecovery as u8);
+                data.put_u32_le(ext_data.len() as u32);
+                data.put_slice(&ext_data);
+            }
+            if self.utf8_support {
+                data.put_u8(FeatureExt::Utf8Support as u8);
+                data.put_u32_le(0);
+            }
+            if let Some(version) = self.data_classification {
+                data.put_u8(FeatureExt::DataClassification as u8);
+                data.put_u32_le(1);
+                data.put_u8(version);
+            }
+            data.put_u8(FeatureExt::Terminator as u8);
+        }
 
-        dst.put_u32_le(total_length as u32);
+        dst.put_u32_le((FIXED_LEN + data.len()) as u32);
         dst.put_u32_le(self.tds_version as u32);
         dst.put_u32_le(self.packet_size);
         dst.put_u32_le(self.client_prog_ver);
@@ -297,103 +480,8 @@ impl TdsMessageCodec for LoginMessage {
         dst.put_u32_le(self.client_timezone as u32);
         dst.put_u32_le(self.client_lcid);
 
-        // variable length data
-        let mut options = Vec::<(VariableProperty, usize, usize, &Option<String>)>::with_capacity(13);
-        let get_position = |v: &Vec<(VariableProperty, usize, usize, &Option<String>)>|
-            v.last().unwrap().1 + v.last().unwrap().2;
-
-        options.push((VariableProperty::HostName, FIXED_LEN, get_length(&self.hostname)*2, &self.hostname));
-        options.push((VariableProperty::UserName, get_position(&options), get_length(&self.username)*2, &self.username));
-        options.push((VariableProperty::Password, get_position(&options), get_length(&self.password)*2, &self.password));
-        options.push((VariableProperty::ApplicationName, get_position(&options), get_length(&self.app_name)*2, &self.app_name));
-        options.push((VariableProperty::ServerName, get_position(&options), get_length(&self.server_name)*2, &self.server_name));
-
-        // check if we have extensions
-        if self.option_flags_3.contains(OptionFlag3::ExtensionUsed) {
-            options.push((VariableProperty::FeatureExt, get_position(&options), 4, &None));
-        } else {
-            options.push((VariableProperty::Unused, get_position(&options), 0, &None));
-        }
-        options.push((VariableProperty::LibraryName, get_position(&options), get_length(&self.library_name)*2, &self.library_name));
-        options.push((VariableProperty::Language, get_position(&options), get_length(&self.language)*2, &self.language));
-        options.push((VariableProperty::Database, get_position(&options), get_length(&self.db_name)*2, &self.db_name));
-
-        let last_position = get_position(&options);
-        options.push((VariableProperty::ClientId, 0, 0, &None));
-
-        if let Some(is) = &self.integrated_security {
-            options.push((VariableProperty::SSPI, last_position, is.len(), &None));
-        } else {
-            options.push((VariableProperty::SSPI, last_position, 0, &None));
-        }
-        options.push((VariableProperty::AttachedDatabaseFile, get_position(&options), get_length(&self.attached_database)*2, &self.attached_database));
-        options.push((VariableProperty::ChangePassword, get_position(&options), get_length(&self.change_password)*2, &self.change_password));
-
-        for (ty, position, length, _) in &options {
-            match ty {
-                VariableProperty::ClientId => {
-                    dst.put_u32_le(0); // TODO: get real client id
-                    dst.put_u16_le(42);
-                }
-                _ => {
-                    dst.put_u16_le(*length as u16);
-                    dst.put_u16_le(*position as u16);
-                }
-            }
-        }
-
-        // skip long SSPI
-        dst.put_u32_le(0);
-
-        let mut feature_ext_found = false;
-        let mut current_option = 0;
-        while current_option < options.len() {
-            let (ty, _, length, data) = &options.index(current_option);
-            if *length == 0 {
-                current_option += 1;
-                continue;
-            }
-
-            match ty {
-                VariableProperty::Password | VariableProperty::ChangePassword => {
-                    panic!("todo");
-                    // todo(mrhamburg) check and fix this
-                    // let b = if *ty == VariableProperty::Password {
-                    //     &self.password
-                    // } else {
-                    //     &self.change_password
-                    // };
-                    // for byte in b.encode_utf16().flat_map(|x| x.to_le_bytes()) {
-                    //     dst.write_u8(((byte << 4) & 0xf0 | (byte >> 4) & 0x0f) ^ 0xA5).await?;
-                    // }
-                }
-                VariableProperty::FeatureExt => {
-                    if !feature_ext_found {
-                        let position = options.last().unwrap().1 + options.last().unwrap().2;
-                        dst.put_u32_le(position as u32);
-                        feature_ext_found = true;
-                        current_option -= 1;
-                        continue;
-                    }
-                    dst.put_slice(&fed_auth_buf);
-                }
-                VariableProperty::SSPI => {
-                    // TODO, this
-                    todo!();
-                }
-                _ => {
-                    if data.is_some() {
-                        dst.put_slice(data.as_ref().unwrap()
-                            .encode_utf16()
-                            .flat_map(|x| x.to_le_bytes())
-                            .collect::<Vec<u8>>()
-                            .as_slice());
-                    }
-                }
-            }
-
-            current_option += 1;
-        }
+        dst.put_slice(&offsets);
+        dst.put_slice(&data);
 
         Ok(())
     }
@@ -401,6 +489,7 @@ impl TdsMessageCodec for LoginMessage {
     fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage> {
         // For decoding the clientid: https://docs.rs/mac_address/latest/src/mac_address/lib.rs.html#167
         let mut ret = Self::new();
+        check_remaining(src, FIXED_LEN, "LOGIN7 header")?;
 
         // Decode Packet Header
         let length = src.get_u32_le();
@@ -408,19 +497,23 @@ impl TdsMessageCodec for LoginMessage {
             return Err(TdsWireError::Protocol("Login message too long".to_string()));
         }
 
-        ret.tds_version =
-            FeatureLevel::try_from(src.get_u32_le()).expect("Cannot parse feature level");
+        let tds_version = src.get_u32_le();
+        ret.tds_version = FeatureLevel::try_from(tds_version).map_err(|_| {
+            TdsWireError::Protocol(format!("Unsupported TDS version: {:#x}", tds_version))
+        })?;
         ret.packet_size = src.get_u32_le();
         ret.client_prog_ver = src.get_u32_le();
         ret.client_pid = src.get_u32_le();
         ret.connection_id = src.get_u32_le();
+        let invalid_flags = |name: &str| TdsWireError::Protocol(format!("Invalid {}", name));
         ret.option_flags_1 =
-            BitFlags::from_bits(src.get_u8()).expect("option_flags_1 verification");
+            BitFlags::from_bits(src.get_u8()).map_err(|_| invalid_flags("option_flags_1"))?;
         ret.option_flags_2 =
-            BitFlags::from_bits(src.get_u8()).expect("option_flags_2 verification");
-        ret.type_flags = BitFlags::from_bits(src.get_u8()).expect("type_flags verification");
+            BitFlags::from_bits(src.get_u8()).map_err(|_| invalid_flags("option_flags_2"))?;
+        ret.type_flags =
+            BitFlags::from_bits(src.get_u8()).map_err(|_| invalid_flags("type_flags"))?;
         ret.option_flags_3 =
-            BitFlags::from_bits(src.get_u8()).expect("option_flags_3 verification");
+            BitFlags::from_bits(src.get_u8()).map_err(|_| invalid_flags("option_flags_3"))?;
         ret.client_timezone = src.get_u32_le() as i32;
         ret.client_lcid = src.get_u32_le();
 
@@ -563,18 +656,25 @@ impl TdsMessageCodec for LoginMessage {
                 continue;
             }
 
-            // skip data between options
-            let diff = *offset - current_offset;
-            if diff > 0 {
-                src.advance(diff);
-                current_offset += diff;
+            // options are expected in order, skip data between options
+            if *offset < current_offset {
+                return Err(TdsWireError::Protocol(format!(
+                    "Invalid offset for {:?}",
+                    property
+                )));
             }
+            let diff = *offset - current_offset;
 
             // real length is x2 since we need 2 bytes for each read (besides exceptions)
             let length = match property {
                 VariableProperty::FeatureExt => *length,
                 _ => *length * 2,
             };
+            check_remaining(src, diff + length, "LOGIN7 variable data")?;
+            if diff > 0 {
+                src.advance(diff);
+                current_offset += diff;
+            }
 
             match property {
                 VariableProperty::Password | VariableProperty::ChangePassword => {
@@ -608,7 +708,13 @@ impl TdsMessageCodec for LoginMessage {
                     let (_, _) = src.read_and_advance(length);
                 }
                 VariableProperty::FeatureExt => {
+                    if length < 4 {
+                        return Err(TdsWireError::Protocol(
+                            "Invalid FeatureExt offset".to_string(),
+                        ));
+                    }
                     feature_ext_offset = src.get_u32_le() as usize;
+                    src.advance(length - 4);
                 }
                 _ => {
                     let (_, buff) = src.read_and_advance(length);
@@ -650,9 +756,6 @@ impl TdsMessageCodec for LoginMessage {
                         VariableProperty::ChangePassword => {
                             ret.change_password = value;
                         }
-                        VariableProperty::ClientId => {
-                            ret.client_id = value;
-                        }
                         _ => {}
                     }
                 }
@@ -667,9 +770,15 @@ impl TdsMessageCodec for LoginMessage {
             return Ok(TdsMessage::Login(ret));
         }
 
+        // skip data up to the feature extensions
+        if feature_ext_offset > current_offset {
+            src.advance((feature_ext_offset - current_offset).min(src.remaining()));
+        }
+
         // fetch feature extensions
         loop {
             // get type
+            check_remaining(src, 1, "FeatureExt")?;
             let feature_type = FeatureExt::try_from(src.get_u8()).map_err(|_| {
                 unilake_common::error::Error::Protocol("Invalid FeatureExt found".into())
             })?;
@@ -678,100 +787,43 @@ impl TdsMessageCodec for LoginMessage {
                 break;
             }
 
+            check_remaining(src, 4, "FeatureExt length")?;
             let length = src.get_u32_le() as usize;
-            let (_, mut buff) = src.read_and_advance(length);
+            check_remaining(src, length, "FeatureExt data")?;
+            let mut buff = src.split_to(length);
 
             match feature_type {
-                FeatureExt::SessionRecovery => continue,
+                FeatureExt::SessionRecovery => {
+                    ret.session_recovery = Some(SessionRecoveryExt::decode(&mut buff)?);
+                }
                 FeatureExt::FedAuth => {
-                    let _options = buff.get_u8();
-                    let token_len = buff.get_u32_le() as usize;
-
-                    let token = {
-                        let (_, buff) = buff.read_and_advance(token_len);
-                        // todo(mrhamburg): improve this
-                        let buff = buff
-                            .chunks(2)
-                            .map(|x| LittleEndian::read_u16(&x[..]))
-                            .collect::<Vec<u16>>();
-                        String::from_utf16(&buff[..]).expect("Failed to convert token to UTF-16")
-                    };
+                    ret.fed_auth_ext = Some(FedAuthExt::decode(&mut buff)?);
                 }
                 FeatureExt::ColumnEncryption => continue,
                 FeatureExt::GlobalTransactions => continue,
                 FeatureExt::AzureSqlSupport => continue,
-                FeatureExt::DataClassification => continue,
-                FeatureExt::Utf8Support => continue,
+                FeatureExt::DataClassification => {
+                    check_remaining(&buff, 1, "data classification version")?;
+                    ret.data_classification = Some(buff.get_u8());
+                }
+                FeatureExt::Utf8Support => {
+                    ret.utf8_support = true;
+                }
                 FeatureExt::AzureSqlDnsCaching => continue,
                 _ => break,
             }
         }
 
-        //             loop {
-        //         let fe = FeatureExt::try_from(src.get_u8()).map_err(|_| {
-        //             Error::Protocol("header: invalid feature extension type".into())
-        //         })?;
-        //         match fe {
-        //             FeatureExt::SessionRecovery => todo!(),
-        //             FeatureExt::FedAuth => {
-        //                 let fea_ext_len = src.get_u32_le();
-        //                 let mut options = src.get_u8();
-        //                 let fed_auth_echo = (options & 1) == 1;
-        //                 options = options >> 1;
-        //                 // if options != FED_AUTH_LIBRARY_SECURITYTOKEN {
-        //                 //     return Err(Error::Input(String::from("Invalid fed_auth_echo")));
-        //                 // }
-
-        //                 let token_len = src.get_u32_le() as usize;
-        //                 let token = {
-        //                     let (_, buff) = src.read_and_advance(token_len);
-        //                     // todo(mrhamburg): improve this
-        //                     let buff = buff
-        //                         .chunks(2)
-        //                         .map(|x| LittleEndian::read_u16(&x[..]))
-        //                         .collect::<Vec<u16>>();
-        //                     String::from_utf16(&buff[..])
-        //                         .expect("Failed to convert token to UTF-16")
-        //                 };
-
-        //                 let remaining = fea_ext_len - ((token.len() * 2) as u32 + 5);
-        //                 let nonce = if remaining == 32 {
-        //                     let (_, n) = src.read_and_advance(32);
-        //                     Some(n)
-        //                 } else if remaining == 0 {
-        //                     None
-        //                 } else {
-        //                     return Err(TdsWireError::Protocol(
-        //                         "Invalid fed_auth_echo".to_string(),
-        //                     ));
-        //                 };
-
-        //                 ret.fed_auth_ext = Some(FedAuthExt {
-        //                     fed_auth_echo,
-        //                     fed_auth_token: token.into(),
-        //                     nonce,
-        //                 });
-        //             }
-        //             FeatureExt::ColumnEncryption => todo!(),
-        //             FeatureExt::GlobalTransactions => todo!(),
-        //             FeatureExt::AzureSqlSupport => todo!(),
-        //             FeatureExt::DataClassification => todo!(),
-        //             FeatureExt::Utf8Support => todo!(),
-        //             FeatureExt::AzureSqlDnsCaching => todo!(),
-        //             FeatureExt::Terminator => break,
-        //         }
-        //     }
-        // }u
-
         Ok(TdsMessage::Login(ret))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use tokio_util::bytes::BytesMut;
+    use tokio_util::bytes::{BufMut, BytesMut};
 
-    use crate::frontend::tds::codec::login::FedAuthExt;
+    use crate::frontend::tds::codec::login::{FedAuthExt, FedAuthLibrary};
+    use crate::frontend::tds::codec::{SessionRecoveryData, SessionRecoveryExt, SessionStateData};
     use crate::frontend::{LoginMessage, OptionFlag3, PacketHeader};
     use crate::frontend::{TdsMessage, TdsMessageCodec};
 
@@ -943,6 +995,92 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decode_fed_auth_security_token() {
+        let token = "eyJhbGciOiJFZERTQSJ9"
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let mut buff = BytesMut::new();
+        buff.put_u8(0x01 << 1 | 1);
+        buff.put_u32_le(token.len() as u32);
+        buff.put_slice(&token);
+        buff.put_slice(&[3u8; 32]);
+
+        assert_eq!(
+            FedAuthExt::decode(&mut buff).unwrap(),
+            FedAuthExt {
+                fed_auth_echo: true,
+                library: FedAuthLibrary::SecurityToken,
+                fed_auth_token: "eyJhbGciOiJFZERTQSJ9".to_string(),
+                nonce: Some(vec![3u8; 32]),
+            }
+        );
+    }
+
+    #[test]
+    fn decode_fed_auth_adal_workflow() {
+        let mut buff = BytesMut::from(&[0x02 << 1, 0x01][..]);
+        let ext = FedAuthExt::decode(&mut buff).unwrap();
+        assert_eq!(ext.library, FedAuthLibrary::Adal { workflow: 0x01 });
+        assert!(!ext.fed_auth_echo);
+
+        let mut login = LoginMessage::new();
+        login.fed_auth_ext = Some(ext);
+        assert!(login.expects_fed_auth_token_message());
+    }
+
+    #[test]
+    fn decode_fed_auth_invalid() {
+        // unknown library
+        let mut buff = BytesMut::from(&[0x7F << 1][..]);
+        assert!(FedAuthExt::decode(&mut buff).is_err());
+        // token length exceeds the available data
+        let mut buff = BytesMut::from(&[0x01 << 1, 0x10, 0x00, 0x00, 0x00, 0x41, 0x00][..]);
+        assert!(FedAuthExt::decode(&mut buff).is_err());
+    }
+
+    #[test]
+    fn login_message_with_session_recovery_round_trip() {
+        let mut input = LoginMessage::new();
+        input.option_flags_3.insert(OptionFlag3::ExtensionUsed);
+        input.session_recovery = Some(SessionRecoveryExt {
+            recovery_data: Some(SessionRecoveryData {
+                database: "sales".to_string(),
+                collation: vec![],
+                language: "us_english".to_string(),
+                states: vec![SessionStateData::new(0, vec![1, 2, 3])],
+            }),
+        });
+
+        let mut buff = BytesMut::new();
+        input.clone().encode(&mut buff).expect("should be ok");
+
+        if let TdsMessage::Login(result) = LoginMessage::decode(&mut buff).unwrap() {
+            assert_eq!(input.session_recovery, result.session_recovery);
+        } else {
+            panic!("unexpected message type");
+        }
+    }
+
+    #[test]
+    fn login_message_with_utf8_support_and_data_classification_round_trip() {
+        let mut input = LoginMessage::new();
+        input.option_flags_3.insert(OptionFlag3::ExtensionUsed);
+        input.utf8_support = true;
+        input.data_classification = Some(2);
+
+        let mut buff = BytesMut::new();
+        input.clone().encode(&mut buff).expect("should be ok");
+
+        if let TdsMessage::Login(result) = LoginMessage::decode(&mut buff).unwrap() {
+            assert!(result.utf8_support);
+            assert_eq!(result.data_classification, Some(2));
+        } else {
+            panic!("unexpected message type");
+        }
+    }
+
     #[test]
     fn specify_aad_token() {
         let mut input = LoginMessage::new();
@@ -955,6 +1093,7 @@ mod tests {
             input.fed_auth_ext.expect("fed_auto_specified"),
             FedAuthExt {
                 fed_auth_echo: true,
+                library: FedAuthLibrary::SecurityToken,
                 fed_auth_token: token,
                 nonce: Some(nonce)
             }
@@ -1,8 +1,10 @@
 use super::{batch_request::BatchRequest, ResponseMessage};
 use crate::frontend::tds::codec::rpc_request::RpcRequest;
-use crate::frontend::{AttentionSignal, LoginMessage, PacketType, PreloginMessage, TokenFedAuth};
+use crate::frontend::{
+    AttentionSignal, FedAuthTokenMessage, LoginMessage, PacketType, PreloginMessage, TokenFedAuth,
+};
 use tokio_util::bytes::BytesMut;
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 #[derive(Debug)]
 pub enum TdsMessage {
@@ -11,6 +13,7 @@ pub enum TdsMessage {
     Response(ResponseMessage),
     BatchRequest(BatchRequest),
     FedAuth(TokenFedAuth),
+    FedAuthToken(FedAuthTokenMessage),
     Attention(AttentionSignal),
     RemoteProcedureCall(RpcRequest),
 }
@@ -25,13 +28,15 @@ impl TdsMessage {
             PacketType::TDSv7Login => LoginMessage::decode(buf),
             PacketType::Rpc => RpcRequest::decode(buf),
             PacketType::Attention => AttentionSignal::decode(buf),
-
-            // todo(mrhamburg): improve this, should return a specific error message
-            PacketType::FederatedAuthenticationInfo => todo!(),
-            PacketType::BulkLoad => todo!(),
-            PacketType::TransactionManagerReq => todo!(),
-            // _ => TdsWireError::new("Unknown message type").into(),
-            _ => unimplemented!("unknown message type"),
+            PacketType::Fat => FedAuthTokenMessage::decode(buf),
+            PacketType::BulkLoad
+            | PacketType::TransactionManagerReq
+            | PacketType::Sspi
+            | PacketType::TabularResult
+            | PacketType::FederatedAuthenticationInfo => Err(TdsWireError::Protocol(format!(
+                "Unsupported message type: {}",
+                packet_type
+            ))),
         }
     }
 
@@ -40,11 +45,14 @@ impl TdsMessage {
         match self {
             TdsMessage::PreLogin(p) => p.encode(dst),
             TdsMessage::Login(l) => l.encode(dst),
+            TdsMessage::FedAuthToken(t) => t.encode(dst),
             TdsMessage::Response(r) => r.encode(dst),
-
-            // todo(mrhamburg): improve this, should return a specific error message
-            // _ => TdsWireError::new("Unknown message type").into(),
-            _ => unimplemented!("unknown message type"),
+            TdsMessage::BatchRequest(b) => b.encode(dst),
+            TdsMessage::FedAuth(_)
+            | TdsMessage::Attention(_)
+            | TdsMessage::RemoteProcedureCall(_) => Err(TdsWireError::Protocol(
+                "Message type cannot be sent by the server".to_string(),
+            )),
         }
     }
 }
@@ -71,4 +79,5 @@ impl_into_tdsmessage!(LoginMessage, TdsMessage::Login);
 impl_into_tdsmessage!(ResponseMessage, TdsMessage::Response);
 impl_into_tdsmessage!(BatchRequest, TdsMessage::BatchRequest);
 impl_into_tdsmessage!(TokenFedAuth, TdsMessage::FedAuth);
+impl_into_tdsmessage!(FedAuthTokenMessage, TdsMessage::FedAuthToken);
 impl_into_tdsmessage!(AttentionSignal, TdsMessage::Attention);
@@ -1,9 +1,9 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::tds::codec::guid::reorder_bytes;
 use crate::frontend::tds::EncryptionLevel;
-use crate::frontend::utils::ReadAndAdvance;
 use crate::frontend::{TdsMessage, TdsMessageCodec};
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 use uuid::Uuid;
 
 /// Client application activity id token used for debugging purposes introduced in TDS 7.4.
@@ -69,129 +69,127 @@ impl PreloginMessage {
 
 impl TdsMessageCodec for PreloginMessage {
     fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage> {
+        // option positions are relative to the start of the message
+        let payload = src.split();
         let mut ret = PreloginMessage::new();
         let options = {
+            let mut header = &payload[..];
             let mut options = Vec::new();
             loop {
-                let token = src.get_u8();
+                check_remaining(&header, 1, "pre-login option")?;
+                let token = header.get_u8();
 
                 // read until terminator
-                if token == 0xff {
+                if token == PRELOGIN_TERMINATOR {
                     break;
                 }
-                let position = src.get_u16();
-                let length = src.get_u16();
+                check_remaining(&header, 4, "pre-login option")?;
+                let position = header.get_u16() as usize;
+                let length = header.get_u16() as usize;
+                if position + length > payload.len() {
+                    return Err(TdsWireError::Protocol(format!(
+                        "pre-login option {} out of bounds",
+                        token
+                    )));
+                }
                 options.push((token, position, length));
             }
-
-            options.sort_by(|a, b| a.1.cmp(&b.1));
             options
         };
 
-        // get initial offset
-        let mut decode_offset_initial: u16 = options.len() as u16 * 5u16 + 1u16;
-        // read all options
-        for option in options.iter().enumerate() {
-            let token = option.1 .0;
-            let position = option.1 .1;
-            let length = option.1 .2;
-
-            while decode_offset_initial < position {
-                let _ = src.get_u8();
-                decode_offset_initial += 1;
-            }
-
-            // verify whether the server acts in accordance to what we requested
-            // and if we can handle on what we seemingly agreed to
+        // verify whether the client acts in accordance to what we requested
+        // and if we can handle on what we seemingly agreed to
+        for (token, position, length) in options {
+            let mut data = BytesMut::from(&payload[position..position + length]);
+            let expect_length = |expected: usize| {
+                if length < expected {
+                    Err(TdsWireError::Protocol(format!(
+                        "invalid pre-login option {} length: {}",
+                        token, length
+                    )))
+                } else {
+                    Ok(())
+                }
+            };
             match token {
                 // version
                 PRELOGIN_VERSION => {
-                    ret.version = src.get_u32();
-                    ret.sub_build = src.get_u16();
-                    decode_offset_initial += 6;
+                    expect_length(6)?;
+                    ret.version = data.get_u32();
+                    ret.sub_build = data.get_u16();
                 }
                 // encryption
                 PRELOGIN_ENCRYPTION => {
-                    let encrypt = src.get_u8();
-                    ret.encryption = Some(
-                        crate::frontend::tds::EncryptionLevel::try_from(encrypt).map_err(|_| {
-                            unilake_common::error::Error::Protocol(
-                                format!("invalid encryption value: {}", encrypt).into(),
-                            )
-                        })?,
-                    );
-                    decode_offset_initial += 1;
+                    expect_length(1)?;
+                    let encrypt = data.get_u8();
+                    ret.encryption = Some(EncryptionLevel::try_from(encrypt).map_err(|_| {
+                        TdsWireError::Protocol(format!("invalid encryption value: {}", encrypt))
+                    })?);
                 }
                 // instance name
                 PRELOGIN_INSTOPT => {
-                    let mut bytes = Vec::new();
-                    let mut next_byte = src.get_u8();
-                    decode_offset_initial += 1;
-
-                    while next_byte != 0x00 {
-                        bytes.push(next_byte);
-                        next_byte = src.get_u8();
-                        decode_offset_initial += 1;
-                    }
-
+                    let bytes = data[..].split(|b| *b == 0x00).next().unwrap_or_default();
                     if !bytes.is_empty() {
-                        ret.instance_name = Some(String::from_utf8_lossy(&bytes).into_owned());
+                        ret.instance_name = Some(String::from_utf8_lossy(bytes).into_owned());
                     }
                 }
                 PRELOGIN_THREADID => {
-                    ret.thread_id = if length == 0 {
-                        0
-                    } else if length == 4 {
-                        src.get_u32()
-                    } else {
-                        panic!("should never happen")
+                    ret.thread_id = match length {
+                        0 => 0,
+                        4 => data.get_u32(),
+                        _ => {
+                            return Err(TdsWireError::Protocol(format!(
+                                "invalid thread id length: {}",
+                                length
+                            )))
+                        }
                     };
-                    decode_offset_initial += 4;
                 }
                 // mars
                 PRELOGIN_MARS => {
-                    ret.mars = src.get_u8() == 0x01;
-                    decode_offset_initial += 1;
+                    expect_length(1)?;
+                    ret.mars = data.get_u8() == 0x01;
                 }
                 // activity id
                 PRELOGIN_TRACEID => {
                     // Data is a Guid, 16 bytes and ordered the wrong way around than Uuid.
-                    let (length, data) = src.read_and_advance(16);
-                    if length < 16 {
-                        return Err(unilake_common::error::Error::Protocol(
-                            format!("invalid trace length: {}", length).into(),
-                        ));
+                    if length < 36 {
+                        return Err(TdsWireError::Protocol(format!(
+                            "invalid trace length: {}",
+                            length
+                        )));
                     }
-                    let mut data: [u8; 16] = data.to_vec().try_into().unwrap();
-                    reorder_bytes(&mut data);
+                    let mut id = [0u8; 16];
+                    data.copy_to_slice(&mut id);
+                    reorder_bytes(&mut id);
 
                     let mut sequence = [0u8; 20];
-                    src.put_and_advance(&mut sequence)?;
+                    data.copy_to_slice(&mut sequence);
 
                     ret.activity_id = Some(ActivityId {
-                        id: Uuid::from_bytes(data),
-                        sequence: sequence,
+                        id: Uuid::from_bytes(id),
+                        sequence,
                     });
-                    decode_offset_initial += 36;
                 }
                 // fed auth
                 PRELOGIN_FEDAUTHREQUIRED => {
-                    ret.fed_auth_required = Some(src.get_u8() != 0);
-                    decode_offset_initial += 1;
+                    expect_length(1)?;
+                    ret.fed_auth_required = Some(data.get_u8() != 0);
                 }
                 // nonce
                 PRELOGIN_NONCEOPT => {
-                    let (length, data) = src.read_and_advance(32);
                     if length != 32 {
-                        return Err(unilake_common::error::Error::Protocol(
-                            format!("invalid nonce length: {}", length).into(),
-                        ));
+                        return Err(TdsWireError::Protocol(format!(
+                            "invalid nonce length: {}",
+                            length
+                        )));
                     }
-                    ret.nonce = Some(data.to_vec().try_into().unwrap());
-                    decode_offset_initial += 32;
+                    let mut nonce = [0u8; 32];
+                    data.copy_to_slice(&mut nonce);
+                    ret.nonce = Some(nonce);
                 }
-                // todo(mrhamburg): instead of panicking, we should return an error
-                _ => panic!("unsupported pre-login token: {}", token),
+                // options introduced by later versions of the protocol can safely be ignored
+                _ => tracing::debug!("Ignoring unsupported pre-login option: {}", token),
             }
         }
 
@@ -208,7 +206,7 @@ impl TdsMessageCodec for PreloginMessage {
         if self.activity_id.is_some() {
             options.push((PRELOGIN_TRACEID, 36, 0));
         }
-        if self.fed_auth_required.is_some() & self.fed_auth_required.unwrap() {
+        if self.fed_auth_required == Some(true) {
             options.push((PRELOGIN_FEDAUTHREQUIRED, 1, 0));
         }
         if self.nonce.is_some() {
@@ -254,7 +252,7 @@ impl TdsMessageCodec for PreloginMessage {
         // }
 
         // write fed_auth_required
-        if self.fed_auth_required.is_some() & self.fed_auth_required.unwrap() {
+        if self.fed_auth_required == Some(true) {
             dst.put_u8(self.fed_auth_required.unwrap() as u8);
         }
 
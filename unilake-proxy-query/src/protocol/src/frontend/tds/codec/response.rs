@@ -39,8 +39,9 @@ impl ResponseMessage {
             TdsToken::ReturnValue(token) => token.encode(dst),
             TdsToken::Row(token) => token.encode(dst),
             TdsToken::SessionState(token) => token.encode(dst),
-            // TdsToken::Sspi(token) => token.encode(dst),
-            _ => unimplemented!(),
+            TdsToken::DataClassification(token) => token.encode(dst),
+            TdsToken::ReturnStatus(token) => token.encode(dst),
+            TdsToken::Sspi(token) => token.encode(dst),
         }
     }
 }
@@ -62,7 +63,12 @@ impl TdsMessageCodec for ResponseMessage {
                 TdsTokenType::Order => TokenOrder::decode(src)?,
                 TdsTokenType::ReturnValue => TokenReturnValue::decode(src)?,
                 TdsTokenType::LoginAck => TokenLoginAck::decode(src)?,
-                _ => todo!(),
+                _ => {
+                    return Err(TdsWireError::Protocol(format!(
+                        "Unsupported token type in response: {:?}",
+                        token_type
+                    )))
+                }
             };
             ret.add_token(token);
         }
@@ -1,8 +1,10 @@
 // MS-TDS: [2.2.6.6]
-use crate::frontend::tds::codec::decode::read_b_varchar;
+use crate::frontend::tds::codec::decode::{
+    check_remaining, read_all_headers, read_b_varchar, read_string,
+};
 use crate::frontend::{ColumnData, TdsMessage, TdsMessageCodec, TypeInfo};
 use tokio_util::bytes::{Buf, BytesMut};
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 uint_enum! {
     #[repr(u16)]
@@ -25,13 +27,82 @@ uint_enum! {
     }
 }
 
+impl ProcedureType {
+    /// Resolve a well-known procedure by name, for clients sending the name instead of the id
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let name = name
+            .trim_start_matches("master.")
+            .trim_start_matches("dbo.")
+            .trim_start_matches("sys.")
+            .trim_start_matches('.');
+        Some(match name {
+            "sp_cursor" => ProcedureType::SpCursor,
+            "sp_cursoropen" => ProcedureType::SpCursorOpen,
+            "sp_cursorprepare" => ProcedureType::SpCursorPrepare,
+            "sp_cursorexecute" => ProcedureType::SpCursorExecute,
+            "sp_cursorprepexec" => ProcedureType::SpCursorPrepExec,
+            "sp_cursorunprepare" => ProcedureType::SpCursorUnprepare,
+            "sp_cursorfetch" => ProcedureType::SpCursorFetch,
+            "sp_cursoroption" => ProcedureType::SpCursorOption,
+            "sp_cursorclose" => ProcedureType::SpCursorClose,
+            "sp_executesql" => ProcedureType::SpExecuteSql,
+            "sp_prepare" => ProcedureType::SpPrepare,
+            "sp_execute" => ProcedureType::SpExecute,
+            "sp_prepexec" => ProcedureType::SpPrepExec,
+            "sp_prepexecrpc" => ProcedureType::SpPrepExecRpc,
+            "sp_unprepare" => ProcedureType::SpUnprepare,
+            _ => return None,
+        })
+    }
+}
+
+/// Status flags of an RPC parameter [2.2.6.6]
+const PARAMETER_STATUS_BY_REF_VALUE: u8 = 0x01;
+const PARAMETER_STATUS_DEFAULT_VALUE: u8 = 0x02;
+
+/// Batch separators, used when multiple RPC requests are sent in a single message
+const RPC_BATCH_FLAG: u8 = 0x80;
+const RPC_NO_EXEC_FLAG: u8 = 0xFF;
+
 #[derive(Debug)]
 pub struct RpcRequest {
     outstanding_requests: u32,
-    procedure_type: ProcedureType,
+    transaction_descriptor: u64,
+    procedure_type: Option<ProcedureType>,
+    procedure_name: Option<String>,
+    option_flags: u16,
     parameters: Vec<RpcParameter>,
 }
 
+impl RpcRequest {
+    /// The well-known procedure to execute, if any
+    pub fn procedure_type(&self) -> Option<ProcedureType> {
+        self.procedure_type
+    }
+
+    /// Name of the procedure, only available if the client sent the procedure by name
+    pub fn procedure_name(&self) -> Option<&str> {
+        self.procedure_name.as_deref()
+    }
+
+    pub fn parameters(&self) -> &[RpcParameter] {
+        &self.parameters
+    }
+
+    pub fn outstanding_requests(&self) -> u32 {
+        self.outstanding_requests
+    }
+
+    pub fn transaction_descriptor(&self) -> u64 {
+        self.transaction_descriptor
+    }
+
+    pub fn option_flags(&self) -> u16 {
+        self.option_flags
+    }
+}
+
 #[derive(Debug)]
 pub struct RpcParameter {
     name: String,
@@ -40,35 +111,65 @@ pub struct RpcParameter {
     value: ColumnData,
 }
 
+impl RpcParameter {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Parameter is passed by reference (OUTPUT parameter)
+    pub fn is_output(&self) -> bool {
+        self.status & PARAMETER_STATUS_BY_REF_VALUE != 0
+    }
+
+    /// Parameter uses the default value
+    pub fn is_default(&self) -> bool {
+        self.status & PARAMETER_STATUS_DEFAULT_VALUE != 0
+    }
+
+    pub fn type_info(&self) -> &TypeInfo {
+        &self.type_info
+    }
+
+    pub fn value(&self) -> &ColumnData {
+        &self.value
+    }
+}
+
 impl TdsMessageCodec for RpcRequest {
     fn decode(src: &mut BytesMut) -> TdsWireResult<TdsMessage>
     where
         Self: Sized,
     {
-        let _total_length = src.get_u32_le();
-
-        // Header
-        let _header_length = src.get_u32_le();
-        // let mut src_header = src.split_to(header_length as usize);
-        let _rpc_header_type = src.get_u16_le();
-        let _transaction_descriptor = src.get_f64_le();
-        let outstanding_requests = src.get_u32_le();
-
-        // Procedure Name Length
-        let _procedure_name_length = src.get_u16_le();
+        let (transaction_descriptor, outstanding_requests) = read_all_headers(src)?;
 
-        // Stored Procedure ID
-        let procedure_type = ProcedureType::try_from(src.get_u16_le()).map_err(|_| {
-            unilake_common::error::Error::Protocol("invalid procedure type".to_string())
-        })?;
+        // Procedure name or id
+        check_remaining(src, 2, "rpc procedure name")?;
+        let procedure_name_length = src.get_u16_le();
+        let (procedure_type, procedure_name) = if procedure_name_length == 0xFFFF {
+            check_remaining(src, 2, "rpc procedure id")?;
+            let procedure_type = ProcedureType::try_from(src.get_u16_le())
+                .map_err(|_| TdsWireError::Protocol("invalid procedure type".to_string()))?;
+            (Some(procedure_type), None)
+        } else {
+            let name = read_string(src, procedure_name_length as usize)?;
+            (ProcedureType::from_name(&name), Some(name))
+        };
 
         // Options Flag
-        let _options_flag = src.get_u16_le();
+        check_remaining(src, 2, "rpc option flags")?;
+        let option_flags = src.get_u16_le();
 
         // Parameters
         let mut parameters = Vec::<RpcParameter>::new();
         while src.has_remaining() {
+            if matches!(src[0], RPC_BATCH_FLAG | RPC_NO_EXEC_FLAG) {
+                tracing::warn!("Batched RPC requests are not supported, ignoring remaining");
+                src.advance(src.remaining());
+                break;
+            }
+
             let name = read_b_varchar(src)?;
+            check_remaining(src, 1, "rpc parameter status")?;
             let status = src.get_u8();
             let type_info = TypeInfo::decode(src)?;
             let value = ColumnData::decode(src, &type_info)?;
@@ -83,21 +184,26 @@ impl TdsMessageCodec for RpcRequest {
 
         Ok(TdsMessage::RemoteProcedureCall(RpcRequest {
             outstanding_requests,
+            transaction_descriptor,
             procedure_type,
+            procedure_name,
+            option_flags,
             parameters,
         }))
     }
 
     fn encode(&self, _: &mut BytesMut) -> TdsWireResult<()> {
-        unimplemented!("Encode on RpcRequest is not a server implementation")
+        Err(TdsWireError::Protocol(
+            "Encode on RpcRequest is not a server implementation".to_string(),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::frontend::tds::codec::rpc_request::RpcRequest;
-    use crate::frontend::{TdsMessage, TdsMessageCodec};
-    use tokio_util::bytes::{Buf, BytesMut};
+    use crate::frontend::tds::codec::rpc_request::{ProcedureType, RpcRequest};
+    use crate::frontend::{ColumnData, TdsMessage, TdsMessageCodec};
+    use tokio_util::bytes::{Buf, BufMut, BytesMut};
 
     const RAW_BYTES: &[u8] = &[
         0x16, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -116,6 +222,48 @@ mod tests {
         assert_eq!(buf.remaining(), 0);
         if let TdsMessage::RemoteProcedureCall(rpc) = msg {
             assert_eq!(rpc.parameters.iter().count(), 1);
+            assert_eq!(rpc.procedure_type(), Some(ProcedureType::SpExecuteSql));
+            assert_eq!(rpc.outstanding_requests(), 1);
+            assert!(matches!(
+                rpc.parameters()[0].value(),
+                ColumnData::String(s) if s.as_str().is_some()
+            ));
+        } else {
+            panic!("Incorrect return type found")
+        }
+    }
+
+    #[test]
+    fn decode_named_procedure_with_int_parameter() {
+        let mut raw = BytesMut::from(&RAW_BYTES[..22]);
+        // procedure name: sp_execute
+        raw.put_u16_le(10);
+        "sp_execute".encode_utf16().for_each(|c| raw.put_u16_le(c));
+        raw.put_u16_le(0);
+        // parameter: @handle int = 1
+        raw.put_u8(0);
+        raw.put_u8(0);
+        raw.put_slice(&[0x26, 0x04, 0x04, 0x01, 0x00, 0x00, 0x00]);
+        // parameter: @p1 int output = null
+        raw.put_u8(3);
+        "@p1".encode_utf16().for_each(|c| raw.put_u16_le(c));
+        raw.put_u8(1);
+        raw.put_slice(&[0x26, 0x04, 0x00]);
+
+        let msg = RpcRequest::decode(&mut raw).unwrap();
+        if let TdsMessage::RemoteProcedureCall(rpc) = msg {
+            assert_eq!(rpc.procedure_type(), Some(ProcedureType::SpExecute));
+            assert_eq!(rpc.procedure_name(), Some("sp_execute"));
+            assert!(matches!(
+                rpc.parameters()[0].value(),
+                ColumnData::I32N(Some(1))
+            ));
+            assert_eq!(rpc.parameters()[1].name(), "@p1");
+            assert!(rpc.parameters()[1].is_output());
+            assert!(matches!(
+                rpc.parameters()[1].value(),
+                ColumnData::I32N(None)
+            ));
         } else {
             panic!("Incorrect return type found")
         }
@@ -0,0 +1,207 @@
+use crate::frontend::tds::codec::decode::{check_remaining, read_b_varchar};
+use crate::frontend::tds::codec::encode::write_b_varchar;
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Marks a state value length which does not fit in a single byte
+const STATE_LEN_DWORD: u8 = 0xff;
+
+/// Session state of a single state id [2.2.7.21], the value is only interpreted by the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionStateData {
+    pub state_id: u8,
+    pub value: Vec<u8>,
+}
+
+impl SessionStateData {
+    pub fn new(state_id: u8, value: Vec<u8>) -> Self {
+        SessionStateData { state_id, value }
+    }
+
+    pub fn encode(&self, dest: &mut BytesMut) {
+        dest.put_u8(self.state_id);
+        if self.value.len() < STATE_LEN_DWORD as usize {
+            dest.put_u8(self.value.len() as u8);
+        } else {
+            dest.put_u8(STATE_LEN_DWORD);
+            dest.put_u32_le(self.value.len() as u32);
+        }
+        dest.put_slice(&self.value);
+    }
+
+    /// Decodes all states until the end of the source
+    pub fn decode_set(src: &mut BytesMut) -> TdsWireResult<Vec<Self>> {
+        let mut states = Vec::new();
+        while src.has_remaining() {
+            check_remaining(src, 2, "session state")?;
+            let state_id = src.get_u8();
+            let length = match src.get_u8() {
+                STATE_LEN_DWORD => {
+                    check_remaining(src, 4, "session state length")?;
+                    src.get_u32_le() as usize
+                }
+                length => length as usize,
+            };
+            check_remaining(src, length, "session state value")?;
+            states.push(SessionStateData::new(
+                state_id,
+                src.split_to(length).to_vec(),
+            ));
+        }
+        Ok(states)
+    }
+}
+
+/// Session recovery data [2.2.6.4], the state of a session as known by the client. Used to
+/// acknowledge session recovery and to recover the session when reconnecting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionRecoveryData {
+    pub database: String,
+    /// Collation of the database, empty for the server default
+    pub collation: Vec<u8>,
+    pub language: String,
+    pub states: Vec<SessionStateData>,
+}
+
+impl SessionRecoveryData {
+    pub fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        let mut data = BytesMut::new();
+        write_b_varchar(&mut data, &self.database)?;
+        data.put_u8(self.collation.len() as u8);
+        data.put_slice(&self.collation);
+        write_b_varchar(&mut data, &self.language)?;
+        for state in &self.states {
+            state.encode(&mut data);
+        }
+
+        dest.put_u32_le(data.len() as u32);
+        dest.put_slice(&data);
+        Ok(())
+    }
+
+    pub fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        check_remaining(src, 4, "session recovery data length")?;
+        let length = src.get_u32_le() as usize;
+        check_remaining(src, length, "session recovery data")?;
+        let mut src = src.split_to(length);
+
+        let database = read_b_varchar(&mut src)?;
+        check_remaining(&src, 1, "session recovery collation")?;
+        let collation_length = src.get_u8() as usize;
+        check_remaining(&src, collation_length, "session recovery collation")?;
+        let collation = src.split_to(collation_length).to_vec();
+        let language = read_b_varchar(&mut src)?;
+        let states = SessionStateData::decode_set(&mut src)?;
+
+        Ok(SessionRecoveryData {
+            database,
+            collation,
+            language,
+            states,
+        })
+    }
+
+    /// Applies the changes made since the initial state, empty values are unchanged
+    fn apply(&mut self, changes: SessionRecoveryData) {
+        if !changes.database.is_empty() {
+            self.database = changes.database;
+        }
+        if !changes.collation.is_empty() {
+            self.collation = changes.collation;
+        }
+        if !changes.language.is_empty() {
+            self.language = changes.language;
+        }
+        for state in changes.states {
+            self.states.retain(|s| s.state_id != state.state_id);
+            self.states.push(state);
+        }
+    }
+}
+
+/// Session recovery feature extension of the LOGIN7 message [2.2.6.4]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionRecoveryExt {
+    /// The state to recover, none when the client requests session recovery for a new session
+    pub recovery_data: Option<SessionRecoveryData>,
+}
+
+impl SessionRecoveryExt {
+    pub fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        // the initial state is sent as-is, without changes to apply
+        if let Some(data) = &self.recovery_data {
+            data.encode(dest)?;
+            SessionRecoveryData::default().encode(dest)?;
+        }
+        Ok(())
+    }
+
+    pub fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        if !src.has_remaining() {
+            return Ok(SessionRecoveryExt::default());
+        }
+
+        // the initial state of the session, followed by the changes made afterward
+        let mut data = SessionRecoveryData::decode(src)?;
+        let changes = SessionRecoveryData::decode(src)?;
+        if src.has_remaining() {
+            return Err(TdsWireError::Protocol(
+                "Invalid session recovery feature extension".to_string(),
+            ));
+        }
+        data.apply(changes);
+
+        Ok(SessionRecoveryExt {
+            recovery_data: Some(data),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::tds::codec::session_recovery::{
+        SessionRecoveryData, SessionRecoveryExt, SessionStateData,
+    };
+    use tokio_util::bytes::BytesMut;
+
+    #[test]
+    fn decode_recovery_data_with_changes() {
+        let initial = SessionRecoveryData {
+            database: "main".to_string(),
+            collation: vec![],
+            language: "us_english".to_string(),
+            states: vec![
+                SessionStateData::new(0, vec![1, 2, 3]),
+                SessionStateData::new(1, vec![0; 300]),
+            ],
+        };
+        let changes = SessionRecoveryData {
+            database: "sales".to_string(),
+            states: vec![SessionStateData::new(0, vec![4])],
+            ..Default::default()
+        };
+        let mut buffer = BytesMut::new();
+        initial.encode(&mut buffer).unwrap();
+        changes.encode(&mut buffer).unwrap();
+
+        let ext = SessionRecoveryExt::decode(&mut buffer).unwrap();
+        assert_eq!(
+            ext.recovery_data,
+            Some(SessionRecoveryData {
+                database: "sales".to_string(),
+                collation: vec![],
+                language: "us_english".to_string(),
+                states: vec![
+                    SessionStateData::new(1, vec![0; 300]),
+                    SessionStateData::new(0, vec![4]),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn decode_recovery_request() {
+        let ext = SessionRecoveryExt::decode(&mut BytesMut::new()).unwrap();
+        assert_eq!(ext.recovery_data, None);
+    }
+}
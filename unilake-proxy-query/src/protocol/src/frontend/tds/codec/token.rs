@@ -1,4 +1,5 @@
 mod token_col_metadata;
+mod token_data_classification;
 mod token_done;
 mod token_env_change;
 mod token_error;
@@ -15,6 +16,7 @@ mod token_sspi;
 mod token_type;
 
 pub use token_col_metadata::*;
+pub use token_data_classification::*;
 pub use token_done::*;
 pub use token_env_change::*;
 pub use token_feature_ext_ack::*;
@@ -43,10 +45,12 @@ pub enum TdsToken {
     ColMetaData(TokenColMetaData),
     FedAuth(TokenFedAuth),
     LoginAck(TokenLoginAck),
+    ReturnStatus(TokenReturnStatus),
     ReturnValue(TokenReturnValue),
     Row(TokenRow),
     Sspi(TokenSspi),
     SessionState(TokenSessionState),
+    DataClassification(TokenDataClassification),
 }
 
 pub trait TdsTokenCodec {
@@ -78,10 +82,12 @@ impl TdsToken {
             ColMetaData,
             FedAuth,
             LoginAck,
+            ReturnStatus,
             ReturnValue,
             Row,
             Sspi,
-            SessionState
+            SessionState,
+            DataClassification
         )
     }
 }
@@ -105,6 +111,9 @@ impl_into_tdstoken!(TokenFeatureExtAck, TdsToken::FeatureExtAck);
 impl_into_tdstoken!(TokenColMetaData, TdsToken::ColMetaData);
 impl_into_tdstoken!(TokenFedAuth, TdsToken::FedAuth);
 impl_into_tdstoken!(TokenLoginAck, TdsToken::LoginAck);
+impl_into_tdstoken!(TokenReturnStatus, TdsToken::ReturnStatus);
 impl_into_tdstoken!(TokenReturnValue, TdsToken::ReturnValue);
 impl_into_tdstoken!(TokenSspi, TdsToken::Sspi);
 impl_into_tdstoken!(TokenRow, TdsToken::Row);
+impl_into_tdstoken!(TokenSessionState, TdsToken::SessionState);
+impl_into_tdstoken!(TokenDataClassification, TdsToken::DataClassification);
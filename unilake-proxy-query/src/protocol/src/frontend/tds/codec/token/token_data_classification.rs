@@ -0,0 +1,213 @@
+use crate::frontend::tds::codec::decode::{check_remaining, read_us_varchar};
+use crate::frontend::tds::codec::encode::write_us_varchar;
+use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use unilake_common::error::TdsWireResult;
+
+/// Data classification version supported by the server
+pub const DATA_CLASSIFICATION_VERSION: u8 = 1;
+
+/// Index of a missing sensitivity label or information type
+const NO_PROPERTY: u16 = 0xffff;
+
+/// Sensitivity label or information type of classified data
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitivityProperty {
+    pub name: String,
+    pub id: String,
+}
+
+impl SensitivityProperty {
+    pub fn new(name: String, id: String) -> Self {
+        SensitivityProperty { name, id }
+    }
+}
+
+/// Classification of a single source of a column, as indexes into the sensitivity labels and
+/// information types of the token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensitivityClassification {
+    pub label: Option<u16>,
+    pub information_type: Option<u16>,
+}
+
+/// Data classification token [2.2.7.5]
+/// Introduced in TDS 7.4, sent after COLMETADATA to describe the sensitivity of each column of
+/// the result set. Only sent to clients which requested the data classification feature.
+#[derive(Debug, Default)]
+pub struct TokenDataClassification {
+    labels: Vec<SensitivityProperty>,
+    information_types: Vec<SensitivityProperty>,
+    /// Classifications of each column of the result set, empty for unclassified columns
+    columns: Vec<Vec<SensitivityClassification>>,
+}
+
+impl TokenDataClassification {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the next column of the result set, classified by each pair of sensitivity label and
+    /// information type
+    pub fn add_column(&mut self, sources: Vec<(SensitivityProperty, SensitivityProperty)>) {
+        let column = sources
+            .into_iter()
+            .map(|(label, information_type)| SensitivityClassification {
+                label: Some(Self::index_of(&mut self.labels, label)),
+                information_type: Some(Self::index_of(
+                    &mut self.information_types,
+                    information_type,
+                )),
+            })
+            .collect();
+        self.columns.push(column);
+    }
+
+    /// Whether any of the columns is classified
+    pub fn is_classified(&self) -> bool {
+        self.columns.iter().any(|c| !c.is_empty())
+    }
+
+    pub fn labels(&self) -> &[SensitivityProperty] {
+        &self.labels
+    }
+
+    pub fn information_types(&self) -> &[SensitivityProperty] {
+        &self.information_types
+    }
+
+    pub fn columns(&self) -> &[Vec<SensitivityClassification>] {
+        &self.columns
+    }
+
+    fn index_of(properties: &mut Vec<SensitivityProperty>, property: SensitivityProperty) -> u16 {
+        match properties.iter().position(|p| *p == property) {
+            Some(index) => index as u16,
+            None => {
+                properties.push(property);
+                (properties.len() - 1) as u16
+            }
+        }
+    }
+
+    fn encode_properties(
+        dest: &mut BytesMut,
+        properties: &[SensitivityProperty],
+    ) -> TdsWireResult<()> {
+        dest.put_u16_le(properties.len() as u16);
+        for property in properties {
+            write_us_varchar(dest, &property.name)?;
+            write_us_varchar(dest, &property.id)?;
+        }
+        Ok(())
+    }
+
+    fn decode_properties(src: &mut BytesMut) -> TdsWireResult<Vec<SensitivityProperty>> {
+        check_remaining(src, 2, "sensitivity property count")?;
+        let count = src.get_u16_le();
+        (0..count)
+            .map(|_| {
+                let name = read_us_varchar(src)?;
+                let id = read_us_varchar(src)?;
+                Ok(SensitivityProperty::new(name, id))
+            })
+            .collect()
+    }
+}
+
+impl TdsTokenCodec for TokenDataClassification {
+    fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        dest.put_u8(TdsTokenType::DataClassification as u8);
+        Self::encode_properties(dest, &self.labels)?;
+        Self::encode_properties(dest, &self.information_types)?;
+
+        dest.put_u16_le(self.columns.len() as u16);
+        for column in &self.columns {
+            dest.put_u16_le(column.len() as u16);
+            for source in column {
+                dest.put_u16_le(source.label.unwrap_or(NO_PROPERTY));
+                dest.put_u16_le(source.information_type.unwrap_or(NO_PROPERTY));
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(src: &mut BytesMut) -> TdsWireResult<TdsToken> {
+        let labels = Self::decode_properties(src)?;
+        let information_types = Self::decode_properties(src)?;
+
+        check_remaining(src, 2, "classified column count")?;
+        let column_count = src.get_u16_le();
+        let mut columns = Vec::with_capacity(column_count as usize);
+        for _ in 0..column_count {
+            check_remaining(src, 2, "column source count")?;
+            let source_count = src.get_u16_le() as usize;
+            check_remaining(src, source_count * 4, "column sources")?;
+            let column = (0..source_count)
+                .map(|_| {
+                    let label = src.get_u16_le();
+                    let information_type = src.get_u16_le();
+                    SensitivityClassification {
+                        label: (label != NO_PROPERTY).then_some(label),
+                        information_type: (information_type != NO_PROPERTY)
+                            .then_some(information_type),
+                    }
+                })
+                .collect();
+            columns.push(column);
+        }
+
+        Ok(TdsToken::DataClassification(TokenDataClassification {
+            labels,
+            information_types,
+            columns,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        SensitivityClassification, SensitivityProperty, TdsToken, TdsTokenCodec, TdsTokenType,
+        TokenDataClassification,
+    };
+    use tokio_util::bytes::{Buf, BytesMut};
+
+    #[test]
+    fn encode_decode_token_data_classification() {
+        let label =
+            SensitivityProperty::new("Confidential".to_string(), "confidential".to_string());
+        let email = SensitivityProperty::new("PII email".to_string(), "pii::email".to_string());
+        let name = SensitivityProperty::new("PII name".to_string(), "pii::name".to_string());
+
+        let mut token = TokenDataClassification::new();
+        token.add_column(vec![]);
+        token.add_column(vec![(label.clone(), email.clone())]);
+        token.add_column(vec![
+            (label.clone(), email.clone()),
+            (label.clone(), name.clone()),
+        ]);
+        assert!(token.is_classified());
+
+        let mut buff = BytesMut::new();
+        token.encode(&mut buff).unwrap();
+        assert_eq!(buff.get_u8(), TdsTokenType::DataClassification as u8);
+
+        match TokenDataClassification::decode(&mut buff).unwrap() {
+            TdsToken::DataClassification(result) => {
+                assert_eq!(result.labels(), &[label]);
+                assert_eq!(result.information_types(), &[email, name]);
+                let source = |information_type| SensitivityClassification {
+                    label: Some(0),
+                    information_type: Some(information_type),
+                };
+                assert_eq!(
+                    result.columns(),
+                    &[vec![], vec![source(0)], vec![source(0), source(1)]]
+                );
+            }
+            _ => panic!("unexpected token"),
+        }
+        assert!(!buff.has_remaining());
+    }
+}
@@ -8,6 +8,8 @@ use unilake_common::error::TdsWireResult;
 /// Indicates the completion status of a SQL statement.
 #[derive(Debug)]
 pub struct TokenDone {
+    /// Token type, either DONE, DONEPROC [2.2.7.7] or DONEINPROC [2.2.7.8]
+    pub token_type: TdsTokenType,
     /// Status
     pub status: BitFlags<DoneStatus>,
     /// The token of the current SQL statement. The token value is provided and controlled by the application layer, which utilizes TDS. The TDS layer does not evaluate the value.
@@ -41,6 +43,7 @@ impl TokenDone {
     /// DONE_SRVERROR. Used in place of DONE_ERROR when an error occurred on the current SQL statement, which is severe enough to require the result set, if any, to be discarded.
     pub fn new_srv_error(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::SrvError),
             cur_cmd,
             done_rows: 0,
@@ -50,6 +53,7 @@ impl TokenDone {
     /// Unknown
     pub fn new_rpc_in_batch(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::RpcInBatch),
             cur_cmd,
             done_rows: 0,
@@ -59,6 +63,7 @@ impl TokenDone {
     /// DONE_ATTN. The DONE message is a server acknowledgement of a client ATTENTION message.
     pub fn new_attention(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::Attention),
             cur_cmd,
             done_rows: 0,
@@ -68,6 +73,7 @@ impl TokenDone {
     /// DONE_COUNT. The DoneRowCount value is valid. This is used to distinguish between a valid value of 0 for DoneRowCount or just an initialized variable.
     pub fn new_count(cur_cmd: u16, done_rows: u64) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::Count),
             cur_cmd,
             done_rows,
@@ -77,6 +83,7 @@ impl TokenDone {
     /// DONE_INXACT. A transaction is in progress
     pub fn new_inexact(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::More),
             cur_cmd,
             done_rows: 0,
@@ -86,6 +93,7 @@ impl TokenDone {
     /// DONE_ERROR. An error occurred on the current SQL statement. A preceding ERROR token SHOULD be sent when this bit is set.
     pub fn new_error(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::Error),
             cur_cmd,
             done_rows: 0,
@@ -95,6 +103,7 @@ impl TokenDone {
     /// DONE_MORE. This DONE message is not the final DONE message in the response. Subsequent data streams to follow.
     pub fn new_more(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::from_flag(DoneStatus::More),
             cur_cmd,
             done_rows: 0,
@@ -104,6 +113,7 @@ impl TokenDone {
     /// DONE_FINAL. This DONE is the final DONE in the request. Also contains the done_rows
     pub fn new_done(cur_cmd: u16) -> Self {
         TokenDone {
+            token_type: TdsTokenType::Done,
             status: BitFlags::empty(),
             cur_cmd,
             done_rows: 0,
@@ -141,11 +151,27 @@ impl TokenDone {
     pub fn new_final() -> Self {
         Self::new_done(0)
     }
+
+    /// DONEPROC. Indicates the completion status of a stored procedure (RPC request)
+    pub fn new_proc(cur_cmd: u16) -> Self {
+        TokenDone {
+            token_type: TdsTokenType::DoneProc,
+            ..Self::new_done(cur_cmd)
+        }
+    }
+
+    /// DONEINPROC. Indicates the completion status of a SQL statement within a stored procedure
+    pub fn new_in_proc(cur_cmd: u16, done_rows: u64) -> Self {
+        TokenDone {
+            token_type: TdsTokenType::DoneInProc,
+            ..Self::new_count(cur_cmd, done_rows)
+        }
+    }
 }
 
 impl TdsTokenCodec for TokenDone {
     fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
-        dest.put_u8(TdsTokenType::Done as u8);
+        dest.put_u8(self.token_type as u8);
         dest.put_u16_le(self.status.bits());
         dest.put_u16_le(self.cur_cmd);
         dest.put_u64_le(self.done_rows);
@@ -160,6 +186,7 @@ impl TdsTokenCodec for TokenDone {
         let done_rows = src.get_u64_le();
 
         Ok(TdsToken::Done(TokenDone {
+            token_type: TdsTokenType::Done,
             status,
             cur_cmd,
             done_rows,
@@ -217,6 +244,7 @@ mod tests {
     #[test]
     fn encode_decode_token_done_final() -> TdsWireResult<()> {
         let input = TokenDone {
+            token_type: TdsTokenType::Done,
             done_rows: 128,
             cur_cmd: 1,
             status: BitFlags::empty(),
@@ -242,4 +270,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encode_token_done_proc() -> TdsWireResult<()> {
+        let mut buff = BytesMut::new();
+        TokenDone::new_in_proc(0xC1, 3).encode(&mut buff)?;
+        TokenDone::new_proc(0xE0).encode(&mut buff)?;
+
+        assert_eq!(buff.get_u8(), TdsTokenType::DoneInProc as u8);
+        if let TdsToken::Done(result) = TokenDone::decode(&mut buff)? {
+            assert_eq!(result.done_rows, 3);
+        }
+        assert_eq!(buff.get_u8(), TdsTokenType::DoneProc as u8);
+        if let TdsToken::Done(result) = TokenDone::decode(&mut buff)? {
+            assert_eq!(result.cur_cmd, 0xE0);
+            assert!(result.status.is_empty());
+        }
+        Ok(())
+    }
 }
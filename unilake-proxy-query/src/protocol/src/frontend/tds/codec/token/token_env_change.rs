@@ -1,4 +1,5 @@
 use crate::frontend::tds::codec::{decode, encode};
+use crate::frontend::tds::collation::Collation;
 use crate::frontend::utils::ReadAndAdvance;
 use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType};
 use std::fmt::{self, Debug};
@@ -69,7 +70,7 @@ pub enum TokenEnvChange {
     CharacterSet(String, String),
     RealTimeLogShipping(String, String),
     PacketSize(String, String),
-    SqlCollation(String, String),
+    SqlCollation(Option<Collation>, Option<Collation>),
     BeginTransaction([u8; 8]),
     CommitTransaction,
     RollbackTransaction,
@@ -87,7 +88,7 @@ impl TokenEnvChange {
     pub fn new_language_change(from: String, to: String) -> Self {
         Self::Language(from, to)
     }
-    pub fn new_collation_change(from: String, to: String) -> Self {
+    pub fn new_collation_change(from: Option<Collation>, to: Option<Collation>) -> Self {
         Self::SqlCollation(from, to)
     }
     pub fn new_packet_size_change(from: String, to: String) -> Self {
@@ -139,8 +140,11 @@ impl TdsTokenCodec for TokenEnvChange {
 
         // write changed data
         match self {
+            TokenEnvChange::SqlCollation(new, old) => {
+                write_collation(&mut buff, old);
+                write_collation(&mut buff, new);
+            }
             TokenEnvChange::Database(new, old)
-            | TokenEnvChange::SqlCollation(new, old)
             | TokenEnvChange::PacketSize(new, old)
             | TokenEnvChange::Language(new, old)
             | TokenEnvChange::CharacterSet(new, old)
@@ -181,6 +185,12 @@ impl TdsTokenCodec for TokenEnvChange {
 
                 TokenEnvChange::Database(new_value, old_value)
             }
+            EnvChangeType::SqlCollation => {
+                let new_value = read_collation(&mut buf)?;
+                let old_value = read_collation(&mut buf)?;
+
+                TokenEnvChange::SqlCollation(old_value, new_value)
+            }
             EnvChangeType::BeginTransaction | EnvChangeType::EnlistDTCTransaction => {
                 let len = buf.get_u8();
                 assert_eq!(len, 8);
@@ -213,8 +223,31 @@ impl TdsTokenCodec for TokenEnvChange {
     }
 }
 
+/// Collation values are sent as B_VARBYTE, empty if there is no collation
+fn write_collation(dest: &mut BytesMut, collation: &Option<Collation>) {
+    match collation {
+        Some(collation) => {
+            dest.put_u8(5);
+            collation.encode(dest);
+        }
+        None => dest.put_u8(0),
+    }
+}
+
+fn read_collation(src: &mut BytesMut) -> TdsWireResult<Option<Collation>> {
+    decode::check_remaining(src, 1, "collation length")?;
+    match src.get_u8() {
+        0 => Ok(None),
+        5 => Collation::decode(src).map(Some),
+        len => Err(unilake_common::error::Error::Protocol(
+            format!("invalid collation length {}", len).into(),
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::frontend::tds::collation::Collation;
     use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType, TokenEnvChange};
     use tokio_util::bytes::{Buf, BytesMut};
     use unilake_common::error::TdsWireResult;
@@ -248,4 +281,26 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn encode_decode_token_envchange_collation() -> TdsWireResult<()> {
+        let input = TokenEnvChange::new_collation_change(None, Some(Collation::utf8()));
+
+        let mut buff = BytesMut::new();
+        input.encode(&mut buff)?;
+        assert_eq!(
+            &buff[..],
+            &[0xe3, 0x08, 0x00, 0x07, 0x05, 0x09, 0x04, 0xd0, 0x24, 0x00, 0x00]
+        );
+
+        buff.advance(1);
+        match TokenEnvChange::decode(&mut buff)? {
+            TdsToken::EnvChange(TokenEnvChange::SqlCollation(from, to)) => {
+                assert_eq!(from, None);
+                assert_eq!(to, Some(Collation::utf8()));
+            }
+            result => std::panic!("unexpected result: {:?}", result),
+        }
+        Ok(())
+    }
 }
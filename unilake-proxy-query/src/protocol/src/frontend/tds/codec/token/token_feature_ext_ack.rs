@@ -1,6 +1,9 @@
-use crate::frontend::{utils::ReadAndAdvance, FeatureExt, TdsToken, TdsTokenCodec, TdsTokenType};
+use crate::frontend::tds::codec::decode::check_remaining;
+use crate::frontend::{
+    utils::ReadAndAdvance, FeatureExt, SessionStateData, TdsToken, TdsTokenCodec, TdsTokenType,
+};
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 /// Feature Extension Acknowledgement token [2.2.7.11]
 /// Introduced in TDS 7.4, FEATUREEXTACK is used to send an optional acknowledge
@@ -19,14 +22,34 @@ pub enum FedAuthAck {
 #[derive(Debug)]
 pub enum FeatureAck {
     FedAuth(FedAuthAck),
-    SessionRecovery,
+    /// The initial session state of the session
+    SessionRecovery(Vec<SessionStateData>),
     GenericOption(Vec<u8>),
     Utf8Support(bool),
+    DataClassification {
+        version: u8,
+        enabled: bool,
+    },
 }
 
 impl FeatureAck {
-    pub fn new_session_recovery() -> Self {
-        FeatureAck::SessionRecovery
+    pub fn new_session_recovery(states: Vec<SessionStateData>) -> Self {
+        FeatureAck::SessionRecovery(states)
+    }
+
+    pub fn new_fed_auth() -> Self {
+        FeatureAck::FedAuth(FedAuthAck::SecurityToken { nonce: None })
+    }
+
+    pub fn new_utf8_support() -> Self {
+        FeatureAck::Utf8Support(true)
+    }
+
+    pub fn new_data_classification(version: u8) -> Self {
+        FeatureAck::DataClassification {
+            version,
+            enabled: true,
+        }
     }
 }
 
@@ -37,12 +60,31 @@ impl TdsTokenCodec for TokenFeatureExtAck {
             match item {
                 FeatureAck::FedAuth(s) => match s {
                     FedAuthAck::SecurityToken { nonce } => {
+                        // the ack data is empty for the security token and ADAL/MSAL libraries
                         dest.put_u8(FeatureExt::FedAuth as u8);
-                        let len = nonce.as_ref().unwrap().len();
-                        dest.put_u32_le(len as u32);
-                        dest.put_slice(nonce.as_ref().unwrap());
+                        let nonce = nonce.as_deref().unwrap_or_default();
+                        dest.put_u32_le(nonce.len() as u32);
+                        dest.put_slice(nonce);
                     }
                 },
+                FeatureAck::SessionRecovery(states) => {
+                    let mut data = BytesMut::new();
+                    states.iter().for_each(|s| s.encode(&mut data));
+                    dest.put_u8(FeatureExt::SessionRecovery as u8);
+                    dest.put_u32_le(data.len() as u32);
+                    dest.put_slice(&data);
+                }
+                FeatureAck::Utf8Support(enabled) => {
+                    dest.put_u8(FeatureExt::Utf8Support as u8);
+                    dest.put_u32_le(1);
+                    dest.put_u8(*enabled as u8);
+                }
+                FeatureAck::DataClassification { version, enabled } => {
+                    dest.put_u8(FeatureExt::DataClassification as u8);
+                    dest.put_u32_le(2);
+                    dest.put_u8(*version);
+                    dest.put_u8(*enabled as u8);
+                }
                 _ => unimplemented!("unsupported feature {:?}", item),
             }
         }
@@ -67,10 +109,36 @@ impl TdsTokenCodec for TokenFeatureExtAck {
                 } else if data_len == 0 {
                     None
                 } else {
-                    panic!("invalid Feature_Ext_Ack token");
+                    return Err(TdsWireError::Protocol(
+                        "Invalid FeatureExtAck token".to_string(),
+                    ));
                 };
 
                 features.push(FeatureAck::FedAuth(FedAuthAck::SecurityToken { nonce }))
+            } else if feature_id == FeatureExt::SessionRecovery as u8 {
+                check_remaining(src, 4, "session recovery ack length")?;
+                let data_len = src.get_u32_le() as usize;
+                check_remaining(src, data_len, "session recovery ack")?;
+                let states = SessionStateData::decode_set(&mut src.split_to(data_len))?;
+                features.push(FeatureAck::SessionRecovery(states))
+            } else if feature_id == FeatureExt::Utf8Support as u8 {
+                check_remaining(src, 5, "utf8 support ack")?;
+                if src.get_u32_le() != 1 {
+                    return Err(TdsWireError::Protocol(
+                        "Invalid FeatureExtAck token".to_string(),
+                    ));
+                }
+                features.push(FeatureAck::Utf8Support(src.get_u8() & 0x01 != 0))
+            } else if feature_id == FeatureExt::DataClassification as u8 {
+                check_remaining(src, 6, "data classification ack")?;
+                if src.get_u32_le() != 2 {
+                    return Err(TdsWireError::Protocol(
+                        "Invalid FeatureExtAck token".to_string(),
+                    ));
+                }
+                let version = src.get_u8();
+                let enabled = src.get_u8() != 0;
+                features.push(FeatureAck::DataClassification { version, enabled })
             } else {
                 unimplemented!("unsupported feature {}", feature_id)
             }
@@ -85,7 +153,8 @@ mod tests {
     use tokio_util::bytes::{Buf, BytesMut};
 
     use crate::frontend::{
-        FeatureAck, FedAuthAck, TdsToken, TdsTokenCodec, TdsTokenType, TokenFeatureExtAck,
+        FeatureAck, FedAuthAck, SessionStateData, TdsToken, TdsTokenCodec, TdsTokenType,
+        TokenFeatureExtAck,
     };
 
     #[test]
@@ -112,4 +181,56 @@ mod tests {
             assert_eq!(result.features.len(), input.features.len());
         }
     }
+
+    #[test]
+    fn encode_decode_session_recovery_ack() {
+        let states = vec![SessionStateData::new(0, vec![1, 2, 3])];
+        let input = TokenFeatureExtAck {
+            features: vec![FeatureAck::new_session_recovery(states.clone())],
+        };
+
+        let mut buff = BytesMut::new();
+        input.encode(&mut buff).expect("should be ok");
+        buff.advance(1);
+
+        match TokenFeatureExtAck::decode(&mut buff).unwrap() {
+            TdsToken::FeatureExtAck(result) => match &result.features[..] {
+                [FeatureAck::SessionRecovery(result)] => assert_eq!(result, &states),
+                features => panic!("unexpected features: {:?}", features),
+            },
+            _ => panic!("unexpected token"),
+        }
+    }
+
+    #[test]
+    fn encode_decode_utf8_support_and_data_classification_ack() {
+        let input = TokenFeatureExtAck {
+            features: vec![
+                FeatureAck::new_utf8_support(),
+                FeatureAck::new_data_classification(1),
+            ],
+        };
+
+        let mut buff = BytesMut::new();
+        input.encode(&mut buff).expect("should be ok");
+        assert_eq!(
+            &buff[..],
+            &[
+                0xae, 0x0a, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x00, 0x00, 0x00, 0x01, 0x01,
+                0xff
+            ]
+        );
+        buff.advance(1);
+
+        match TokenFeatureExtAck::decode(&mut buff).unwrap() {
+            TdsToken::FeatureExtAck(result) => match &result.features[..] {
+                [FeatureAck::Utf8Support(true), FeatureAck::DataClassification {
+                    version: 1,
+                    enabled: true,
+                }] => {}
+                features => panic!("unexpected features: {:?}", features),
+            },
+            _ => panic!("unexpected token"),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::frontend::tds::codec::decode::read_string;
 use crate::frontend::{utils::ReadAndAdvance, TdsToken, TdsTokenCodec, TdsTokenType};
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::TdsWireResult;
@@ -17,42 +18,53 @@ pub enum TokenPreLoginFedAuthRequiredOption {
 }
 
 /// FedAuthInfo Token [2.2.7.12]
+/// Sent in response to a LOGIN7 message with the ADAL/MSAL workflow, contains the information
+/// the client needs to acquire an access token. Both values are sent as unicode strings.
 #[derive(Debug)]
 pub struct TokenFedAuth {
     pub options: Vec<TokenFedAuthOption>,
 }
 
+const FED_AUTH_INFO_ID_STSURL: u8 = 0x01;
+const FED_AUTH_INFO_ID_SPN: u8 = 0x02;
+
+impl TokenFedAuth {
+    pub fn new(sts_url: &str, spn: &str) -> Self {
+        TokenFedAuth {
+            options: vec![
+                TokenFedAuthOption::StsUrl(sts_url.to_string()),
+                TokenFedAuthOption::Spn(spn.to_string()),
+            ],
+        }
+    }
+}
+
 impl TdsTokenCodec for TokenFedAuth {
     fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
         dest.put_u8(TdsTokenType::FedAuthInfo as u8);
         let options_length = self.options.len() * 9;
         let mut token_length = 4 + options_length;
-        let mut buff = Vec::with_capacity(token_length as usize);
+        let mut buff = Vec::with_capacity(token_length);
+        let mut items = Vec::with_capacity(self.options.len());
         for t in &self.options {
-            match t {
-                TokenFedAuthOption::Spn(s) | TokenFedAuthOption::StsUrl(s) => {
-                    let data = s.as_bytes();
-                    buff.extend_from_slice(data);
-                    token_length += data.len();
-                }
-            }
+            let (id, s) = match t {
+                TokenFedAuthOption::StsUrl(s) => (FED_AUTH_INFO_ID_STSURL, s),
+                TokenFedAuthOption::Spn(s) => (FED_AUTH_INFO_ID_SPN, s),
+            };
+            let data = s
+                .encode_utf16()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<u8>>();
+            buff.extend_from_slice(&data);
+            token_length += data.len();
+            items.push((id, data.len() as u32));
         }
 
         dest.put_u32_le(token_length as u32);
         dest.put_u32_le(self.options.len() as u32);
         let mut curr_offset = (4 + options_length) as u32;
-        for t in &self.options {
-            let length;
-            match t {
-                TokenFedAuthOption::Spn(d) => {
-                    dest.put_u8(0x01);
-                    length = d.as_bytes().len() as u32;
-                }
-                TokenFedAuthOption::StsUrl(d) => {
-                    dest.put_u8(0x02);
-                    length = d.as_bytes().len() as u32;
-                }
-            }
+        for (id, length) in items {
+            dest.put_u8(id);
             dest.put_u32_le(length);
             dest.put_u32_le(curr_offset);
             curr_offset += length;
@@ -78,27 +90,20 @@ impl TdsTokenCodec for TokenFedAuth {
         }
 
         for (ty, info_data_length, _) in items {
-            let (_, buff) = src.read_and_advance(info_data_length as usize);
-
-            let content = String::from_utf8(buff.to_vec())
-                .map_err(|_| {
-                    unilake_common::error::Error::Protocol(
-                        "Failed to convert UTF-8 to String".to_string(),
-                    )
-                })
-                .unwrap();
+            let (_, mut buff) = src.read_and_advance(info_data_length as usize);
+            let content = read_string(&mut buff, info_data_length as usize / 2)?;
 
             match ty {
                 // STS URL as Token Endpoint
-                0x01 => {
+                FED_AUTH_INFO_ID_STSURL => {
                     options.push(TokenFedAuthOption::StsUrl(content));
                 }
                 // Service Principal Name
-                0x02 => {
+                FED_AUTH_INFO_ID_SPN => {
                     options.push(TokenFedAuthOption::Spn(content));
                 }
                 // Invalid InfoId
-                0xEE | _ => {
+                _ => {
                     break;
                 }
             }
@@ -1,61 +1,71 @@
-use crate::frontend::TdsTokenType;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::frontend::tds::codec::decode::check_remaining;
+use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::TdsWireResult;
 
 /// Return Status token [2.2.7.18]
 /// Used to send the status value of an RPC to the client.
 /// The server also uses this token to send the result status value of a T-SQL EXEC query.
+#[derive(Debug)]
 pub struct TokenReturnStatus {
-    value: i32,
+    pub value: i32,
 }
 
 impl TokenReturnStatus {
-    pub async fn decode<R>(src: &mut R) -> TdsWireResult<Self>
-    where
-        R: AsyncRead + Unpin,
-    {
-        Ok(TokenReturnStatus {
-            value: src.read_i32_le().await?,
-        })
+    pub fn new(value: i32) -> Self {
+        TokenReturnStatus { value }
     }
+}
 
-    pub async fn encode<W>(&mut self, dest: &mut W) -> TdsWireResult<()>
-    where
-        W: AsyncWrite + Unpin,
-    {
-        dest.write_u8(TdsTokenType::ReturnStatus as u8).await?;
-        dest.write_i32_le(self.value).await?;
+impl TdsTokenCodec for TokenReturnStatus {
+    fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        dest.put_u8(TdsTokenType::ReturnStatus as u8);
+        dest.put_i32_le(self.value);
         Ok(())
     }
+
+    fn decode(src: &mut BytesMut) -> TdsWireResult<TdsToken> {
+        check_remaining(src, 4, "return status")?;
+        Ok(TdsToken::ReturnStatus(TokenReturnStatus {
+            value: src.get_i32_le(),
+        }))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::frontend::{TdsTokenType, TokenReturnStatus};
-    use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+    use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType, TokenReturnStatus};
+    use tokio_util::bytes::{Buf, BytesMut};
     use unilake_common::error::TdsWireResult;
 
-    #[tokio::test]
-    async fn encode_decode_token_return_status() -> TdsWireResult<()> {
-        let mut input = TokenReturnStatus { value: 12 };
+    #[test]
+    fn encode_decode_token_return_status() -> TdsWireResult<()> {
+        let input = TokenReturnStatus::new(12);
 
         // arrange
-        let (inner, outer) = tokio::io::duplex(256);
-        let mut writer = BufWriter::new(inner);
-        let mut reader = BufReader::new(outer);
+        let mut buff = BytesMut::new();
 
         // encode
-        input.encode(&mut writer).await?;
-        writer.flush().await?;
+        input.encode(&mut buff)?;
 
         // decode
-        let tokentype = reader.read_u8().await?;
-        let result = TokenReturnStatus::decode(&mut reader).await?;
+        let tokentype = buff.get_u8();
+        let result = TokenReturnStatus::decode(&mut buff)?;
 
         // assert
         assert_eq!(tokentype, TdsTokenType::ReturnStatus as u8);
-        assert_eq!(input.value, result.value);
+        if let TdsToken::ReturnStatus(result) = result {
+            assert_eq!(input.value, result.value);
+        } else {
+            panic!("Could not find Return Status Token")
+        }
 
         Ok(())
     }
+
+    #[test]
+    fn decode_truncated_token_return_status() {
+        let mut buff = BytesMut::from(&[0x0c, 0x00][..]);
+        assert!(TokenReturnStatus::decode(&mut buff).is_err());
+    }
 }
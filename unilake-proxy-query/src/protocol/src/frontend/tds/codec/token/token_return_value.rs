@@ -1,9 +1,13 @@
 use super::BaseMetaDataColumn;
-use crate::frontend::tds::codec::encode;
+use crate::frontend::tds::codec::{decode, encode};
 use crate::frontend::{ColumnData, TdsToken, TdsTokenCodec, TdsTokenType};
-use tokio_util::bytes::{BufMut, BytesMut};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::TdsWireResult;
 
+/// Status flags of a return value [2.2.7.19]
+const RETURN_VALUE_STATUS_OUTPUT: u8 = 0x01;
+const RETURN_VALUE_STATUS_UDF: u8 = 0x02;
+
 /// ReturnValue Token [2.2.7.19]
 /// Used to send the return value of an RPC to the client. When an RPC is executed,
 /// the associated parameters might be defined as input or output (or "return") parameters.
@@ -25,16 +29,31 @@ impl TdsTokenCodec for TokenReturnValue {
 
         dest.put_u16_le(self.param_ordinal);
         encode::write_b_varchar(dest, &self.param_name)?;
-        dest.put_u8(self.udf as u8);
+        dest.put_u8(if self.udf {
+            RETURN_VALUE_STATUS_UDF
+        } else {
+            RETURN_VALUE_STATUS_OUTPUT
+        });
         self.meta.encode(dest);
         self.value.encode(dest)?;
 
         Ok(())
     }
 
-    /// Decode is not implemented for this token type.
-    fn decode(_src: &mut BytesMut) -> TdsWireResult<TdsToken> {
-        unimplemented!()
+    fn decode(src: &mut BytesMut) -> TdsWireResult<TdsToken> {
+        let param_ordinal = src.get_u16_le();
+        let param_name = decode::read_b_varchar(src)?;
+        let udf = src.get_u8() & RETURN_VALUE_STATUS_UDF != 0;
+        let meta = BaseMetaDataColumn::decode(src)?;
+        let value = ColumnData::decode(src, &meta.ty)?;
+
+        Ok(TdsToken::ReturnValue(TokenReturnValue {
+            param_ordinal,
+            param_name,
+            udf,
+            meta,
+            value,
+        }))
     }
 }
 
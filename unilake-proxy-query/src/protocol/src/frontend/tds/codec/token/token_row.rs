@@ -68,6 +68,11 @@ impl TokenRow {
         self.data.get(index)
     }
 
+    /// Gives mutable access to the columnar data with the given index.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut ColumnData> {
+        self.data.get_mut(index)
+    }
+
     /// Adds a new value to the row.
     pub fn push<V>(&mut self, value: V)
     where
@@ -147,7 +152,6 @@ impl RowBitmap {
                 | ColumnData::I64N(None)
                 | ColumnData::F32N(None)
                 | ColumnData::F64N(None)
-                | ColumnData::Binary(None)
                 | ColumnData::Numeric(None)
                 | ColumnData::DateTime(None)
                 | ColumnData::SmallDateTime(None)
@@ -163,6 +167,11 @@ impl RowBitmap {
                         ret.set_null(i);
                     }
                 }
+                ColumnData::Binary(b) => {
+                    if b.is_empty() {
+                        ret.set_null(i);
+                    }
+                }
                 _ => {}
             }
         }
@@ -1,37 +1,40 @@
-use crate::frontend::{TdsToken, TdsTokenCodec, TdsTokenType};
+use crate::frontend::{SessionStateData, TdsToken, TdsTokenCodec, TdsTokenType};
 use tokio_util::bytes::{BufMut, BytesMut};
 use unilake_common::error::{TdsWireError, TdsWireResult};
 
+/// Status flag marking the session state as recoverable
+const FRECOVERABLE: u8 = 0x01;
+
 /// SESSIONSTATE token [2.2.7.21]
 /// For sending session state information to the client
 #[derive(Debug)]
 pub struct TokenSessionState {
+    /// Sequence number of the change, increments with every change of the session state
     seq_no: u32,
-    status: u8,
-    state_id: u8,
-    state_value: Vec<u8>,
+    recoverable: bool,
+    states: Vec<SessionStateData>,
 }
 
 impl TokenSessionState {
-    pub fn new(seq_no: u32, status: u8, state_id: u8, state_value: Vec<u8>) -> TokenSessionState {
+    pub fn new(seq_no: u32, recoverable: bool, states: Vec<SessionStateData>) -> TokenSessionState {
         TokenSessionState {
             seq_no,
-            status,
-            state_id,
-            state_value,
+            recoverable,
+            states,
         }
     }
 }
 
 impl TdsTokenCodec for TokenSessionState {
     fn encode(&self, dest: &mut BytesMut) -> TdsWireResult<()> {
+        let mut states = BytesMut::new();
+        self.states.iter().for_each(|s| s.encode(&mut states));
+
         dest.put_u8(TdsTokenType::SessionState as u8);
-        dest.put_u32_le(6 + self.state_value.len() as u32);
+        dest.put_u32_le(4 + 1 + states.len() as u32);
         dest.put_u32_le(self.seq_no);
-        dest.put_u8(self.status);
-        dest.put_u8(self.state_id);
-        dest.put_u8(self.state_value.len() as u8);
-        dest.put_slice(&self.state_value);
+        dest.put_u8(if self.recoverable { FRECOVERABLE } else { 0 });
+        dest.put_slice(&states);
         Ok(())
     }
 
@@ -41,3 +44,36 @@ impl TdsTokenCodec for TokenSessionState {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{SessionStateData, TdsTokenCodec, TdsTokenType, TokenSessionState};
+    use tokio_util::bytes::{Buf, BytesMut};
+
+    #[test]
+    fn encode_token_session_state() {
+        let token = TokenSessionState::new(
+            7,
+            true,
+            vec![
+                SessionStateData::new(0, vec![1, 2]),
+                SessionStateData::new(1, vec![0; 300]),
+            ],
+        );
+        let mut buff = BytesMut::new();
+        token.encode(&mut buff).unwrap();
+
+        assert_eq!(buff.get_u8(), TdsTokenType::SessionState as u8);
+        let length = buff.get_u32_le() as usize;
+        assert_eq!(length, buff.remaining());
+        assert_eq!(buff.get_u32_le(), 7);
+        assert_eq!(buff.get_u8(), 0x01);
+        assert_eq!(&buff[..4], &[0, 2, 1, 2]);
+        buff.advance(4);
+        // the second state does not fit a single length byte
+        assert_eq!(&buff[..2], &[1, 0xff]);
+        buff.advance(2);
+        assert_eq!(buff.get_u32_le(), 300);
+        assert_eq!(buff.remaining(), 300);
+    }
+}
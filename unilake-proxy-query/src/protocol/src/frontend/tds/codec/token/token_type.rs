@@ -92,6 +92,10 @@ uint_enum! {
         /// for session recovery during login and login response.
         SessionState = 0xE4,
 
+        /// Introduced in TDS 7.4, used to send the data classification (sensitivity) of the
+        /// columns of a result set. Sent directly after COLMETADATA.
+        DataClassification = 0xA3,
+
         // The following types have been left out
         /*
             AlternativeMetadata = 0x88,
@@ -104,7 +108,4 @@ uint_enum! {
 
 // TODO: check if the following tokens should be implemented:
 // ColumnInfo = 0xA5,
-// DoneProcedure = 0xFE,
-// DoneInProc = 0xFF,
 // FedAuthInfo = 0xEE,
-// ReturnStatus = 0x79,
@@ -1,7 +1,9 @@
 use super::TdsToken;
-use crate::frontend::{PacketHeader, TdsMessage};
+use crate::frontend::{
+    PacketHeader, TdsMessage, ALL_HEADERS_LEN_TX, MAX_MESSAGE_SIZE, MAX_PACKET_SIZE,
+};
 use tokio_util::bytes::{Buf, BytesMut};
-use unilake_common::error::TdsWireResult;
+use unilake_common::error::{TdsWireError, TdsWireResult};
 
 // Complete Frontend Request
 pub struct TdsFrontendRequest {
@@ -9,30 +11,76 @@ pub struct TdsFrontendRequest {
 }
 
 impl TdsFrontendRequest {
+    /// Decode a single message, which can be split across multiple packets. Returns none, without
+    /// consuming any data, if not all packets of the message have been received yet.
     pub fn decode(buf: &mut BytesMut) -> TdsWireResult<Option<Self>> {
-        let mut messages = Vec::new();
-        while buf.has_remaining() {
-            let header = PacketHeader::decode(buf)?;
-            tracing::debug!(
-                message = "Receiving packet",
-                message_type = header.ty.to_string(),
-                message_length = header.length
-            );
-
-            // ignore packets with status "IgnoreEvent" (0x01 must also be set)
-            if header.is_ignore_event && header.is_end_of_message {
-                continue;
+        // find the end of the message
+        let mut message_length = 0;
+        loop {
+            let header = match buf.get(message_length..message_length + ALL_HEADERS_LEN_TX) {
+                Some(header) => PacketHeader::decode(&mut BytesMut::from(header))?,
+                None => return Ok(None),
+            };
+            let packet_length = header.length as usize;
+            if packet_length < ALL_HEADERS_LEN_TX || packet_length > MAX_PACKET_SIZE {
+                return Err(TdsWireError::Protocol(format!(
+                    "Invalid packet size: {}",
+                    packet_length
+                )));
+            }
+            message_length += packet_length;
+            if message_length > MAX_MESSAGE_SIZE {
+                return Err(TdsWireError::Protocol("Message too large".to_string()));
+            }
+            if buf.len() < message_length {
+                // wait for more data
+                return Ok(None);
             }
-
-            // add message to result
-            messages.push((header, TdsMessage::decode(buf, header.ty)?));
-
-            // no need to proceed with EOM messages
             if header.is_end_of_message {
                 break;
             }
         }
-        Ok(Some(Self { messages }))
+
+        // combine the payload of all packets
+        let mut packets = buf.split_to(message_length);
+        let header = PacketHeader::decode(&mut packets)?;
+        let mut payload = packets.split_to(header.length as usize - ALL_HEADERS_LEN_TX);
+        let mut is_ignore_event = header.is_ignore_event;
+        while packets.has_remaining() {
+            let next = PacketHeader::decode(&mut packets)?;
+            if next.ty != header.ty {
+                return Err(TdsWireError::Protocol(format!(
+                    "Unexpected {} packet while receiving a {} message",
+                    next.ty, header.ty
+                )));
+            }
+            payload.unsplit(packets.split_to(next.length as usize - ALL_HEADERS_LEN_TX));
+            is_ignore_event = next.is_ignore_event;
+        }
+        tracing::debug!(
+            message = "Receiving message",
+            message_type = header.ty.to_string(),
+            message_length = payload.len()
+        );
+
+        // the client cancelled sending the message, ignore it
+        if is_ignore_event {
+            return Ok(Some(Self {
+                messages: Vec::new(),
+            }));
+        }
+
+        let message = TdsMessage::decode(&mut payload, header.ty)?;
+        if payload.has_remaining() {
+            tracing::warn!(
+                "Ignoring {} residual bytes of {} message",
+                payload.len(),
+                header.ty
+            );
+        }
+        Ok(Some(Self {
+            messages: vec![(header, message)],
+        }))
     }
 }
 
@@ -42,3 +90,205 @@ pub enum TdsBackendResponse {
     Message(TdsMessage),
     Done,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        BatchRequest, FedAuthTokenMessage, LoginMessage, PacketHeader, PacketType, PreloginMessage,
+        TdsFrontendRequest, TdsMessage, TdsMessageCodec,
+    };
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use tokio_util::bytes::BytesMut;
+
+    /// sp_executesql with parameters of most supported types
+    const RPC: &[u8] = &[
+        0x16, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xE7, 0x40, 0x1F, 0x09, 0x04, 0xD0, 0x00, 0x34, 0x14, 0x00, 0x73, 0x00, 0x65, 0x00, 0x6C,
+        0x00, 0x65, 0x00, 0x63, 0x00, 0x74, 0x00, 0x20, 0x00, 0x40, 0x00, 0x70, 0x00, 0x31, 0x00,
+        0x03, 0x40, 0x00, 0x70, 0x00, 0x31, 0x00, 0x00, 0x26, 0x04, 0x04, 0x2A, 0x00, 0x00, 0x00,
+        0x03, 0x40, 0x00, 0x70, 0x00, 0x32, 0x00, 0x00, 0x6A, 0x11, 0x12, 0x02, 0x05, 0x01, 0x39,
+        0x30, 0x00, 0x00, 0x03, 0x40, 0x00, 0x70, 0x00, 0x33, 0x00, 0x00, 0x2A, 0x07, 0x08, 0x80,
+        0xB7, 0x14, 0xAB, 0x08, 0xBB, 0x29, 0x0B, 0x03, 0x40, 0x00, 0x70, 0x00, 0x34, 0x00, 0x00,
+        0xE7, 0xFF, 0xFF, 0x09, 0x04, 0xD0, 0x00, 0x34, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x74, 0x00, 0x65, 0x00, 0x78, 0x00, 0x74, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x03, 0x40, 0x00, 0x70, 0x00, 0x35, 0x00, 0x00, 0xA5, 0x64, 0x00, 0x03, 0x00,
+        0x01, 0x02, 0x03, 0x03, 0x40, 0x00, 0x70, 0x00, 0x36, 0x00, 0x00, 0x6F, 0x08, 0x08, 0x88,
+        0x90, 0x00, 0x00, 0x2C, 0x01, 0x00, 0x00, 0x03, 0x40, 0x00, 0x70, 0x00, 0x37, 0x00, 0x01,
+        0x3E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF8, 0x3F,
+    ];
+
+    fn encode<M: TdsMessageCodec>(message: M) -> Vec<u8> {
+        let mut buff = BytesMut::new();
+        message.encode(&mut buff).unwrap();
+        buff.to_vec()
+    }
+
+    /// Valid messages sent by clients, used as the seed of the corpus
+    fn seeds() -> Vec<(PacketType, Vec<u8>)> {
+        let mut login = LoginMessage::new();
+        login.username = Some("user".to_string());
+        login.password = Some("password".to_string());
+        login.db_name = Some("database".to_string());
+        let mut fed_auth_login = LoginMessage::new();
+        fed_auth_login.aad_token(
+            "eyJhbGciOiJFZERTQSJ9".to_string(),
+            true,
+            Some(vec![7u8; 32]),
+        );
+
+        vec![
+            (PacketType::PreLogin, encode(PreloginMessage::new())),
+            (PacketType::TDSv7Login, encode(login)),
+            (PacketType::TDSv7Login, encode(fed_auth_login)),
+            (
+                PacketType::Fat,
+                encode(FedAuthTokenMessage::new(
+                    "eyJ0eXAiOiJKV1QifQ".to_string(),
+                    None,
+                )),
+            ),
+            (
+                PacketType::SQLBatch,
+                encode(BatchRequest {
+                    query: "select 1".to_string(),
+                    query_lowercased: "select 1".to_string(),
+                    transaction_descriptor: vec![0; 8],
+                }),
+            ),
+            (PacketType::Rpc, RPC.to_vec()),
+            (PacketType::Attention, Vec::new()),
+        ]
+    }
+
+    /// Frame the payload in packets of at most the given payload size
+    fn packets(ty: PacketType, payload: &[u8], size: usize) -> Vec<u8> {
+        let mut buff = BytesMut::new();
+        let chunks = payload.chunks(size.max(1)).collect::<Vec<_>>();
+        let count = chunks.len().max(1);
+        for i in 0..count {
+            let chunk = chunks.get(i).copied().unwrap_or_default();
+            let mut header = PacketHeader::new(chunk.len() + 8, i as u8 + 1);
+            header.ty = ty;
+            header.is_end_of_message = i == count - 1;
+            header.encode(&mut buff).unwrap();
+            buff.extend_from_slice(chunk);
+        }
+        buff.to_vec()
+    }
+
+    /// Decode the input, fails if decoding panics
+    fn decode(input: &[u8]) -> Option<TdsFrontendRequest> {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            TdsFrontendRequest::decode(&mut BytesMut::from(input))
+        }));
+        match result {
+            Ok(result) => result.ok().flatten(),
+            Err(_) => panic!("decoding panicked for input {:02X?}", input),
+        }
+    }
+
+    #[test]
+    fn decode_seeds() {
+        for (ty, payload) in seeds() {
+            let request = decode(&packets(ty, &payload, 4096)).unwrap();
+            assert_eq!(request.messages.len(), 1, "{} not decoded", ty);
+        }
+    }
+
+    #[test]
+    fn decode_message_split_across_packets() {
+        let query = "select * from table_with_a_long_name where id = 1".to_string();
+        let payload = encode(BatchRequest {
+            query: query.clone(),
+            query_lowercased: query.to_lowercase(),
+            transaction_descriptor: vec![0; 8],
+        });
+        let input = packets(PacketType::SQLBatch, &payload, 16);
+
+        // nothing is consumed until the last packet has been received
+        let mut buff = BytesMut::from(&input[..input.len() - 1]);
+        assert!(TdsFrontendRequest::decode(&mut buff).unwrap().is_none());
+        assert_eq!(buff.len(), input.len() - 1);
+
+        let mut buff = BytesMut::from(&input[..]);
+        let request = TdsFrontendRequest::decode(&mut buff).unwrap().unwrap();
+        assert!(buff.is_empty());
+        match &request.messages[0].1 {
+            TdsMessage::BatchRequest(b) => assert_eq!(b.query, query),
+            m => panic!("unexpected message type: {:?}", m),
+        }
+    }
+
+    #[test]
+    fn ignore_event_discards_message() {
+        let mut input = packets(PacketType::SQLBatch, &seeds()[4].1, 4096);
+        input[1] |= 0x02;
+        let request = decode(&input).unwrap();
+        assert!(request.messages.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_packets() {
+        let payload = &seeds()[4].1;
+        let mut input = packets(PacketType::SQLBatch, payload, 16);
+        // packet type changes within the message
+        input[24] = PacketType::Rpc as u8;
+        assert!(TdsFrontendRequest::decode(&mut BytesMut::from(&input[..])).is_err());
+        // packet length smaller than the header
+        let mut input = packets(PacketType::SQLBatch, payload, 4096);
+        input[3] = 0x04;
+        assert!(TdsFrontendRequest::decode(&mut BytesMut::from(&input[..])).is_err());
+        // unsupported packet type
+        let input = packets(PacketType::BulkLoad, payload, 4096);
+        assert!(TdsFrontendRequest::decode(&mut BytesMut::from(&input[..])).is_err());
+    }
+
+    #[test]
+    fn malformed_packets_do_not_panic() {
+        // xorshift, deterministic so failures can be reproduced
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for (ty, payload) in seeds() {
+            // truncated payloads, framed as a complete message
+            for len in 0..payload.len() {
+                decode(&packets(ty, &payload[..len], 4096));
+                decode(&packets(ty, &payload[..len], 7));
+            }
+
+            // truncated packets
+            let input = packets(ty, &payload, 4096);
+            for len in 0..input.len() {
+                decode(&input[..len]);
+            }
+
+            // single byte mutations of the header and payload
+            for i in 0..input.len() {
+                for value in [0x00, 0xFF, input[i] ^ 0x01, input[i] ^ 0x80] {
+                    let mut mutated = input.clone();
+                    mutated[i] = value;
+                    decode(&mutated);
+                }
+            }
+
+            // random mutations of multiple bytes
+            for _ in 0..2000 {
+                let mut mutated = payload.clone();
+                if mutated.is_empty() {
+                    break;
+                }
+                for _ in 0..(random() % 8 + 1) {
+                    let i = random() as usize % mutated.len();
+                    mutated[i] = random() as u8;
+                }
+                decode(&packets(ty, &mutated, 4096));
+            }
+        }
+    }
+}
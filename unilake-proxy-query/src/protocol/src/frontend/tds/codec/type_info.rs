@@ -1,8 +1,12 @@
+use crate::frontend::tds::codec::decode::check_remaining;
 use crate::frontend::tds::collation::Collation;
 use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::{Error, TdsWireResult};
 
-#[derive(Debug)]
+/// Maximum length of a (non max) varchar, in bytes
+const MAX_VARCHAR_LENGTH: usize = 8000;
+
+#[derive(Debug, Clone)]
 pub enum TypeInfo {
     FixedLen(FixedLenType),
     VarLenSized(VarLenContext),
@@ -15,7 +19,10 @@ pub enum TypeInfo {
 }
 
 impl TypeInfo {
-    pub fn new_bit() -> Self {
+    pub fn new_bit(is_nullable: bool) -> Self {
+        if is_nullable {
+            return Self::VarLenSized(VarLenContext::new(VarLenType::Bitn, 1, None));
+        }
         Self::FixedLen(FixedLenType::Bit)
     }
     pub fn new_tinyint(is_nullable: bool) -> Self {
@@ -43,11 +50,18 @@ impl TypeInfo {
         Self::FixedLen(FixedLenType::Int8)
     }
     pub fn new_decimal(precision: u8, scale: u8) -> Self {
+        // the size is the maximum length of a value, including the sign byte
+        let size = match precision {
+            0..=9 => 5,
+            10..=19 => 9,
+            20..=28 => 13,
+            _ => 17,
+        };
         Self::VarLenSizedPrecision {
             ty: VarLenType::Decimaln,
-            size: (precision + scale) as usize,
-            precision: precision,
-            scale: scale,
+            size,
+            precision,
+            scale,
         }
     }
     pub fn new_float_32(is_nullable: bool) -> Self {
@@ -65,7 +79,10 @@ impl TypeInfo {
     pub fn new_date() -> Self {
         Self::VarLenSized(VarLenContext::new(VarLenType::Daten, 0, None))
     }
-    pub fn new_datetime() -> Self {
+    pub fn new_time() -> Self {
+        Self::VarLenSized(VarLenContext::new(VarLenType::Timen, 7, None))
+    }
+    pub fn new_datetime2() -> Self {
         Self::VarLenSized(VarLenContext::new(VarLenType::Datetime2, 7, None))
     }
     pub fn new_nvarchar(max_len: usize) -> Self {
@@ -82,6 +99,22 @@ impl TypeInfo {
             Some(Collation::default()),
         ))
     }
+    /// UTF-8 encoded varchar, of max length if the length exceeds that of a regular varchar
+    pub fn new_varchar_utf8(max_len: usize) -> Self {
+        let max_len = if max_len > MAX_VARCHAR_LENGTH {
+            0xFFFF
+        } else {
+            max_len
+        };
+        Self::VarLenSized(VarLenContext::new(
+            VarLenType::BigVarChar,
+            max_len,
+            Some(Collation::utf8()),
+        ))
+    }
+    pub fn new_varbinary(max_len: usize) -> Self {
+        Self::VarLenSized(VarLenContext::new(VarLenType::BigVarBin, max_len, None))
+    }
     //todo(mrhamburg): ssvariant?
 }
 
@@ -102,7 +135,7 @@ impl VarLenContext {
     }
 
     pub fn new_nvarchar() -> Self {
-        todo!()
+        Self::new(VarLenType::NVarchar, 0xFFFF, Some(Collation::default()))
     }
 
     /// Get the var len context's r#type.
@@ -164,6 +197,7 @@ uint_enum! {
 
 impl TypeInfo {
     pub fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        check_remaining(src, 1, "type")?;
         let ty = src.get_u8();
 
         if let Ok(ty) = FixedLenType::try_from(ty) {
@@ -174,43 +208,38 @@ impl TypeInfo {
             Err(()) => Err(Error::Protocol(
                 format!("invalid or unsupported column type: {:?}", ty).into(),
             )),
+            Ok(VarLenType::SSVariant) => Err(Error::Protocol(
+                "unsupported column type: sql_variant".to_string(),
+            )),
             Ok(ty) => {
                 let len = match ty {
-                    VarLenType::Timen | VarLenType::DatetimeOffsetn | VarLenType::Datetime2 => {
-                        src.get_u8() as usize
-                    }
                     VarLenType::Daten => 3,
-                    VarLenType::Bitn
-                    | VarLenType::Intn
-                    | VarLenType::Floatn
-                    | VarLenType::Decimaln
-                    | VarLenType::Numericn
-                    | VarLenType::Datetimen => src.get_u8() as usize,
                     VarLenType::NChar
                     | VarLenType::BigChar
                     | VarLenType::NVarchar
                     | VarLenType::BigVarChar
                     | VarLenType::BigBinary
-                    | VarLenType::BigVarBin => src.get_u16_le() as usize,
-                    _ => todo!("not yet implemented for {:?}", ty),
+                    | VarLenType::BigVarBin => {
+                        check_remaining(src, 2, "type length")?;
+                        src.get_u16_le() as usize
+                    }
+                    _ => {
+                        check_remaining(src, 1, "type length")?;
+                        src.get_u8() as usize
+                    }
                 };
 
                 let collation = match ty {
                     VarLenType::BigChar
                     | VarLenType::NChar
                     | VarLenType::NVarchar
-                    | VarLenType::BigVarChar => {
-                        let codepage = src.get_u16_le();
-                        let flags = src.get_u16_le();
-                        let charset_id = src.get_u8();
-
-                        Some(Collation::new(codepage, flags, charset_id))
-                    }
+                    | VarLenType::BigVarChar => Some(Collation::decode(src)?),
                     _ => None,
                 };
 
                 let vty = match ty {
                     VarLenType::Decimaln | VarLenType::Numericn => {
+                        check_remaining(src, 2, "precision and scale")?;
                         let precision = src.get_u8();
                         let scale = src.get_u8();
 
@@ -256,13 +285,8 @@ impl TypeInfo {
                 }
 
                 // write collation
-                match ty.collation {
-                    Some(c) => {
-                        dest.put_u16_le(c.codepage);
-                        dest.put_u16_le(c.flags);
-                        dest.put_u8(c.charset_id);
-                    }
-                    _ => {}
+                if let Some(c) = ty.collation {
+                    c.encode(dest);
                 }
             }
             TypeInfo::VarLenSizedPrecision {
@@ -5,10 +5,15 @@
 ///!
 ///! [1] https://github.com/Microsoft/mssql-jdbc/blob/eb14f63077c47ef1fc1c690deb8cfab602baeb85/src/main/java/com/microsoft/sqlserver/jdbc/SQLCollation.java
 ///! [2] https://github.com/lifthrasiir/rust-encoding/blob/496823171f15d9b9446b2ec3fb7765f22346256b/src/label.rs#L282
+use crate::frontend::tds::codec::decode::check_remaining;
 use encoding::{self, Encoding};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
 use unilake_common::error::{Error, TdsWireResult};
 
-#[derive(Debug, Clone, Copy)]
+/// Set in the flags if character data is UTF-8 encoded (fUTF8)
+const FLAG_UTF8: u16 = 0x0400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// Collation consists of 5 bytes (codepage (2), flags (2), charset_id (1))
 pub struct Collation {
     pub codepage: u16,
@@ -27,11 +32,36 @@ impl Collation {
         }
     }
 
-    /// Follows the StarRocks default (Latin1_General_100_CI_AS_SC_UTF8)
+    /// Unicode only collation, used for NVARCHAR data
     pub fn default() -> Self {
         Self::new(0x0439, 0x0000, 0x00)
     }
 
+    /// Follows the StarRocks default (Latin1_General_100_CI_AS_SC_UTF8), used for VARCHAR data
+    /// of clients supporting UTF-8
+    pub fn utf8() -> Self {
+        Self::new(0x0409, 0x24d0, 0x00)
+    }
+
+    /// Whether character data using this collation is UTF-8 encoded
+    pub fn is_utf8(&self) -> bool {
+        self.flags & FLAG_UTF8 != 0
+    }
+
+    pub fn encode(&self, dest: &mut BytesMut) {
+        dest.put_u16_le(self.codepage);
+        dest.put_u16_le(self.flags);
+        dest.put_u8(self.charset_id);
+    }
+
+    pub fn decode(src: &mut BytesMut) -> TdsWireResult<Self> {
+        check_remaining(src, 5, "collation")?;
+        let codepage = src.get_u16_le();
+        let flags = src.get_u16_le();
+        let charset_id = src.get_u8();
+        Ok(Self::new(codepage, flags, charset_id))
+    }
+
     /// return the locale id part of the LCID (the specification here uses ambiguous terms)
     pub fn lcid(&self) -> u16 {
         (self.flags & 0xffff) as u16
@@ -43,7 +73,9 @@ impl Collation {
 
     /// return an encoding for a given collation
     pub fn encoding(&self) -> TdsWireResult<&'static (dyn Encoding + Send + Sync)> {
-        let res = if self.codepage == 0 {
+        let res = if self.is_utf8() {
+            Some(encoding::all::UTF_8 as &'static (dyn Encoding + Send + Sync))
+        } else if self.codepage == 0 {
             lcid_to_encoding(self.lcid())
         } else {
             sortid_to_encoding(self.charset_id)
@@ -1,9 +1,26 @@
 // todo(mhramburg): move this file one level up, should not belong here
-use std::{collections::HashMap, env, str::FromStr};
+use std::{collections::HashMap, env, str::FromStr, time::Duration};
+
+use ring::{hmac, rand};
+use tokio_rustls::TlsAcceptor;
+use unilake_common::error::{TdsWireError, TdsWireResult};
+use unilake_common::settings::{
+    settings_server_encryption, settings_server_fedauth_jwks_file, settings_server_fedauth_spn,
+    settings_server_fedauth_sts_url, settings_server_login_timeout_in_seconds,
+    settings_server_max_packet_size, settings_server_packet_size,
+    settings_server_pending_session_limit, settings_server_session_limit,
+    settings_server_session_limit_per_address, settings_server_session_limit_per_user,
+    settings_server_session_recovery, settings_server_session_recovery_key,
+    settings_server_tls_certificate_path, settings_server_tls_private_key_path,
+};
 
 use super::{codec::*, EncryptionLevel};
+use crate::frontend::tls::create_tls_acceptor;
 
-const DEFAULT_PACKET_SIZE: u16 = 4096;
+/// Packet size used until the packet size has been negotiated at login
+pub const DEFAULT_PACKET_SIZE: u16 = 4096;
+/// Smallest packet size a client can negotiate
+const MIN_PACKET_SIZE: u16 = 512;
 
 /// Context, that might be required to make sure we understand and are understood by the server
 pub struct ServerContext {
@@ -12,12 +29,28 @@ pub struct ServerContext {
     pub server_name: String,
     /// The version of the server, as reported by the server. (major, minor, build, sub_build)
     server_version: (u8, u8, u16, u8),
+    /// Packet size of sessions of which the client leaves the packet size up to the server
     pub packet_size: u16,
+    /// Maximum packet size a client can negotiate
+    pub max_packet_size: u16,
     pub encryption: EncryptionLevel,
+    /// PEM encoded certificate (chain) used for TLS
     pub encryption_certificate: Option<Vec<u8>>,
+    /// PEM encoded private key belonging to the certificate
+    pub encryption_private_key: Option<Vec<u8>>,
     pub fed_auth_options: TokenPreLoginFedAuthRequiredOption,
     pub session_limit: usize,
+    /// Maximum number of concurrent sessions of a single user, 0 for no limit
+    pub session_limit_per_user: usize,
+    /// Maximum number of concurrent sessions from a single client address, 0 for no limit
+    pub session_limit_per_address: usize,
+    /// Maximum number of concurrent sessions which have not logged in yet, 0 for no limit
+    pub pending_session_limit: usize,
+    /// Time a session gets to log in before its connection is closed, zero for no timeout
+    pub login_timeout: Duration,
     pub session_recovery_enabled: bool,
+    /// Key signing the session state sent to clients for session recovery
+    pub session_recovery_key: Option<hmac::Key>,
 }
 
 pub fn optional_env<T>(env: &HashMap<String, String>, key: &str, default: T) -> T
@@ -53,14 +86,21 @@ impl ServerContext {
         ServerContext {
             server_version: (16, 0, 4135, 0),
             packet_size: DEFAULT_PACKET_SIZE,
+            max_packet_size: MAX_PACKET_SIZE as u16,
             server_name: String::from("Unilake SQL Proxy"),
-            sts_url: String::from("https://database.windows.net/"),
-            server_principal_name: String::from("https://login.windows.net/common"),
+            sts_url: String::from("https://login.windows.net/common"),
+            server_principal_name: String::from("https://database.windows.net/"),
             encryption: EncryptionLevel::NotSupported,
             encryption_certificate: None,
+            encryption_private_key: None,
             fed_auth_options: TokenPreLoginFedAuthRequiredOption::FedAuthNotRequired,
             session_limit: 1000,
+            session_limit_per_user: 0,
+            session_limit_per_address: 0,
+            pending_session_limit: 100,
+            login_timeout: Duration::from_secs(60),
             session_recovery_enabled: false,
+            session_recovery_key: None,
         }
     }
 
@@ -91,6 +131,12 @@ impl ServerContext {
         self
     }
 
+    /// The version of the server in its textual form, e.g. "16.0.4135.0"
+    pub fn get_server_version_string(&self) -> String {
+        let (major, minor, build, sub_build) = self.server_version;
+        format!("{}.{}.{}.{}", major, minor, build, sub_build)
+    }
+
     // todo(mrhamburg): the current version as returned is incorrect! add tests
     pub fn get_server_version(&self) -> u32 {
         let major = self.server_version.0;
@@ -114,6 +160,158 @@ impl ServerContext {
         self
     }
 
+    pub fn with_max_packet_size(mut self, max_packet_size: u16) -> Self {
+        self.max_packet_size = max_packet_size.clamp(MIN_PACKET_SIZE, MAX_PACKET_SIZE as u16);
+        self
+    }
+
+    /// Configure the packet sizes based on the server settings
+    pub fn with_packet_size_from_settings(self) -> Self {
+        self.with_packet_size(settings_server_packet_size())
+            .with_max_packet_size(settings_server_max_packet_size())
+    }
+
+    /// The packet size of a session, based on the packet size requested by the client in
+    /// LOGIN7. A client requesting no specific packet size (0) gets the server packet size.
+    pub fn negotiate_packet_size(&self, requested: u32) -> u16 {
+        let requested = match requested {
+            0 => self.packet_size as u32,
+            requested => requested,
+        };
+        requested.clamp(MIN_PACKET_SIZE as u32, self.max_packet_size as u32) as u16
+    }
+
+    pub fn with_encryption(
+        mut self,
+        level: EncryptionLevel,
+        certificate: Vec<u8>,
+        private_key: Vec<u8>,
+    ) -> Self {
+        self.encryption = level;
+        self.encryption_certificate = Some(certificate);
+        self.encryption_private_key = Some(private_key);
+        self
+    }
+
+    /// Configure encryption based on the server settings, the certificate and private key are
+    /// loaded from the configured (PEM) files
+    pub fn with_encryption_from_settings(self) -> TdsWireResult<Self> {
+        let level = match settings_server_encryption().as_deref() {
+            None => return Ok(self),
+            Some("off") => EncryptionLevel::Off,
+            Some("on") => EncryptionLevel::On,
+            Some("required") => EncryptionLevel::Required,
+            Some("not_supported") => return Ok(self),
+            Some(other) => {
+                return Err(TdsWireError::Tls(format!(
+                    "Unknown encryption level: {}",
+                    other
+                )))
+            }
+        };
+
+        let read_file = |path: Option<String>, name: &str| {
+            let path = path.ok_or_else(|| {
+                TdsWireError::Tls(format!("Encryption enabled, but no {} configured", name))
+            })?;
+            std::fs::read(&path)
+                .map_err(|e| TdsWireError::Tls(format!("Could not read {} {}: {}", name, path, e)))
+        };
+        let certificate = read_file(settings_server_tls_certificate_path(), "certificate")?;
+        let private_key = read_file(settings_server_tls_private_key_path(), "private key")?;
+        Ok(self.with_encryption(level, certificate, private_key))
+    }
+
+    /// Enable federated authentication if a JWKS file has been configured, the STS url and SPN
+    /// are sent to clients using the ADAL/MSAL workflow
+    pub fn with_federated_authentication_from_settings(mut self) -> Self {
+        if settings_server_fedauth_jwks_file().is_none() {
+            return self;
+        }
+        self.fed_auth_options = TokenPreLoginFedAuthRequiredOption::FedAuthRequired;
+        if let Some(sts_url) = settings_server_fedauth_sts_url() {
+            self.sts_url = sts_url;
+        }
+        if let Some(spn) = settings_server_fedauth_spn() {
+            self.server_principal_name = spn;
+        }
+        self
+    }
+
+    pub fn with_session_limits(
+        mut self,
+        session_limit: usize,
+        per_user: usize,
+        per_address: usize,
+    ) -> Self {
+        self.session_limit = session_limit;
+        self.session_limit_per_user = per_user;
+        self.session_limit_per_address = per_address;
+        self
+    }
+
+    /// Limit the sessions which have not logged in yet, in number and in time
+    pub fn with_pending_session_limits(mut self, limit: usize, login_timeout: Duration) -> Self {
+        self.pending_session_limit = limit;
+        self.login_timeout = login_timeout;
+        self
+    }
+
+    /// Configure the concurrent session limits based on the server settings
+    pub fn with_session_limits_from_settings(self) -> Self {
+        self.with_session_limits(
+            settings_server_session_limit(),
+            settings_server_session_limit_per_user(),
+            settings_server_session_limit_per_address(),
+        )
+        .with_pending_session_limits(
+            settings_server_pending_session_limit(),
+            Duration::from_secs(settings_server_login_timeout_in_seconds()),
+        )
+    }
+
+    /// Acknowledge session recovery, the session state sent to clients is signed using the
+    /// given key
+    pub fn with_session_recovery(mut self, key: &[u8]) -> Self {
+        self.session_recovery_enabled = true;
+        self.session_recovery_key = Some(hmac::Key::new(hmac::HMAC_SHA256, key));
+        self
+    }
+
+    /// Configure session recovery based on the server settings, without a configured key
+    /// sessions can only be recovered by this server instance
+    pub fn with_session_recovery_from_settings(mut self) -> TdsWireResult<Self> {
+        if !settings_server_session_recovery() {
+            return Ok(self);
+        }
+        if let Some(key) = settings_server_session_recovery_key() {
+            return Ok(self.with_session_recovery(key.as_bytes()));
+        }
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &rand::SystemRandom::new())
+            .map_err(|_| TdsWireError::Protocol("Failed to generate key".to_string()))?;
+        self.session_recovery_enabled = true;
+        self.session_recovery_key = Some(key);
+        Ok(self)
+    }
+
+    /// Create the TLS acceptor for this context, none if encryption is not configured
+    pub fn tls_acceptor(&self) -> TdsWireResult<Option<TlsAcceptor>> {
+        match (&self.encryption_certificate, &self.encryption_private_key) {
+            (Some(certificate), Some(private_key)) => {
+                create_tls_acceptor(certificate, private_key).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether the negotiated encryption level requires a TLS handshake after PRELOGIN
+    pub fn requires_tls_handshake(level: EncryptionLevel) -> bool {
+        matches!(
+            level,
+            EncryptionLevel::Off | EncryptionLevel::On | EncryptionLevel::Required
+        )
+    }
+
     pub fn build(self) -> Self {
         self
     }
@@ -152,8 +350,11 @@ impl ServerContext {
                 EncryptionLevel::On if (ctx.encryption == EncryptionLevel::None) => {
                     EncryptionLevel::None
                 }
-                // todo(mrhamburg): see below, function should return result
-                EncryptionLevel::On => todo!("return error, encryption not supported"),
+                // client will close the connection if it requires encryption
+                EncryptionLevel::On => EncryptionLevel::NotSupported,
+                EncryptionLevel::Required if (ctx.encryption == EncryptionLevel::NotSupported) => {
+                    EncryptionLevel::NotSupported
+                }
                 EncryptionLevel::None => EncryptionLevel::None,
                 _ => EncryptionLevel::Required,
             }
@@ -164,6 +365,55 @@ impl ServerContext {
 #[cfg(test)]
 mod tests {
     use crate::frontend::tds::server_context::ServerContext;
+    use crate::frontend::tds::EncryptionLevel;
+
+    #[test]
+    fn encryption_response_levels() {
+        let ctx = |level| ServerContext::new().with_encryption(level, vec![], vec![]);
+        let not_supported = ServerContext::new();
+
+        assert_eq!(
+            ServerContext::encryption_response(&not_supported, Some(EncryptionLevel::On)),
+            EncryptionLevel::NotSupported
+        );
+        assert_eq!(
+            ServerContext::encryption_response(&not_supported, Some(EncryptionLevel::Off)),
+            EncryptionLevel::NotSupported
+        );
+        assert_eq!(
+            ServerContext::encryption_response(
+                &ctx(EncryptionLevel::Off),
+                Some(EncryptionLevel::Off)
+            ),
+            EncryptionLevel::Off
+        );
+        assert_eq!(
+            ServerContext::encryption_response(
+                &ctx(EncryptionLevel::Required),
+                Some(EncryptionLevel::On)
+            ),
+            EncryptionLevel::On
+        );
+        assert_eq!(
+            ServerContext::encryption_response(
+                &ctx(EncryptionLevel::Required),
+                Some(EncryptionLevel::NotSupported)
+            ),
+            EncryptionLevel::Required
+        );
+    }
+
+    #[test]
+    fn negotiate_packet_size() {
+        let ctx = ServerContext::new().with_max_packet_size(16384);
+        assert_eq!(ctx.negotiate_packet_size(8000), 8000);
+        assert_eq!(ctx.negotiate_packet_size(32767), 16384);
+        assert_eq!(ctx.negotiate_packet_size(0), 4096);
+        assert_eq!(ctx.negotiate_packet_size(100), 512);
+
+        let ctx = ServerContext::new().with_max_packet_size(u16::MAX);
+        assert_eq!(ctx.negotiate_packet_size(u32::MAX), 32767);
+    }
 
     #[test]
     fn encode_server_version() {
@@ -0,0 +1,397 @@
+use crate::frontend::{PacketHeader, PacketType, ALL_HEADERS_LEN_TX};
+use std::io::{Error as IOError, ErrorKind};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::version::TLS12;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::bytes::{Buf, BytesMut};
+use unilake_common::error::{TdsWireError, TdsWireResult};
+
+/// Create a TLS acceptor from a PEM encoded certificate (chain) and private key
+pub fn create_tls_acceptor(certificate: &[u8], private_key: &[u8]) -> TdsWireResult<TlsAcceptor> {
+    let certs = CertificateDer::pem_slice_iter(certificate)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TdsWireError::Tls(format!("Invalid certificate: {}", e)))?;
+    if certs.is_empty() {
+        return Err(TdsWireError::Tls(
+            "No certificate found in provided PEM data".to_string(),
+        ));
+    }
+
+    let key = PrivateKeyDer::from_pem_slice(private_key)
+        .map_err(|e| TdsWireError::Tls(format!("Invalid private key: {}", e)))?;
+
+    // TDS 7.x clients wrap the handshake in PRELOGIN packets, which is only defined for TLS 1.2
+    let config = ServerConfig::builder_with_provider(Arc::new(
+        tokio_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&TLS12])
+    .map_err(|e| TdsWireError::Tls(e.to_string()))?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| TdsWireError::Tls(e.to_string()))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Stream wrapper used for TLS negotiation over TDS. During the handshake all TLS records are
+/// wrapped in (and unwrapped from) PRELOGIN packets. Once the handshake is completed, the
+/// wrapper becomes fully transparent and raw TLS is sent over the wire.
+pub struct TlsPreLoginWrapper<S> {
+    stream: S,
+    wrap_reads: bool,
+    wrap_writes: bool,
+    packet_size: usize,
+    packet_id: u8,
+    header: [u8; ALL_HEADERS_LEN_TX],
+    header_pos: usize,
+    read_remaining: usize,
+    read_buffer: BytesMut,
+    write_buffer: BytesMut,
+}
+
+impl<S> TlsPreLoginWrapper<S> {
+    pub fn new(stream: S, packet_size: usize) -> Self {
+        TlsPreLoginWrapper {
+            stream,
+            wrap_reads: true,
+            wrap_writes: true,
+            packet_size,
+            packet_id: 0,
+            header: [0; ALL_HEADERS_LEN_TX],
+            header_pos: 0,
+            read_remaining: 0,
+            read_buffer: BytesMut::new(),
+            write_buffer: BytesMut::new(),
+        }
+    }
+
+    /// Stop wrapping TLS records in PRELOGIN packets
+    pub fn handshake_complete(&mut self) {
+        self.wrap_reads = false;
+        self.wrap_writes = false;
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn wrap_into_packets(&mut self, buf: &[u8]) -> Result<(), IOError> {
+        let max_payload = self.packet_size - ALL_HEADERS_LEN_TX;
+        let mut chunks = buf.chunks(max_payload).peekable();
+        while let Some(chunk) = chunks.next() {
+            self.packet_id = self.packet_id.wrapping_add(1);
+            let mut header = PacketHeader::new(chunk.len() + ALL_HEADERS_LEN_TX, self.packet_id);
+            header.ty = PacketType::PreLogin;
+            header.is_end_of_message = chunks.peek().is_none();
+            header
+                .encode(&mut self.write_buffer)
+                .map_err(|e| IOError::new(ErrorKind::InvalidData, e.to_string()))?;
+            self.write_buffer.extend_from_slice(chunk);
+        }
+        Ok(())
+    }
+}
+
+impl<S> TlsPreLoginWrapper<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), IOError>> {
+        while self.write_buffer.has_remaining() {
+            let written = ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_buffer))?;
+            if written == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+            self.write_buffer.advance(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> TlsPreLoginWrapper<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read_raw(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.read_buffer.has_remaining() {
+            let len = std::cmp::min(self.read_buffer.len(), buf.remaining());
+            buf.put_slice(&self.read_buffer.split_to(len));
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncRead for TlsPreLoginWrapper<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if !this.wrap_reads {
+            return this.poll_read_raw(cx, buf);
+        }
+
+        // read the next PRELOGIN packet header
+        while this.read_remaining == 0 {
+            while this.header_pos < ALL_HEADERS_LEN_TX {
+                let mut header_buf = ReadBuf::new(&mut this.header[this.header_pos..]);
+                ready!(Pin::new(&mut this.stream).poll_read(cx, &mut header_buf))?;
+                let read = header_buf.filled().len();
+                if read == 0 {
+                    return Poll::Ready(Ok(()));
+                }
+                this.header_pos += read;
+
+                // a raw TLS record (the PRELOGIN type is never a valid TLS content type), the
+                // handshake has been completed and the client no longer wraps its records
+                if this.header[0] != PacketType::PreLogin as u8 {
+                    this.wrap_reads = false;
+                    this.read_buffer
+                        .extend_from_slice(&this.header[..this.header_pos]);
+                    this.header_pos = 0;
+                    return this.poll_read_raw(cx, buf);
+                }
+            }
+
+            let header = PacketHeader::decode(&mut BytesMut::from(&this.header[..]))
+                .map_err(|e| IOError::new(ErrorKind::InvalidData, e.to_string()))?;
+            if (header.length as usize) < ALL_HEADERS_LEN_TX {
+                return Poll::Ready(Err(IOError::new(
+                    ErrorKind::InvalidData,
+                    "Invalid PRELOGIN packet length during TLS handshake",
+                )));
+            }
+
+            this.header_pos = 0;
+            this.read_remaining = header.length as usize - ALL_HEADERS_LEN_TX;
+        }
+
+        // read (part of) the packet payload
+        let mut payload = vec![0u8; std::cmp::min(this.read_remaining, buf.remaining())];
+        let mut payload_buf = ReadBuf::new(&mut payload);
+        ready!(Pin::new(&mut this.stream).poll_read(cx, &mut payload_buf))?;
+        this.read_remaining -= payload_buf.filled().len();
+        buf.put_slice(payload_buf.filled());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for TlsPreLoginWrapper<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IOError>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        if !this.wrap_writes {
+            return Pin::new(&mut this.stream).poll_write(cx, buf);
+        }
+
+        // data is buffered and written out on flush
+        this.wrap_into_packets(buf)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IOError>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IOError>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::tls::{create_tls_acceptor, TlsPreLoginWrapper};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::client::danger::{
+        HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+    };
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+    use tokio_rustls::TlsConnector;
+
+    #[derive(Debug)]
+    struct AcceptAnyCertificate;
+
+    impl ServerCertVerifier for AcceptAnyCertificate {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            tokio_rustls::rustls::crypto::ring::default_provider()
+                .signature_verification_algorithms
+                .supported_schemes()
+        }
+    }
+
+    fn self_signed_certificate() -> (Vec<u8>, Vec<u8>) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        (
+            cert.cert.pem().into_bytes(),
+            cert.key_pair.serialize_pem().into_bytes(),
+        )
+    }
+
+    fn tls_connector() -> TlsConnector {
+        let config = ClientConfig::builder_with_provider(Arc::new(
+            tokio_rustls::rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+        .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    #[test]
+    fn create_acceptor_invalid_pem() {
+        assert!(create_tls_acceptor(b"invalid", b"invalid").is_err());
+        let (cert, _) = self_signed_certificate();
+        assert!(create_tls_acceptor(&cert, b"invalid").is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_handshake_wrapped_in_prelogin() {
+        let (cert, key) = self_signed_certificate();
+        let acceptor = create_tls_acceptor(&cert, &key).unwrap();
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let server = tokio::spawn(async move {
+            let mut tls = acceptor
+                .accept(TlsPreLoginWrapper::new(server, 4096))
+                .await
+                .unwrap();
+            tls.get_mut().0.handshake_complete();
+
+            let mut buf = [0u8; 5];
+            tls.read_exact(&mut buf).await.unwrap();
+            tls.write_all(&buf).await.unwrap();
+            tls.flush().await.unwrap();
+        });
+
+        // the client side wraps records as well, as a TDS client would do
+        let mut tls = tls_connector()
+            .connect(
+                ServerName::try_from("localhost").unwrap(),
+                TlsPreLoginWrapper::new(client, 4096),
+            )
+            .await
+            .unwrap();
+        tls.get_mut().0.handshake_complete();
+
+        tls.write_all(b"hello").await.unwrap();
+        tls.flush().await.unwrap();
+        let mut buf = [0u8; 5];
+        tls.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prelogin_wrapper_splits_packets() {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let mut wrapper = TlsPreLoginWrapper::new(client, 512);
+        let payload = vec![0xabu8; 1000];
+        wrapper.write_all(&payload).await.unwrap();
+        wrapper.flush().await.unwrap();
+
+        let mut buf = vec![0u8; 1000 + 2 * 8];
+        server.read_exact(&mut buf).await.unwrap();
+        // first packet: PRELOGIN, not EOM, full packet size
+        assert_eq!(&buf[..4], &[0x12, 0x00, 0x02, 0x00]);
+        // second packet: PRELOGIN, EOM, remaining bytes
+        assert_eq!(&buf[512..516], &[0x12, 0x01, 0x01, 0xf8]);
+    }
+
+    #[tokio::test]
+    async fn prelogin_wrapper_unwraps_packets() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut wrapper = TlsPreLoginWrapper::new(client, 4096);
+        server
+            .write_all(&[0x12, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x00, 0x16, 0x03])
+            .await
+            .unwrap();
+        server
+            .write_all(&[0x12, 0x01, 0x00, 0x09, 0x00, 0x00, 0x02, 0x00, 0x01])
+            .await
+            .unwrap();
+        let mut buf = [0u8; 3];
+        wrapper.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x16, 0x03, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn prelogin_wrapper_switches_to_raw_tls() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut wrapper = TlsPreLoginWrapper::new(client, 4096);
+        server.write_all(&[0x17, 0x03, 0x03, 0x00]).await.unwrap();
+        let mut buf = [0u8; 4];
+        wrapper.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x17, 0x03, 0x03, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn prelogin_wrapper_rejects_invalid_length() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut wrapper = TlsPreLoginWrapper::new(client, 4096);
+        server
+            .write_all(&[0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x01, 0x00])
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1];
+        assert!(wrapper.read_exact(&mut buf).await.is_err());
+    }
+}
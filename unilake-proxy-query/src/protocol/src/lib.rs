@@ -1,3 +1,7 @@
+pub mod audit;
+pub mod auth;
 pub mod backend;
 pub mod frontend;
 mod session;
+#[cfg(test)]
+mod test_util;
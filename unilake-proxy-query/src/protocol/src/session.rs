@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::atomic::AtomicU16;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use ulid::Ulid;
 
 pub const SESSION_VARIABLE_DIALECT: &str = "proxy_dialect";
@@ -11,6 +12,10 @@ pub const SESSION_VARIABLE_CATALOG: &str = "proxy_catalog";
 pub const SESSION_VARIABLE_DATABASE: &str = "proxy_database";
 pub const SESSION_VARIABLE_SECURITY_IMPERSONATE: &str = "proxy_security_impersonate";
 pub const SESSION_VARIABLE_SEND_TELEMETRY: &str = "proxy_send_telemetry";
+/// Abort the remainder of a batch when one of its statements fails ("on" or "off")
+pub const SESSION_VARIABLE_XACT_ABORT: &str = "xact_abort";
+/// Omit the row count of statements ("on" or "off")
+pub const SESSION_VARIABLE_NOCOUNT: &str = "nocount";
 
 pub trait SessionInfo: Send + Sync {
     /// Currently in use socket
@@ -107,6 +112,39 @@ pub trait SessionInfo: Send + Sync {
 
     /// Get all session variables
     fn get_session_variables(&self) -> HashMap<&str, &SessionVariable>;
+
+    /// Register a prepared statement, returns the handle used by the client to refer to it
+    fn add_prepared_statement(&mut self, statement: PreparedStatement) -> i32;
+
+    /// Get a prepared statement by its handle
+    fn get_prepared_statement(&self, handle: i32) -> Option<Arc<PreparedStatement>>;
+
+    /// Remove a prepared statement, returns false if the handle is unknown
+    fn remove_prepared_statement(&mut self, handle: i32) -> bool;
+
+    /// Cancellation token of the request currently being processed, cancelled on attention
+    fn get_cancellation_token(&self) -> CancellationToken;
+
+    /// Set the cancellation token for the request about to be processed
+    fn set_cancellation_token(&mut self, token: CancellationToken);
+}
+
+/// Statement prepared through sp_prepare or sp_prepexec
+#[derive(Debug)]
+pub struct PreparedStatement {
+    /// Parameter declaration, e.g. "@p1 int, @p2 nvarchar(50)"
+    pub declaration: Arc<str>,
+    /// Statement text referencing the declared parameters
+    pub statement: Arc<str>,
+}
+
+impl PreparedStatement {
+    pub fn new(declaration: &str, statement: &str) -> Self {
+        PreparedStatement {
+            declaration: Arc::from(declaration),
+            statement: Arc::from(statement),
+        }
+    }
 }
 
 pub enum SessionVariable {
@@ -9,6 +9,7 @@ use serde::Serialize;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::time::Duration;
+use unilake_common::metrics::metrics;
 
 fn get_key_hash<H>(key: H) -> u64
 where
@@ -19,6 +20,13 @@ where
     hasher.finish()
 }
 
+/// Name of the cached type, used to label the cache metrics
+fn cache_name<V>() -> &'static str {
+    let name = std::any::type_name::<V>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
 pub struct MultiLayeredCache<K, V> {
     local_cache: MokaCache<K, V>,
     distributed_cache: Box<dyn BackendProvider<K, V>>,
@@ -54,17 +62,28 @@ where
     }
 
     pub async fn get(&self, key: &K) -> Option<V> {
+        let cache = cache_name::<V>();
+        let metrics = metrics();
         match self.local_cache.get(key).await {
-            Some(v) => Some(v),
+            Some(v) => {
+                metrics.inc_cache_request(cache, "local", true);
+                Some(v)
+            }
             None => {
+                metrics.inc_cache_request(cache, "local", false);
                 // get from backend
                 if let Ok(Some(v)) = self.distributed_cache.get(key).await {
+                    metrics.inc_cache_request(cache, "distributed", true);
                     self.local_cache.insert(key.clone(), v.clone()).await;
                     return Some(v);
                 }
+                metrics.inc_cache_request(cache, "distributed", false);
                 // get from repo
                 match self.get_from_repo(key).await {
-                    Ok(v) => return v,
+                    Ok(v) => {
+                        metrics.inc_cache_request(cache, "repository", v.is_some());
+                        return v;
+                    }
                     Err(e) => tracing::error!("Error getting data from repo: {}", e),
                 }
                 None
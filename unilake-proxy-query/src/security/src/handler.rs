@@ -27,6 +27,15 @@ use unilake_sql::{
 
 const SELECT: &str = "SELECT";
 
+/// Error code of a query denied by the policies
+pub const ACCESS_DENIED_ERROR_CODE: u32 = 90308;
+/// Severity of errors the user can correct, such as missing permissions
+pub const SEVERITY_ACCESS_ERROR: u8 = 14;
+/// Severity of errors in the query itself
+const SEVERITY_QUERY_ERROR: u8 = 15;
+/// Severity of errors in the proxy, which the user cannot correct
+const SEVERITY_INTERNAL_ERROR: u8 = 16;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum AttributeScanType {
     /// Attribute is explicitly stated in the request
@@ -88,7 +97,7 @@ impl From<SecurityHandlerError> for TokenError {
                 code,
                 line: 0,
                 message: e.to_string(),
-                class: 0,
+                class: SEVERITY_INTERNAL_ERROR,
                 procedure: "".to_string(),
                 server: settings_server_name(),
                 state: 0,
@@ -101,7 +110,7 @@ impl From<SecurityHandlerError> for TokenError {
                             "{}. Line: {}, Col: {}. {}. Query Id: {}",
                             err.start_context, err.line, err.col, err.description, id
                         ),
-                        class: 0,
+                        class: SEVERITY_QUERY_ERROR,
                         line: err.line,
                         procedure: "".to_string(),
                         server: settings_server_name(),
@@ -112,7 +121,7 @@ impl From<SecurityHandlerError> for TokenError {
                     code,
                     line: 0,
                     message: format!("Parser error: {}. Query Id: {}", e.message, id),
-                    class: 0,
+                    class: SEVERITY_QUERY_ERROR,
                     procedure: "".to_string(),
                     server: settings_server_name(),
                     state: 0,
@@ -126,7 +135,7 @@ impl From<SecurityHandlerError> for TokenError {
                         "Unable to process query, check logs for more details. Query Id: {}",
                         id
                     ),
-                    class: 0,
+                    class: SEVERITY_INTERNAL_ERROR,
                     procedure: "".to_string(),
                     server: settings_server_name(),
                     state: 0,
@@ -135,7 +144,7 @@ impl From<SecurityHandlerError> for TokenError {
                     code,
                     line: 0,
                     message: format!("{}. Query Id: {}", s.message, id),
-                    class: 0,
+                    class: SEVERITY_ACCESS_ERROR,
                     procedure: "".to_string(),
                     server: settings_server_name(),
                     state: 0,
@@ -145,6 +154,39 @@ impl From<SecurityHandlerError> for TokenError {
     }
 }
 
+/// Messages listing each attribute denied to the query and where access can be requested, sent
+/// along with the access denied error.
+pub fn access_denied_messages(
+    cause: &[TranspilerDenyCause],
+    access_links: &[PolicyAccessRequestUrl],
+) -> Vec<String> {
+    let mut attributes = cause
+        .iter()
+        .map(|c| (c.attribute.as_str(), c.policy_id.as_deref()))
+        .collect::<Vec<_>>();
+    attributes.sort();
+    attributes.dedup();
+
+    attributes
+        .into_iter()
+        .map(|(attribute, policy_id)| match policy_id {
+            Some(policy_id) => format!(
+                "Access denied to attribute '{}' by policy '{}'.",
+                attribute, policy_id
+            ),
+            None => format!("Access denied to attribute '{}'.", attribute),
+        })
+        .chain(
+            access_links
+                .iter()
+                .map(|link| match link.message.is_empty() {
+                    true => format!("Request access here: {}", link.url),
+                    false => format!("{}: {}", link.message, link.url),
+                }),
+        )
+        .collect()
+}
+
 pub struct SecurityHandler {
     query_id: Ulid,
     scan_output: Option<ScanOutput>,
@@ -159,6 +201,7 @@ pub struct SecurityHandler {
     cached_backend: CacheContainer,
     repo_backend: Box<dyn RepoBackend>,
     abac_model: Option<DefaultModel>,
+    attribute_tags: HashMap<String, Vec<String>>,
 }
 
 impl SecurityHandler {
@@ -184,6 +227,7 @@ impl SecurityHandler {
             cached_backend,
             repo_backend,
             abac_model,
+            attribute_tags: HashMap::new(),
         }
     }
 
@@ -263,8 +307,95 @@ impl SecurityHandler {
             // Something like: Access to entity {} with action {} not allowed. Query ID: {}
         }
 
+        let transpiler_input = self.process_policies(&scan_output).await?;
+
+        // check for any security violations
+        if let Some(cause) = transpiler_input.cause {
+            self.close_handler();
+            return Ok(HandleResult::AccessDenied(
+                cause,
+                transpiler_input.request_url,
+            ));
+        }
+        let output_query = self.transpile_query(&transpiler_input, false)?;
+
+        self.scan_output = Some(scan_output);
+        self.transpiler_input = Some(transpiler_input);
+        self.output_query = Some(Arc::from(output_query));
+
+        Ok(HandleResult::Query(
+            self.output_query.as_ref().unwrap().clone(),
+        ))
+    }
+
+    /// Returns the schema visible to the user for the given entities, expressed in
+    /// `<catalog>.<schema>.<entity>`, as if all their attributes were requested. Entities without
+    /// an entity model are not visible.
+    pub async fn get_visible_schema(
+        &mut self,
+        entities: &[String],
+    ) -> Result<HashMap<String, Catalog>, SecurityHandlerError> {
+        let mut known_entities = Vec::new();
+        for entity in entities {
+            if self.cached_backend.entity_model.get(entity).await.is_some() {
+                known_entities.push(entity.as_str());
+            }
+        }
+        if known_entities.is_empty() {
+            self.close_handler();
+            return Ok(HashMap::new());
+        }
+
+        let scan_output = Self::get_visible_schema_scan_output(&known_entities);
+        let transpiler_input = self.process_policies(&scan_output).await?;
+        self.close_handler();
+        Ok(transpiler_input.visible_schema.unwrap_or_default())
+    }
+
+    /// Scan output selecting all attributes of the given entities
+    fn get_visible_schema_scan_output(entities: &[&str]) -> ScanOutput {
+        let mut entity_set = HashSet::new();
+        let mut attributes = HashSet::new();
+        for (i, entity) in entities.iter().enumerate() {
+            let mut parts = entity.splitn(3, '.');
+            let (Some(catalog), Some(db), Some(name)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let alias = format!("t{}", i);
+            entity_set.insert(ScanEntity {
+                catalog: Some(catalog.to_string()),
+                db: Some(db.to_string()),
+                name: name.to_string(),
+                alias: alias.clone(),
+            });
+            attributes.insert(ScanAttribute {
+                entity_alias: alias,
+                name: "*".to_string(),
+            });
+        }
+
+        ScanOutput {
+            objects: vec![ScanOutputObject {
+                scope: 0,
+                entities: entity_set,
+                attributes,
+                is_agg: false,
+            }],
+            dialect: "".to_string(),
+            query: Some("".to_string()),
+            query_type: SELECT.to_string(),
+            error: None,
+            target_entity: None,
+        }
+    }
+
+    /// Decide on the policies applying to the scanned query, retried once on an invalid cache
+    async fn process_policies(
+        &mut self,
+        scan_output: &ScanOutput,
+    ) -> Result<TranspilerInput, SecurityHandlerError> {
         let mut iterations = 2;
-        let transpiler_input: Option<TranspilerInput>;
         loop {
             iterations -= 1;
             if iterations == 0 {
@@ -279,20 +410,22 @@ impl SecurityHandler {
                 );
             }
 
-            match QueryPolicyDecision::new(
-                &self.cached_backend,
-                self.cached_adapter.take(),
-                self.abac_model.take(),
-                &self.session_model,
-                self.cached_rules.clone(),
-                &self.repo_backend,
-            )
-            .process(&scan_output)
-            .await
-            {
+            let (result, attribute_tags) = {
+                let mut decision = QueryPolicyDecision::new(
+                    &self.cached_backend,
+                    self.cached_adapter.take(),
+                    self.abac_model.take(),
+                    &self.session_model,
+                    self.cached_rules.clone(),
+                    &self.repo_backend,
+                );
+                let result = decision.process(scan_output).await;
+                (result, decision.attribute_tags)
+            };
+            match result {
                 Ok(ti) => {
-                    transpiler_input = Some(ti);
-                    break;
+                    self.attribute_tags = attribute_tags;
+                    return Ok(ti);
                 }
                 Err(e) => match e {
                     SecurityHandlerResult::InvalidCacheError => {
@@ -310,27 +443,6 @@ impl SecurityHandler {
                 },
             }
         }
-
-        // unwrap: we can only get here if the above loop was successful
-        let transpiler_input = transpiler_input.unwrap();
-
-        // check for any security violations
-        if let Some(cause) = transpiler_input.cause {
-            self.close_handler();
-            return Ok(HandleResult::AccessDenied(
-                cause,
-                transpiler_input.request_url,
-            ));
-        }
-        let output_query = self.transpile_query(&transpiler_input, false)?;
-
-        self.scan_output = Some(scan_output);
-        self.transpiler_input = Some(transpiler_input);
-        self.output_query = Some(Arc::from(output_query));
-
-        Ok(HandleResult::Query(
-            self.output_query.as_ref().unwrap().clone(),
-        ))
     }
 
     /// Closes this queryhandler making sure that it cannot be reused.
@@ -433,6 +545,12 @@ impl SecurityHandler {
         self.output_query.clone()
     }
 
+    /// Returns the tags of the attributes selected by the query, by lowercase attribute name.
+    /// Tags of attributes with the same name in different entities are combined.
+    pub fn get_attribute_tags(&self) -> &HashMap<String, Vec<String>> {
+        &self.attribute_tags
+    }
+
     /// Checks if the current user has access to the entity involved with the given intent.
     async fn check_user_access(&self, scan_output: &ScanOutput) -> bool {
         if scan_output.query_type == SELECT {
@@ -499,6 +617,8 @@ struct QueryPolicyDecision<'a> {
     policy_hit_cache: Arc<Box<dyn Cache<u64, (String, HitRule)>>>,
     /// Repository backend for interacting with the database and other data sources
     repo_backend: &'a Box<dyn RepoBackend>,
+    /// Tags of the selected (explicit or starred) attributes, by lowercase attribute name
+    attribute_tags: HashMap<String, Vec<String>>,
 }
 
 impl<'a> QueryPolicyDecision<'a> {
@@ -517,6 +637,7 @@ impl<'a> QueryPolicyDecision<'a> {
             repo_backend,
             cached_adapter,
             abac_model,
+            attribute_tags: HashMap::new(),
         }
     }
 
@@ -637,6 +758,17 @@ impl<'a> QueryPolicyDecision<'a> {
                     Some(om) => om,
                 };
 
+                // keep the tags of selected attributes, used to classify the result columns
+                if scan_entity_attribute.scan_type != AttributeScanType::Filtering {
+                    let tags = self
+                        .attribute_tags
+                        .entry(object_model.name.to_lowercase())
+                        .or_default();
+                    tags.extend(object_model.tags.iter().cloned());
+                    tags.sort();
+                    tags.dedup();
+                }
+
                 // check access based on policy
                 for execution_context in policy_enforce_context {
                     if let Some((user_model, group_model, session_model)) = execution_context {
@@ -1170,7 +1302,10 @@ impl<'a> QueryPolicyDecision<'a> {
 mod tests {
     use crate::adapter::cached_adapter::{CachedAdapter, CachedPolicyRules};
     use crate::caching::layered_cache::{BackendProvider, MultiLayeredCache};
-    use crate::handler::{CacheContainer, QueryPolicyDecision, SecurityHandlerResult};
+    use crate::handler::{
+        access_denied_messages, CacheContainer, QueryPolicyDecision, SecurityHandler,
+        SecurityHandlerResult,
+    };
     use crate::repository::RepoBackend;
     use crate::{HitRule, ABAC_MODEL};
     use async_trait::async_trait;
@@ -1184,7 +1319,10 @@ mod tests {
         AccessPolicyModel, AppInfoModel, DataAccessRequestResponse, EntityAttributeModel,
         EntityModel, GroupInstance, GroupModel, IpInfoModel, PolicyRule, SessionModel, UserModel,
     };
-    use unilake_sql::{ScanAttribute, ScanEntity, ScanOutput, ScanOutputObject, TranspilerInput};
+    use unilake_sql::{
+        PolicyAccessRequestUrl, ScanAttribute, ScanEntity, ScanOutput, ScanOutputObject,
+        TranspilerDenyCause, TranspilerInput,
+    };
 
     async fn run_default_test(
         rules: Vec<PolicyRule>,
@@ -1853,6 +1991,30 @@ mod tests {
         assert!(result.filters.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_query_policy_decision_attribute_tags() {
+        let (abac_model, _, cache_container) = get_defaults(None, None, None, None).await;
+        let (_, adapter) = get_default_policy_cache(get_default_policy());
+        let session_model = get_session_model_input();
+        let policy_cache: Arc<Box<dyn Cache<u64, (String, HitRule)>>> =
+            Arc::new(Box::new(DefaultCache::new(10)));
+        let fake_backend: Box<dyn RepoBackend> = Box::new(FakeRepoBackend {});
+        let mut sut = QueryPolicyDecision::new(
+            &cache_container,
+            Some(adapter),
+            Some(abac_model),
+            &session_model,
+            policy_cache,
+            &fake_backend,
+        );
+
+        // all attributes are selected by the star
+        assert!(sut.process(&get_scan_star_output()).await.is_ok());
+        assert_eq!(sut.attribute_tags.len(), 4);
+        assert_eq!(sut.attribute_tags["email"], vec!["pii::email".to_string()]);
+        assert_eq!(sut.attribute_tags["user_id"], vec!["pii::id".to_string()]);
+    }
+
     // todo: add test for visible schema (check if this is actually correct)
 
     #[tokio::test]
@@ -2009,6 +2171,65 @@ mod tests {
         assert_eq!(fields, 3);
     }
 
+    #[tokio::test]
+    async fn test_visible_schema_hidden_and_denied_attributes() {
+        // test: the visible schema of an entity, hides hidden and denied attributes
+        let policies = vec![
+            PolicyRule::new(
+                "p",
+                "catalog.schema.customers.*",
+                "true",
+                "allow",
+                // {"full_access": true}
+                "eyJmdWxsX2FjY2VzcyI6IHRydWV9",
+                "policy_id",
+            ),
+            PolicyRule::new(
+                "p",
+                "catalog.schema.customers.*",
+                "TagExists(r.object, \"pii::firstname\")",
+                "allow",
+                // {"name": "hidden", "properties": null}
+                "eyJuYW1lIjogImhpZGRlbiIsICJwcm9wZXJ0aWVzIjogbnVsbH0=",
+                "policy_id",
+            ),
+            PolicyRule::new(
+                "p",
+                "catalog.schema.customers.*",
+                "TagExists(r.object, \"pii::lastname\")",
+                "deny",
+                // {"full_access": true}
+                "eyJmdWxsX2FjY2VzcyI6IHRydWV9",
+                "policy_id",
+            ),
+        ];
+
+        let scan_output =
+            SecurityHandler::get_visible_schema_scan_output(&["catalog.schema.customers"]);
+        let result = run_default_test(policies, Some(scan_output), None, None, None, None, None)
+            .await
+            .unwrap();
+
+        // visible columns are not a cause for denying access
+        assert!(result.cause.is_none());
+        let mut columns = result
+            .visible_schema
+            .unwrap()
+            .remove("catalog")
+            .unwrap()
+            .db
+            .remove("schema")
+            .unwrap()
+            .table
+            .remove("customers")
+            .unwrap()
+            .columns
+            .into_keys()
+            .collect::<Vec<_>>();
+        columns.sort();
+        assert_eq!(columns, vec!["email", "user_id"]);
+    }
+
     #[tokio::test]
     async fn test_query_policy_decision_deny_hidden_attribute() {
         // test: deny access to a hidden column based on the policy (hidden)
@@ -2077,6 +2298,41 @@ mod tests {
         assert!(result.request_url.is_some());
     }
 
+    #[test]
+    fn test_access_denied_messages() {
+        let cause = |scope, attribute: &str, policy_id: Option<&str>| TranspilerDenyCause {
+            scope,
+            attribute: attribute.to_string(),
+            policy_id: policy_id.map(|p| p.to_string()),
+        };
+        let messages = access_denied_messages(
+            &[
+                cause(1, "b.ssn", Some("p1")),
+                cause(0, "a.email", None),
+                cause(0, "b.ssn", Some("p1")),
+            ],
+            &[
+                PolicyAccessRequestUrl {
+                    url: "https://portal/request/1".to_string(),
+                    message: "".to_string(),
+                },
+                PolicyAccessRequestUrl {
+                    url: "https://portal/request/2".to_string(),
+                    message: "Request access to PII".to_string(),
+                },
+            ],
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "Access denied to attribute 'a.email'.",
+                "Access denied to attribute 'b.ssn' by policy 'p1'.",
+                "Request access here: https://portal/request/1",
+                "Request access to PII: https://portal/request/2",
+            ]
+        );
+    }
+
     #[test]
     fn test_get_entity_attributes_found() {
         // let scan_output = get_scan_default_output();
@@ -5,6 +5,7 @@ use pyo3::prelude::*;
 use serde::Serialize;
 use serde_json;
 use std::collections::{HashMap, HashSet};
+use unilake_common::metrics::metrics;
 
 pub fn run_scan_operation(
     query: &str,
@@ -23,11 +24,47 @@ pub fn run_scan_operation(
 
         let elapsed_time = std::time::Instant::now().duration_since(start_time);
         tracing::trace!("Elapsed time [Scan]: {:?}", elapsed_time);
+        metrics().observe_sql_operation("scan", elapsed_time);
 
         Ok(result.extract::<ScanOutput>()?)
     })
 }
 
+/// Splits a batch into its separate statements, on failure the batch is returned as a single
+/// statement so the scan of the batch reports the error
+pub fn run_split_operation(query: &str, dialect: &str) -> PyResult<Vec<String>> {
+    let start_time = std::time::Instant::now();
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let builtins = PyModule::import_bound(py, "sqlparser")?;
+        let result = builtins.getattr("split")?.call1((query, dialect))?;
+
+        let elapsed_time = std::time::Instant::now().duration_since(start_time);
+        tracing::trace!("Elapsed time [Split]: {:?}", elapsed_time);
+        metrics().observe_sql_operation("split", elapsed_time);
+
+        result.extract::<Vec<String>>()
+    })
+}
+
+/// Executes the query on the emulated system catalog, given as json, see [`CatalogOutput`]
+pub fn run_catalog_operation(query: &str, dialect: &str, tables: &str) -> PyResult<CatalogOutput> {
+    let start_time = std::time::Instant::now();
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let builtins = PyModule::import_bound(py, "sqlparser")?;
+        let result = builtins
+            .getattr("execute_catalog")?
+            .call1((query, dialect, tables))?;
+
+        let elapsed_time = std::time::Instant::now().duration_since(start_time);
+        tracing::trace!("Elapsed time [Catalog]: {:?}", elapsed_time);
+        metrics().observe_sql_operation("catalog", elapsed_time);
+
+        result.extract::<CatalogOutput>()
+    })
+}
+
 pub fn run_transpile_operation(
     input: &TranspilerInput,
     secure_output: bool,
@@ -45,6 +82,7 @@ pub fn run_transpile_operation(
 
         let elapsed_time = std::time::Instant::now().duration_since(start_time);
         tracing::trace!("Elapsed time [Transpile]: {:?}", elapsed_time);
+        metrics().observe_sql_operation("transpile", elapsed_time);
 
         Ok(result.extract::<TranspilerOutput>()?)
     })
@@ -64,6 +102,7 @@ pub fn run_secure_operation(input: &str) -> PyResult<String> {
 
         let elapsed_time = std::time::Instant::now().duration_since(start_time);
         tracing::trace!("Elapsed time [Secure]: {:?}", elapsed_time);
+        metrics().observe_sql_operation("secure", elapsed_time);
 
         Ok(result.extract::<String>()?)
     })
@@ -88,6 +127,25 @@ pub struct TranspilerOutput {
     pub error: Option<ParserError>,
 }
 
+/// Result of a query on the emulated system catalog
+#[derive(FromPyObject, Debug)]
+pub struct CatalogOutput {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<CatalogValue>>>,
+    /// False if the query references other tables than those of the system catalog, in which
+    /// case the query has not been executed
+    pub is_catalog_query: bool,
+    pub error: Option<String>,
+}
+
+#[derive(FromPyObject, Debug, Clone, PartialEq)]
+pub enum CatalogValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
 pub struct ScanOutput {
     pub objects: Vec<ScanOutputObject>,
     pub dialect: String,
@@ -275,8 +333,9 @@ pub struct TranspilerInputFilter {
 #[cfg(test)]
 mod tests {
     use crate::{
-        run_scan_operation, run_transpile_operation, TranspilerInput, TranspilerInputFilter,
-        TranspilerInputRule, VisibleSchemaBuilder,
+        run_catalog_operation, run_scan_operation, run_split_operation, run_transpile_operation,
+        CatalogValue, TranspilerInput, TranspilerInputFilter, TranspilerInputRule,
+        VisibleSchemaBuilder,
     };
     use pyo3::PyResult;
     use serde_json::json;
@@ -297,6 +356,33 @@ mod tests {
         assert_eq!(output.error.unwrap().errors.len(), 1);
     }
 
+    #[test]
+    fn test_split_operation() {
+        let sql = "USE db1; select top 100 * from employees;";
+        let output = run_split_operation(sql, "tsql").unwrap();
+        assert_eq!(output, vec!["USE db1", "select top 100 * from employees"]);
+    }
+
+    #[test]
+    fn test_catalog_operation() {
+        let tables = json!({"sys": {"tables": [{"name": "orders", "object_id": 1}]}}).to_string();
+        let output =
+            run_catalog_operation("SELECT name, object_id FROM sys.tables", "tsql", &tables)
+                .unwrap();
+        assert!(output.is_catalog_query);
+        assert_eq!(output.columns, vec!["name", "object_id"]);
+        assert_eq!(
+            output.rows,
+            vec![vec![
+                Some(CatalogValue::Text("orders".to_string())),
+                Some(CatalogValue::Int(1))
+            ]]
+        );
+
+        let output = run_catalog_operation("SELECT * FROM employees", "tsql", &tables).unwrap();
+        assert!(!output.is_catalog_query);
+    }
+
     #[test]
     fn test_transpile_operation_happy_flow() -> PyResult<()> {
         let sql = "select top 100 * from employees";